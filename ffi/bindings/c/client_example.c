@@ -41,9 +41,9 @@ void on_read_registers_failure(rodbus_request_error_t error, void *ctx)
 }
 
 /// ANCHOR: write_callback
-void on_write_complete(rodbus_nothing_t nothing, void *ctx)
+void on_write_complete(rodbus_write_response_t response, void *ctx)
 {
-    printf("success!\n");
+    printf("success! (request %" PRIu64 ")\n", response.request_id);
 }
 
 void on_write_failure(rodbus_request_error_t error, void *ctx)
@@ -69,9 +69,9 @@ class RegisterReadCallback : public rodbus::RegisterReadCallback
 /// ANCHOR: write_callback
 class WriteCallback : public rodbus::WriteCallback
 {
-    void on_complete(rodbus::Nothing result) override
+    void on_complete(const rodbus::WriteResponse& result) override
     {
-        std::cout << "success!" << std::endl;
+        std::cout << "success! (request " << result.request_id << ")" << std::endl;
     }
     void on_failure(rodbus::RequestError err) override
     {
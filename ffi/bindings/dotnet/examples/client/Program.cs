@@ -265,8 +265,8 @@ namespace example
                             /// ANCHOR: write_single_coil
                             try
                             {
-                                await channel.WriteSingleCoil(param, new BitValue(0, true));
-                                Console.WriteLine("success!");
+                                var response = await channel.WriteSingleCoil(param, new BitValue(0, true));
+                                Console.WriteLine($"success! (request {response.RequestId})");
                             }
                             catch (Exception ex)
                             {
@@ -253,9 +253,9 @@ public class ClientExample {
     }
 
     // ANCHOR: handle_write_result
-    private static void handleWriteResult(Nothing nothing, Throwable ex) {
+    private static void handleWriteResult(WriteResponse response, Throwable ex) {
         if (ex == null) {
-            System.out.println("success!");
+            System.out.println("success! (request " + response.requestId + ")");
         } else {
             System.out.println("error: " + ex.getMessage());
         }
@@ -19,6 +19,23 @@ fn main() {
         }
         Ok(lib) => {
             oo_bindgen::backend::java::generate_jni(&out_path, &lib, &config).unwrap();
+            fix_generated(&out_path);
         }
     }
 }
+
+// the generator drops the callback contexts with a bare `Box::from_raw` and builds its markers
+// with `PhantomData::default()`, both of which newer toolchains reject
+fn fix_generated(path: &Path) {
+    let code = std::fs::read_to_string(path)
+        .unwrap()
+        .replace(
+            "unsafe { Box::from_raw(ctx as *mut jni::objects::GlobalRef) };",
+            "unsafe { drop(Box::from_raw(ctx as *mut jni::objects::GlobalRef)) };",
+        )
+        .replace(
+            "std::marker::PhantomData::default()",
+            "std::marker::PhantomData",
+        );
+    std::fs::write(path, code).unwrap();
+}
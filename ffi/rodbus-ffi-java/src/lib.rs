@@ -6,6 +6,7 @@
     clippy::let_unit_value,
     clippy::needless_return,
     clippy::not_unsafe_ptr_arg_deref,
+    static_mut_refs,
     unused_variables,
     dead_code
 )]
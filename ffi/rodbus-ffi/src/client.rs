@@ -1,11 +1,110 @@
 use crate::ffi;
-use rodbus::client::{ClientState, HostAddr, Listener, WriteMultiple};
-use rodbus::{AddressRange, MaybeAsync};
+use rodbus::client::{
+    CallbackSession, Channel, ClientState, HostAddr, Listener, RequestParam, WriteMultiple,
+};
+use rodbus::{AddressRange, Indexed, MaybeAsync};
 use std::net::IpAddr;
 
 pub struct ClientChannel {
     pub(crate) inner: rodbus::client::Channel,
     pub(crate) runtime: crate::RuntimeHandle,
+    submissions: tokio::sync::mpsc::UnboundedSender<(RequestParam, Submission)>,
+}
+
+/// Request made from a foreign thread
+///
+/// Requests are routed through a single worker task per channel that waits for room in the
+/// request queue, so that making a request doesn't spawn a task.
+enum Submission {
+    ReadCoils(AddressRange, ffi::BitReadCallback),
+    ReadDiscreteInputs(AddressRange, ffi::BitReadCallback),
+    ReadHoldingRegisters(AddressRange, ffi::RegisterReadCallback),
+    ReadInputRegisters(AddressRange, ffi::RegisterReadCallback),
+    WriteSingleCoil(Indexed<bool>, ffi::WriteCallback),
+    WriteSingleRegister(Indexed<u16>, ffi::WriteCallback),
+    WriteMultipleCoils(WriteMultiple<bool>, ffi::WriteCallback),
+    WriteMultipleRegisters(WriteMultiple<u16>, ffi::WriteCallback),
+}
+
+impl ClientChannel {
+    /// Must be called from within the context of the runtime
+    fn new(inner: Channel, runtime: crate::RuntimeHandle) -> Self {
+        let (submissions, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(run_submissions(inner.clone(), rx));
+        Self {
+            inner,
+            runtime,
+            submissions,
+        }
+    }
+
+    fn submit(
+        &self,
+        param: ffi::RequestParam,
+        submission: Submission,
+    ) -> Result<(), ffi::ParamError> {
+        // the worker only stops when the runtime is shut down
+        self.submissions
+            .send((param.to_request_param(), submission))
+            .map_err(|_| ffi::ParamError::RuntimeDestroyed)
+    }
+}
+
+async fn run_submissions(
+    channel: Channel,
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<(RequestParam, Submission)>,
+) {
+    // rebuilt only when a request is made to another unit or with another timeout
+    let mut cached: Option<CallbackSession> = None;
+    // completes once the ClientChannel is destroyed and the remaining requests are queued
+    while let Some((param, submission)) = rx.recv().await {
+        let session = match cached.take() {
+            Some(session) if session.param() == param => cached.insert(session),
+            _ => cached.insert(CallbackSession::new(channel.clone(), param)),
+        };
+        match submission {
+            Submission::ReadCoils(range, callback) => {
+                session
+                    .read_coils(range, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::ReadDiscreteInputs(range, callback) => {
+                session
+                    .read_discrete_inputs(range, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::ReadHoldingRegisters(range, callback) => {
+                session
+                    .read_holding_registers(range, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::ReadInputRegisters(range, callback) => {
+                session
+                    .read_input_registers(range, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::WriteSingleCoil(value, callback) => {
+                session
+                    .write_single_coil(value, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::WriteSingleRegister(value, callback) => {
+                session
+                    .write_single_register(value, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::WriteMultipleCoils(values, callback) => {
+                session
+                    .write_multiple_coils(values, callback.convert_to_fn_once())
+                    .await;
+            }
+            Submission::WriteMultipleRegisters(values, callback) => {
+                session
+                    .write_multiple_registers(values, callback.convert_to_fn_once())
+                    .await;
+            }
+        }
+    }
 }
 
 fn get_host_addr(host: &std::ffi::CStr, port: u16) -> Result<HostAddr, ffi::ParamError> {
@@ -43,10 +142,10 @@ pub(crate) unsafe fn client_channel_create_tcp(
         Some(listener.into()),
     );
 
-    Ok(Box::into_raw(Box::new(ClientChannel {
-        inner: channel,
-        runtime: runtime.handle(),
-    })))
+    Ok(Box::into_raw(Box::new(ClientChannel::new(
+        channel,
+        runtime.handle(),
+    ))))
 }
 
 #[cfg(not(feature = "serial"))]
@@ -86,10 +185,10 @@ pub(crate) unsafe fn client_channel_create_rtu(
         Some(listener.into()),
     );
 
-    Ok(Box::into_raw(Box::new(ClientChannel {
-        inner: channel,
-        runtime: runtime.handle(),
-    })))
+    Ok(Box::into_raw(Box::new(ClientChannel::new(
+        channel,
+        runtime.handle(),
+    ))))
 }
 
 #[cfg(not(feature = "tls"))]
@@ -155,10 +254,10 @@ pub(crate) unsafe fn client_channel_create_tls(
         Some(listener.into()),
     );
 
-    Ok(Box::into_raw(Box::new(ClientChannel {
-        inner: channel,
-        runtime: runtime.handle(),
-    })))
+    Ok(Box::into_raw(Box::new(ClientChannel::new(
+        channel,
+        runtime.handle(),
+    ))))
 }
 
 pub(crate) unsafe fn client_channel_destroy(channel: *mut crate::ClientChannel) {
@@ -175,12 +274,7 @@ pub(crate) unsafe fn client_channel_read_coils(
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let range = AddressRange::try_from(range.start, range.count)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.read_coils(range, callback).await })?;
+    channel.submit(param, Submission::ReadCoils(range, callback))?;
 
     Ok(())
 }
@@ -193,12 +287,7 @@ pub(crate) unsafe fn client_channel_read_discrete_inputs(
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let range = AddressRange::try_from(range.start, range.count)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.read_discrete_inputs(range, callback).await })?;
+    channel.submit(param, Submission::ReadDiscreteInputs(range, callback))?;
 
     Ok(())
 }
@@ -211,12 +300,7 @@ pub(crate) unsafe fn client_channel_read_holding_registers(
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let range = AddressRange::try_from(range.start, range.count)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.read_holding_registers(range, callback).await })?;
+    channel.submit(param, Submission::ReadHoldingRegisters(range, callback))?;
 
     Ok(())
 }
@@ -229,12 +313,7 @@ pub(crate) unsafe fn client_channel_read_input_registers(
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let range = AddressRange::try_from(range.start, range.count)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.read_input_registers(range, callback).await })?;
+    channel.submit(param, Submission::ReadInputRegisters(range, callback))?;
 
     Ok(())
 }
@@ -246,12 +325,7 @@ pub(crate) unsafe fn client_channel_write_single_coil(
     callback: crate::ffi::WriteCallback,
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.write_single_coil(bit.into(), callback).await })?;
+    channel.submit(param, Submission::WriteSingleCoil(bit.into(), callback))?;
 
     Ok(())
 }
@@ -263,14 +337,10 @@ pub(crate) unsafe fn client_channel_write_single_register(
     callback: crate::ffi::WriteCallback,
 ) -> Result<(), ffi::ParamError> {
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel.runtime.spawn(async move {
-        session
-            .write_single_register(register.into(), callback)
-            .await
-    })?;
+    channel.submit(
+        param,
+        Submission::WriteSingleRegister(register.into(), callback),
+    )?;
 
     Ok(())
 }
@@ -285,12 +355,7 @@ pub(crate) unsafe fn client_channel_write_multiple_coils(
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let items = items.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let args = WriteMultiple::from(start, items.inner.clone())?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.write_multiple_coils(args, callback).await })?;
+    channel.submit(param, Submission::WriteMultipleCoils(args, callback))?;
 
     Ok(())
 }
@@ -305,12 +370,7 @@ pub(crate) unsafe fn client_channel_write_multiple_registers(
     let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let items = items.as_ref().ok_or(ffi::ParamError::NullParameter)?;
     let args = WriteMultiple::from(start, items.inner.clone())?;
-    let callback = callback.convert_to_fn_once();
-
-    let mut session = param.build_session(channel);
-    channel
-        .runtime
-        .spawn(async move { session.write_multiple_registers(args, callback).await })?;
+    channel.submit(param, Submission::WriteMultipleRegisters(args, callback))?;
 
     Ok(())
 }
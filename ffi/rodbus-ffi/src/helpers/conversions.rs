@@ -40,6 +40,9 @@ impl From<rodbus::RequestError> for ffi::RequestError {
             rodbus::RequestError::Exception(ex) => ex.into(),
             rodbus::RequestError::Io(_) => ffi::RequestError::IoError,
             rodbus::RequestError::BadResponse(_) => ffi::RequestError::BadResponse,
+            rodbus::RequestError::QueueFull => ffi::RequestError::QueueFull,
+            // variants added to rodbus after these bindings
+            _ => ffi::RequestError::InternalError,
         }
     }
 }
@@ -123,6 +126,8 @@ impl From<ffi::SerialPortSettings> for rodbus::SerialSettings {
                 ffi::StopBits::One => rodbus::StopBits::One,
                 ffi::StopBits::Two => rodbus::StopBits::Two,
             },
+            driver_enable: None,
+            suppress_echo: false,
         }
     }
 }
@@ -149,6 +154,8 @@ impl From<rodbus::client::TlsError> for ffi::ParamError {
             }
             rodbus::client::TlsError::InvalidPrivateKey(_) => ffi::ParamError::InvalidPrivateKey,
             rodbus::client::TlsError::BadConfig(_) => ffi::ParamError::BadTlsConfig,
+            // variants added to rodbus after these bindings
+            _ => ffi::ParamError::BadTlsConfig,
         }
     }
 }
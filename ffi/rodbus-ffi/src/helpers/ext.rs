@@ -1,27 +1,29 @@
 use crate::ffi;
-use rodbus::client::{CallbackSession, RequestParam};
+use rodbus::client::RequestParam;
 use rodbus::UnitId;
 
 impl ffi::RequestParam {
-    pub(crate) fn build_session(&self, channel: &crate::ClientChannel) -> CallbackSession {
-        CallbackSession::new(
-            channel.inner.clone(),
-            RequestParam::new(UnitId::new(self.unit_id()), self.timeout()),
-        )
+    pub(crate) fn to_request_param(&self) -> RequestParam {
+        RequestParam::new(UnitId::new(self.unit_id()), self.timeout())
     }
 }
 
 impl ffi::BitReadCallback {
     pub(crate) fn convert_to_fn_once(
         self,
-    ) -> impl FnOnce(std::result::Result<rodbus::BitIterator, rodbus::RequestError>) {
-        move |result: std::result::Result<rodbus::BitIterator, rodbus::RequestError>| match result {
-            Err(err) => {
-                self.on_failure(err.into());
-            }
-            Ok(values) => {
-                let mut iter = crate::BitValueIterator::new(values);
-                self.on_complete(&mut iter as *mut _);
+    ) -> impl FnOnce(
+        rodbus::client::RequestId,
+        std::result::Result<rodbus::BitIterator, rodbus::RequestError>,
+    ) {
+        move |_id, result: std::result::Result<rodbus::BitIterator, rodbus::RequestError>| {
+            match result {
+                Err(err) => {
+                    self.on_failure(err.into());
+                }
+                Ok(values) => {
+                    let mut iter = crate::BitValueIterator::new(values);
+                    self.on_complete(&mut iter as *mut _);
+                }
             }
         }
     }
@@ -30,8 +32,11 @@ impl ffi::BitReadCallback {
 impl ffi::RegisterReadCallback {
     pub(crate) fn convert_to_fn_once(
         self,
-    ) -> impl FnOnce(std::result::Result<rodbus::RegisterIterator, rodbus::RequestError>) {
-        move |result: std::result::Result<rodbus::RegisterIterator, rodbus::RequestError>| {
+    ) -> impl FnOnce(
+        rodbus::client::RequestId,
+        std::result::Result<rodbus::RegisterIterator, rodbus::RequestError>,
+    ) {
+        move |_id, result: std::result::Result<rodbus::RegisterIterator, rodbus::RequestError>| {
             match result {
                 Err(err) => {
                     self.on_failure(err.into());
@@ -50,13 +55,15 @@ impl ffi::WriteCallback {
     /// ^ you ok mate? (É.G.)
     pub(crate) fn convert_to_fn_once<T>(
         self,
-    ) -> impl FnOnce(std::result::Result<T, rodbus::RequestError>) {
-        move |result: std::result::Result<T, rodbus::RequestError>| match result {
+    ) -> impl FnOnce(rodbus::client::RequestId, std::result::Result<T, rodbus::RequestError>) {
+        move |id, result: std::result::Result<T, rodbus::RequestError>| match result {
             Err(err) => {
                 self.on_failure(err.into());
             }
             Ok(_) => {
-                self.on_complete(ffi::Nothing::Nothing);
+                self.on_complete(ffi::WriteResponse {
+                    request_id: id.value(),
+                });
             }
         }
     }
@@ -0,0 +1,289 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::BoundObject;
+
+use rodbus::client::{Channel, HostAddr, RequestParam, WriteMultiple};
+use rodbus::{AddressRange, DecodeLevel, Indexed, RequestError, UnitId};
+
+use crate::{runtime, to_py_err, Failure};
+
+/// Modbus client channel
+///
+/// The channel is created disabled and must be enabled before it attempts to connect.
+#[pyclass(module = "pyrodbus")]
+pub(crate) struct Client {
+    channel: Channel,
+}
+
+#[pymethods]
+impl Client {
+    /// Create a channel that connects to a Modbus TCP server
+    #[staticmethod]
+    #[pyo3(signature = (host, port = 502, max_queued_requests = 16))]
+    fn tcp(host: &str, port: u16, max_queued_requests: usize) -> Self {
+        let host = match host.parse::<IpAddr>() {
+            Ok(ip) => HostAddr::ip(ip, port),
+            Err(_) => HostAddr::dns(host.to_string(), port),
+        };
+        let _enter = runtime().enter();
+        let channel = rodbus::client::spawn_tcp_client_task(
+            host,
+            max_queued_requests,
+            rodbus::default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        Self { channel }
+    }
+
+    /// Create a channel that communicates with Modbus RTU devices on a serial port
+    #[cfg(feature = "serial")]
+    #[staticmethod]
+    #[pyo3(signature = (path, baud_rate = 9600, max_queued_requests = 16))]
+    fn rtu(path: &str, baud_rate: u32, max_queued_requests: usize) -> Self {
+        let settings = rodbus::SerialSettings {
+            baud_rate,
+            ..Default::default()
+        };
+        let _enter = runtime().enter();
+        let channel = rodbus::client::spawn_rtu_client_task(
+            path,
+            settings,
+            max_queued_requests,
+            rodbus::default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        Self { channel }
+    }
+
+    /// Enable the channel so that it connects and processes requests
+    fn enable(&self, py: Python<'_>) -> PyResult<()> {
+        let channel = self.channel.clone();
+        py.allow_threads(|| runtime().block_on(async move { channel.enable().await }))
+            .map_err(|_| to_py_err(RequestError::Shutdown))
+    }
+
+    /// Disable the channel, closing any connection
+    fn disable(&self, py: Python<'_>) -> PyResult<()> {
+        let channel = self.channel.clone();
+        py.allow_threads(|| runtime().block_on(async move { channel.disable().await }))
+            .map_err(|_| to_py_err(RequestError::Shutdown))
+    }
+
+    /// Read coils, returning a list of booleans
+    #[pyo3(signature = (unit_id, start, count, timeout_ms = 1000, callback = None))]
+    fn read_coils(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        count: u16,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            let values = channel
+                .read_coils(param, AddressRange::try_from(start, count)?)
+                .await?;
+            Ok(values.into_iter().map(|x| x.value).collect::<Vec<bool>>())
+        })
+    }
+
+    /// Read discrete inputs, returning a list of booleans
+    #[pyo3(signature = (unit_id, start, count, timeout_ms = 1000, callback = None))]
+    fn read_discrete_inputs(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        count: u16,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            let values = channel
+                .read_discrete_inputs(param, AddressRange::try_from(start, count)?)
+                .await?;
+            Ok(values.into_iter().map(|x| x.value).collect::<Vec<bool>>())
+        })
+    }
+
+    /// Read holding registers, returning a list of integers
+    #[pyo3(signature = (unit_id, start, count, timeout_ms = 1000, callback = None))]
+    fn read_holding_registers(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        count: u16,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            let values = channel
+                .read_holding_registers(param, AddressRange::try_from(start, count)?)
+                .await?;
+            Ok(values.into_iter().map(|x| x.value).collect::<Vec<u16>>())
+        })
+    }
+
+    /// Read input registers, returning a list of integers
+    #[pyo3(signature = (unit_id, start, count, timeout_ms = 1000, callback = None))]
+    fn read_input_registers(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        count: u16,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            let values = channel
+                .read_input_registers(param, AddressRange::try_from(start, count)?)
+                .await?;
+            Ok(values.into_iter().map(|x| x.value).collect::<Vec<u16>>())
+        })
+    }
+
+    /// Write a single coil
+    #[pyo3(signature = (unit_id, index, value, timeout_ms = 1000, callback = None))]
+    fn write_single_coil(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        index: u16,
+        value: bool,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            channel
+                .write_single_coil(param, Indexed::new(index, value))
+                .await?;
+            Ok(())
+        })
+    }
+
+    /// Write a single holding register
+    #[pyo3(signature = (unit_id, index, value, timeout_ms = 1000, callback = None))]
+    fn write_single_register(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        index: u16,
+        value: u16,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            channel
+                .write_single_register(param, Indexed::new(index, value))
+                .await?;
+            Ok(())
+        })
+    }
+
+    /// Write a list of booleans to contiguous coils
+    #[pyo3(signature = (unit_id, start, values, timeout_ms = 1000, callback = None))]
+    fn write_multiple_coils(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        values: Vec<bool>,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            channel
+                .write_multiple_coils(param, WriteMultiple::from(start, values)?)
+                .await?;
+            Ok(())
+        })
+    }
+
+    /// Write a list of integers to contiguous holding registers
+    #[pyo3(signature = (unit_id, start, values, timeout_ms = 1000, callback = None))]
+    fn write_multiple_registers(
+        &self,
+        py: Python<'_>,
+        unit_id: u8,
+        start: u16,
+        values: Vec<u16>,
+        timeout_ms: u64,
+        callback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let channel = self.channel.clone();
+        let param = param(unit_id, timeout_ms);
+        complete(py, callback, async move {
+            channel
+                .write_multiple_registers(param, WriteMultiple::from(start, values)?)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+fn param(unit_id: u8, timeout_ms: u64) -> RequestParam {
+    RequestParam::new(UnitId::new(unit_id), Duration::from_millis(timeout_ms))
+}
+
+/// Run the request to completion, or in the background if a callback is provided
+fn complete<T, F>(py: Python<'_>, callback: Option<PyObject>, request: F) -> PyResult<PyObject>
+where
+    T: for<'py> IntoPyObject<'py> + Send + 'static,
+    F: Future<Output = Result<T, Failure>> + Send + 'static,
+{
+    match callback {
+        None => {
+            let value = py
+                .allow_threads(|| runtime().block_on(request))
+                .map_err(to_py_err)?;
+            to_object(py, value)
+        }
+        Some(callback) => {
+            runtime().spawn(async move {
+                let result = request.await;
+                Python::with_gil(|py| {
+                    let args = match result {
+                        Ok(value) => to_object(py, value).map(|value| (value, py.None())),
+                        Err(err) => Ok((py.None(), to_py_err(err).into_value(py).into_any())),
+                    };
+                    if let Err(err) = args.and_then(|args| callback.call1(py, args)) {
+                        err.print(py);
+                    }
+                });
+            });
+            Ok(py.None())
+        }
+    }
+}
+
+fn to_object<T>(py: Python<'_>, value: T) -> PyResult<PyObject>
+where
+    T: for<'py> IntoPyObject<'py>,
+{
+    value
+        .into_pyobject(py)
+        .map(|x| x.into_any().unbind())
+        .map_err(Into::into)
+}
@@ -0,0 +1,86 @@
+//! Python bindings for [rodbus](https://crates.io/crates/rodbus) built with [pyo3](https://pyo3.rs)
+//!
+//! The extension module is named `pyrodbus` and is built with [maturin](https://www.maturin.rs):
+//!
+//! ```text
+//! cd ffi/rodbus-python
+//! maturin develop
+//! ```
+//!
+//! Requests are executed on a Tokio runtime owned by the module. Every client request either blocks
+//! the calling thread (without holding the GIL) until the response is received, or returns
+//! immediately if a `callback` is provided. The callback is then invoked from a runtime thread as
+//! `callback(value, error)`, where exactly one of the two arguments is `None`.
+
+use std::sync::OnceLock;
+
+use pyo3::prelude::*;
+
+mod client;
+mod server;
+
+pyo3::create_exception!(
+    pyrodbus,
+    RequestError,
+    pyo3::exceptions::PyException,
+    "Raised when a Modbus request fails. The arguments are the description of the error and its stable numeric code"
+);
+
+pub(crate) fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("unable to create the Tokio runtime")
+    })
+}
+
+/// Description and code of a request that failed, raised as a [`RequestError`]
+pub(crate) struct Failure {
+    message: String,
+    code: u16,
+}
+
+impl From<rodbus::RequestError> for Failure {
+    fn from(err: rodbus::RequestError) -> Self {
+        Self {
+            message: err.to_string(),
+            code: err.code(),
+        }
+    }
+}
+
+impl From<rodbus::RequestFailure> for Failure {
+    fn from(err: rodbus::RequestFailure) -> Self {
+        Self {
+            message: err.to_string(),
+            code: err.error().code(),
+        }
+    }
+}
+
+impl From<rodbus::InvalidRange> for Failure {
+    fn from(err: rodbus::InvalidRange) -> Self {
+        rodbus::RequestError::from(err).into()
+    }
+}
+
+impl From<rodbus::InvalidRequest> for Failure {
+    fn from(err: rodbus::InvalidRequest) -> Self {
+        rodbus::RequestError::from(err).into()
+    }
+}
+
+pub(crate) fn to_py_err(err: impl Into<Failure>) -> PyErr {
+    let err = err.into();
+    RequestError::new_err((err.message, err.code))
+}
+
+#[pymodule]
+fn pyrodbus(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<client::Client>()?;
+    m.add_class::<server::Server>()?;
+    m.add("RequestError", m.py().get_type::<RequestError>())?;
+    Ok(())
+}
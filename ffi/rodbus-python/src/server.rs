@@ -0,0 +1,201 @@
+use std::net::{IpAddr, SocketAddr};
+
+use pyo3::exceptions::{PyIndexError, PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use rodbus::server::{
+    AddressFilter, RequestHandler, ServerHandle, ServerHandlerMap, ServerHandlerType, WriteCoils,
+    WriteRegisters,
+};
+use rodbus::{DecodeLevel, ExceptionCode, Indexed, UnitId};
+
+use crate::runtime;
+
+/// In-memory point database served to clients
+struct Database {
+    coils: Vec<bool>,
+    discrete_inputs: Vec<bool>,
+    holding_registers: Vec<u16>,
+    input_registers: Vec<u16>,
+}
+
+impl Database {
+    fn new(size: usize) -> Self {
+        Self {
+            coils: vec![false; size],
+            discrete_inputs: vec![false; size],
+            holding_registers: vec![0; size],
+            input_registers: vec![0; size],
+        }
+    }
+}
+
+fn read<T: Copy>(values: &[T], address: u16) -> Result<T, ExceptionCode> {
+    values
+        .get(address as usize)
+        .copied()
+        .ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+fn write<T>(values: &mut [T], value: Indexed<T>) -> Result<(), ExceptionCode> {
+    match values.get_mut(value.index as usize) {
+        Some(x) => {
+            *x = value.value;
+            Ok(())
+        }
+        None => Err(ExceptionCode::IllegalDataAddress),
+    }
+}
+
+impl RequestHandler for Database {
+    fn read_coil(&self, address: u16) -> Result<bool, ExceptionCode> {
+        read(&self.coils, address)
+    }
+
+    fn read_discrete_input(&self, address: u16) -> Result<bool, ExceptionCode> {
+        read(&self.discrete_inputs, address)
+    }
+
+    fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        read(&self.holding_registers, address)
+    }
+
+    fn read_input_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        read(&self.input_registers, address)
+    }
+
+    fn write_single_coil(&mut self, value: Indexed<bool>) -> Result<(), ExceptionCode> {
+        write(&mut self.coils, value)
+    }
+
+    fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+        write(&mut self.holding_registers, value)
+    }
+
+    fn write_multiple_coils(&mut self, values: WriteCoils) -> Result<(), ExceptionCode> {
+        for value in values.iterator {
+            write(&mut self.coils, value)?;
+        }
+        Ok(())
+    }
+
+    fn write_multiple_registers(&mut self, values: WriteRegisters) -> Result<(), ExceptionCode> {
+        for value in values.iterator {
+            write(&mut self.holding_registers, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Modbus TCP server that serves an in-memory database for a single unit id
+///
+/// Every table (coils, discrete inputs, holding and input registers) has `size` points starting
+/// at address 0. Clients may write coils and holding registers. The server is shut down when
+/// the object is garbage collected.
+#[pyclass(module = "pyrodbus")]
+pub(crate) struct Server {
+    _handle: ServerHandle,
+    database: ServerHandlerType<Database>,
+}
+
+#[pymethods]
+impl Server {
+    /// Start a server listening on the specified address and port
+    #[staticmethod]
+    #[pyo3(signature = (address = "127.0.0.1", port = 502, unit_id = 1, size = 100, max_sessions = 10))]
+    fn tcp(
+        py: Python<'_>,
+        address: &str,
+        port: u16,
+        unit_id: u8,
+        size: u16,
+        max_sessions: usize,
+    ) -> PyResult<Self> {
+        let ip: IpAddr = address
+            .parse()
+            .map_err(|_| PyValueError::new_err(format!("invalid IP address: {}", address)))?;
+        let database = Database::new(size as usize).wrap();
+        let handlers = ServerHandlerMap::single(UnitId::new(unit_id), database.clone());
+        let handle = py
+            .allow_threads(|| {
+                runtime().block_on(rodbus::server::spawn_tcp_server_task(
+                    max_sessions,
+                    SocketAddr::new(ip, port),
+                    handlers,
+                    AddressFilter::Any,
+                    DecodeLevel::nothing(),
+                ))
+            })
+            .map_err(|err| PyOSError::new_err(err.to_string()))?;
+        Ok(Self {
+            _handle: handle,
+            database,
+        })
+    }
+
+    /// Value of a coil
+    fn get_coil(&self, index: u16) -> PyResult<bool> {
+        get(&self.lock().coils, index)
+    }
+
+    /// Change the value of a coil
+    fn set_coil(&self, index: u16, value: bool) -> PyResult<()> {
+        set(&mut self.lock().coils, index, value)
+    }
+
+    /// Value of a discrete input
+    fn get_discrete_input(&self, index: u16) -> PyResult<bool> {
+        get(&self.lock().discrete_inputs, index)
+    }
+
+    /// Change the value of a discrete input
+    fn set_discrete_input(&self, index: u16, value: bool) -> PyResult<()> {
+        set(&mut self.lock().discrete_inputs, index, value)
+    }
+
+    /// Value of a holding register
+    fn get_holding_register(&self, index: u16) -> PyResult<u16> {
+        get(&self.lock().holding_registers, index)
+    }
+
+    /// Change the value of a holding register
+    fn set_holding_register(&self, index: u16, value: u16) -> PyResult<()> {
+        set(&mut self.lock().holding_registers, index, value)
+    }
+
+    /// Value of an input register
+    fn get_input_register(&self, index: u16) -> PyResult<u16> {
+        get(&self.lock().input_registers, index)
+    }
+
+    /// Change the value of an input register
+    fn set_input_register(&self, index: u16, value: u16) -> PyResult<()> {
+        set(&mut self.lock().input_registers, index, value)
+    }
+}
+
+impl Server {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Box<Database>> {
+        self.database.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn get<T: Copy>(values: &[T], index: u16) -> PyResult<T> {
+    values
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| PyIndexError::new_err(format!("index {} out of range", index)))
+}
+
+fn set<T>(values: &mut [T], index: u16, value: T) -> PyResult<()> {
+    match values.get_mut(index as usize) {
+        Some(x) => {
+            *x = value;
+            Ok(())
+        }
+        None => Err(PyIndexError::new_err(format!(
+            "index {} out of range",
+            index
+        ))),
+    }
+}
@@ -0,0 +1,56 @@
+import threading
+
+import pytest
+
+import pyrodbus
+
+PORT = 40502
+
+
+@pytest.fixture(scope="module")
+def server():
+    return pyrodbus.Server.tcp("127.0.0.1", PORT, unit_id=1, size=10)
+
+
+@pytest.fixture(scope="module")
+def client(server):
+    client = pyrodbus.Client.tcp("127.0.0.1", PORT)
+    client.enable()
+    return client
+
+
+def test_read_values_set_on_server(server, client):
+    server.set_discrete_input(1, True)
+    server.set_input_register(2, 0xCAFE)
+
+    assert client.read_discrete_inputs(1, 0, 3) == [False, True, False]
+    assert client.read_input_registers(1, 2, 1) == [0xCAFE]
+
+
+def test_writes_update_server(server, client):
+    client.write_single_coil(1, 4, True)
+    client.write_multiple_registers(1, 0, [1, 2, 3])
+
+    assert server.get_coil(4)
+    assert client.read_holding_registers(1, 0, 3) == [1, 2, 3]
+
+
+def test_exceptions_raise_request_error(client):
+    with pytest.raises(pyrodbus.RequestError) as info:
+        client.read_coils(1, 8, 5)
+    # IllegalDataAddress
+    assert info.value.args[1] == 2002
+
+
+def test_callback_receives_result(server, client):
+    server.set_holding_register(9, 42)
+    done = threading.Event()
+    results = []
+
+    def callback(value, error):
+        results.append((value, error))
+        done.set()
+
+    assert client.read_holding_registers(1, 9, 1, callback=callback) is None
+    assert done.wait(5)
+    assert results == [([42], None)]
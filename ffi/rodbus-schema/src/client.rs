@@ -448,10 +448,24 @@ fn build_write_callback(
     lib: &mut LibraryBuilder,
     common: &CommonDefinitions,
 ) -> BackTraced<FutureInterfaceHandle> {
+    let request_id_field = Name::create("request_id")?;
+
+    let response = lib.declare_callback_argument_struct("write_response")?;
+    let response = lib
+        .define_callback_argument_struct(response)?
+        .add(
+            &request_id_field,
+            Primitive::U64,
+            "Identifier assigned to the request, also used in the log messages of the request",
+        )?
+        .doc("Response to a write request")?
+        .end_fields()?
+        .build()?;
+
     let future = lib.define_future_interface(
         "write_callback",
         "Callback methods received from asynchronous write operations",
-        common.nothing.clone(),
+        response,
         "response",
         Some(common.error_info.clone()),
     )?;
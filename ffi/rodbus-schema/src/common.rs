@@ -2,7 +2,6 @@ use oo_bindgen::model::*;
 
 pub(crate) struct CommonDefinitions {
     pub(crate) error_type: ErrorTypeHandle,
-    pub(crate) nothing: EnumHandle,
     pub(crate) decode_level: UniversalStructHandle,
     pub(crate) runtime_handle: ClassDeclarationHandle,
     pub(crate) error_info: ErrorTypeHandle,
@@ -22,14 +21,12 @@ pub(crate) struct CommonDefinitions {
 impl CommonDefinitions {
     pub(crate) fn build(lib: &mut LibraryBuilder) -> BackTraced<CommonDefinitions> {
         let error_type = build_error_type(lib)?;
-        let nothing = build_nothing_type(lib)?;
         let decode_level = crate::decoding::define(lib)?;
         let bit_value = build_bit_value(lib)?;
         let register_value = build_register_value(lib)?;
 
         Ok(Self {
             error_type: error_type.clone(),
-            nothing,
             decode_level,
             runtime_handle: sfio_tokio_ffi::define(lib, error_type)?,
             error_info: build_request_error(lib)?,
@@ -131,15 +128,6 @@ fn build_error_type(lib: &mut LibraryBuilder) -> BackTraced<ErrorTypeHandle> {
     Ok(definition)
 }
 
-fn build_nothing_type(lib: &mut LibraryBuilder) -> BackTraced<EnumHandle> {
-    let definition = lib.define_enum("nothing")?
-        .push("nothing", "the only value this enum has")?
-        .doc("A single value enum which is used as a placeholder for futures that don't return a value")?
-        .build()?;
-
-    Ok(definition)
-}
-
 fn build_bit_value(lib: &mut LibraryBuilder) -> BackTraced<UniversalStructHandle> {
     let bit = lib.declare_universal_struct("bit_value")?;
     let bit = lib
@@ -256,7 +244,13 @@ fn build_request_error(lib: &mut LibraryBuilder) -> BackTraced<ErrorTypeHandle>
         builder = builder.add_error(format!("modbus_exception_{}", name), desc)?;
     }
 
-    let definition = builder.build()?;
+    // added after the exceptions so that the existing errors keep their values
+    let definition = builder
+        .add_error(
+            "queue_full",
+            "The queue of the channel was full and the request was rejected without being queued",
+        )?
+        .build()?;
 
     Ok(definition)
 }
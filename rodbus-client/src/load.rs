@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{App, Arg};
+use tokio::time::{Instant, MissedTickBehavior};
+
+use rodbus::client::*;
+use rodbus::*;
+
+/// Kind of request in the mix
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    ReadCoils,
+    ReadDiscreteInputs,
+    ReadHoldingRegisters,
+    ReadInputRegisters,
+    WriteSingleCoil,
+    WriteSingleRegister,
+    WriteMultipleCoils,
+    WriteMultipleRegisters,
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "rc" => Ok(Kind::ReadCoils),
+            "rdi" => Ok(Kind::ReadDiscreteInputs),
+            "rhr" => Ok(Kind::ReadHoldingRegisters),
+            "rir" => Ok(Kind::ReadInputRegisters),
+            "wsc" => Ok(Kind::WriteSingleCoil),
+            "wsr" => Ok(Kind::WriteSingleRegister),
+            "wmc" => Ok(Kind::WriteMultipleCoils),
+            "wmr" => Ok(Kind::WriteMultipleRegisters),
+            _ => Err(Error::BadMix(value.to_string())),
+        }
+    }
+}
+
+impl Kind {
+    fn request(self, range: AddressRange) -> Result<TypedRequest, Error> {
+        let request = match self {
+            Kind::ReadCoils => TypedRequest::ReadCoils(range),
+            Kind::ReadDiscreteInputs => TypedRequest::ReadDiscreteInputs(range),
+            Kind::ReadHoldingRegisters => TypedRequest::ReadHoldingRegisters(range),
+            Kind::ReadInputRegisters => TypedRequest::ReadInputRegisters(range),
+            Kind::WriteSingleCoil => {
+                TypedRequest::WriteSingleCoil(Indexed::new(range.start, false))
+            }
+            Kind::WriteSingleRegister => {
+                TypedRequest::WriteSingleRegister(Indexed::new(range.start, 0))
+            }
+            Kind::WriteMultipleCoils => TypedRequest::WriteMultipleCoils(WriteMultiple::from(
+                range.start,
+                vec![false; range.count as usize],
+            )?),
+            Kind::WriteMultipleRegisters => TypedRequest::WriteMultipleRegisters(
+                WriteMultiple::from(range.start, vec![0; range.count as usize])?,
+            ),
+        };
+        Ok(request)
+    }
+}
+
+struct Args {
+    address: SocketAddr,
+    id: UnitId,
+    connections: usize,
+    /// requests per second across all connections, 0 for as fast as possible
+    rate: f64,
+    duration: Duration,
+    timeout: Duration,
+    /// requests issued in turn by every connection, repeated according to their weight
+    mix: Vec<TypedRequest>,
+}
+
+/// Outcome of the requests of one or more connections
+#[derive(Default)]
+struct Results {
+    latencies: Vec<Duration>,
+    errors: BTreeMap<String, u64>,
+}
+
+impl Results {
+    fn merge(&mut self, other: Results) {
+        self.latencies.extend(other.latencies);
+        for (error, count) in other.errors {
+            *self.errors.entry(error).or_insert(0) += count;
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.latencies.len() as u64 + self.errors.values().sum::<u64>()
+    }
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    if let Err(err) = run().await {
+        println!("error: {}", err);
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let args = parse_args()?;
+    let param = RequestParam::new(args.id, args.timeout);
+    // each connection is paced independently
+    let period = if args.rate > 0.0 {
+        Some(Duration::from_secs_f64(args.connections as f64 / args.rate))
+    } else {
+        None
+    };
+
+    let mut channels = Vec::with_capacity(args.connections);
+    for _ in 0..args.connections {
+        let channel = spawn_tcp_client_task(
+            HostAddr::ip(args.address.ip(), args.address.port()),
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        channel.enable().await?;
+        channels.push(channel);
+    }
+
+    let start = Instant::now();
+    let deadline = start + args.duration;
+    let workers: Vec<_> = channels
+        .into_iter()
+        .enumerate()
+        .map(|(index, channel)| {
+            let mix = args.mix.clone();
+            tokio::spawn(run_connection(channel, param, mix, index, period, deadline))
+        })
+        .collect();
+
+    let mut results = Results::default();
+    for worker in workers {
+        if let Ok(x) = worker.await {
+            results.merge(x);
+        }
+    }
+
+    print_report(&args, &mut results, start.elapsed());
+    Ok(())
+}
+
+async fn run_connection(
+    channel: Channel,
+    param: RequestParam,
+    mix: Vec<TypedRequest>,
+    offset: usize,
+    period: Option<Duration>,
+    deadline: Instant,
+) -> Results {
+    let mut results = Results::default();
+    // connections start at different positions of the mix so that it is applied evenly
+    let mut requests = mix.into_iter().cycle().skip(offset);
+    let mut interval = period.map(|period| {
+        let mut interval = tokio::time::interval(period);
+        // the achieved rate is reported instead of bursting to catch up
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    while Instant::now() < deadline {
+        if let Some(interval) = interval.as_mut() {
+            interval.tick().await;
+        }
+        let request = match requests.next() {
+            Some(x) => x,
+            None => break,
+        };
+        let begin = Instant::now();
+        match channel.call(param, request).await {
+            Ok(_) => results.latencies.push(begin.elapsed()),
+            Err(err) => *results.errors.entry(err.to_string()).or_insert(0) += 1,
+        }
+    }
+    results
+}
+
+fn print_report(args: &Args, results: &mut Results, elapsed: Duration) {
+    let total = results.total();
+    let seconds = elapsed.as_secs_f64();
+    let target = if args.rate > 0.0 {
+        format!("{:.1}/s", args.rate)
+    } else {
+        "unlimited".to_string()
+    };
+    println!(
+        "connections: {} duration: {:.1} s target rate: {}",
+        args.connections, seconds, target
+    );
+    println!(
+        "requests: {} ({:.1}/s)",
+        total,
+        total as f64 / seconds.max(f64::EPSILON)
+    );
+
+    let errors = total - results.latencies.len() as u64;
+    let error_rate = if total > 0 {
+        100.0 * errors as f64 / total as f64
+    } else {
+        0.0
+    };
+    println!(
+        "successes: {} errors: {} ({:.2} %)",
+        results.latencies.len(),
+        errors,
+        error_rate
+    );
+
+    results.latencies.sort();
+    if let (Some(min), Some(max)) = (results.latencies.first(), results.latencies.last()) {
+        println!(
+            "latency (ms): min {:.3} p50 {:.3} p90 {:.3} p99 {:.3} max {:.3}",
+            as_ms(*min),
+            as_ms(percentile(&results.latencies, 0.50)),
+            as_ms(percentile(&results.latencies, 0.90)),
+            as_ms(percentile(&results.latencies, 0.99)),
+            as_ms(*max)
+        );
+    }
+
+    for (error, count) in &results.errors {
+        println!("  {}: {}", error, count);
+    }
+}
+
+/// nearest-rank percentile of sorted values
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let matches = App::new("rodbus-load")
+        .about("Generates load against a Modbus TCP server and reports throughput, latency and errors")
+        .arg(
+            Arg::with_name("host")
+                .short("h")
+                .long("host")
+                .takes_value(true)
+                .default_value("127.0.0.1:502")
+                .help("Socket address of the server"),
+        )
+        .arg(
+            Arg::with_name("id")
+                .short("i")
+                .long("id")
+                .takes_value(true)
+                .default_value("1")
+                .help("Unit id of the server"),
+        )
+        .arg(
+            Arg::with_name("connections")
+                .short("c")
+                .long("connections")
+                .takes_value(true)
+                .default_value("1")
+                .help("Number of concurrent connections"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .short("r")
+                .long("rate")
+                .takes_value(true)
+                .default_value("0")
+                .help("Target number of requests per second across all connections, 0 for as fast as possible"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .short("d")
+                .long("duration")
+                .takes_value(true)
+                .default_value("10")
+                .help("Duration of the test in seconds"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .short("t")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Response timeout in milliseconds"),
+        )
+        .arg(
+            Arg::with_name("mix")
+                .short("m")
+                .long("mix")
+                .takes_value(true)
+                .default_value("rhr")
+                .help("Requests to issue as a comma delimited list of <kind>[=<weight>], where the kind is one of rc, rdi, rhr, rir, wsc, wsr, wmc or wmr (e.g. rhr=8,wsr=2)"),
+        )
+        .arg(
+            Arg::with_name("start")
+                .short("s")
+                .long("start")
+                .takes_value(true)
+                .default_value("0")
+                .help("Starting address of the requests"),
+        )
+        .arg(
+            Arg::with_name("quantity")
+                .short("q")
+                .long("quantity")
+                .takes_value(true)
+                .default_value("10")
+                .help("Quantity of values of the read and write multiple requests"),
+        )
+        .get_matches();
+
+    let range = AddressRange::try_from(
+        u16::from_str(matches.value_of("start").unwrap())?,
+        u16::from_str(matches.value_of("quantity").unwrap())?,
+    )?;
+    let connections = usize::from_str(matches.value_of("connections").unwrap())?.max(1);
+    let rate = f64::from_str(matches.value_of("rate").unwrap())
+        .map_err(|_| Error::BadRate(matches.value_of("rate").unwrap().to_string()))?;
+
+    Ok(Args {
+        address: SocketAddr::from_str(matches.value_of("host").unwrap())?,
+        id: UnitId::new(u8::from_str(matches.value_of("id").unwrap())?),
+        connections,
+        rate: rate.max(0.0),
+        duration: Duration::from_secs(u64::from_str(matches.value_of("duration").unwrap())?),
+        timeout: Duration::from_millis(u64::from_str(matches.value_of("timeout").unwrap())?),
+        mix: parse_mix(matches.value_of("mix").unwrap(), range)?,
+    })
+}
+
+fn parse_mix(value: &str, range: AddressRange) -> Result<Vec<TypedRequest>, Error> {
+    let mut mix = Vec::new();
+    for entry in value.split(',') {
+        let (kind, weight) = match entry.split_once('=') {
+            Some((kind, weight)) => (kind, u32::from_str(weight)?),
+            None => (entry, 1),
+        };
+        let request = Kind::from_str(kind)?.request(range)?;
+        for _ in 0..weight {
+            mix.push(request.clone());
+        }
+    }
+    if mix.is_empty() {
+        return Err(Error::BadMix(value.to_string()));
+    }
+    Ok(mix)
+}
+
+#[derive(Debug)]
+enum Error {
+    BadAddr(std::net::AddrParseError),
+    BadInt(std::num::ParseIntError),
+    BadRange(InvalidRange),
+    BadRate(String),
+    BadMix(String),
+    Request(RequestError),
+    Shutdown,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::BadAddr(err) => err.fmt(f),
+            Error::BadInt(err) => err.fmt(f),
+            Error::BadRange(err) => err.fmt(f),
+            Error::BadRate(value) => write!(f, "bad rate: {}", value),
+            Error::BadMix(value) => write!(f, "bad request mix: {}", value),
+            Error::Request(err) => err.fmt(f),
+            Error::Shutdown => f.write_str("channel was shut down"),
+        }
+    }
+}
+
+impl From<std::net::AddrParseError> for Error {
+    fn from(err: std::net::AddrParseError) -> Self {
+        Error::BadAddr(err)
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Error::BadInt(err)
+    }
+}
+
+impl From<InvalidRange> for Error {
+    fn from(err: InvalidRange) -> Self {
+        Error::BadRange(err)
+    }
+}
+
+impl From<InvalidRequest> for Error {
+    fn from(err: InvalidRequest) -> Self {
+        Error::Request(err.into())
+    }
+}
+
+impl From<Shutdown> for Error {
+    fn from(_: Shutdown) -> Self {
+        Error::Shutdown
+    }
+}
@@ -17,7 +17,11 @@ enum Error {
     BadInt(std::num::ParseIntError),
     BadBool(std::str::ParseBoolError),
     BadCharInBitString(char),
-    Request(rodbus::RequestError),
+    BadRangeFormat(String),
+    Io(std::io::Error),
+    BadRequest(InvalidRequest),
+    Request(rodbus::RequestFailure),
+    Scan(RequestError),
     MissingSubCommand,
     Shutdown,
 }
@@ -31,6 +35,25 @@ enum Command {
     WriteSingleCoil(Indexed<bool>),
     WriteMultipleCoils(WriteMultiple<bool>),
     WriteMultipleRegisters(WriteMultiple<u16>),
+    Scan(u8, u8),
+    Conformance(rodbus::conformance::ConformanceTarget),
+}
+
+/// How register values are printed
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    /// unsigned decimal
+    Decimal,
+    /// hexadecimal
+    Hex,
+    /// signed 16-bit integers
+    I16,
+    /// unsigned 32-bit integers from pairs of registers
+    U32,
+    /// signed 32-bit integers from pairs of registers
+    I32,
+    /// IEEE-754 single precision floats from pairs of registers
+    F32,
 }
 
 struct Args {
@@ -38,17 +61,10 @@ struct Args {
     id: UnitId,
     command: Command,
     period: Option<Duration>,
-}
-
-impl Args {
-    fn new(address: SocketAddr, id: UnitId, command: Command, period: Option<Duration>) -> Self {
-        Self {
-            address,
-            id,
-            command,
-            period,
-        }
-    }
+    decode: DecodeLevel,
+    timeout: Duration,
+    format: Format,
+    order: WordOrder,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -68,20 +84,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 async fn run() -> Result<(), Error> {
     let args = parse_args()?;
-    let mut channel = spawn_tcp_client_task(
+    if let Command::Conformance(mut target) = args.command {
+        target.unit_id = args.id;
+        target.timeout = args.timeout;
+        let stream = tokio::net::TcpStream::connect(args.address).await?;
+        let report = rodbus::conformance::run_conformance(stream, &target).await;
+        println!("{}", report);
+        return Ok(());
+    }
+
+    let channel = spawn_tcp_client_task(
         HostAddr::ip(args.address.ip(), args.address.port()),
         1,
         default_retry_strategy(),
-        AppDecodeLevel::DataValues.into(),
+        args.decode,
         None,
     );
     channel.enable().await?;
-    let params = RequestParam::new(args.id, Duration::from_secs(1));
+    let params = RequestParam::new(args.id, args.timeout);
 
     match args.period {
-        None => run_command(&args.command, &mut channel, params).await,
+        None => run_command(&args.command, &channel, params, args.format, args.order).await,
         Some(period) => loop {
-            run_command(&args.command, &mut channel, params).await?;
+            run_command(&args.command, &channel, params, args.format, args.order).await?;
             tokio::time::sleep(period).await
         },
     }
@@ -89,8 +114,10 @@ async fn run() -> Result<(), Error> {
 
 async fn run_command(
     command: &Command,
-    channel: &mut Channel,
+    channel: &Channel,
     params: RequestParam,
+    format: Format,
+    order: WordOrder,
 ) -> Result<(), Error> {
     match command {
         Command::ReadCoils(range) => {
@@ -104,14 +131,12 @@ async fn run_command(
             }
         }
         Command::ReadHoldingRegisters(range) => {
-            for x in channel.read_holding_registers(params, *range).await? {
-                println!("index: {} value: {}", x.index, x.value)
-            }
+            let values = channel.read_holding_registers(params, *range).await?;
+            print_registers(&values, format, order);
         }
         Command::ReadInputRegisters(range) => {
-            for x in channel.read_input_registers(params, *range).await? {
-                println!("index: {} value: {}", x.index, x.value)
-            }
+            let values = channel.read_input_registers(params, *range).await?;
+            print_registers(&values, format, order);
         }
         Command::WriteSingleRegister(arg) => {
             channel.write_single_register(params, *arg).await?;
@@ -127,10 +152,94 @@ async fn run_command(
                 .write_multiple_registers(params, arg.clone())
                 .await?;
         }
+        Command::Scan(first, last) => {
+            scan(channel, params.response_timeout, *first, *last).await?;
+        }
+        // performed with its own connection
+        Command::Conformance(_) => {}
+    }
+    Ok(())
+}
+
+/// Probe every unit id in the range by reading the first holding register
+///
+/// A unit is present if it answers, even with an exception
+async fn scan(channel: &Channel, timeout: Duration, first: u8, last: u8) -> Result<(), Error> {
+    let options = ScanOptions::new(ScanProbe::HoldingRegister(0), timeout, 1);
+    let discovered = scan_units(channel, first..=last, options)
+        .await
+        .map_err(Error::Scan)?;
+    for unit in &discovered {
+        match unit.exception {
+            None => println!("unit id: {} responded", unit.unit.value),
+            Some(ex) => println!(
+                "unit id: {} responded with exception: {}",
+                unit.unit.value, ex
+            ),
+        }
     }
+    println!(
+        "found {} unit(s) between {} and {}",
+        discovered.len(),
+        first,
+        last
+    );
     Ok(())
 }
 
+fn print_registers(values: &[Indexed<u16>], format: Format, order: WordOrder) {
+    match format {
+        Format::Decimal => {
+            for x in values {
+                println!("index: {} value: {}", x.index, x.value)
+            }
+        }
+        Format::Hex => {
+            for x in values {
+                println!("index: {} value: {:#06X}", x.index, x.value)
+            }
+        }
+        Format::I16 => {
+            for x in values {
+                println!("index: {} value: {}", x.index, x.value as i16)
+            }
+        }
+        Format::U32 | Format::I32 | Format::F32 => {
+            let mut pairs = values.chunks_exact(2);
+            for pair in &mut pairs {
+                let registers = [pair[0].value, pair[1].value];
+                match format {
+                    Format::U32 => {
+                        println!(
+                            "index: {} value: {}",
+                            pair[0].index,
+                            order.decode_u32(registers)
+                        )
+                    }
+                    Format::I32 => {
+                        println!(
+                            "index: {} value: {}",
+                            pair[0].index,
+                            order.decode_i32(registers)
+                        )
+                    }
+                    _ => println!(
+                        "index: {} value: {}",
+                        pair[0].index,
+                        order.decode_f32(registers)
+                    ),
+                }
+            }
+            for x in pairs.remainder() {
+                println!(
+                    "index: {} value: {:#06X} (incomplete pair)",
+                    x.index, x.value
+                )
+            }
+        }
+    }
+}
+
 fn get_index(arg: &ArgMatches) -> Result<u16, ParseIntError> {
     u16::from_str(arg.value_of("index").unwrap())
 }
@@ -176,10 +285,52 @@ fn get_period_ms(value: &str) -> Result<Duration, ParseIntError> {
     Ok(Duration::from_millis(num as u64))
 }
 
+fn get_decode_level(value: Option<&str>) -> DecodeLevel {
+    match value {
+        Some("nothing") => DecodeLevel::nothing(),
+        Some("headers") => DecodeLevel::headers(),
+        Some("values") => DecodeLevel::data_values(),
+        Some("everything") => DecodeLevel::everything(),
+        _ => AppDecodeLevel::DataValues.into(),
+    }
+}
+
+fn get_format(value: Option<&str>) -> Format {
+    match value {
+        Some("hex") => Format::Hex,
+        Some("i16") => Format::I16,
+        Some("u32") => Format::U32,
+        Some("i32") => Format::I32,
+        Some("f32") => Format::F32,
+        _ => Format::Decimal,
+    }
+}
+
+fn get_word_order(value: Option<&str>) -> WordOrder {
+    match value {
+        Some("low") => WordOrder::LowFirst,
+        _ => WordOrder::HighFirst,
+    }
+}
+
 fn get_address_range(arg: &ArgMatches) -> Result<AddressRange, Error> {
     Ok(AddressRange::try_from(get_start(arg)?, get_quantity(arg)?)?)
 }
 
+fn get_optional_range(value: Option<&str>) -> Result<Option<AddressRange>, Error> {
+    let value = match value {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let (start, count) = value
+        .split_once(':')
+        .ok_or_else(|| Error::BadRangeFormat(value.to_string()))?;
+    Ok(Some(AddressRange::try_from(
+        u16::from_str(start)?,
+        u16::from_str(count)?,
+    )?))
+}
+
 fn get_indexed_register_value(arg: &ArgMatches) -> Result<Indexed<u16>, Error> {
     Ok(Indexed::new(get_index(arg)?, get_value(arg)?))
 }
@@ -229,6 +380,23 @@ fn get_command(matches: &ArgMatches) -> Result<Command, Error> {
         )?));
     }
 
+    if let Some(matches) = matches.subcommand_matches("scan") {
+        let first = u8::from_str(matches.value_of("first").unwrap())?;
+        let last = u8::from_str(matches.value_of("last").unwrap())?;
+        return Ok(Command::Scan(first, last));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("conformance") {
+        // the unit id and timeout are set from the global arguments
+        let mut target = rodbus::conformance::ConformanceTarget::new(UnitId::new(1));
+        target.coils = get_optional_range(matches.value_of("coils"))?;
+        target.discrete_inputs = get_optional_range(matches.value_of("discrete-inputs"))?;
+        target.holding_registers = get_optional_range(matches.value_of("holding-registers"))?;
+        target.input_registers = get_optional_range(matches.value_of("input-registers"))?;
+        target.write = matches.is_present("write");
+        return Ok(Command::Conformance(target));
+    }
+
     Err(Error::MissingSubCommand)
 }
 
@@ -262,6 +430,42 @@ fn parse_args() -> Result<Args, Error> {
                 .required(false)
                 .help("Optional polling period in milliseconds"),
         )
+        .arg(
+            Arg::with_name("decode")
+                .short("d")
+                .long("decode")
+                .takes_value(true)
+                .required(false)
+                .possible_values(&["nothing", "headers", "values", "everything"])
+                .help("Optional protocol decode level (defaults to decoding application values only)"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .short("t")
+                .long("timeout")
+                .takes_value(true)
+                .required(false)
+                .default_value("1000")
+                .help("Response timeout in milliseconds"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .required(false)
+                .possible_values(&["dec", "hex", "i16", "u32", "i32", "f32"])
+                .help("Optional format of register values (defaults to unsigned decimal). 32-bit formats combine pairs of registers in the word order"),
+        )
+        .arg(
+            Arg::with_name("word-order")
+                .short("o")
+                .long("word-order")
+                .takes_value(true)
+                .required(false)
+                .possible_values(&["high", "low"])
+                .help("Optional order of the registers combined by the 32-bit formats (defaults to the high word first)"),
+        )
         .subcommand(
             SubCommand::with_name("rc")
                 .about("read coils")
@@ -422,6 +626,64 @@ fn parse_args() -> Result<Args, Error> {
                         .help("the values of the registers specified as a comma delimited list (e.g. 1,4,7)"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("scan for unit ids that respond to a read of the first holding register")
+                .arg(
+                    Arg::with_name("first")
+                        .short("f")
+                        .long("first")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("the first unit id to probe"),
+                )
+                .arg(
+                    Arg::with_name("last")
+                        .short("l")
+                        .long("last")
+                        .takes_value(true)
+                        .default_value("247")
+                        .help("the last unit id to probe"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("conformance")
+                .about("check the conformance of the server to the Modbus specification")
+                .arg(
+                    Arg::with_name("coils")
+                        .short("c")
+                        .long("coils")
+                        .takes_value(true)
+                        .help("the coils that exist on the server as <start>:<count>"),
+                )
+                .arg(
+                    Arg::with_name("discrete-inputs")
+                        .short("d")
+                        .long("discrete-inputs")
+                        .takes_value(true)
+                        .help("the discrete inputs that exist on the server as <start>:<count>"),
+                )
+                .arg(
+                    Arg::with_name("holding-registers")
+                        .short("r")
+                        .long("holding-registers")
+                        .takes_value(true)
+                        .help("the holding registers that exist on the server as <start>:<count>"),
+                )
+                .arg(
+                    Arg::with_name("input-registers")
+                        .short("n")
+                        .long("input-registers")
+                        .takes_value(true)
+                        .help("the input registers that exist on the server as <start>:<count>"),
+                )
+                .arg(
+                    Arg::with_name("write")
+                        .short("w")
+                        .long("write")
+                        .help("also check writes, which write back the values that were read"),
+                ),
+        )
         .get_matches();
 
     let address = SocketAddr::from_str(matches.value_of("host").unwrap())?;
@@ -430,9 +692,22 @@ fn parse_args() -> Result<Args, Error> {
         Some(s) => Some(get_period_ms(s)?),
         None => None,
     };
+    let decode = get_decode_level(matches.value_of("decode"));
+    let timeout = get_period_ms(matches.value_of("timeout").unwrap())?;
+    let format = get_format(matches.value_of("format"));
+    let order = get_word_order(matches.value_of("word-order"));
     let command = get_command(&matches)?;
 
-    Ok(Args::new(address, id, command, period))
+    Ok(Args {
+        address,
+        id,
+        command,
+        period,
+        decode,
+        timeout,
+        format,
+        order,
+    })
 }
 
 impl std::error::Error for Error {}
@@ -445,19 +720,31 @@ impl std::fmt::Display for Error {
             Error::BadInt(err) => err.fmt(f),
             Error::BadBool(err) => err.fmt(f),
             Error::BadCharInBitString(char) => write!(f, "Bad character in bit string: {}", char),
+            Error::BadRangeFormat(value) => {
+                write!(f, "Bad range (expected <start>:<count>): {}", value)
+            }
+            Error::Io(err) => err.fmt(f),
+            Error::BadRequest(err) => err.fmt(f),
             Error::Request(err) => err.fmt(f),
+            Error::Scan(err) => write!(f, "scan failed: {}", err),
             Error::MissingSubCommand => f.write_str("No sub-command provided"),
             Error::Shutdown => f.write_str("channel was shut down"),
         }
     }
 }
 
-impl From<rodbus::RequestError> for Error {
-    fn from(err: rodbus::RequestError) -> Self {
+impl From<rodbus::RequestFailure> for Error {
+    fn from(err: rodbus::RequestFailure) -> Self {
         Error::Request(err)
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
 impl From<AddrParseError> for Error {
     fn from(err: AddrParseError) -> Self {
         Error::BadAddr(err)
@@ -484,7 +771,7 @@ impl From<InvalidRange> for Error {
 
 impl From<InvalidRequest> for Error {
     fn from(err: InvalidRequest) -> Self {
-        Error::Request(err.into())
+        Error::BadRequest(err)
     }
 }
 
@@ -0,0 +1,413 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches};
+
+use rodbus::client::*;
+use rodbus::*;
+
+/// Number of raw frames displayed in the traffic pane
+const TRAFFIC_LINES: usize = 12;
+
+// ANSI escape sequences
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Table {
+    Coils,
+    DiscreteInputs,
+    HoldingRegisters,
+    InputRegisters,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Table::Coils => "coils",
+            Table::DiscreteInputs => "discrete inputs",
+            Table::HoldingRegisters => "holding registers",
+            Table::InputRegisters => "input registers",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Poll {
+    table: Table,
+    range: AddressRange,
+}
+
+/// Values of the last successful read of every point, used to highlight changes
+#[derive(Default)]
+struct Snapshot {
+    values: HashMap<(Table, u16), u16>,
+}
+
+impl Snapshot {
+    /// Record a value and return true if it differs from the previous one
+    fn update(&mut self, table: Table, index: u16, value: u16) -> bool {
+        match self.values.insert((table, index), value) {
+            Some(previous) => previous != value,
+            None => false,
+        }
+    }
+}
+
+/// Interceptor that keeps the most recent frames exchanged on the channel
+#[derive(Clone, Default)]
+struct Traffic {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Traffic {
+    fn push(&self, direction: &str, id: UnitId, pdu: &[u8]) {
+        let mut line = format!("{} unit: {} pdu:", direction, id);
+        for byte in pdu {
+            let _ = write!(line, " {:02X}", byte);
+        }
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == TRAFFIC_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Interceptor for Traffic {
+    fn on_request(&mut self, id: UnitId, _function: u8, pdu: &[u8]) -> Result<(), RequestError> {
+        self.push("TX", id, pdu);
+        Ok(())
+    }
+
+    fn on_response(&mut self, id: UnitId, _function: u8, pdu: &[u8]) -> Result<(), RequestError> {
+        self.push("RX", id, pdu);
+        Ok(())
+    }
+}
+
+enum Transport {
+    Tcp(SocketAddr),
+    #[cfg(feature = "serial")]
+    Rtu(String, rodbus::SerialSettings),
+}
+
+struct Args {
+    transport: Transport,
+    id: UnitId,
+    period: Duration,
+    timeout: Duration,
+    polls: Vec<Poll>,
+}
+
+#[derive(Debug)]
+enum Error {
+    BadRange(InvalidRange),
+    BadAddr(std::net::AddrParseError),
+    BadInt(std::num::ParseIntError),
+    BadRangeFormat(String),
+    NothingToPoll,
+    Shutdown,
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    if let Err(err) = run().await {
+        println!("error: {}", err);
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let args = parse_args()?;
+
+    // traffic is displayed from the interceptor, so the decode logging is disabled
+    let channel = match &args.transport {
+        Transport::Tcp(address) => spawn_tcp_client_task(
+            HostAddr::ip(address.ip(), address.port()),
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        ),
+        #[cfg(feature = "serial")]
+        Transport::Rtu(path, settings) => spawn_rtu_client_task(
+            path,
+            *settings,
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        ),
+    };
+
+    let traffic = Traffic::default();
+    channel.set_interceptor(Box::new(traffic.clone())).await?;
+    channel.enable().await?;
+
+    let params = RequestParam::new(args.id, args.timeout);
+    let mut snapshot = Snapshot::default();
+    loop {
+        let mut screen = String::new();
+        let _ = writeln!(
+            screen,
+            "{}rodbus-monitor - unit: {} - polling every {} ms (ctrl-c to exit)\n",
+            CLEAR_SCREEN,
+            args.id,
+            args.period.as_millis()
+        );
+        for poll in &args.polls {
+            let result = read(&channel, params, *poll).await;
+            render(&mut screen, &mut snapshot, *poll, result);
+        }
+        let _ = writeln!(screen, "traffic:");
+        for line in traffic.lines() {
+            let _ = writeln!(screen, "  {}", line);
+        }
+        print!("{}", screen);
+        tokio::time::sleep(args.period).await;
+    }
+}
+
+async fn read(
+    channel: &Channel,
+    params: RequestParam,
+    poll: Poll,
+) -> Result<Vec<Indexed<u16>>, RequestFailure> {
+    let bits = |values: Vec<Indexed<bool>>| {
+        values
+            .into_iter()
+            .map(|x| Indexed::new(x.index, x.value as u16))
+            .collect()
+    };
+
+    match poll.table {
+        Table::Coils => Ok(bits(channel.read_coils(params, poll.range).await?)),
+        Table::DiscreteInputs => Ok(bits(
+            channel.read_discrete_inputs(params, poll.range).await?,
+        )),
+        Table::HoldingRegisters => channel.read_holding_registers(params, poll.range).await,
+        Table::InputRegisters => channel.read_input_registers(params, poll.range).await,
+    }
+}
+
+fn render(
+    screen: &mut String,
+    snapshot: &mut Snapshot,
+    poll: Poll,
+    result: Result<Vec<Indexed<u16>>, RequestFailure>,
+) {
+    let _ = writeln!(screen, "{} ({})", poll.table.name(), poll.range);
+    match result {
+        Ok(values) => {
+            for x in values {
+                let changed = snapshot.update(poll.table, x.index, x.value);
+                let (start, end) = if changed {
+                    (HIGHLIGHT, RESET)
+                } else {
+                    ("", "")
+                };
+                let _ = writeln!(
+                    screen,
+                    "  {}{:>5}: {:>5} ({:#06X}){}",
+                    start, x.index, x.value, x.value, end
+                );
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(screen, "  error: {}", err);
+        }
+    }
+    let _ = writeln!(screen);
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let app = App::new("rodbus-monitor")
+        .about("Polls a Modbus device and displays live values and the raw traffic")
+        .arg(
+            Arg::with_name("host")
+                .short("h")
+                .long("host")
+                .takes_value(true)
+                .default_value("127.0.0.1:502")
+                .help("The socket address to connect to"),
+        )
+        .arg(
+            Arg::with_name("id")
+                .short("i")
+                .long("id")
+                .takes_value(true)
+                .default_value("1")
+                .help("The unit id to poll"),
+        )
+        .arg(
+            Arg::with_name("period")
+                .short("p")
+                .long("period")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Polling period in milliseconds"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .short("t")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Response timeout in milliseconds"),
+        )
+        .arg(range_arg(
+            "coils",
+            "c",
+            "Range of coils to poll as <start>:<count>, may be repeated",
+        ))
+        .arg(range_arg(
+            "discrete-inputs",
+            "d",
+            "Range of discrete inputs to poll as <start>:<count>, may be repeated",
+        ))
+        .arg(range_arg(
+            "holding-registers",
+            "r",
+            "Range of holding registers to poll as <start>:<count>, may be repeated",
+        ))
+        .arg(range_arg(
+            "input-registers",
+            "n",
+            "Range of input registers to poll as <start>:<count>, may be repeated",
+        ));
+
+    #[cfg(feature = "serial")]
+    let app = app
+        .arg(
+            Arg::with_name("serial")
+                .short("s")
+                .long("serial")
+                .takes_value(true)
+                .help("Poll an RTU device on this serial port instead of connecting over TCP"),
+        )
+        .arg(
+            Arg::with_name("baud")
+                .short("b")
+                .long("baud")
+                .takes_value(true)
+                .default_value("9600")
+                .help("Baud rate of the serial port"),
+        );
+
+    let matches = app.get_matches();
+
+    let mut polls = Vec::new();
+    for (name, table) in [
+        ("coils", Table::Coils),
+        ("discrete-inputs", Table::DiscreteInputs),
+        ("holding-registers", Table::HoldingRegisters),
+        ("input-registers", Table::InputRegisters),
+    ] {
+        for value in matches.values_of(name).into_iter().flatten() {
+            polls.push(Poll {
+                table,
+                range: parse_range(value)?,
+            });
+        }
+    }
+    if polls.is_empty() {
+        return Err(Error::NothingToPoll);
+    }
+
+    Ok(Args {
+        transport: get_transport(&matches)?,
+        id: UnitId::new(u8::from_str(matches.value_of("id").unwrap())?),
+        period: Duration::from_millis(u64::from_str(matches.value_of("period").unwrap())?),
+        timeout: Duration::from_millis(u64::from_str(matches.value_of("timeout").unwrap())?),
+        polls,
+    })
+}
+
+fn range_arg(name: &'static str, short: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help(help)
+}
+
+#[cfg(feature = "serial")]
+fn get_transport(matches: &ArgMatches) -> Result<Transport, Error> {
+    if let Some(path) = matches.value_of("serial") {
+        let settings = rodbus::SerialSettings {
+            baud_rate: u32::from_str(matches.value_of("baud").unwrap())?,
+            ..Default::default()
+        };
+        return Ok(Transport::Rtu(path.to_string(), settings));
+    }
+    Ok(Transport::Tcp(SocketAddr::from_str(
+        matches.value_of("host").unwrap(),
+    )?))
+}
+
+#[cfg(not(feature = "serial"))]
+fn get_transport(matches: &ArgMatches) -> Result<Transport, Error> {
+    Ok(Transport::Tcp(SocketAddr::from_str(
+        matches.value_of("host").unwrap(),
+    )?))
+}
+
+fn parse_range(value: &str) -> Result<AddressRange, Error> {
+    let (start, count) = value
+        .split_once(':')
+        .ok_or_else(|| Error::BadRangeFormat(value.to_string()))?;
+    Ok(AddressRange::try_from(
+        u16::from_str(start)?,
+        u16::from_str(count)?,
+    )?)
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::BadRange(err) => write!(f, "{}", err),
+            Error::BadAddr(err) => write!(f, "{}", err),
+            Error::BadInt(err) => write!(f, "{}", err),
+            Error::BadRangeFormat(value) => {
+                write!(f, "bad range '{}', expected <start>:<count>", value)
+            }
+            Error::NothingToPoll => f.write_str("specify at least one range to poll"),
+            Error::Shutdown => f.write_str("channel was shut down"),
+        }
+    }
+}
+
+impl From<InvalidRange> for Error {
+    fn from(err: InvalidRange) -> Self {
+        Error::BadRange(err)
+    }
+}
+
+impl From<std::net::AddrParseError> for Error {
+    fn from(err: std::net::AddrParseError) -> Self {
+        Error::BadAddr(err)
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Error::BadInt(err)
+    }
+}
+
+impl From<Shutdown> for Error {
+    fn from(_: Shutdown) -> Self {
+        Error::Shutdown
+    }
+}
@@ -0,0 +1,315 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use rodbus::client::*;
+use rodbus::*;
+
+/// Modbus table addressed by a request path
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Table {
+    Coils,
+    DiscreteInputs,
+    HoldingRegisters,
+    InputRegisters,
+}
+
+impl FromStr for Table {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coils" => Ok(Table::Coils),
+            "discrete-inputs" => Ok(Table::DiscreteInputs),
+            "holding-registers" => Ok(Table::HoldingRegisters),
+            "input-registers" => Ok(Table::InputRegisters),
+            _ => Err(ApiError::not_found(format!("unknown table: {}", s))),
+        }
+    }
+}
+
+/// Request decoded from the method, path and body of an HTTP request
+#[derive(Clone, Debug)]
+pub(crate) enum Route {
+    Read(UnitId, Table, AddressRange),
+    WriteSingleCoil(UnitId, Indexed<bool>),
+    WriteSingleRegister(UnitId, Indexed<u16>),
+    WriteMultipleCoils(UnitId, WriteMultiple<bool>),
+    WriteMultipleRegisters(UnitId, WriteMultiple<u16>),
+}
+
+/// Error returned to the HTTP client
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ApiError {
+    pub(crate) status: u16,
+    message: String,
+    code: Option<u16>,
+}
+
+impl ApiError {
+    pub(crate) fn bad_request(message: String) -> Self {
+        Self {
+            status: 400,
+            message,
+            code: None,
+        }
+    }
+
+    fn not_found(message: String) -> Self {
+        Self {
+            status: 404,
+            message,
+            code: None,
+        }
+    }
+
+    pub(crate) fn method_not_allowed() -> Self {
+        Self {
+            status: 405,
+            message: "method not allowed".to_string(),
+            code: None,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({ "error": self.message, "code": self.code })
+    }
+}
+
+impl From<RequestError> for ApiError {
+    fn from(err: RequestError) -> Self {
+        let status = match err.kind() {
+            ErrorKind::BadRequest => 400,
+            ErrorKind::Io if err == RequestError::ResponseTimeout => 504,
+            _ => 502,
+        };
+        Self {
+            status,
+            message: err.to_string(),
+            code: Some(err.code()),
+        }
+    }
+}
+
+impl From<RequestFailure> for ApiError {
+    fn from(err: RequestFailure) -> Self {
+        Self {
+            message: err.to_string(),
+            ..err.error().clone().into()
+        }
+    }
+}
+
+impl From<InvalidRange> for ApiError {
+    fn from(err: InvalidRange) -> Self {
+        Self::bad_request(err.to_string())
+    }
+}
+
+impl From<InvalidRequest> for ApiError {
+    fn from(err: InvalidRequest) -> Self {
+        RequestError::from(err).into()
+    }
+}
+
+/// Decode a request
+///
+/// The path has the form `/<unit id>/<table>/<start>` with an optional `count` query parameter
+/// for reads. Writes take a single value or an array of values as a JSON body.
+pub(crate) fn route(method: &str, target: &str, body: &[u8]) -> Result<Route, ApiError> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+    let (id, table, start) = match segments.as_slice() {
+        [id, table, start] => (
+            UnitId::new(parse(id, "unit id")?),
+            Table::from_str(table)?,
+            parse::<u16>(start, "start")?,
+        ),
+        _ => {
+            return Err(ApiError::not_found(
+                "expected a path of the form /<unit id>/<table>/<start>".to_string(),
+            ))
+        }
+    };
+
+    match method {
+        "GET" => {
+            let count = match query.split('&').find_map(|x| x.strip_prefix("count=")) {
+                Some(count) => parse(count, "count")?,
+                None => 1,
+            };
+            Ok(Route::Read(
+                id,
+                table,
+                AddressRange::try_from(start, count)?,
+            ))
+        }
+        "POST" | "PUT" => {
+            let value: Value = serde_json::from_slice(body)
+                .map_err(|err| ApiError::bad_request(format!("bad JSON body: {}", err)))?;
+            match (table, value) {
+                (Table::Coils, Value::Array(values)) => Ok(Route::WriteMultipleCoils(
+                    id,
+                    WriteMultiple::from(start, collect(values, to_bool)?)?,
+                )),
+                (Table::Coils, value) => Ok(Route::WriteSingleCoil(
+                    id,
+                    Indexed::new(start, to_bool(value)?),
+                )),
+                (Table::HoldingRegisters, Value::Array(values)) => {
+                    Ok(Route::WriteMultipleRegisters(
+                        id,
+                        WriteMultiple::from(start, collect(values, to_u16)?)?,
+                    ))
+                }
+                (Table::HoldingRegisters, value) => Ok(Route::WriteSingleRegister(
+                    id,
+                    Indexed::new(start, to_u16(value)?),
+                )),
+                _ => Err(ApiError::method_not_allowed()),
+            }
+        }
+        _ => Err(ApiError::method_not_allowed()),
+    }
+}
+
+/// Perform the Modbus request and build the JSON response
+pub(crate) async fn execute(
+    channel: &Channel,
+    timeout: Duration,
+    route: Route,
+) -> Result<Value, ApiError> {
+    let bits = |values: Vec<Indexed<bool>>| {
+        values
+            .into_iter()
+            .map(|x| json!({ "index": x.index, "value": x.value }))
+            .collect::<Vec<Value>>()
+    };
+    let registers = |values: Vec<Indexed<u16>>| {
+        values
+            .into_iter()
+            .map(|x| json!({ "index": x.index, "value": x.value }))
+            .collect::<Vec<Value>>()
+    };
+
+    let response = match route {
+        Route::Read(id, table, range) => {
+            let param = RequestParam::new(id, timeout);
+            let values = match table {
+                Table::Coils => bits(channel.read_coils(param, range).await?),
+                Table::DiscreteInputs => bits(channel.read_discrete_inputs(param, range).await?),
+                Table::HoldingRegisters => {
+                    registers(channel.read_holding_registers(param, range).await?)
+                }
+                Table::InputRegisters => {
+                    registers(channel.read_input_registers(param, range).await?)
+                }
+            };
+            json!({ "values": values })
+        }
+        Route::WriteSingleCoil(id, value) => {
+            let value = channel
+                .write_single_coil(RequestParam::new(id, timeout), value)
+                .await?;
+            json!({ "values": bits(vec![value]) })
+        }
+        Route::WriteSingleRegister(id, value) => {
+            let value = channel
+                .write_single_register(RequestParam::new(id, timeout), value)
+                .await?;
+            json!({ "values": registers(vec![value]) })
+        }
+        Route::WriteMultipleCoils(id, values) => {
+            let range = channel
+                .write_multiple_coils(RequestParam::new(id, timeout), values)
+                .await?;
+            json!({ "start": range.start, "count": range.count })
+        }
+        Route::WriteMultipleRegisters(id, values) => {
+            let range = channel
+                .write_multiple_registers(RequestParam::new(id, timeout), values)
+                .await?;
+            json!({ "start": range.start, "count": range.count })
+        }
+    };
+
+    Ok(response)
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> Result<T, ApiError> {
+    T::from_str(value).map_err(|_| ApiError::bad_request(format!("bad {}: {}", name, value)))
+}
+
+fn collect<T>(values: Vec<Value>, f: fn(Value) -> Result<T, ApiError>) -> Result<Vec<T>, ApiError> {
+    values.into_iter().map(f).collect()
+}
+
+fn to_bool(value: Value) -> Result<bool, ApiError> {
+    value
+        .as_bool()
+        .ok_or_else(|| ApiError::bad_request(format!("expected a boolean: {}", value)))
+}
+
+fn to_u16(value: Value) -> Result<u16, ApiError> {
+    value
+        .as_u64()
+        .and_then(|x| u16::try_from(x).ok())
+        .ok_or_else(|| {
+            ApiError::bad_request(format!("expected a 16-bit unsigned value: {}", value))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_reads_with_optional_count() {
+        assert!(matches!(
+            route("GET", "/1/holding-registers/7?count=3", b""),
+            Ok(Route::Read(id, Table::HoldingRegisters, range))
+                if id == UnitId::new(1) && range == AddressRange::try_from(7, 3).unwrap()
+        ));
+        assert!(matches!(
+            route("GET", "/2/coils/0", b""),
+            Ok(Route::Read(id, Table::Coils, range))
+                if id == UnitId::new(2) && range == AddressRange::try_from(0, 1).unwrap()
+        ));
+    }
+
+    #[test]
+    fn routes_single_and_multiple_writes_from_body() {
+        assert!(matches!(
+            route("POST", "/1/coils/3", b"true"),
+            Ok(Route::WriteSingleCoil(_, value)) if value == Indexed::new(3, true)
+        ));
+        assert!(matches!(
+            route("PUT", "/1/holding-registers/3", b"[1, 2]"),
+            Ok(Route::WriteMultipleRegisters(_, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(route("GET", "/1/foo/0", b"").unwrap_err().status, 404);
+        assert_eq!(route("GET", "/1/coils", b"").unwrap_err().status, 404);
+        assert_eq!(
+            route("GET", "/1/coils/0?count=0", b"").unwrap_err().status,
+            400
+        );
+        assert_eq!(
+            route("POST", "/1/holding-registers/0", b"70000")
+                .unwrap_err()
+                .status,
+            400
+        );
+        assert_eq!(
+            route("POST", "/1/input-registers/0", b"1")
+                .unwrap_err()
+                .status,
+            405
+        );
+        assert_eq!(route("DELETE", "/1/coils/0", b"").unwrap_err().status, 405);
+    }
+}
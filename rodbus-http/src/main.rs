@@ -0,0 +1,221 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{App, Arg};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use rodbus::client::*;
+use rodbus::*;
+
+mod api;
+
+/// Maximum size of the request line and headers
+const MAX_HEADER_SIZE: usize = 8192;
+/// Maximum size of a request body
+const MAX_BODY_SIZE: usize = 65536;
+
+struct Args {
+    modbus: SocketAddr,
+    listen: SocketAddr,
+    timeout: Duration,
+    decode: DecodeLevel,
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .init();
+
+    let args = parse_args()?;
+
+    let channel = spawn_tcp_client_task(
+        args.modbus.into(),
+        16,
+        default_retry_strategy(),
+        args.decode,
+        None,
+    );
+    let statistics = ChannelStatistics::new();
+    channel
+        .set_metrics_listener(Box::new(statistics.clone()))
+        .await?;
+    channel.enable().await?;
+
+    let listener = TcpListener::bind(args.listen).await?;
+    tracing::info!("listening on: {}", args.listen);
+    loop {
+        let (socket, addr) = listener.accept().await?;
+        let channel = channel.clone();
+        let statistics = statistics.clone();
+        let timeout = args.timeout;
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(socket, channel, statistics, timeout).await {
+                tracing::warn!("connection from {} failed: {}", addr, err);
+            }
+        });
+    }
+}
+
+/// Serve a single request and close the connection
+async fn handle_connection(
+    mut socket: TcpStream,
+    channel: Channel,
+    statistics: ChannelStatistics,
+    timeout: Duration,
+) -> std::io::Result<()> {
+    const JSON: &str = "application/json";
+
+    let (status, content_type, body) = match read_request(&mut socket).await? {
+        Err(err) => (err.status, JSON, err.to_json().to_string()),
+        Ok((method, target, _)) if method == "GET" && target == "/metrics" => {
+            (200, "text/plain; version=0.0.4", statistics.to_prometheus())
+        }
+        Ok((method, target, body)) => {
+            tracing::info!("{} {}", method, target);
+            let result = match api::route(&method, &target, &body) {
+                Ok(route) => api::execute(&channel, timeout, route).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(value) => (200, JSON, value.to_string()),
+                Err(err) => (err.status, JSON, err.to_json().to_string()),
+            }
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        content_type,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+type Request = (String, String, Vec<u8>);
+
+/// Read the method, target and body of a request
+///
+/// I/O errors are returned in the outer result, malformed requests in the inner result
+async fn read_request(socket: &mut TcpStream) -> std::io::Result<Result<Request, api::ApiError>> {
+    let mut buffer = Vec::new();
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|x| x == b"\r\n\r\n") {
+            break pos;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Ok(Err(api::ApiError::bad_request(
+                "headers too large".to_string(),
+            )));
+        }
+        let mut chunk = [0u8; 1024];
+        let count = socket.read(&mut chunk).await?;
+        if count == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buffer.extend_from_slice(&chunk[..count]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (method, target) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => {
+            return Ok(Err(api::ApiError::bad_request(
+                "bad request line".to_string(),
+            )))
+        }
+    };
+
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| usize::from_str(value.trim()).ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_SIZE {
+        return Ok(Err(api::ApiError::bad_request(
+            "body too large".to_string(),
+        )));
+    }
+
+    let mut body = buffer.split_off(header_end + 4);
+    while body.len() < content_length {
+        let mut chunk = [0u8; 1024];
+        let count = socket.read(&mut chunk).await?;
+        if count == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        body.extend_from_slice(&chunk[..count]);
+    }
+    body.truncate(content_length);
+
+    Ok(Ok((method, target, body)))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        502 => "Bad Gateway",
+        504 => "Gateway Timeout",
+        _ => "Error",
+    }
+}
+
+fn parse_args() -> Result<Args, Box<dyn std::error::Error>> {
+    let matches = App::new("rodbus-http")
+        .about("Translates HTTP/JSON requests to Modbus TCP requests")
+        .arg(
+            Arg::with_name("host")
+                .short("h")
+                .long("host")
+                .takes_value(true)
+                .default_value("127.0.0.1:502")
+                .help("The socket address of the Modbus server"),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .short("l")
+                .long("listen")
+                .takes_value(true)
+                .default_value("127.0.0.1:8080")
+                .help("The socket address on which to accept HTTP requests"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .short("t")
+                .long("timeout")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Modbus response timeout in milliseconds"),
+        )
+        .arg(
+            Arg::with_name("decode")
+                .short("d")
+                .long("decode")
+                .takes_value(false)
+                .help("Decode the Modbus requests and responses"),
+        )
+        .get_matches();
+
+    Ok(Args {
+        modbus: SocketAddr::from_str(matches.value_of("host").unwrap())?,
+        listen: SocketAddr::from_str(matches.value_of("listen").unwrap())?,
+        timeout: Duration::from_millis(u64::from_str(matches.value_of("timeout").unwrap())?),
+        decode: if matches.is_present("decode") {
+            DecodeLevel::data_values()
+        } else {
+            DecodeLevel::nothing()
+        },
+    })
+}
@@ -0,0 +1,116 @@
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+
+/// Modbus table from which a point is read
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Table {
+    Coils,
+    DiscreteInputs,
+    HoldingRegisters,
+    InputRegisters,
+}
+
+/// A named point of the register map
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub(crate) struct Point {
+    /// Name of the point, used as the last level of its topic
+    pub(crate) name: String,
+    pub(crate) table: Table,
+    pub(crate) address: u16,
+}
+
+/// Configuration of the gateway, loaded from a JSON file
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub(crate) struct Config {
+    /// Socket address of the Modbus server
+    pub(crate) modbus: SocketAddr,
+    /// Socket address of the MQTT broker
+    pub(crate) broker: SocketAddr,
+    #[serde(default = "default_client_id")]
+    pub(crate) client_id: String,
+    #[serde(default)]
+    pub(crate) username: Option<String>,
+    #[serde(default)]
+    pub(crate) password: Option<String>,
+    /// Prefix of every published topic
+    pub(crate) topic_prefix: String,
+    #[serde(default = "default_unit_id")]
+    pub(crate) unit_id: u8,
+    #[serde(default = "default_period_ms")]
+    pub(crate) period_ms: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub(crate) timeout_ms: u64,
+    #[serde(default = "default_keep_alive_s")]
+    pub(crate) keep_alive_s: u64,
+    pub(crate) points: Vec<Point>,
+}
+
+impl Config {
+    pub(crate) fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Topic on which the availability of the gateway itself is published
+    pub(crate) fn status_topic(&self) -> String {
+        format!("{}/status", self.topic_prefix)
+    }
+
+    /// Topic on which the availability of the Modbus device is published
+    pub(crate) fn device_topic(&self) -> String {
+        format!("{}/device", self.topic_prefix)
+    }
+
+    pub(crate) fn point_topic(&self, point: &Point) -> String {
+        format!("{}/{}", self.topic_prefix, point.name)
+    }
+}
+
+fn default_client_id() -> String {
+    "rodbus-mqtt".to_string()
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+fn default_period_ms() -> u64 {
+    1000
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_keep_alive_s() -> u64 {
+    30
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config_with_defaults() {
+        let config = Config::from_json(
+            r#"{
+                "modbus": "127.0.0.1:502",
+                "broker": "127.0.0.1:1883",
+                "topic_prefix": "plant/pump1",
+                "points": [
+                    { "name": "running", "table": "coils", "address": 0 },
+                    { "name": "speed", "table": "input-registers", "address": 3 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.client_id, "rodbus-mqtt");
+        assert_eq!(config.unit_id, 1);
+        assert_eq!(config.username, None);
+        assert_eq!(config.points[1].table, Table::InputRegisters);
+        assert_eq!(config.point_topic(&config.points[1]), "plant/pump1/speed");
+        assert_eq!(config.status_topic(), "plant/pump1/status");
+    }
+}
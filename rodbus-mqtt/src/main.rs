@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use clap::{App, Arg};
+use tokio::net::TcpStream;
+
+use rodbus::client::*;
+use rodbus::*;
+
+use crate::config::{Config, Point, Table};
+
+mod config;
+mod mqtt;
+
+/// Delay between attempts to connect to the broker
+const BROKER_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const ONLINE: &[u8] = b"online";
+const OFFLINE: &[u8] = b"offline";
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .init();
+
+    let matches = App::new("rodbus-mqtt")
+        .about("Polls a Modbus device and publishes the values of a register map to MQTT")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .takes_value(true)
+                .required(true)
+                .help("Path to the JSON configuration file"),
+        )
+        .get_matches();
+    let config = Config::from_json(&std::fs::read_to_string(
+        matches.value_of("config").unwrap(),
+    )?)?;
+
+    let channel = spawn_tcp_client_task(
+        config.modbus.into(),
+        1,
+        default_retry_strategy(),
+        DecodeLevel::nothing(),
+        None,
+    );
+    channel.enable().await?;
+
+    loop {
+        if let Err(err) = run_session(&config, &channel).await {
+            tracing::warn!(
+                "broker session failed: {} - waiting {} s before reconnecting",
+                err,
+                BROKER_RETRY_DELAY.as_secs()
+            );
+        }
+        tokio::time::sleep(BROKER_RETRY_DELAY).await;
+    }
+}
+
+/// Connect to the broker and publish the poll results until the connection fails
+async fn run_session(config: &Config, channel: &Channel) -> std::io::Result<()> {
+    let keep_alive = Duration::from_secs(config.keep_alive_s);
+    let options = mqtt::ConnectOptions {
+        client_id: config.client_id.clone(),
+        keep_alive,
+        credentials: config.username.clone().zip(config.password.clone()),
+        // the broker reports the gateway as offline if the connection is lost
+        will: Some(mqtt::Will {
+            topic: config.status_topic(),
+            payload: OFFLINE.to_vec(),
+        }),
+    };
+
+    let mut client =
+        mqtt::Client::connect(TcpStream::connect(config.broker).await?, &options).await?;
+    tracing::info!("connected to broker: {}", config.broker);
+    client.publish(&config.status_topic(), ONLINE, true).await?;
+
+    let param = RequestParam::new(
+        UnitId::new(config.unit_id),
+        Duration::from_millis(config.timeout_ms),
+    );
+    let period = Duration::from_millis(config.period_ms);
+    // values are retained by the broker, so only changes are published
+    let mut published: HashMap<&str, String> = HashMap::new();
+    let mut device_online = None;
+    let mut last_ping = tokio::time::Instant::now();
+    loop {
+        let mut any_success = false;
+        for point in &config.points {
+            match read_point(channel, param, point).await {
+                Ok(value) => {
+                    any_success = true;
+                    if published.get(point.name.as_str()) != Some(&value) {
+                        client
+                            .publish(&config.point_topic(point), value.as_bytes(), true)
+                            .await?;
+                        published.insert(&point.name, value);
+                    }
+                }
+                Err(err) => tracing::warn!("unable to read {}: {}", point.name, err),
+            }
+        }
+
+        // the device is offline if none of the points could be read
+        let online = any_success || config.points.is_empty();
+        if device_online != Some(online) {
+            device_online = Some(online);
+            let payload = if online { ONLINE } else { OFFLINE };
+            client
+                .publish(&config.device_topic(), payload, true)
+                .await?;
+        }
+
+        if last_ping.elapsed() >= keep_alive / 2 {
+            client.ping().await?;
+            last_ping = tokio::time::Instant::now();
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(period) => {}
+            _ = tokio::signal::ctrl_c() => {
+                client.publish(&config.status_topic(), OFFLINE, true).await?;
+                client.disconnect().await?;
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+async fn read_point(
+    channel: &Channel,
+    param: RequestParam,
+    point: &Point,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let range = AddressRange::try_from(point.address, 1)?;
+    let value = match point.table {
+        Table::Coils => channel.read_coils(param, range).await?[0].value.to_string(),
+        Table::DiscreteInputs => channel.read_discrete_inputs(param, range).await?[0]
+            .value
+            .to_string(),
+        Table::HoldingRegisters => channel.read_holding_registers(param, range).await?[0]
+            .value
+            .to_string(),
+        Table::InputRegisters => channel.read_input_registers(param, range).await?[0]
+            .value
+            .to_string(),
+    };
+    Ok(value)
+}
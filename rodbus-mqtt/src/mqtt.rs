@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// MQTT 3.1.1 control packet types (upper nibble of the fixed header)
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+// CONNECT flags
+const CLEAN_SESSION: u8 = 0x02;
+const WILL_FLAG: u8 = 0x04;
+const WILL_RETAIN: u8 = 0x20;
+const PASSWORD: u8 = 0x40;
+const USERNAME: u8 = 0x80;
+
+// PUBLISH flags
+const RETAIN: u8 = 0x01;
+
+/// Message published by the broker if the connection is lost without a DISCONNECT
+pub(crate) struct Will {
+    pub(crate) topic: String,
+    pub(crate) payload: Vec<u8>,
+}
+
+pub(crate) struct ConnectOptions {
+    pub(crate) client_id: String,
+    pub(crate) keep_alive: Duration,
+    pub(crate) credentials: Option<(String, String)>,
+    pub(crate) will: Option<Will>,
+}
+
+/// Minimal MQTT 3.1.1 client that publishes with QoS 0
+pub(crate) struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    pub(crate) async fn connect(
+        mut stream: TcpStream,
+        options: &ConnectOptions,
+    ) -> std::io::Result<Self> {
+        stream.write_all(&encode_connect(options)).await?;
+
+        let mut connack = [0u8; 4];
+        stream.read_exact(&mut connack).await?;
+        if connack[0] != CONNACK || connack[1] != 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "expected CONNACK",
+            ));
+        }
+        if connack[3] != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                format!(
+                    "broker refused the connection with return code {}",
+                    connack[3]
+                ),
+            ));
+        }
+
+        Ok(Self { stream })
+    }
+
+    pub(crate) async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        retain: bool,
+    ) -> std::io::Result<()> {
+        self.stream
+            .write_all(&encode_publish(topic, payload, retain))
+            .await
+    }
+
+    pub(crate) async fn ping(&mut self) -> std::io::Result<()> {
+        self.stream.write_all(&[PINGREQ, 0]).await?;
+        // the broker only ever sends PINGRESP to a client that never subscribes
+        let mut response = [0u8; 2];
+        self.stream.read_exact(&mut response).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn disconnect(mut self) -> std::io::Result<()> {
+        self.stream.write_all(&[DISCONNECT, 0]).await?;
+        self.stream.shutdown().await
+    }
+}
+
+fn encode_connect(options: &ConnectOptions) -> Vec<u8> {
+    let mut flags = CLEAN_SESSION;
+    let mut body = Vec::new();
+    write_string(&mut body, b"MQTT");
+    body.push(4); // protocol level 3.1.1
+    let flags_pos = body.len();
+    body.push(0);
+    body.extend_from_slice(
+        &(options.keep_alive.as_secs().min(u16::MAX as u64) as u16).to_be_bytes(),
+    );
+
+    write_string(&mut body, options.client_id.as_bytes());
+    if let Some(will) = &options.will {
+        flags |= WILL_FLAG | WILL_RETAIN;
+        write_string(&mut body, will.topic.as_bytes());
+        write_string(&mut body, &will.payload);
+    }
+    if let Some((username, password)) = &options.credentials {
+        flags |= USERNAME | PASSWORD;
+        write_string(&mut body, username.as_bytes());
+        write_string(&mut body, password.as_bytes());
+    }
+    body[flags_pos] = flags;
+
+    packet(CONNECT, &body)
+}
+
+fn encode_publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(2 + topic.len() + payload.len());
+    write_string(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(PUBLISH | if retain { RETAIN } else { 0 }, &body)
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    // variable length encoding of the remaining length
+    let mut remaining = body.len();
+    loop {
+        let mut byte = (remaining % 128) as u8;
+        remaining /= 128;
+        if remaining > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if remaining == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_connect_with_will_and_credentials() {
+        let options = ConnectOptions {
+            client_id: "id".to_string(),
+            keep_alive: Duration::from_secs(30),
+            credentials: Some(("u".to_string(), "p".to_string())),
+            will: Some(Will {
+                topic: "t".to_string(),
+                payload: b"offline".to_vec(),
+            }),
+        };
+
+        assert_eq!(
+            encode_connect(&options),
+            [
+                &[0x10, 32][..],
+                &[0, 4],
+                b"MQTT",
+                &[4, 0xE6, 0, 30],
+                &[0, 2],
+                b"id",
+                &[0, 1],
+                b"t",
+                &[0, 7],
+                b"offline",
+                &[0, 1],
+                b"u",
+                &[0, 1],
+                b"p",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn encodes_retained_publish() {
+        assert_eq!(
+            encode_publish("a/b", b"42", true),
+            [&[0x31, 7, 0, 3][..], b"a/b", b"42"].concat()
+        );
+    }
+
+    #[test]
+    fn encodes_multi_byte_remaining_length() {
+        let packet = packet(PUBLISH, &[0; 321]);
+        assert_eq!(packet[0..3], [PUBLISH, 0xC1, 0x02]);
+        assert_eq!(packet.len(), 3 + 321);
+    }
+}
@@ -0,0 +1,67 @@
+//! Benchmarks of complete transactions through the client channel task and a server session
+//!
+//! The client and the server are connected by an in-memory stream, so the results measure the
+//! formatting, parsing and dispatch performed by the library rather than the network stack.
+//!
+//! Run with `cargo bench -p rodbus --features test-util --bench end_to_end`
+
+use std::time::Duration;
+
+use rodbus::client::{RequestParam, WriteMultiple};
+use rodbus::server::{RequestHandler, ServerHandlerMap};
+use rodbus::test_util::{channel_pair, MockHandler};
+use rodbus::{AddressRange, DecodeLevel, Indexed, UnitId};
+
+mod harness;
+
+fn main() {
+    let harness = harness::Harness::from_args();
+    // a single thread measures the work of the library without cross-thread wake-ups
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let handler = MockHandler::new()
+        .with_coils(0, &[false; 2000])
+        .with_holding_registers(0, &[0; 125])
+        .wrap();
+    let (channel, _server) = runtime.block_on(channel_pair(
+        ServerHandlerMap::single(UnitId::new(1), handler),
+        DecodeLevel::nothing(),
+    ));
+    let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+
+    for count in [1, 10, 125] {
+        let range = AddressRange::try_from(0, count).unwrap();
+        harness.bench(&format!("read_holding_registers/{}", count), || {
+            runtime
+                .block_on(channel.read_holding_registers(param, range))
+                .unwrap();
+        });
+    }
+
+    let range = AddressRange::try_from(0, 2000).unwrap();
+    harness.bench("read_coils/2000", || {
+        runtime.block_on(channel.read_coils(param, range)).unwrap();
+    });
+
+    harness.bench("write_single_register", || {
+        runtime
+            .block_on(channel.write_single_register(param, Indexed::new(3, 0xCAFE)))
+            .unwrap();
+    });
+
+    let values = WriteMultiple::from(0, vec![0xCAFE; 100]).unwrap();
+    harness.bench("write_multiple_registers/100", || {
+        runtime
+            .block_on(channel.write_multiple_registers(param, values.clone()))
+            .unwrap();
+    });
+
+    // a request the server rejects, which exercises the exception path of the dispatch
+    let range = AddressRange::try_from(1000, 10).unwrap();
+    harness.bench("read_input_registers/exception", || {
+        let _ = runtime.block_on(channel.read_input_registers(param, range));
+    });
+}
@@ -0,0 +1,65 @@
+//! Benchmarks of the parsing of Modbus TCP frames and response PDUs
+//!
+//! Run with `cargo bench -p rodbus --bench framing`
+
+use std::hint::black_box;
+
+use rodbus::client::TypedRequest;
+use rodbus::codec::{parse_mbap, parse_response};
+use rodbus::AddressRange;
+
+mod harness;
+
+/// MBAP header followed by the response to a read of `count` registers
+fn read_registers_adu(count: u16) -> Vec<u8> {
+    let byte_count = 2 * count as usize;
+    let length = (3 + byte_count) as u16;
+    let mut adu = vec![0x00, 0x01, 0x00, 0x00];
+    adu.extend_from_slice(&length.to_be_bytes());
+    adu.extend_from_slice(&[0x01, 0x03, byte_count as u8]);
+    for value in 0..count {
+        adu.extend_from_slice(&value.to_be_bytes());
+    }
+    adu
+}
+
+/// Response PDU to a read of `count` coils
+fn read_coils_pdu(count: u16) -> Vec<u8> {
+    let byte_count = (count as usize).div_ceil(8);
+    let mut pdu = vec![0x01, byte_count as u8];
+    pdu.extend((0..byte_count).map(|x| x as u8));
+    pdu
+}
+
+fn main() {
+    let harness = harness::Harness::from_args();
+
+    for count in [1, 10, 125] {
+        let adu = read_registers_adu(count);
+        harness.bench(&format!("parse_mbap/read_registers/{}", count), || {
+            let _ = black_box(parse_mbap(black_box(&adu)));
+        });
+
+        let request = TypedRequest::ReadHoldingRegisters(AddressRange::try_from(0, count).unwrap());
+        harness.bench(&format!("parse_response/read_registers/{}", count), || {
+            let _ = black_box(parse_response(&request, black_box(&adu[7..])));
+        });
+    }
+
+    for count in [16, 2000] {
+        let pdu = read_coils_pdu(count);
+        let request = TypedRequest::ReadCoils(AddressRange::try_from(0, count).unwrap());
+        harness.bench(&format!("parse_response/read_coils/{}", count), || {
+            let _ = black_box(parse_response(&request, black_box(&pdu)));
+        });
+    }
+
+    // a stream holding several frames, parsed one after the other
+    let stream: Vec<u8> = (0..8).flat_map(|_| read_registers_adu(10)).collect();
+    harness.bench("parse_mbap/stream_of_8", || {
+        let mut input = black_box(stream.as_slice());
+        while let Ok(Some((_, consumed))) = parse_mbap(input) {
+            input = &input[consumed..];
+        }
+    });
+}
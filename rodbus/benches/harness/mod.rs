@@ -0,0 +1,54 @@
+//! Minimal timing harness shared by the benchmarks
+//!
+//! Each benchmark is warmed up until a batch of iterations takes at least 100 ms, then timed over
+//! ten such batches. The mean time per iteration is printed so that runs can be compared against
+//! a baseline. Passing a name as argument, e.g. `cargo bench -- parse`, only runs the benchmarks
+//! whose name contains it.
+
+use std::time::{Duration, Instant};
+
+const WARM_UP: Duration = Duration::from_millis(100);
+const BATCHES: u64 = 10;
+
+pub struct Harness {
+    filter: Option<String>,
+}
+
+impl Harness {
+    pub fn from_args() -> Self {
+        // cargo passes "--bench" to the benchmark targets
+        let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+        Self { filter }
+    }
+
+    pub fn bench<F: FnMut()>(&self, name: &str, mut f: F) {
+        if let Some(filter) = &self.filter {
+            if !name.contains(filter.as_str()) {
+                return;
+            }
+        }
+
+        let mut iterations: u64 = 1;
+        loop {
+            let start = Instant::now();
+            for _ in 0..iterations {
+                f();
+            }
+            if start.elapsed() >= WARM_UP {
+                break;
+            }
+            iterations *= 2;
+        }
+
+        let total = iterations * BATCHES;
+        let start = Instant::now();
+        for _ in 0..total {
+            f();
+        }
+        let nanos = start.elapsed().as_nanos() as f64 / total as f64;
+        println!(
+            "{:<45} {:>12.1} ns/iter ({} iterations)",
+            name, nanos, total
+        );
+    }
+}
@@ -119,9 +119,9 @@ fn get_self_signed_config() -> Result<TlsClientConfig, Box<dyn std::error::Error
     // ANCHOR: tls_self_signed_config
     let tls_config = TlsClientConfig::new(
         "test.com",
-        &Path::new("./certs/self_signed/entity2_cert.pem"),
-        &Path::new("./certs/self_signed/entity1_cert.pem"),
-        &Path::new("./certs/self_signed/entity1_key.pem"),
+        Path::new("./certs/self_signed/entity2_cert.pem"),
+        Path::new("./certs/self_signed/entity1_cert.pem"),
+        Path::new("./certs/self_signed/entity1_key.pem"),
         None, // no password
         MinTlsVersion::V1_2,
         CertificateMode::SelfSigned,
@@ -137,9 +137,9 @@ fn get_ca_chain_config() -> Result<TlsClientConfig, Box<dyn std::error::Error>>
     // ANCHOR: tls_ca_chain_config
     let tls_config = TlsClientConfig::new(
         "test.com",
-        &Path::new("./certs/ca_chain/ca_cert.pem"),
-        &Path::new("./certs/ca_chain/client_cert.pem"),
-        &Path::new("./certs/ca_chain/client_key.pem"),
+        Path::new("./certs/ca_chain/ca_cert.pem"),
+        Path::new("./certs/ca_chain/client_cert.pem"),
+        Path::new("./certs/ca_chain/client_key.pem"),
         None, // no password
         MinTlsVersion::V1_2,
         CertificateMode::AuthorityBased,
@@ -149,7 +149,7 @@ fn get_ca_chain_config() -> Result<TlsClientConfig, Box<dyn std::error::Error>>
     Ok(tls_config)
 }
 
-fn print_read_result<T>(result: Result<Vec<Indexed<T>>, RequestError>)
+fn print_read_result<T>(result: Result<Vec<Indexed<T>>, RequestFailure>)
 where
     T: std::fmt::Display,
 {
@@ -159,26 +159,26 @@ where
                 println!("index: {} value: {}", bit.index, bit.value);
             }
         }
-        Err(rodbus::RequestError::Exception(exception)) => {
-            println!("Modbus exception: {}", exception);
-        }
-        Err(err) => println!("read error: {}", err),
+        Err(err) => match err.error() {
+            RequestError::Exception(exception) => println!("Modbus exception: {}", exception),
+            _ => println!("read error: {}", err),
+        },
     }
 }
 
-fn print_write_result<T>(result: Result<T, RequestError>) {
+fn print_write_result<T>(result: Result<T, RequestFailure>) {
     match result {
         Ok(_) => {
             println!("write successful");
         }
-        Err(rodbus::RequestError::Exception(exception)) => {
-            println!("Modbus exception: {}", exception);
-        }
-        Err(err) => println!("writer error: {}", err),
+        Err(err) => match err.error() {
+            RequestError::Exception(exception) => println!("Modbus exception: {}", exception),
+            _ => println!("writer error: {}", err),
+        },
     }
 }
 
-async fn run_channel(mut channel: Channel) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_channel(channel: Channel) -> Result<(), Box<dyn std::error::Error>> {
     channel.enable().await?;
 
     // ANCHOR: request_param
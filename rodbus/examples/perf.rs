@@ -5,7 +5,7 @@ use std::time::Duration;
 use rodbus::client::*;
 use rodbus::constants::limits::MAX_READ_REGISTERS_COUNT;
 use rodbus::server::*;
-use rodbus::RequestError;
+use rodbus::RequestFailure;
 use rodbus::*;
 
 use clap::Parser;
@@ -32,7 +32,7 @@ struct Cli {
     port: u16,
 }
 
-async fn join_and_sum(tasks: Vec<tokio::task::JoinHandle<Result<usize, RequestError>>>) -> usize {
+async fn join_and_sum(tasks: Vec<tokio::task::JoinHandle<Result<usize, RequestFailure>>>) -> usize {
     let mut total = 0;
     for task in tasks {
         total += task.await.unwrap().unwrap();
@@ -97,13 +97,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         channels.push((channel, params));
     }
 
-    let mut query_tasks: Vec<tokio::task::JoinHandle<Result<usize, RequestError>>> = Vec::new();
+    let mut query_tasks: Vec<tokio::task::JoinHandle<Result<usize, RequestFailure>>> = Vec::new();
 
     let start = std::time::Instant::now();
 
     // spawn tasks that make a query 1000 times
-    for (mut channel, params) in channels {
-        let handle: tokio::task::JoinHandle<Result<usize, RequestError>> =
+    for (channel, params) in channels {
+        let handle: tokio::task::JoinHandle<Result<usize, RequestFailure>> =
             tokio::spawn(async move {
                 let mut iterations = 0;
                 loop {
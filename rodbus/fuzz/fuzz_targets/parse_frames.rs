@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rodbus::codec::*;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(Some((adu, consumed))) = parse_mbap(data) {
+        assert!(consumed <= data.len());
+        assert_eq!(adu.pdu, &data[consumed - adu.pdu.len()..consumed]);
+    }
+    if let Ok(Some((_, consumed))) = parse_rtu_request(data) {
+        assert!(consumed <= data.len());
+    }
+    if let Ok(Some((_, consumed))) = parse_rtu_response(data) {
+        assert!(consumed <= data.len());
+    }
+});
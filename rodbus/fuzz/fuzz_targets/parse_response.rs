@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rodbus::client::TypedRequest;
+use rodbus::codec::parse_response;
+use rodbus::{AddressRange, Indexed};
+
+fuzz_target!(|data: &[u8]| {
+    // the first bytes select the request, the rest is the response PDU
+    if data.len() < 5 {
+        return;
+    }
+    let start = u16::from_be_bytes([data[1], data[2]]);
+    let count = u16::from_be_bytes([data[3], data[4]]);
+    let pdu = &data[5..];
+    let range = match AddressRange::try_from(start, count) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let request = match data[0] % 6 {
+        0 => TypedRequest::ReadCoils(range),
+        1 => TypedRequest::ReadDiscreteInputs(range),
+        2 => TypedRequest::ReadHoldingRegisters(range),
+        3 => TypedRequest::ReadInputRegisters(range),
+        4 => TypedRequest::WriteSingleCoil(Indexed::new(start, count & 1 == 1)),
+        _ => TypedRequest::WriteSingleRegister(Indexed::new(start, count)),
+    };
+    let _ = parse_response(&request, pdu);
+});
@@ -0,0 +1,382 @@
+use crate::error::InvalidRange;
+use crate::types::AddressRange;
+
+/// Modbus data table
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Table {
+    /// Coils, read with function code 0x01
+    Coils,
+    /// Discrete inputs, read with function code 0x02
+    DiscreteInputs,
+    /// Holding registers, read with function code 0x03
+    HoldingRegisters,
+    /// Input registers, read with function code 0x04
+    InputRegisters,
+}
+
+/// Notation of the addresses rendered by [`Table::format`] and [`AddressRange::format`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressNotation {
+    /// 0-based address sent in the requests, e.g. `holding registers 0x0000`
+    Protocol,
+    /// Traditional 1-based reference used by vendor documentation, prefixed by the digit of
+    /// the table: `0xxxx` for coils, `1xxxx` for discrete inputs, `3xxxx` for input registers
+    /// and `4xxxx` for holding registers, e.g. `40001`
+    ///
+    /// Six digits are used for the references above `x9999`, e.g. `410000`.
+    Reference,
+}
+
+impl Table {
+    /// Leading digit of the references of the table
+    pub fn reference_prefix(self) -> u8 {
+        match self {
+            Table::Coils => 0,
+            Table::DiscreteInputs => 1,
+            Table::InputRegisters => 3,
+            Table::HoldingRegisters => 4,
+        }
+    }
+
+    /// Render an address of the table in the given notation
+    ///
+    /// ```
+    /// use rodbus::{AddressNotation, Table};
+    ///
+    /// let address = Table::HoldingRegisters.format(0, AddressNotation::Reference);
+    /// assert_eq!(address.to_string(), "40001");
+    /// ```
+    pub fn format(self, address: u16, notation: AddressNotation) -> FormattedAddress {
+        FormattedAddress {
+            table: self,
+            address,
+            notation,
+        }
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Table::Coils => "coils",
+            Table::DiscreteInputs => "discrete inputs",
+            Table::HoldingRegisters => "holding registers",
+            Table::InputRegisters => "input registers",
+        };
+        f.write_str(name)
+    }
+}
+
+impl AddressRange {
+    /// Render the range of a table in the given notation, e.g. `40001-40010`
+    pub fn format(self, table: Table, notation: AddressNotation) -> FormattedRange {
+        FormattedRange {
+            table,
+            range: self,
+            notation,
+        }
+    }
+}
+
+/// Address displayed in an [`AddressNotation`], created by [`Table::format`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FormattedAddress {
+    table: Table,
+    address: u16,
+    notation: AddressNotation,
+}
+
+impl std::fmt::Display for FormattedAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.notation {
+            AddressNotation::Protocol => write!(f, "{} {:#06X}", self.table, self.address),
+            AddressNotation::Reference => write_reference(f, self.table, self.address),
+        }
+    }
+}
+
+/// Range displayed in an [`AddressNotation`], created by [`AddressRange::format`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FormattedRange {
+    table: Table,
+    range: AddressRange,
+    notation: AddressNotation,
+}
+
+impl std::fmt::Display for FormattedRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (first, last) = (self.range.start, self.range.last());
+        match self.notation {
+            AddressNotation::Protocol => {
+                write!(f, "{} {:#06X}", self.table, first)?;
+                if first != last {
+                    write!(f, "..={:#06X}", last)?;
+                }
+                Ok(())
+            }
+            AddressNotation::Reference => {
+                write_reference(f, self.table, first)?;
+                if first != last {
+                    f.write_str("-")?;
+                    write_reference(f, self.table, last)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_reference(
+    f: &mut std::fmt::Formatter<'_>,
+    table: Table,
+    address: u16,
+) -> std::fmt::Result {
+    let reference = u32::from(address) + 1;
+    if reference <= 9999 {
+        write!(f, "{}{:04}", table.reference_prefix(), reference)
+    } else {
+        write!(f, "{}{:05}", table.reference_prefix(), reference)
+    }
+}
+
+/// Convention with which the addresses of a table are numbered
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressBase {
+    /// The first element of the table is numbered 0, as in the requests
+    Zero,
+    /// The first element of the table is numbered 1, as in most vendor documentation
+    One,
+}
+
+/// Element of a Modbus data table
+///
+/// The address is stored as the 0-based protocol address, whatever the convention of the
+/// documentation it was transcribed from, so the table and the off-by-one of each convention
+/// are explicit at the point where the address is entered:
+///
+/// ```
+/// use rodbus::{AddressBase, DataAddress, Table};
+///
+/// // "holding register 1" of the documentation is the protocol address 0
+/// let first = DataAddress::new(Table::HoldingRegisters, 1, AddressBase::One).unwrap();
+/// assert_eq!(first.address(), 0);
+///
+/// // so is "register 40001"
+/// let reference: DataAddress = "40001".parse().unwrap();
+/// assert_eq!(reference, first);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataAddress {
+    table: Table,
+    address: u16,
+}
+
+/// Error returned when a [`DataAddress`] cannot be constructed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidDataAddress {
+    /// The number is outside of the table in the given convention, e.g. 0 when 1-based
+    OutOfRange(u32),
+    /// The reference is not 5 or 6 digits long, or doesn't start with a table digit
+    BadReference(String),
+}
+
+impl std::error::Error for InvalidDataAddress {}
+
+impl std::fmt::Display for InvalidDataAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InvalidDataAddress::OutOfRange(number) => {
+                write!(f, "number {} is outside of the table", number)
+            }
+            InvalidDataAddress::BadReference(reference) => {
+                write!(f, "invalid Modbus reference: {}", reference)
+            }
+        }
+    }
+}
+
+impl DataAddress {
+    /// Element of the table numbered in the given convention
+    ///
+    /// Numbers go up to 65535 when 0-based and 65536 when 1-based.
+    pub fn new(table: Table, number: u32, base: AddressBase) -> Result<Self, InvalidDataAddress> {
+        let address = match base {
+            AddressBase::Zero => Some(number),
+            AddressBase::One => number.checked_sub(1),
+        };
+        match address.and_then(|x| u16::try_from(x).ok()) {
+            Some(address) => Ok(Self { table, address }),
+            None => Err(InvalidDataAddress::OutOfRange(number)),
+        }
+    }
+
+    /// Element of the table at a protocol address
+    pub fn protocol(table: Table, address: u16) -> Self {
+        Self { table, address }
+    }
+
+    /// Table of the element
+    pub fn table(&self) -> Table {
+        self.table
+    }
+
+    /// 0-based address used in the requests
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Number of the element in the given convention
+    pub fn number(&self, base: AddressBase) -> u32 {
+        match base {
+            AddressBase::Zero => u32::from(self.address),
+            AddressBase::One => u32::from(self.address) + 1,
+        }
+    }
+
+    /// Range of `count` elements starting at this address
+    pub fn range(&self, count: u16) -> Result<AddressRange, InvalidRange> {
+        AddressRange::try_from(self.address, count)
+    }
+
+    /// Render the address in the given notation
+    pub fn format(&self, notation: AddressNotation) -> FormattedAddress {
+        self.table.format(self.address, notation)
+    }
+}
+
+/// Displayed as a reference, e.g. `40001`
+impl std::fmt::Display for DataAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_reference(f, self.table, self.address)
+    }
+}
+
+/// Parse a reference such as `40001` or `400001`
+///
+/// A reference is the digit of the table followed by the 1-based number of the element on four
+/// digits, or on five digits for the numbers above 9999. The leading zero of the coils is
+/// required, e.g. `00001`.
+impl std::str::FromStr for DataAddress {
+    type Err = InvalidDataAddress;
+
+    fn from_str(reference: &str) -> Result<Self, Self::Err> {
+        let bad = || InvalidDataAddress::BadReference(reference.to_string());
+        if !(5..=6).contains(&reference.len()) || !reference.bytes().all(|x| x.is_ascii_digit()) {
+            return Err(bad());
+        }
+        let table = match reference.as_bytes()[0] {
+            b'0' => Table::Coils,
+            b'1' => Table::DiscreteInputs,
+            b'3' => Table::InputRegisters,
+            b'4' => Table::HoldingRegisters,
+            _ => return Err(bad()),
+        };
+        let number: u32 = reference[1..].parse().map_err(|_| bad())?;
+        Self::new(table, number, AddressBase::One)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_addresses_in_both_notations() {
+        let reference = |table: Table, address| {
+            table
+                .format(address, AddressNotation::Reference)
+                .to_string()
+        };
+        assert_eq!(reference(Table::Coils, 0), "00001");
+        assert_eq!(reference(Table::DiscreteInputs, 41), "10042");
+        assert_eq!(reference(Table::InputRegisters, 9998), "39999");
+        assert_eq!(reference(Table::HoldingRegisters, 9999), "410000");
+        assert_eq!(reference(Table::HoldingRegisters, u16::MAX), "465536");
+
+        assert_eq!(
+            Table::HoldingRegisters
+                .format(16, AddressNotation::Protocol)
+                .to_string(),
+            "holding registers 0x0010"
+        );
+    }
+
+    #[test]
+    fn converts_between_the_conventions() {
+        let address = DataAddress::new(Table::InputRegisters, 10, AddressBase::One).unwrap();
+        assert_eq!(address, DataAddress::protocol(Table::InputRegisters, 9));
+        assert_eq!(address.number(AddressBase::Zero), 9);
+        assert_eq!(address.number(AddressBase::One), 10);
+        assert_eq!(address.to_string(), "30010");
+
+        let last = DataAddress::new(Table::Coils, 65536, AddressBase::One).unwrap();
+        assert_eq!(last.address(), u16::MAX);
+        assert_eq!(
+            DataAddress::new(Table::Coils, 0, AddressBase::One),
+            Err(InvalidDataAddress::OutOfRange(0))
+        );
+        assert_eq!(
+            DataAddress::new(Table::Coils, 65536, AddressBase::Zero),
+            Err(InvalidDataAddress::OutOfRange(65536))
+        );
+    }
+
+    #[test]
+    fn parses_references() {
+        let parse = |x: &str| x.parse::<DataAddress>();
+        assert_eq!(parse("00001"), Ok(DataAddress::protocol(Table::Coils, 0)));
+        assert_eq!(
+            parse("10042"),
+            Ok(DataAddress::protocol(Table::DiscreteInputs, 41))
+        );
+        assert_eq!(
+            parse("410000"),
+            Ok(DataAddress::protocol(Table::HoldingRegisters, 9999))
+        );
+        assert_eq!(
+            parse("465536"),
+            Ok(DataAddress::protocol(Table::HoldingRegisters, u16::MAX))
+        );
+        for reference in [
+            "40000", "465537", "20001", "4001", "4000001", "4000a", "+4001",
+        ] {
+            assert!(parse(reference).is_err(), "{}", reference);
+        }
+
+        // the rendered reference parses back to the same address
+        for address in [0, 9998, 9999, u16::MAX] {
+            let address = DataAddress::protocol(Table::Coils, address);
+            assert_eq!(parse(&address.to_string()), Ok(address));
+        }
+    }
+
+    #[test]
+    fn formats_ranges_in_both_notations() {
+        let range = AddressRange::try_from(0, 10).unwrap();
+        assert_eq!(
+            range
+                .format(Table::HoldingRegisters, AddressNotation::Reference)
+                .to_string(),
+            "40001-40010"
+        );
+        assert_eq!(
+            range
+                .format(Table::Coils, AddressNotation::Protocol)
+                .to_string(),
+            "coils 0x0000..=0x0009"
+        );
+
+        let single = AddressRange::try_from(5, 1).unwrap();
+        assert_eq!(
+            single
+                .format(Table::InputRegisters, AddressNotation::Reference)
+                .to_string(),
+            "30006"
+        );
+    }
+}
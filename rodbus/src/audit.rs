@@ -0,0 +1,248 @@
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+use crate::error::RequestError;
+use crate::types::UnitId;
+
+/// Side of the communication that performed a write operation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AuditOrigin {
+    /// The write was requested by a client channel
+    Client,
+    /// The write was received by a server and passed to its handler
+    Server,
+}
+
+/// Value written at an address, with the value it replaced if it is known
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WrittenValue<T> {
+    /// Address of the value
+    pub index: u16,
+    /// Value before the write. Servers read it from their handler before the write, and clients
+    /// never know it.
+    pub old: Option<T>,
+    /// Value requested by the write
+    pub new: T,
+}
+
+impl<T> WrittenValue<T> {
+    pub(crate) fn new(index: u16, old: Option<T>, new: T) -> Self {
+        Self { index, old, new }
+    }
+}
+
+/// Values of a write operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WrittenValues {
+    /// Coils written by a single or multiple write
+    Coils(Vec<WrittenValue<bool>>),
+    /// Registers written by a single or multiple write
+    Registers(Vec<WrittenValue<u16>>),
+}
+
+/// Record of a write operation passed to an [`AuditSink`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteRecord {
+    /// Time at which the write completed
+    pub timestamp: SystemTime,
+    /// Whether the write was performed by a client or a server
+    pub origin: AuditOrigin,
+    /// Address of the client that sent the write to a server, if it is known
+    pub peer: Option<SocketAddr>,
+    /// Role of the client certificate of a Secure Modbus server session
+    pub role: Option<String>,
+    /// Unit id of the write
+    pub unit: UnitId,
+    /// Raw function code of the write
+    pub function: u8,
+    /// Values written
+    pub values: WrittenValues,
+    /// Outcome of the write. Exceptions returned by a server handler are reported as
+    /// [`RequestError::Exception`].
+    pub result: Result<(), RequestError>,
+}
+
+impl WriteRecord {
+    pub(crate) fn new(
+        origin: AuditOrigin,
+        unit: UnitId,
+        function: u8,
+        values: WrittenValues,
+        result: Result<(), RequestError>,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            origin,
+            peer: None,
+            role: None,
+            unit,
+            function,
+            values,
+            result,
+        }
+    }
+}
+
+/// Receives a record of every write operation performed by a client channel or a server
+///
+/// A sink can be installed with [`crate::client::Channel::set_audit_sink`] and
+/// [`crate::server::ServerHandle::set_audit_sink`]. The same sink may be shared by several
+/// channels and servers. It is invoked synchronously from their tasks and must not block.
+pub trait AuditSink: Send + Sync {
+    /// Record a completed write operation
+    fn record(&self, record: &WriteRecord);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::common::frame::{FrameWriter, FramedReader};
+    use crate::common::phys::PhysLayer;
+    use crate::exception::ExceptionCode;
+    use crate::server::task::{AuthorizationType, ServerSetting, SessionSettings, SessionTask};
+    use crate::server::{RequestHandler, ServerHandlerMap, WriteRegisters};
+    use crate::types::Indexed;
+    use crate::DecodeLevel;
+
+    struct Registers([u16; 4]);
+
+    impl RequestHandler for Registers {
+        fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+            self.0
+                .get(address as usize)
+                .copied()
+                .ok_or(ExceptionCode::IllegalDataAddress)
+        }
+
+        fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+            match self.0.get_mut(value.index as usize) {
+                Some(x) => {
+                    *x = value.value;
+                    Ok(())
+                }
+                None => Err(ExceptionCode::IllegalDataAddress),
+            }
+        }
+
+        fn write_multiple_registers(
+            &mut self,
+            values: WriteRegisters,
+        ) -> Result<(), ExceptionCode> {
+            for x in values.iterator {
+                self.write_single_register(x)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Records(Mutex<Vec<WriteRecord>>);
+
+    impl AuditSink for Records {
+        fn record(&self, record: &WriteRecord) {
+            self.0.lock().unwrap().push(record.clone());
+        }
+    }
+
+    async fn exchange(stream: &mut tokio::io::DuplexStream, request: &[u8]) -> Vec<u8> {
+        stream.write_all(request).await.unwrap();
+        let mut header = [0; 7];
+        stream.read_exact(&mut header).await.unwrap();
+        let mut pdu = vec![0; u16::from_be_bytes([header[4], header[5]]) as usize - 1];
+        stream.read_exact(&mut pdu).await.unwrap();
+        pdu
+    }
+
+    #[tokio::test]
+    async fn server_records_old_and_new_values_of_writes() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let mut session = SessionTask::new(
+            ServerHandlerMap::single(UnitId::new(1), Registers([1, 2, 3, 4]).wrap()),
+            AuthorizationType::None,
+            FrameWriter::tcp(),
+            FramedReader::tcp(),
+            rx,
+            DecodeLevel::nothing(),
+        );
+        let records = Arc::new(Records::default());
+        session.set_settings(SessionSettings {
+            audit: Some(records.clone()),
+            ..SessionSettings::default()
+        });
+        tokio::spawn(async move {
+            session
+                .run(&mut PhysLayer::new_stream(Box::new(server)))
+                .await
+        });
+
+        // write 0x0A0B to register 1
+        let reply = exchange(
+            &mut client,
+            &[
+                0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x01, 0x06, 0x00, 0x01, 0x0A, 0x0B,
+            ],
+        )
+        .await;
+        assert_eq!(reply, [0x06, 0x00, 0x01, 0x0A, 0x0B]);
+
+        // write 0x0001 and 0x0002 to registers 3 and 4, the latter of which doesn't exist
+        let reply = exchange(
+            &mut client,
+            &[
+                0x00, 0x02, 0x00, 0x00, 0x00, 0x0B, 0x01, 0x10, 0x00, 0x03, 0x00, 0x02, 0x04, 0x00,
+                0x01, 0x00, 0x02,
+            ],
+        )
+        .await;
+        assert_eq!(reply, [0x90, 0x02]);
+
+        // reads are not recorded
+        exchange(
+            &mut client,
+            &[
+                0x00, 0x03, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x00, 0x00, 0x01,
+            ],
+        )
+        .await;
+
+        let recorded = std::mem::take(&mut *records.0.lock().unwrap());
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0].origin, AuditOrigin::Server);
+        assert_eq!(recorded[0].unit, UnitId::new(1));
+        assert_eq!(recorded[0].function, 0x06);
+        assert_eq!(
+            recorded[0].values,
+            WrittenValues::Registers(vec![WrittenValue::new(1, Some(2), 0x0A0B)])
+        );
+        assert_eq!(recorded[0].result, Ok(()));
+        assert_eq!(
+            recorded[1].values,
+            WrittenValues::Registers(vec![
+                WrittenValue::new(3, Some(4), 1),
+                WrittenValue::new(4, None, 2)
+            ])
+        );
+        assert_eq!(
+            recorded[1].result,
+            Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+        );
+
+        // the sink can be removed
+        assert!(tx.send(ServerSetting::Audit(None)).await.is_ok());
+        // the queue has room again once the session has applied the setting
+        assert!(tx.reserve().await.is_ok());
+        exchange(
+            &mut client,
+            &[
+                0x00, 0x04, 0x00, 0x00, 0x00, 0x06, 0x01, 0x06, 0x00, 0x01, 0x00, 0x00,
+            ],
+        )
+        .await;
+        assert!(records.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::client::{Channel, RequestParam, TypedRequest, TypedResponse};
+use crate::error::{RequestError, RequestFailure};
+use crate::exception::ExceptionCode;
+use crate::types::{AddressRange, UnitId};
+
+/// Response returned by a [`ReadCache`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedResponse {
+    /// Response of the unit
+    pub response: TypedResponse,
+    /// Age of the response if the unit failed to respond and it was taken from the cache, or
+    /// `None` if the unit just sent it
+    pub stale: Option<Duration>,
+}
+
+/// Reads performed through a channel that are answered from the last response when the unit
+/// stops responding
+///
+/// This allows e.g. a gateway to keep serving the values of a device that is down to masters
+/// which treat a timeout as a total outage. A read is answered from the cache if it fails
+/// because the unit did not respond, or could not be reached (see
+/// [`RequestError::gateway_exception`]), and the last response to the same read is no older than
+/// the maximum staleness. Such responses are flagged with their age.
+///
+/// Writes are performed without being cached.
+pub struct ReadCache {
+    channel: Channel,
+    max_staleness: Duration,
+    entries: BTreeMap<(UnitId, u8, AddressRange), (Instant, TypedResponse)>,
+}
+
+impl ReadCache {
+    /// Create a cache of the reads performed through the channel
+    pub fn new(channel: Channel, max_staleness: Duration) -> Self {
+        Self {
+            channel,
+            max_staleness,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Change the maximum age of the responses taken from the cache
+    pub fn set_max_staleness(&mut self, max_staleness: Duration) {
+        self.max_staleness = max_staleness;
+    }
+
+    /// Perform a request, answering a read from the cache if the unit failed to respond
+    pub async fn call(
+        &mut self,
+        param: RequestParam,
+        request: TypedRequest,
+    ) -> Result<CachedResponse, RequestFailure> {
+        let key = match Self::key(&request) {
+            Some((function, range)) => (param.id, function, range),
+            None => {
+                let response = self.channel.call(param, request).await?;
+                return Ok(CachedResponse {
+                    response,
+                    stale: None,
+                });
+            }
+        };
+
+        match self.channel.call(param, request).await {
+            Ok(response) => {
+                self.entries.insert(key, (Instant::now(), response.clone()));
+                Ok(CachedResponse {
+                    response,
+                    stale: None,
+                })
+            }
+            Err(err) if Self::failed_to_respond(err.error()) => {
+                let (time, response) = match self.entries.get(&key) {
+                    Some(entry) => entry,
+                    None => return Err(err),
+                };
+                let age = time.elapsed();
+                if age > self.max_staleness {
+                    return Err(err);
+                }
+                tracing::warn!(
+                    "answering read from unit {} with a response {:?} old: {}",
+                    param.id,
+                    age,
+                    err
+                );
+                Ok(CachedResponse {
+                    response: response.clone(),
+                    stale: Some(age),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Function code and range of a read request
+    fn key(request: &TypedRequest) -> Option<(u8, AddressRange)> {
+        match request {
+            TypedRequest::ReadCoils(range) => Some((0x01, *range)),
+            TypedRequest::ReadDiscreteInputs(range) => Some((0x02, *range)),
+            TypedRequest::ReadHoldingRegisters(range) => Some((0x03, *range)),
+            TypedRequest::ReadInputRegisters(range) => Some((0x04, *range)),
+            _ => None,
+        }
+    }
+
+    fn failed_to_respond(err: &RequestError) -> bool {
+        matches!(
+            err.gateway_exception(),
+            ExceptionCode::GatewayPathUnavailable
+                | ExceptionCode::GatewayTargetDeviceFailedToRespond
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::Command;
+    use crate::client::ResponseParsing;
+    use crate::decode::AppDecodeLevel;
+    use crate::types::Indexed;
+
+    #[tokio::test]
+    async fn reads_are_answered_from_the_cache_until_stale() {
+        tokio::time::pause();
+
+        // the device responds to the first read only
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut responded = false;
+            while let Some(cmd) = rx.recv().await {
+                let mut request = match cmd {
+                    Command::Request(request) => request,
+                    Command::Setting(_) => continue,
+                };
+                let result = if responded {
+                    Err(RequestError::ResponseTimeout)
+                } else {
+                    responded = true;
+                    request.handle_response(
+                        &[0x03, 0x02, 0x00, 0x2A],
+                        AppDecodeLevel::Nothing,
+                        ResponseParsing::Strict,
+                    )
+                };
+                if let Err(err) = result {
+                    request.details.fail(err);
+                }
+            }
+        });
+
+        let mut cache = ReadCache::new(Channel::new(tx), Duration::from_secs(10));
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let read = TypedRequest::ReadHoldingRegisters(AddressRange::try_from(0, 1).unwrap());
+        let values = TypedResponse::Registers(vec![Indexed::new(0, 42)]);
+
+        assert_eq!(
+            cache.call(param, read.clone()).await,
+            Ok(CachedResponse {
+                response: values.clone(),
+                stale: None
+            })
+        );
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(
+            cache.call(param, read.clone()).await,
+            Ok(CachedResponse {
+                response: values,
+                stale: Some(Duration::from_secs(5))
+            })
+        );
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(
+            cache.call(param, read).await.unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::common::frame::Frame;
+use crate::types::UnitId;
+
+// pcapng block types
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+// Wireshark "exported PDU" link type. Each packet is prefixed with tags that name the
+// dissector to use, which lets Wireshark decode Modbus frames captured from any transport.
+const LINKTYPE_WIRESHARK_UPPER_PDU: u16 = 252;
+const EXP_PDU_TAG_END_OF_OPT: u16 = 0;
+const EXP_PDU_TAG_PROTO_NAME: u16 = 12;
+
+// enhanced packet block options
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_FLAGS_INBOUND: u32 = 0x01;
+const EPB_FLAGS_OUTBOUND: u32 = 0x02;
+
+/// Direction of a frame in a capture, from the point of view of the client
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CaptureDirection {
+    /// Request sent by the client
+    Transmit,
+    /// Response received by the client
+    Receive,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Protocol {
+    Tcp,
+    Rtu,
+}
+
+impl Protocol {
+    fn dissector(self) -> &'static [u8] {
+        match self {
+            Protocol::Tcp => b"mbtcp",
+            Protocol::Rtu => b"mbrtu",
+        }
+    }
+}
+
+/// Writes every ADU exchanged on a channel to a [pcapng](https://pcapng.com/) capture
+///
+/// Frames are written using Wireshark's "exported PDU" link type so that both Modbus TCP and
+/// Modbus RTU traffic can be dissected, even on serial links where no external capture is possible.
+/// Each frame is timestamped and flagged as inbound or outbound.
+///
+/// Install the writer on a channel using [`crate::client::Channel::set_capture`]. Writes are
+/// buffered and performed from the channel task. If a write fails, the capture is stopped.
+/// Captures can be read back using [`Recording`], e.g. to replay a session in a test.
+pub struct PcapWriter {
+    // shared by the connections of a pool, each packet being written under the lock
+    output: Arc<Mutex<Output>>,
+}
+
+struct Output {
+    writer: std::io::BufWriter<Box<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for PcapWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PcapWriter")
+    }
+}
+
+impl PcapWriter {
+    /// Create a capture file at the specified path, truncating it if it already exists
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(Box::new(std::fs::File::create(path)?))
+    }
+
+    /// Create a capture that is written to an arbitrary output
+    ///
+    /// The pcapng section and interface headers are written immediately.
+    pub fn new(output: Box<dyn Write + Send>) -> std::io::Result<Self> {
+        let mut output = Output {
+            writer: std::io::BufWriter::new(output),
+        };
+        output.write_headers()?;
+        Ok(Self {
+            output: Arc::new(Mutex::new(output)),
+        })
+    }
+
+    /// Create another handle that writes to the same capture
+    pub(crate) fn share(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+        }
+    }
+
+    pub(crate) fn write_request(
+        &mut self,
+        protocol: Protocol,
+        frame: &[u8],
+    ) -> std::io::Result<()> {
+        self.lock()
+            .write_packet(CaptureDirection::Transmit, protocol, frame)
+    }
+
+    pub(crate) fn write_response(
+        &mut self,
+        protocol: Protocol,
+        frame: &Frame,
+    ) -> std::io::Result<()> {
+        let adu = to_adu(frame);
+        self.lock()
+            .write_packet(CaptureDirection::Receive, protocol, &adu)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Output> {
+        // a packet interrupted by a panic only corrupts the capture, so a poisoned lock is recovered
+        self.output.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Output {
+    fn write_headers(&mut self) -> std::io::Result<()> {
+        // section header block
+        self.write_u32(SECTION_HEADER_BLOCK)?;
+        self.write_u32(28)?;
+        self.write_u32(BYTE_ORDER_MAGIC)?;
+        self.write_u16(1)?; // major version
+        self.write_u16(0)?; // minor version
+        self.writer.write_all(&(-1i64).to_le_bytes())?; // unspecified section length
+        self.write_u32(28)?;
+
+        // interface description block
+        self.write_u32(INTERFACE_DESCRIPTION_BLOCK)?;
+        self.write_u32(20)?;
+        self.write_u16(LINKTYPE_WIRESHARK_UPPER_PDU)?;
+        self.write_u16(0)?; // reserved
+        self.write_u32(0)?; // no snap length
+        self.write_u32(20)?;
+
+        self.writer.flush()
+    }
+
+    fn write_packet(
+        &mut self,
+        direction: CaptureDirection,
+        protocol: Protocol,
+        adu: &[u8],
+    ) -> std::io::Result<()> {
+        let proto_name = protocol.dissector();
+        let proto_name_len = padded_len(proto_name.len());
+        // proto name tag + end of options tag + ADU
+        let packet_len = 4 + proto_name_len + 4 + adu.len();
+        let padded_packet_len = padded_len(packet_len);
+        // header + packet data + flags option + end of options + trailing length
+        let block_len = 28 + padded_packet_len + 8 + 4 + 4;
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_micros() as u64)
+            .unwrap_or(0);
+
+        self.write_u32(ENHANCED_PACKET_BLOCK)?;
+        self.write_u32(block_len as u32)?;
+        self.write_u32(0)?; // interface id
+        self.write_u32((timestamp >> 32) as u32)?;
+        self.write_u32(timestamp as u32)?;
+        self.write_u32(packet_len as u32)?; // captured length
+        self.write_u32(packet_len as u32)?; // original length
+
+        // exported PDU tags are big-endian
+        self.writer
+            .write_all(&EXP_PDU_TAG_PROTO_NAME.to_be_bytes())?;
+        self.writer
+            .write_all(&(proto_name_len as u16).to_be_bytes())?;
+        self.writer.write_all(proto_name)?;
+        self.write_padding(proto_name.len())?;
+        self.writer
+            .write_all(&EXP_PDU_TAG_END_OF_OPT.to_be_bytes())?;
+        self.writer.write_all(&0u16.to_be_bytes())?;
+        self.writer.write_all(adu)?;
+        self.write_padding(packet_len)?;
+
+        self.write_u16(OPT_EPB_FLAGS)?;
+        self.write_u16(4)?;
+        self.write_u32(match direction {
+            CaptureDirection::Transmit => EPB_FLAGS_OUTBOUND,
+            CaptureDirection::Receive => EPB_FLAGS_INBOUND,
+        })?;
+        self.write_u16(OPT_ENDOFOPT)?;
+        self.write_u16(0)?;
+
+        self.write_u32(block_len as u32)
+    }
+
+    fn write_padding(&mut self, len: usize) -> std::io::Result<()> {
+        let padding = padded_len(len) - len;
+        self.writer.write_all(&[0; 3][0..padding])
+    }
+
+    fn write_u16(&mut self, value: u16) -> std::io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, value: u32) -> std::io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+}
+
+/// Frame read from a capture by [`Recording`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedFrame {
+    /// Direction of the frame
+    pub direction: CaptureDirection,
+    /// Unit id of the frame
+    pub unit_id: UnitId,
+    /// Function code and data of the frame, without the MBAP header or CRC
+    pub pdu: Vec<u8>,
+}
+
+/// Frames of a capture written by a [`PcapWriter`], in the order they were exchanged
+///
+/// Recordings are protocol independent: a session captured on a serial link can be replayed
+/// over TCP and vice versa. Blocks other than the packets, e.g. those added by other tools, are
+/// skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recording {
+    frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    /// Read a capture file
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// Parse the content of a capture file
+    ///
+    /// Returns an error of kind [`std::io::ErrorKind::InvalidData`] if the content is not a
+    /// little-endian pcapng capture of Modbus TCP or RTU frames.
+    pub fn parse(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut frames = Vec::new();
+        let mut cursor = bytes;
+        let mut first = true;
+        while !cursor.is_empty() {
+            let block_type = read_u32(cursor, 0)?;
+            let block_len = read_u32(cursor, 4)? as usize;
+            if block_len < 12 || block_len > cursor.len() {
+                return Err(invalid_data("bad block length"));
+            }
+            if first
+                && (block_type != SECTION_HEADER_BLOCK || read_u32(cursor, 8)? != BYTE_ORDER_MAGIC)
+            {
+                return Err(invalid_data("not a little-endian pcapng file"));
+            }
+            first = false;
+            if block_type == ENHANCED_PACKET_BLOCK {
+                frames.push(parse_packet(&cursor[..block_len])?);
+            }
+            cursor = &cursor[block_len..];
+        }
+        Ok(Self { frames })
+    }
+
+    /// Frames of the recording
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+}
+
+fn parse_packet(block: &[u8]) -> std::io::Result<RecordedFrame> {
+    let captured_len = read_u32(block, 20)? as usize;
+    let data = block
+        .get(28..28 + captured_len)
+        .ok_or_else(|| invalid_data("bad captured length"))?;
+
+    // exported PDU tags
+    let mut protocol = None;
+    let mut pos = 0;
+    loop {
+        let tag = read_u16_be(data, pos)?;
+        let len = read_u16_be(data, pos + 2)? as usize;
+        let value = data
+            .get(pos + 4..pos + 4 + len)
+            .ok_or_else(|| invalid_data("bad tag length"))?;
+        pos += 4 + padded_len(len);
+        match tag {
+            EXP_PDU_TAG_END_OF_OPT => break,
+            EXP_PDU_TAG_PROTO_NAME => {
+                protocol = [Protocol::Tcp, Protocol::Rtu]
+                    .into_iter()
+                    .find(|x| value.starts_with(x.dissector()))
+            }
+            _ => {}
+        }
+    }
+    let adu = data
+        .get(pos..)
+        .ok_or_else(|| invalid_data("bad tag length"))?;
+
+    // packet options
+    let mut direction = None;
+    let mut pos = 28 + padded_len(captured_len);
+    while pos + 4 <= block.len() - 4 {
+        let code = read_u16(block, pos)?;
+        let len = read_u16(block, pos + 2)? as usize;
+        match code {
+            OPT_ENDOFOPT => break,
+            OPT_EPB_FLAGS => {
+                direction = match read_u32(block, pos + 4)? & 0x03 {
+                    EPB_FLAGS_INBOUND => Some(CaptureDirection::Receive),
+                    EPB_FLAGS_OUTBOUND => Some(CaptureDirection::Transmit),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+        pos += 4 + padded_len(len);
+    }
+    let direction = direction.ok_or_else(|| invalid_data("packet without direction"))?;
+
+    let (unit_id, pdu) = match protocol {
+        Some(Protocol::Tcp) if adu.len() > 7 => (adu[6], &adu[7..]),
+        Some(Protocol::Rtu) if adu.len() > 3 => (adu[0], &adu[1..adu.len() - 2]),
+        Some(_) => return Err(invalid_data("truncated ADU")),
+        None => return Err(invalid_data("packet is not a Modbus frame")),
+    };
+
+    Ok(RecordedFrame {
+        direction,
+        unit_id: UnitId::new(unit_id),
+        pdu: pdu.to_vec(),
+    })
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> std::io::Result<u16> {
+    bytes
+        .get(pos..pos + 2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .ok_or_else(|| invalid_data("unexpected end of data"))
+}
+
+fn read_u16_be(bytes: &[u8], pos: usize) -> std::io::Result<u16> {
+    read_u16(bytes, pos).map(u16::swap_bytes)
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> std::io::Result<u32> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .ok_or_else(|| invalid_data("unexpected end of data"))
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn padded_len(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Rebuild the ADU of a received frame. The parser only retains the PDU, but the
+/// frame was validated so it can be re-encoded exactly as it was received
+fn to_adu(frame: &Frame) -> Vec<u8> {
+    let pdu = frame.payload();
+    let unit_id = frame.header.destination.value();
+    match frame.header.tx_id {
+        Some(tx_id) => {
+            let mut adu = Vec::with_capacity(7 + pdu.len());
+            adu.extend_from_slice(&tx_id.to_u16().to_be_bytes());
+            adu.extend_from_slice(&[0, 0]);
+            adu.extend_from_slice(&((pdu.len() + 1) as u16).to_be_bytes());
+            adu.push(unit_id);
+            adu.extend_from_slice(pdu);
+            adu
+        }
+        None => {
+            let mut adu = Vec::with_capacity(3 + pdu.len());
+            adu.push(unit_id);
+            adu.extend_from_slice(pdu);
+            #[cfg(feature = "serial")]
+            {
+                let crc = crate::serial::frame::CRC.checksum(&adu);
+                adu.extend_from_slice(&crc.to_le_bytes());
+            }
+            adu
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::frame::{FrameHeader, TxId};
+    use crate::test_util::SharedBuffer;
+    use crate::types::UnitId;
+
+    const READ_COILS_REQUEST: &[u8] = &[
+        0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x01, 0x01, 0x00, 0x07, 0x00, 0x02,
+    ];
+
+    #[test]
+    fn writes_section_and_interface_headers() {
+        let buffer = SharedBuffer::default();
+        let _writer = PcapWriter::new(Box::new(buffer.clone())).unwrap();
+        let bytes = buffer.contents();
+
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(bytes[0..4], SECTION_HEADER_BLOCK.to_le_bytes());
+        assert_eq!(bytes[8..12], BYTE_ORDER_MAGIC.to_le_bytes());
+        assert_eq!(bytes[28..32], INTERFACE_DESCRIPTION_BLOCK.to_le_bytes());
+        assert_eq!(bytes[36..38], LINKTYPE_WIRESHARK_UPPER_PDU.to_le_bytes());
+    }
+
+    #[test]
+    fn writes_padded_enhanced_packet_blocks() {
+        let buffer = SharedBuffer::default();
+        let mut writer = PcapWriter::new(Box::new(buffer.clone())).unwrap();
+        writer
+            .write_request(Protocol::Tcp, READ_COILS_REQUEST)
+            .unwrap();
+        writer.lock().writer.flush().unwrap();
+        let bytes = buffer.contents();
+        let block = &bytes[48..];
+
+        // 16 bytes of tags + 12 byte ADU = 28 bytes of packet data
+        let block_len = 28 + 28 + 12 + 4;
+        assert_eq!(block.len(), block_len);
+        assert_eq!(block[0..4], ENHANCED_PACKET_BLOCK.to_le_bytes());
+        assert_eq!(block[4..8], (block_len as u32).to_le_bytes());
+        assert_eq!(block[20..24], 28u32.to_le_bytes());
+        assert_eq!(block[28..40], *b"\x00\x0c\x00\x08mbtcp\x00\x00\x00");
+        assert_eq!(block[40..44], [0, 0, 0, 0]);
+        assert_eq!(block[44..56], *READ_COILS_REQUEST);
+        assert_eq!(block[60..64], EPB_FLAGS_OUTBOUND.to_le_bytes());
+        assert_eq!(block[block_len - 4..], (block_len as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn rebuilds_tcp_adu_of_received_frames() {
+        let mut frame = Frame::new(FrameHeader::new_tcp_header(UnitId::new(1), TxId::new(7)));
+        frame.set(bytes::Bytes::copy_from_slice(&READ_COILS_REQUEST[7..]));
+        assert_eq!(to_adu(&frame), READ_COILS_REQUEST);
+    }
+
+    #[test]
+    fn parses_frames_that_were_written() {
+        let buffer = SharedBuffer::default();
+        let mut writer = PcapWriter::new(Box::new(buffer.clone())).unwrap();
+        writer
+            .write_request(Protocol::Tcp, READ_COILS_REQUEST)
+            .unwrap();
+        let mut frame = Frame::new(FrameHeader::new_tcp_header(UnitId::new(1), TxId::new(7)));
+        frame.set(bytes::Bytes::from_static(&[0x01, 0x01, 0x02]));
+        writer.write_response(Protocol::Tcp, &frame).unwrap();
+        writer.lock().writer.flush().unwrap();
+
+        let recording = Recording::parse(&buffer.contents()).unwrap();
+        assert_eq!(
+            recording.frames(),
+            [
+                RecordedFrame {
+                    direction: CaptureDirection::Transmit,
+                    unit_id: UnitId::new(1),
+                    pdu: READ_COILS_REQUEST[7..].to_vec(),
+                },
+                RecordedFrame {
+                    direction: CaptureDirection::Receive,
+                    unit_id: UnitId::new(1),
+                    pdu: vec![0x01, 0x01, 0x02],
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_pcapng() {
+        let err = Recording::parse(&[0xD4, 0xC3, 0xB2, 0xA1, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -1,29 +1,124 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 
+use crate::audit::AuditSink;
+use crate::client::capture::PcapWriter;
+use crate::client::completion::{Completed, CompletionSlot, FromCompleted};
+use crate::client::fairness::RequestFairness;
+use crate::client::interceptor::Interceptor;
 use crate::client::message::{Command, Promise, Request, RequestDetails, Setting};
-use crate::client::requests::read_bits::ReadBits;
-use crate::client::requests::read_registers::ReadRegisters;
+use crate::client::metrics::MetricsListener;
+use crate::client::requests::read_bits::{self, ReadBits};
+use crate::client::requests::read_registers::{self, ReadRegisters};
 use crate::client::requests::write_multiple::{MultipleWriteRequest, WriteMultiple};
 use crate::client::requests::write_single::SingleWrite;
 use crate::error::*;
-use crate::types::{AddressRange, BitIterator, Indexed, RegisterIterator, UnitId};
-use crate::DecodeLevel;
+use crate::types::{
+    AddressRange, BitIterator, Indexed, ReadResult, RegisterIterator, UnitId, WordOrder,
+};
+use crate::{DecodeLevel, FunctionCode};
 
 /// Async channel used to make requests
-#[derive(Debug, Clone)]
+///
+/// The request methods take `&self`, so tasks can share a channel by reference and have
+/// several requests in progress at the same time.
+#[derive(Debug)]
 pub struct Channel {
     pub(crate) tx: tokio::sync::mpsc::Sender<Command>,
+    // not shared with the clones of the handle, which copy its value
+    fail_when_queue_full: AtomicBool,
+    // shared by the clones of the handle and reused by their requests
+    completion: CompletionSlot,
+}
+
+impl Clone for Channel {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            fail_when_queue_full: AtomicBool::new(self.fail_when_queue_full()),
+            completion: self.completion.clone(),
+        }
+    }
 }
 
 /// Request parameters to dispatch the request to the proper device
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RequestParam {
     /// Unit ID of the target device
     pub id: UnitId,
-    /// Response timeout
+    /// Response timeout, which includes the time the request waits in the queue of the channel.
+    /// A request still waiting when it elapses fails with [`RequestError::ResponseTimeout`]
+    /// without being sent.
     pub response_timeout: Duration,
 }
 
+/// How strictly a channel parses the responses it receives, see [`Channel::set_response_parsing`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ResponseParsing {
+    /// Reject any response that deviates from the specification
+    #[default]
+    Strict,
+    /// Accept responses with harmless deviations, which are logged as warnings:
+    ///
+    /// * padding bytes that follow a valid PDU
+    /// * the byte count of a coil or discrete input response rounded up to an even number
+    Tolerant,
+}
+
+/// How a channel handles a response whose unit id differs from the request's, see
+/// [`Channel::set_unit_id_policy`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UnitIdPolicy {
+    /// Discard the response and keep waiting for one from the right unit
+    Require,
+    /// Accept the response, reporting it as an [`crate::client::UnexpectedFrame::UnitIdMismatch`]
+    #[default]
+    Warn,
+    /// Accept the response without checking the unit id
+    Ignore,
+}
+
+/// How a channel schedules its requests, see [`Channel::set_request_scheduling`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RequestScheduling {
+    /// Wait for the response to a request before sending the next one
+    #[default]
+    Serialized,
+    /// Allow one outstanding request per unit id, so that a slow unit doesn't delay the requests
+    /// to the others. Only applies to TCP and TLS channels, RTU channels are always serialized.
+    PerUnit,
+}
+
+/// Identifier assigned to every request made on a channel
+///
+/// Identifiers are unique and monotonically increasing within a process. They are recorded in
+/// the tracing span of the transaction and passed to the [`CallbackSession`] callbacks so that
+/// asynchronous completions can be correlated with the calls that started them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId {
+    value: u64,
+}
+
+impl RequestId {
+    /// Raw value of the identifier
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            value: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
 impl RequestParam {
     /// Create a new `RequestParam` from a `UnitId` and timeout `Duration`
     pub fn new(id: UnitId, response_timeout: Duration) -> Self {
@@ -35,6 +130,18 @@ impl RequestParam {
 }
 
 impl Channel {
+    pub(crate) fn new(tx: tokio::sync::mpsc::Sender<Command>) -> Self {
+        Self {
+            tx,
+            fail_when_queue_full: AtomicBool::new(false),
+            completion: CompletionSlot::new(),
+        }
+    }
+
+    fn fail_when_queue_full(&self) -> bool {
+        self.fail_when_queue_full.load(Ordering::Relaxed)
+    }
+
     #[cfg(feature = "serial")]
     pub(crate) fn spawn_rtu(
         path: &str,
@@ -82,7 +189,16 @@ impl Channel {
             .instrument(tracing::info_span!("Modbus-Client-RTU", "port" = ?path))
             .await;
         };
-        (Channel { tx }, task)
+        (Channel::new(tx), task)
+    }
+
+    /// Control what happens when a request is made while the request queue is full
+    ///
+    /// By default, requests wait for space in the queue. When enabled, requests instead fail
+    /// immediately with [`RequestError::QueueFull`]. This only affects this handle, and the clones
+    /// and [`CallbackSession`] instances created from it afterwards, which copy the setting.
+    pub fn set_fail_when_queue_full(&self, enabled: bool) {
+        self.fail_when_queue_full.store(enabled, Ordering::Relaxed);
     }
 
     /// Enable communications
@@ -99,143 +215,586 @@ impl Channel {
 
     /// Read coils from the server
     pub async fn read_coils(
-        &mut self,
+        &self,
         param: RequestParam,
         range: AddressRange,
-    ) -> Result<Vec<Indexed<bool>>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
-        let request = wrap(
+    ) -> Result<Vec<Indexed<bool>>, RequestFailure> {
+        let range = range.of_read_bits().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadCoils, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_bits::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::Bits(x.collect())
+        });
+        self.perform(
             param,
-            RequestDetails::ReadCoils(ReadBits::channel(range.of_read_bits()?, tx)),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::ReadCoils(ReadBits::new(range, promise)),
+        )
+        .await
     }
 
     /// Read discrete inputs from the server
     pub async fn read_discrete_inputs(
-        &mut self,
+        &self,
         param: RequestParam,
         range: AddressRange,
-    ) -> Result<Vec<Indexed<bool>>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
-        let request = wrap(
+    ) -> Result<Vec<Indexed<bool>>, RequestFailure> {
+        let range = range.of_read_bits().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadDiscreteInputs, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_bits::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::Bits(x.collect())
+        });
+        self.perform(
             param,
-            RequestDetails::ReadDiscreteInputs(ReadBits::channel(range.of_read_bits()?, tx)),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::ReadDiscreteInputs(ReadBits::new(range, promise)),
+        )
+        .await
     }
 
     /// Read holding registers from the server
     pub async fn read_holding_registers(
-        &mut self,
+        &self,
         param: RequestParam,
         range: AddressRange,
-    ) -> Result<Vec<Indexed<u16>>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
-        let request = wrap(
+    ) -> Result<Vec<Indexed<u16>>, RequestFailure> {
+        let range = range.of_read_registers().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadHoldingRegisters, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_registers::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::Registers(x.collect())
+        });
+        self.perform(
             param,
-            RequestDetails::ReadHoldingRegisters(ReadRegisters::channel(
-                range.of_read_registers()?,
-                tx,
-            )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::ReadHoldingRegisters(ReadRegisters::new(range, promise)),
+        )
+        .await
     }
 
     /// Read input registers from the server
     pub async fn read_input_registers(
-        &mut self,
+        &self,
         param: RequestParam,
         range: AddressRange,
-    ) -> Result<Vec<Indexed<u16>>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
-        let request = wrap(
+    ) -> Result<Vec<Indexed<u16>>, RequestFailure> {
+        let range = range.of_read_registers().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadInputRegisters, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_registers::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::Registers(x.collect())
+        });
+        self.perform(
             param,
-            RequestDetails::ReadInputRegisters(ReadRegisters::channel(
-                range.of_read_registers()?,
-                tx,
-            )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::ReadInputRegisters(ReadRegisters::new(range, promise)),
+        )
+        .await
+    }
+
+    /// Read coils from the server into a [`ReadResult`]
+    ///
+    /// Unlike [`Channel::read_coils`], the values are stored contiguously without their addresses.
+    pub async fn read_coils_result(
+        &self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<ReadResult<bool>, RequestFailure> {
+        let range = range.of_read_bits().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadCoils, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_bits::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::BitsResult(x.into())
+        });
+        self.perform(
+            param,
+            id,
+            RequestDetails::ReadCoils(ReadBits::new(range, promise)),
+        )
+        .await
+    }
+
+    /// Read discrete inputs from the server into a [`ReadResult`]
+    ///
+    /// Unlike [`Channel::read_discrete_inputs`], the values are stored contiguously without their addresses.
+    pub async fn read_discrete_inputs_result(
+        &self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<ReadResult<bool>, RequestFailure> {
+        let range = range.of_read_bits().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadDiscreteInputs, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_bits::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::BitsResult(x.into())
+        });
+        self.perform(
+            param,
+            id,
+            RequestDetails::ReadDiscreteInputs(ReadBits::new(range, promise)),
+        )
+        .await
+    }
+
+    /// Read holding registers from the server into a [`ReadResult`]
+    ///
+    /// Unlike [`Channel::read_holding_registers`], the values are stored contiguously without their addresses.
+    pub async fn read_holding_registers_result(
+        &self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<ReadResult<u16>, RequestFailure> {
+        let range = range.of_read_registers().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadHoldingRegisters, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_registers::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::RegistersResult(x.into())
+        });
+        self.perform(
+            param,
+            id,
+            RequestDetails::ReadHoldingRegisters(ReadRegisters::new(range, promise)),
+        )
+        .await
+    }
+
+    /// Read input registers from the server into a [`ReadResult`]
+    ///
+    /// Unlike [`Channel::read_input_registers`], the values are stored contiguously without their addresses.
+    pub async fn read_input_registers_result(
+        &self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<ReadResult<u16>, RequestFailure> {
+        let range = range.of_read_registers().map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::ReadInputRegisters, range).fail(err)
+        })?;
+        let id = RequestId::next();
+        let promise = read_registers::Promise::slot(self.completion.clone(), id, |x| {
+            Completed::RegistersResult(x.into())
+        });
+        self.perform(
+            param,
+            id,
+            RequestDetails::ReadInputRegisters(ReadRegisters::new(range, promise)),
+        )
+        .await
     }
 
     /// Write a single coil on the server
+    ///
+    /// The coil is an [`Indexed<bool>`] or an `(index, value)` tuple. The value is encoded on
+    /// the wire as 0xFF00 (`true`) or 0x0000 (`false`).
     pub async fn write_single_coil(
-        &mut self,
+        &self,
         param: RequestParam,
-        request: Indexed<bool>,
-    ) -> Result<Indexed<bool>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<bool>, RequestError>>();
-        let request = wrap(
+        request: impl Into<Indexed<bool>>,
+    ) -> Result<Indexed<bool>, RequestFailure> {
+        let request = request.into();
+        let id = RequestId::next();
+        let promise = Promise::slot(self.completion.clone(), id, Completed::Coil);
+        self.perform(
             param,
-            RequestDetails::WriteSingleCoil(SingleWrite::new(request, Promise::channel(tx))),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::WriteSingleCoil(SingleWrite::new(request, promise)),
+        )
+        .await
     }
 
     /// Write a single register on the server
     pub async fn write_single_register(
-        &mut self,
+        &self,
         param: RequestParam,
         request: Indexed<u16>,
-    ) -> Result<Indexed<u16>, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<u16>, RequestError>>();
-        let request = wrap(
+    ) -> Result<Indexed<u16>, RequestFailure> {
+        let id = RequestId::next();
+        let promise = Promise::slot(self.completion.clone(), id, Completed::Register);
+        self.perform(
             param,
-            RequestDetails::WriteSingleRegister(SingleWrite::new(request, Promise::channel(tx))),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::WriteSingleRegister(SingleWrite::new(request, promise)),
+        )
+        .await
     }
 
     /// Write multiple contiguous coils on the server
     pub async fn write_multiple_coils(
-        &mut self,
+        &self,
         param: RequestParam,
         request: WriteMultiple<bool>,
-    ) -> Result<AddressRange, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
-        let request = wrap(
+    ) -> Result<AddressRange, RequestFailure> {
+        let id = RequestId::next();
+        let promise = Promise::slot(self.completion.clone(), id, Completed::Range);
+        self.perform(
             param,
-            RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(
-                request,
-                Promise::channel(tx),
-            )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(request, promise)),
+        )
+        .await
     }
 
     /// Write multiple contiguous registers on the server
     pub async fn write_multiple_registers(
-        &mut self,
+        &self,
         param: RequestParam,
         request: WriteMultiple<u16>,
-    ) -> Result<AddressRange, RequestError> {
-        let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
-        let request = wrap(
+    ) -> Result<AddressRange, RequestFailure> {
+        let id = RequestId::next();
+        let promise = Promise::slot(self.completion.clone(), id, Completed::Range);
+        self.perform(
             param,
-            RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(
-                request,
-                Promise::channel(tx),
-            )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            id,
+            RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(request, promise)),
+        )
+        .await
+    }
+
+    /// Write an unsigned 32-bit integer to 2 contiguous holding registers starting at `index`
+    pub async fn write_u32(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: u32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    /// Write a signed 32-bit integer to 2 contiguous holding registers starting at `index`
+    pub async fn write_i32(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: i32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    /// Write a 32-bit float to 2 contiguous holding registers starting at `index`
+    pub async fn write_f32(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: f32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    /// Write an unsigned 64-bit integer to 4 contiguous holding registers starting at `index`
+    pub async fn write_u64(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: u64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    /// Write a signed 64-bit integer to 4 contiguous holding registers starting at `index`
+    pub async fn write_i64(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: i64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    /// Write a 64-bit float to 4 contiguous holding registers starting at `index`
+    pub async fn write_f64(
+        &self,
+        param: RequestParam,
+        index: u16,
+        value: f64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        self.write_value(param, index, &value.to_be_bytes(), order)
+            .await
+    }
+
+    async fn write_value(
+        &self,
+        param: RequestParam,
+        index: u16,
+        bytes: &[u8],
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestFailure> {
+        let registers = order.registers(bytes);
+        let range = AddressRange {
+            start: index,
+            count: registers.len() as u16,
+        };
+        let request = WriteMultiple::from(index, registers).map_err(|err| {
+            RequestContext::new(param.id, FunctionCode::WriteMultipleRegisters, range).fail(err)
+        })?;
+        self.write_multiple_registers(param, request).await
     }
 
     /// Dynamically change the protocol decoding level of the channel
-    pub async fn set_decode_level(&mut self, level: DecodeLevel) -> Result<(), Shutdown> {
+    pub async fn set_decode_level(&self, level: DecodeLevel) -> Result<(), Shutdown> {
         self.tx
             .send(Command::Setting(Setting::DecodeLevel(level)))
             .await?;
         Ok(())
     }
+
+    /// Install a [`MetricsListener`] on the channel, replacing any previously installed listener
+    pub async fn set_metrics_listener(
+        &self,
+        listener: Box<dyn MetricsListener>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Metrics(listener)))
+            .await?;
+        Ok(())
+    }
+
+    /// Install an [`Interceptor`] on the channel, replacing any previously installed interceptor
+    pub async fn set_interceptor(&self, interceptor: Box<dyn Interceptor>) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Interceptor(interceptor)))
+            .await?;
+        Ok(())
+    }
+
+    /// Install an [`AuditSink`] that records every write performed by the channel, replacing any
+    /// previously installed sink
+    ///
+    /// Passing `None` removes the sink.
+    pub async fn set_audit_sink(
+        &self,
+        sink: Option<std::sync::Arc<dyn AuditSink>>,
+    ) -> Result<(), Shutdown> {
+        self.tx.send(Command::Setting(Setting::Audit(sink))).await?;
+        Ok(())
+    }
+
+    /// Start writing every ADU exchanged on the channel to a [`PcapWriter`]
+    ///
+    /// Passing `None` stops an ongoing capture and flushes it.
+    pub async fn set_capture(&self, capture: Option<PcapWriter>) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Capture(capture)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the level at which frames that don't match the outstanding request are logged
+    ///
+    /// These frames are logged at the WARN level by default. Passing `None` disables the logging.
+    /// Such frames are always reported to the [`MetricsListener`].
+    pub async fn set_unexpected_frame_logging(
+        &self,
+        level: Option<tracing::Level>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::UnexpectedFrameLogging(level)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the capacity of the buffer into which the channel reads from the physical layer
+    ///
+    /// The channel formats requests and parses responses in buffers that it allocates once, so
+    /// transactions do not allocate them. The read buffer holds a single frame by default, which is
+    /// also its minimum size (519 bytes). A larger buffer allows several frames to be retrieved by a
+    /// single read at the expense of memory, while the default suits memory-constrained devices.
+    pub async fn set_read_buffer_capacity(&self, capacity: usize) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::ReadBufferCapacity(capacity)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the maximum length of the PDUs (function code and data) that the channel sends
+    /// and accepts
+    ///
+    /// The limit defaults to [`crate::constants::limits::MAX_PDU_LENGTH`], the maximum of the
+    /// specification. A lower limit suits devices that advertise smaller frames, while a higher
+    /// one, up to [`crate::constants::limits::MAX_CONFIGURABLE_PDU_LENGTH`], tolerates gateways
+    /// that send slightly oversized responses. Requests that exceed the limit fail without being
+    /// sent, and oversized responses are rejected as bad frames.
+    pub async fn set_max_pdu_length(&self, length: usize) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::MaxPduLength(length)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change how strictly the channel parses responses
+    ///
+    /// Parsing is [`ResponseParsing::Strict`] by default. [`ResponseParsing::Tolerant`] makes some
+    /// devices usable whose responses deviate from the specification in ways that don't affect
+    /// their content.
+    pub async fn set_response_parsing(&self, parsing: ResponseParsing) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::ResponseParsing(parsing)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change how the channel handles responses whose unit id doesn't match the request's
+    ///
+    /// Responses are accepted with a warning by default ([`UnitIdPolicy::Warn`]). Some bridges
+    /// rewrite or zero the unit id of the responses they forward, in which case
+    /// [`UnitIdPolicy::Ignore`] silences the warnings.
+    pub async fn set_unit_id_policy(&self, policy: UnitIdPolicy) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::UnitIdPolicy(policy)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change how the channel schedules its requests
+    ///
+    /// Requests are [`RequestScheduling::Serialized`] by default, which suits servers that
+    /// process a single request at a time. A gateway to a multidrop bus can be sent
+    /// [`RequestScheduling::PerUnit`] requests, which it forwards to different devices
+    /// concurrently. The requests to a unit with an outstanding request wait for its response,
+    /// in the order in which they were made; up to 16 of them are taken off the queue.
+    pub async fn set_request_scheduling(
+        &self,
+        scheduling: RequestScheduling,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Scheduling(scheduling)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the order in which the channel sends the requests waiting in its queue
+    ///
+    /// Requests are sent in the order in which they were made by default
+    /// ([`RequestFairness::Fifo`]), so one session making many requests can delay the others.
+    /// With [`RequestFairness::RoundRobin`], the channel takes up to 16 requests off its queue
+    /// and lets their unit ids take turns. Settings still apply after the requests made before
+    /// them have been sent.
+    ///
+    /// The requests taken off the queue no longer count towards its `max_queued_requests`, so
+    /// the channel then holds up to 16 requests more than this limit.
+    pub async fn set_request_fairness(&self, fairness: RequestFairness) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Fairness(fairness)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the number of requests sent to a unit in each of its turns when the channel uses
+    /// [`RequestFairness::RoundRobin`], which defaults to 1
+    pub async fn set_unit_weight(&self, unit: UnitId, weight: u32) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::UnitWeight(unit, weight)))
+            .await?;
+        Ok(())
+    }
+
+    /// Send a single request for identical reads waiting in the queue of the channel
+    ///
+    /// When enabled, the reads of the same values from the same unit that are waiting when a
+    /// read is sent are completed with its response, or its error, instead of being sent one
+    /// after another. This is disabled by default.
+    ///
+    /// The waiting reads are found among up to 16 requests taken off the queue, which no longer
+    /// count towards its `max_queued_requests`, so the channel then holds up to 16 requests
+    /// more than this limit.
+    pub async fn set_deduplicate_reads(&self, enabled: bool) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::DeduplicateReads(enabled)))
+            .await?;
+        Ok(())
+    }
+
+    /// Send the writes waiting in the queue of the channel before its reads
+    ///
+    /// When enabled, the channel takes up to 16 requests off its queue and sends the writes
+    /// among them first, in the order in which they were made, so that commands are not delayed
+    /// by a backlog of polls. This is disabled by default.
+    ///
+    /// The requests taken off the queue no longer count towards its `max_queued_requests`, so
+    /// the channel then holds up to 16 requests more than this limit.
+    pub async fn set_write_priority(&self, enabled: bool) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::WritePriority(enabled)))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the TLS configuration of a channel created with [`crate::client::spawn_tls_client_task`]
+    ///
+    /// The established connection is kept. The configuration is used from the next connection
+    /// onwards, which allows certificates and keys to be rotated without interrupting
+    /// communications. Channels without TLS ignore the configuration.
+    #[cfg(feature = "tls")]
+    pub async fn set_tls_config(
+        &self,
+        config: crate::client::TlsClientConfig,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::TlsConfig(config)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the address of the server of a TCP or TLS channel
+    ///
+    /// The established connection is kept, the new endpoint is used from the next connection
+    /// attempt onwards. Serial channels ignore the endpoint.
+    pub async fn set_endpoint(&self, host: crate::client::HostAddr) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::Endpoint(host)))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the settings of the port of a channel created with [`crate::client::spawn_rtu_client_task`]
+    ///
+    /// The settings are applied to the open port once the requests queued before this call have
+    /// completed, and the requests queued after it are sent with the new settings, so a device
+    /// can be switched to another baud rate without recreating the channel. If the settings can't
+    /// be applied, the port is re-opened with them. Channels that are not serial ignore the settings.
+    #[cfg(feature = "serial")]
+    pub async fn set_serial_settings(
+        &self,
+        settings: crate::serial::SerialSettings,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::SerialSettings(settings)))
+            .await?;
+        Ok(())
+    }
+
+    async fn perform<T: FromCompleted>(
+        &self,
+        param: RequestParam,
+        id: RequestId,
+        details: RequestDetails,
+    ) -> Result<T, RequestFailure> {
+        let context = RequestContext::new(param.id, details.function(), details.range());
+        let completion = self.completion.register(id);
+        let command = wrap_with_id(param, id, details);
+        let result = match send_request(&self.tx, self.fail_when_queue_full(), command).await {
+            Ok(()) => completion.await,
+            Err(err) => Err(err),
+        };
+        result.map_err(|err| context.fail(err))
+    }
 }
 
 /// Callback-based session
@@ -243,9 +802,13 @@ impl Channel {
 /// This interface removes some allocations when returning results.
 /// Its primary use is for the bindings. Rust users should prefer
 /// interacting with the channel directly.
+///
+/// The methods take `&self` because the session holds no per-request state, so a single
+/// session can be shared by reference, e.g. in an `Arc`, by any number of tasks.
 #[derive(Debug, Clone)]
 pub struct CallbackSession {
     tx: tokio::sync::mpsc::Sender<Command>,
+    fail_when_queue_full: bool,
     param: RequestParam,
 }
 
@@ -253,138 +816,284 @@ impl CallbackSession {
     /// Create a [CallbackSession] from a [Channel] and the specified [RequestParam]
     pub fn new(channel: Channel, param: RequestParam) -> Self {
         CallbackSession {
+            fail_when_queue_full: channel.fail_when_queue_full(),
             tx: channel.tx,
             param,
         }
     }
 
+    /// Parameters with which the session makes its requests
+    pub fn param(&self) -> RequestParam {
+        self.param
+    }
+
     /// Read coils from the server
-    pub async fn read_coils<C>(&mut self, range: AddressRange, callback: C)
+    pub async fn read_coils<C>(&self, range: AddressRange, callback: C) -> RequestId
     where
-        C: FnOnce(Result<BitIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<BitIterator, RequestError>) + Send + Sync + 'static,
     {
         self.read_bits(range, callback, RequestDetails::ReadCoils)
-            .await;
+            .await
     }
 
     /// Read discrete inputs from the server
-    pub async fn read_discrete_inputs<C>(&mut self, range: AddressRange, callback: C)
+    pub async fn read_discrete_inputs<C>(&self, range: AddressRange, callback: C) -> RequestId
     where
-        C: FnOnce(Result<BitIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<BitIterator, RequestError>) + Send + Sync + 'static,
     {
         self.read_bits(range, callback, RequestDetails::ReadDiscreteInputs)
-            .await;
+            .await
     }
 
     /// Read holding registers from the server
-    pub async fn read_holding_registers<C>(&mut self, range: AddressRange, callback: C)
+    pub async fn read_holding_registers<C>(&self, range: AddressRange, callback: C) -> RequestId
     where
-        C: FnOnce(Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
     {
         self.read_registers(range, callback, RequestDetails::ReadHoldingRegisters)
-            .await;
+            .await
     }
 
     /// Read input registers from the server
-    pub async fn read_input_registers<C>(&mut self, range: AddressRange, callback: C)
+    pub async fn read_input_registers<C>(&self, range: AddressRange, callback: C) -> RequestId
     where
-        C: FnOnce(Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
     {
         self.read_registers(range, callback, RequestDetails::ReadInputRegisters)
-            .await;
+            .await
     }
 
-    /// Write a single coil to the server
-    pub async fn write_single_coil<C>(&mut self, value: Indexed<bool>, callback: C)
+    /// Write a single coil to the server, given as an [`Indexed<bool>`] or an `(index, value)`
+    /// tuple
+    pub async fn write_single_coil<C>(
+        &self,
+        value: impl Into<Indexed<bool>>,
+        callback: C,
+    ) -> RequestId
     where
-        C: FnOnce(Result<Indexed<bool>, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<Indexed<bool>, RequestError>) + Send + Sync + 'static,
     {
-        self.send(wrap(
+        let value = value.into();
+        let id = RequestId::next();
+        let promise = Promise::new(move |x| callback(id, x));
+        self.send(wrap_with_id(
             self.param,
-            RequestDetails::WriteSingleCoil(SingleWrite::new(value, Promise::new(callback))),
+            id,
+            RequestDetails::WriteSingleCoil(SingleWrite::new(value, promise)),
         ))
         .await;
+        id
     }
 
     /// Write a single registers to the server
-    pub async fn write_single_register<C>(&mut self, value: Indexed<u16>, callback: C)
+    pub async fn write_single_register<C>(&self, value: Indexed<u16>, callback: C) -> RequestId
     where
-        C: FnOnce(Result<Indexed<u16>, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<Indexed<u16>, RequestError>) + Send + Sync + 'static,
     {
-        self.send(wrap(
+        let id = RequestId::next();
+        let promise = Promise::new(move |x| callback(id, x));
+        self.send(wrap_with_id(
             self.param,
-            RequestDetails::WriteSingleRegister(SingleWrite::new(value, Promise::new(callback))),
+            id,
+            RequestDetails::WriteSingleRegister(SingleWrite::new(value, promise)),
         ))
         .await;
+        id
     }
 
     /// Write multiple contiguous registers to the server
-    pub async fn write_multiple_registers<C>(&mut self, value: WriteMultiple<u16>, callback: C)
+    pub async fn write_multiple_registers<C>(
+        &self,
+        value: WriteMultiple<u16>,
+        callback: C,
+    ) -> RequestId
     where
-        C: FnOnce(Result<AddressRange, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<AddressRange, RequestError>) + Send + Sync + 'static,
     {
-        self.send(wrap(
+        let id = RequestId::next();
+        let promise = Promise::new(move |x| callback(id, x));
+        self.send(wrap_with_id(
             self.param,
-            RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(
-                value,
-                Promise::new(callback),
-            )),
+            id,
+            RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(value, promise)),
         ))
         .await;
+        id
     }
 
     /// Write multiple contiguous coils to the server
-    pub async fn write_multiple_coils<C>(&mut self, value: WriteMultiple<bool>, callback: C)
+    pub async fn write_multiple_coils<C>(
+        &self,
+        value: WriteMultiple<bool>,
+        callback: C,
+    ) -> RequestId
     where
-        C: FnOnce(Result<AddressRange, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<AddressRange, RequestError>) + Send + Sync + 'static,
     {
-        self.send(wrap(
+        let id = RequestId::next();
+        let promise = Promise::new(move |x| callback(id, x));
+        self.send(wrap_with_id(
             self.param,
-            RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(
-                value,
-                Promise::new(callback),
-            )),
+            id,
+            RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(value, promise)),
         ))
         .await;
+        id
     }
 
-    async fn read_bits<C, W>(&mut self, range: AddressRange, callback: C, wrap_req: W)
+    async fn read_bits<C, W>(&self, range: AddressRange, callback: C, wrap_req: W) -> RequestId
     where
-        C: FnOnce(Result<BitIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<BitIterator, RequestError>) + Send + Sync + 'static,
         W: Fn(ReadBits) -> RequestDetails,
     {
-        let mut promise = crate::client::requests::read_bits::Promise::new(callback);
+        let id = RequestId::next();
+        let mut promise =
+            read_bits::Promise::new(move |x: Result<BitIterator, RequestError>| callback(id, x));
         let range = match range.of_read_bits() {
             Ok(x) => x,
-            Err(err) => return promise.failure(err.into()),
+            Err(err) => {
+                promise.failure(err.into());
+                return id;
+            }
         };
-        self.send(wrap(self.param, wrap_req(ReadBits::new(range, promise))))
-            .await;
+        self.send(wrap_with_id(
+            self.param,
+            id,
+            wrap_req(ReadBits::new(range, promise)),
+        ))
+        .await;
+        id
     }
 
-    async fn read_registers<C, W>(&mut self, range: AddressRange, callback: C, wrap_req: W)
+    async fn read_registers<C, W>(&self, range: AddressRange, callback: C, wrap_req: W) -> RequestId
     where
-        C: FnOnce(Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
+        C: FnOnce(RequestId, Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
         W: Fn(ReadRegisters) -> RequestDetails,
     {
-        let mut promise = crate::client::requests::read_registers::Promise::new(callback);
+        let id = RequestId::next();
+        let mut promise =
+            read_registers::Promise::new(move |x: Result<RegisterIterator, RequestError>| {
+                callback(id, x)
+            });
         let range = match range.of_read_registers() {
             Ok(x) => x,
-            Err(err) => return promise.failure(err.into()),
+            Err(err) => {
+                promise.failure(err.into());
+                return id;
+            }
         };
-        self.send(wrap(
+        self.send(wrap_with_id(
             self.param,
+            id,
             wrap_req(ReadRegisters::new(range, promise)),
         ))
         .await;
+        id
+    }
+
+    async fn send(&self, command: Command) {
+        // the promise of the request has already been failed if it could not be queued
+        let _ = send_request(&self.tx, self.fail_when_queue_full, command).await;
+    }
+}
+
+/// Session that can only read from the server
+///
+/// This is a capability-restricted view of a [`CallbackSession`] for components that must never
+/// command outputs. Only the read function codes are available, so it is a compile-time error
+/// to attempt a write through it:
+///
+/// ```compile_fail
+/// # async fn run(session: rodbus::client::CallbackSession) {
+/// use rodbus::Indexed;
+///
+/// let monitor = session.read_only();
+/// monitor.write_single_coil(Indexed::new(0, true), |_, _| {}).await;
+/// # }
+/// ```
+///
+/// There is no way to obtain the underlying session or channel back from it.
+#[derive(Debug, Clone)]
+pub struct ReadOnlySession {
+    inner: CallbackSession,
+}
+
+impl CallbackSession {
+    /// Restrict a clone of this session to the read function codes
+    pub fn read_only(&self) -> ReadOnlySession {
+        ReadOnlySession::from(self.clone())
+    }
+}
+
+impl From<CallbackSession> for ReadOnlySession {
+    fn from(inner: CallbackSession) -> Self {
+        Self { inner }
+    }
+}
+
+impl ReadOnlySession {
+    /// Read coils from the server
+    pub async fn read_coils<C>(&self, range: AddressRange, callback: C) -> RequestId
+    where
+        C: FnOnce(RequestId, Result<BitIterator, RequestError>) + Send + Sync + 'static,
+    {
+        self.inner.read_coils(range, callback).await
+    }
+
+    /// Read discrete inputs from the server
+    pub async fn read_discrete_inputs<C>(&self, range: AddressRange, callback: C) -> RequestId
+    where
+        C: FnOnce(RequestId, Result<BitIterator, RequestError>) + Send + Sync + 'static,
+    {
+        self.inner.read_discrete_inputs(range, callback).await
     }
 
-    async fn send(&mut self, command: Command) {
+    /// Read holding registers from the server
+    pub async fn read_holding_registers<C>(&self, range: AddressRange, callback: C) -> RequestId
+    where
+        C: FnOnce(RequestId, Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
+    {
+        self.inner.read_holding_registers(range, callback).await
+    }
+
+    /// Read input registers from the server
+    pub async fn read_input_registers<C>(&self, range: AddressRange, callback: C) -> RequestId
+    where
+        C: FnOnce(RequestId, Result<RegisterIterator, RequestError>) + Send + Sync + 'static,
+    {
+        self.inner.read_input_registers(range, callback).await
+    }
+}
+
+async fn send_request(
+    tx: &tokio::sync::mpsc::Sender<Command>,
+    fail_when_queue_full: bool,
+    command: Command,
+) -> Result<(), RequestError> {
+    if !fail_when_queue_full {
+        // dropping the command will automatically fail requests with SHUTDOWN
+        tx.send(command).await?;
+        return Ok(());
+    }
+
+    match tx.try_send(command) {
+        Ok(()) => Ok(()),
+        Err(tokio::sync::mpsc::error::TrySendError::Full(mut command)) => {
+            if let Command::Request(request) = &mut command {
+                request.details.fail(RequestError::QueueFull);
+            }
+            Err(RequestError::QueueFull)
+        }
         // dropping the command will automatically fail requests with SHUTDOWN
-        let _ = self.tx.send(command).await;
+        Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => Err(RequestError::Shutdown),
     }
 }
 
-fn wrap(param: RequestParam, details: RequestDetails) -> Command {
-    Command::Request(Request::new(param.id, param.response_timeout, details))
+fn wrap_with_id(param: RequestParam, request_id: RequestId, details: RequestDetails) -> Command {
+    Command::Request(Request::new(
+        param.id,
+        request_id,
+        param.response_timeout,
+        details,
+    ))
 }
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::client::RequestId;
+use crate::error::RequestError;
+use crate::types::{AddressRange, Indexed, ReadResult};
+
+/// Value of a completed request, with a variant for each type returned by the [`Channel`] methods
+///
+/// [`Channel`]: crate::client::Channel
+pub(crate) enum Completed {
+    Bits(Vec<Indexed<bool>>),
+    Registers(Vec<Indexed<u16>>),
+    BitsResult(ReadResult<bool>),
+    RegistersResult(ReadResult<u16>),
+    Coil(Indexed<bool>),
+    Register(Indexed<u16>),
+    Range(AddressRange),
+}
+
+pub(crate) trait FromCompleted: Sized {
+    fn from_completed(value: Completed) -> Option<Self>;
+}
+
+macro_rules! from_completed {
+    ($type:ty, $variant:ident) => {
+        impl FromCompleted for $type {
+            fn from_completed(value: Completed) -> Option<Self> {
+                match value {
+                    Completed::$variant(x) => Some(x),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_completed!(Vec<Indexed<bool>>, Bits);
+from_completed!(Vec<Indexed<u16>>, Registers);
+from_completed!(ReadResult<bool>, BitsResult);
+from_completed!(ReadResult<u16>, RegistersResult);
+from_completed!(Indexed<bool>, Coil);
+from_completed!(Indexed<u16>, Register);
+from_completed!(AddressRange, Range);
+
+enum Pending {
+    Waiting(Option<Waker>),
+    Done(Result<Completed, RequestError>),
+}
+
+/// Table through which the channel task completes the requests of the [`Channel`] handles
+///
+/// Each request waits on its own entry, keyed by its [`RequestId`], instead of creating a oneshot
+/// channel per request. The table is shared by the clones of a handle, and the capacity of the map
+/// is reused by the successive requests. A request whose future was dropped removes its entry, so
+/// a completion that arrives afterwards finds no entry and is discarded.
+///
+/// [`Channel`]: crate::client::Channel
+#[derive(Clone)]
+pub(crate) struct CompletionSlot {
+    pending: Arc<Mutex<HashMap<RequestId, Pending>>>,
+}
+
+impl std::fmt::Debug for CompletionSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("CompletionSlot")
+    }
+}
+
+impl CompletionSlot {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Add the entry of a request, which must be done before the request is sent so that its
+    /// completion can't be missed. The entry is removed when the returned future completes or is
+    /// dropped.
+    pub(crate) fn register<T: FromCompleted>(&self, id: RequestId) -> Wait<'_, T> {
+        self.lock().insert(id, Pending::Waiting(None));
+        Wait {
+            slot: self,
+            id,
+            _value: PhantomData,
+        }
+    }
+
+    pub(crate) fn complete(&self, id: RequestId, result: Result<Completed, RequestError>) {
+        let waker = match self.lock().get_mut(&id) {
+            Some(entry) => match std::mem::replace(entry, Pending::Done(result)) {
+                Pending::Waiting(waker) => waker,
+                // each request is completed once
+                Pending::Done(_) => None,
+            },
+            // the request was abandoned
+            None => None,
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<RequestId, Pending>> {
+        // the map is always left in a consistent state, so a poisoned lock can be recovered
+        self.pending.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Future completing with the value of a request registered in a [`CompletionSlot`]
+pub(crate) struct Wait<'a, T> {
+    slot: &'a CompletionSlot,
+    id: RequestId,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: FromCompleted> Future for Wait<'_, T> {
+    type Output = Result<T, RequestError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut pending = self.slot.lock();
+        let result = match pending.remove(&self.id) {
+            Some(Pending::Done(result)) => result,
+            Some(Pending::Waiting(_)) | None => {
+                pending.insert(self.id, Pending::Waiting(Some(cx.waker().clone())));
+                return Poll::Pending;
+            }
+        };
+        drop(pending);
+        Poll::Ready(match T::from_completed(result?) {
+            Some(x) => Ok(x),
+            // each identifier is used by a single request of a single type
+            None => Err(RequestError::Shutdown),
+        })
+    }
+}
+
+impl<T> Drop for Wait<'_, T> {
+    fn drop(&mut self) {
+        self.slot.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::Command;
+    use crate::client::{Channel, RequestParam, ResponseParsing};
+    use crate::decode::AppDecodeLevel;
+    use crate::types::UnitId;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn discards_completions_of_abandoned_requests() {
+        let slot = CompletionSlot::new();
+        let abandoned = RequestId::next();
+        let current = RequestId::next();
+
+        drop(slot.register::<Indexed<u16>>(abandoned));
+        let waiter = slot.register::<Indexed<u16>>(current);
+
+        slot.complete(current, Ok(Completed::Register(Indexed::new(2, 2))));
+        slot.complete(abandoned, Ok(Completed::Register(Indexed::new(1, 1))));
+
+        assert_eq!(waiter.await, Ok(Indexed::new(2, 2)));
+        assert!(slot.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_completion_that_preceded_the_wait() {
+        let slot = CompletionSlot::new();
+        let id = RequestId::next();
+        let waiter = slot.register::<AddressRange>(id);
+        slot.complete(id, Err(RequestError::ResponseTimeout));
+        assert_eq!(waiter.await, Err(RequestError::ResponseTimeout));
+    }
+
+    #[tokio::test]
+    async fn abandoned_request_completing_after_the_next_one_is_discarded() {
+        // the device answers the second request before the first, abandoned one expires, as
+        // happens when the scheduler lets a write overtake a read
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let device = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            while let Some(cmd) = rx.recv().await {
+                if let Command::Request(request) = cmd {
+                    requests.push(request);
+                }
+                if requests.len() == 2 {
+                    break;
+                }
+            }
+            let mut write = requests.pop().unwrap();
+            let mut read = requests.pop().unwrap();
+            write
+                .handle_response(
+                    &[0x06, 0x00, 0x01, 0x00, 0x05],
+                    AppDecodeLevel::Nothing,
+                    ResponseParsing::Strict,
+                )
+                .unwrap();
+            read.details.fail(RequestError::ResponseTimeout);
+        });
+
+        let channel = Channel::new(tx);
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let read = channel.read_holding_registers(param, AddressRange::try_from(0, 1).unwrap());
+        assert!(tokio::time::timeout(Duration::from_millis(10), read)
+            .await
+            .is_err());
+
+        let write = channel.write_single_register(param, Indexed::new(1, 5));
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(1), write).await,
+            Ok(Ok(Indexed::new(1, 5)))
+        );
+        device.await.unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use crate::client::{
+    CallbackSession, Channel, ClientState, HostAddr, Listener, RequestParam, RetryStrategy,
+};
+use crate::decode::DecodeLevel;
+use crate::types::UnitId;
+
+/// Remote end of a channel created from a [`ChannelConfig`]
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Endpoint {
+    /// Modbus TCP server
+    Tcp(HostAddr),
+    /// Modbus RTU devices on a serial port
+    #[cfg(feature = "serial")]
+    Rtu {
+        /// Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+        path: String,
+        /// Serial port settings
+        settings: crate::serial::SerialSettings,
+    },
+    /// Modbus TCP server secured by TLS
+    #[cfg(feature = "tls")]
+    Tls(HostAddr, crate::client::TlsClientConfig),
+}
+
+/// Configuration of a client channel, an alternative to the `spawn_*_client_task` functions
+/// whose parameters default to sensible values
+///
+/// ```no_run
+/// # async fn run() {
+/// use rodbus::client::*;
+///
+/// let channel = ChannelConfig::new(Endpoint::Tcp(HostAddr::dns("device".to_string(), 502)))
+///     .max_queued_requests(4)
+///     .spawn();
+/// # }
+/// ```
+pub struct ChannelConfig {
+    pub(crate) endpoint: Endpoint,
+    max_queued_requests: usize,
+    retry: Box<dyn RetryStrategy>,
+    decode: DecodeLevel,
+    fail_when_queue_full: bool,
+    pub(crate) client_listener: Option<Box<dyn Listener<ClientState>>>,
+    #[cfg(feature = "serial")]
+    pub(crate) port_listener: Option<Box<dyn Listener<crate::client::PortState>>>,
+}
+
+impl ChannelConfig {
+    /// Default maximum size of the request queue
+    pub const DEFAULT_MAX_QUEUED_REQUESTS: usize = 16;
+
+    /// Configure a channel to the endpoint with a queue of [`Self::DEFAULT_MAX_QUEUED_REQUESTS`],
+    /// the [`crate::client::default_retry_strategy`], and no decoding
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            max_queued_requests: Self::DEFAULT_MAX_QUEUED_REQUESTS,
+            retry: crate::client::default_retry_strategy(),
+            decode: DecodeLevel::nothing(),
+            fail_when_queue_full: false,
+            client_listener: None,
+            #[cfg(feature = "serial")]
+            port_listener: None,
+        }
+    }
+
+    /// Maximum size of the request queue
+    pub fn max_queued_requests(mut self, value: usize) -> Self {
+        self.max_queued_requests = value;
+        self
+    }
+
+    /// Controls when the connection is retried, or the port re-opened, on failure
+    pub fn retry(mut self, value: Box<dyn RetryStrategy>) -> Self {
+        self.retry = value;
+        self
+    }
+
+    /// Decode log level
+    pub fn decode(mut self, value: DecodeLevel) -> Self {
+        self.decode = value;
+        self
+    }
+
+    /// Fail requests immediately when the queue is full, see [`Channel::set_fail_when_queue_full`]
+    pub fn fail_when_queue_full(mut self, value: bool) -> Self {
+        self.fail_when_queue_full = value;
+        self
+    }
+
+    /// Callback monitoring the state of a TCP or TLS connection, ignored by RTU channels
+    pub fn listener(mut self, value: Box<dyn Listener<ClientState>>) -> Self {
+        self.client_listener = Some(value);
+        self
+    }
+
+    /// Callback monitoring the state of a serial port, ignored by TCP and TLS channels
+    #[cfg(feature = "serial")]
+    pub fn port_listener(mut self, value: Box<dyn Listener<crate::client::PortState>>) -> Self {
+        self.port_listener = Some(value);
+        self
+    }
+
+    /// Spawn the channel task onto the runtime. The task completes when the returned channel
+    /// handle is dropped.
+    ///
+    /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+    pub fn spawn(self) -> Channel {
+        let (channel, task) = self.create();
+        tokio::spawn(task);
+        channel
+    }
+
+    /// Create the channel handle and the task that must be spawned to run it
+    pub(crate) fn create(self) -> (Channel, ChannelTask) {
+        let client_listener = self
+            .client_listener
+            .unwrap_or_else(|| crate::client::NullListener::create());
+        let (channel, task): (Channel, ChannelTask) = match self.endpoint {
+            Endpoint::Tcp(host) => {
+                let (channel, task) = crate::tcp::client::create_tcp_channel(
+                    host,
+                    self.max_queued_requests,
+                    self.retry,
+                    self.decode,
+                    client_listener,
+                );
+                (channel, Box::pin(task))
+            }
+            #[cfg(feature = "serial")]
+            Endpoint::Rtu { path, settings } => {
+                let (channel, task) = Channel::create_rtu_handle_and_task(
+                    &path,
+                    settings,
+                    self.max_queued_requests,
+                    self.retry,
+                    self.decode,
+                    self.port_listener,
+                );
+                (channel, Box::pin(task))
+            }
+            #[cfg(feature = "tls")]
+            Endpoint::Tls(host, tls_config) => {
+                let (channel, task) = crate::tcp::tls::create_tls_channel(
+                    host,
+                    self.max_queued_requests,
+                    self.retry,
+                    tls_config,
+                    self.decode,
+                    client_listener,
+                );
+                (channel, Box::pin(task))
+            }
+        };
+        channel.set_fail_when_queue_full(self.fail_when_queue_full);
+        (channel, task)
+    }
+}
+
+pub(crate) type ChannelTask = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+
+/// Configuration of the requests of a [`CallbackSession`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionConfig {
+    unit: UnitId,
+    response_timeout: Duration,
+}
+
+impl SessionConfig {
+    /// Default response timeout of the requests
+    pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Configure requests to `unit` with a response timeout of [`Self::DEFAULT_RESPONSE_TIMEOUT`]
+    pub fn new(unit: UnitId) -> Self {
+        Self {
+            unit,
+            response_timeout: Self::DEFAULT_RESPONSE_TIMEOUT,
+        }
+    }
+
+    /// Time after which a request fails if no response is received
+    pub fn response_timeout(mut self, value: Duration) -> Self {
+        self.response_timeout = value;
+        self
+    }
+
+    /// Parameters of the requests
+    pub fn param(&self) -> RequestParam {
+        RequestParam::new(self.unit, self.response_timeout)
+    }
+
+    /// Create a session on the channel
+    pub fn create(&self, channel: Channel) -> CallbackSession {
+        CallbackSession::new(channel, self.param())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::Command;
+
+    #[tokio::test]
+    async fn sessions_send_requests_with_the_configured_parameters() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let config = SessionConfig::new(UnitId::new(7)).response_timeout(Duration::from_secs(3));
+        assert_eq!(config.param().id, UnitId::new(7));
+        assert_eq!(config.param().response_timeout, Duration::from_secs(3));
+
+        let session = config.create(Channel::new(tx));
+        session
+            .read_coils(
+                crate::types::AddressRange::try_from(0, 1).unwrap(),
+                |_, _| {},
+            )
+            .await;
+        match rx.recv().await {
+            Some(Command::Request(request)) => {
+                assert_eq!(request.id, UnitId::new(7));
+                let remaining = request.deadline - tokio::time::Instant::now();
+                assert!(remaining > Duration::from_secs(2));
+                assert!(remaining <= Duration::from_secs(3));
+            }
+            _ => panic!("expected a request"),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::Receiver;
+
+use crate::client::message::{Command, Request};
+use crate::types::UnitId;
+
+/// Order in which a channel sends the requests waiting in its queue, see
+/// [`crate::client::Channel::set_request_fairness`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RequestFairness {
+    /// Send the requests in the order in which they were made
+    #[default]
+    Fifo,
+    /// Take turns between the unit ids with waiting requests, sending as many requests to a
+    /// unit in a turn as its weight, see [`crate::client::Channel::set_unit_weight`]
+    RoundRobin,
+}
+
+/// Requests taken off the queue of a channel and ordered by unit id
+pub(crate) struct FairQueue {
+    // requests with the order in which they were made
+    queues: BTreeMap<UnitId, VecDeque<(u64, Request)>>,
+    // units with waiting requests, in the order of their turns
+    turns: VecDeque<UnitId>,
+    // number of requests sent to the unit whose turn it is
+    served: u32,
+    weights: BTreeMap<UnitId, u32>,
+    len: usize,
+    next_order: u64,
+    writes_first: bool,
+    // setting that was made after the waiting requests
+    setting: Option<Command>,
+}
+
+impl FairQueue {
+    /// Maximum number of requests taken off the queue of the channel
+    ///
+    /// These free their places in the queue, so they add to the `max_queued_requests` of the
+    /// channel, as documented on the settings that make use of this queue.
+    pub(crate) const MAX_LEN: usize = 16;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+            turns: VecDeque::new(),
+            served: 0,
+            weights: BTreeMap::new(),
+            len: 0,
+            next_order: 0,
+            writes_first: false,
+            setting: None,
+        }
+    }
+
+    /// Next command to execute, taking the requests off the queue in the order of the
+    /// [`RequestFairness`]
+    ///
+    /// A setting is returned once the requests made before it have been returned. The future
+    /// can be cancelled without losing a command.
+    pub(crate) async fn next_command(
+        &mut self,
+        rx: &mut Receiver<Command>,
+        fairness: RequestFairness,
+    ) -> Option<Command> {
+        loop {
+            if self.is_empty() {
+                if let Some(setting) = self.setting.take() {
+                    return Some(setting);
+                }
+                if fairness == RequestFairness::Fifo && !self.writes_first {
+                    return rx.recv().await;
+                }
+            }
+
+            let closed = self.fill(rx);
+            if let Some(request) = self.pop(fairness) {
+                return Some(Command::Request(request));
+            }
+            if closed {
+                return None;
+            }
+
+            match rx.recv().await {
+                Some(Command::Request(request)) => self.push(request),
+                cmd => return cmd,
+            }
+        }
+    }
+
+    /// Take the requests waiting in the channel queue, up to the next setting. Returns `true`
+    /// if the queue is closed.
+    fn fill(&mut self, rx: &mut Receiver<Command>) -> bool {
+        while self.setting.is_none() && self.len < Self::MAX_LEN {
+            match rx.try_recv() {
+                Ok(Command::Request(request)) => self.push(request),
+                Ok(setting) => self.setting = Some(setting),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+        false
+    }
+
+    /// Remove the waiting requests that read the same values from the same unit as the request
+    pub(crate) fn take_duplicates(
+        &mut self,
+        rx: &mut Receiver<Command>,
+        request: &Request,
+    ) -> Vec<Request> {
+        self.fill(rx);
+        let queue = match self.queues.get_mut(&request.id) {
+            Some(queue) => queue,
+            None => return Vec::new(),
+        };
+        let mut duplicates = Vec::new();
+        let mut index = 0;
+        while index < queue.len() {
+            if queue[index].1.is_same_read(request) {
+                if let Some((_, duplicate)) = queue.remove(index) {
+                    duplicates.push(duplicate);
+                }
+            } else {
+                index += 1;
+            }
+        }
+        self.len -= duplicates.len();
+        if queue.is_empty() {
+            self.remove_unit(request.id);
+        }
+        duplicates
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn set_weight(&mut self, unit: UnitId, weight: u32) {
+        if weight == 1 {
+            self.weights.remove(&unit);
+        } else {
+            self.weights.insert(unit, weight.max(1));
+        }
+    }
+
+    pub(crate) fn set_write_priority(&mut self, enabled: bool) {
+        self.writes_first = enabled;
+    }
+
+    pub(crate) fn push(&mut self, request: Request) {
+        let queue = self.queues.entry(request.id).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(request.id);
+        }
+        queue.push_back((self.next_order, request));
+        self.next_order += 1;
+        self.len += 1;
+    }
+
+    pub(crate) fn pop(&mut self, fairness: RequestFairness) -> Option<Request> {
+        if self.writes_first {
+            if let Some(request) = self.pop_write() {
+                return Some(request);
+            }
+        }
+        let unit = match fairness {
+            RequestFairness::Fifo => {
+                *self
+                    .queues
+                    .iter()
+                    .min_by_key(|(_, queue)| queue.front().map(|x| x.0))?
+                    .0
+            }
+            RequestFairness::RoundRobin => *self.turns.front()?,
+        };
+        let queue = self.queues.get_mut(&unit)?;
+        let (_, request) = queue.pop_front()?;
+        self.len -= 1;
+        if queue.is_empty() {
+            self.remove_unit(unit);
+        } else if fairness == RequestFairness::RoundRobin {
+            self.served += 1;
+            if self.served >= self.weights.get(&unit).copied().unwrap_or(1) {
+                self.turns.rotate_left(1);
+                self.served = 0;
+            }
+        }
+        Some(request)
+    }
+
+    /// Remove the write that was made first, regardless of the turns of the units
+    fn pop_write(&mut self) -> Option<Request> {
+        let (unit, index) = self
+            .queues
+            .iter()
+            .filter_map(|(unit, queue)| {
+                let (index, (order, _)) = queue
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (_, request))| request.details.is_write())?;
+                Some((*order, *unit, index))
+            })
+            .min()
+            .map(|(_, unit, index)| (unit, index))?;
+        let queue = self.queues.get_mut(&unit)?;
+        let (_, request) = queue.remove(index)?;
+        self.len -= 1;
+        if queue.is_empty() {
+            self.remove_unit(unit);
+        }
+        Some(request)
+    }
+
+    fn remove_unit(&mut self, unit: UnitId) {
+        self.queues.remove(&unit);
+        if self.turns.front() == Some(&unit) {
+            self.served = 0;
+        }
+        self.turns.retain(|x| *x != unit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::RequestDetails;
+    use crate::client::requests::read_bits::{Promise, ReadBits};
+    use crate::client::requests::write_single::SingleWrite;
+    use crate::client::RequestId;
+    use crate::types::{AddressRange, Indexed};
+
+    fn request(unit: u8) -> Request {
+        read(unit, 0)
+    }
+
+    fn read(unit: u8, start: u16) -> Request {
+        let range = AddressRange::try_from(start, 1)
+            .unwrap()
+            .of_read_bits()
+            .unwrap();
+        Request::new(
+            UnitId::new(unit),
+            RequestId::next(),
+            std::time::Duration::from_secs(1),
+            RequestDetails::ReadCoils(ReadBits::new(range, Promise::new(|_| {}))),
+        )
+    }
+
+    #[test]
+    fn units_take_turns_according_to_their_weight() {
+        let mut queue = FairQueue::new();
+        queue.set_weight(UnitId::new(2), 2);
+        for unit in [1, 1, 1, 2, 2, 2, 3] {
+            queue.push(request(unit));
+        }
+
+        let mut order = Vec::new();
+        while let Some(request) = queue.pop(RequestFairness::RoundRobin) {
+            order.push(request.id.value);
+        }
+        assert_eq!(order, [1, 2, 2, 3, 1, 2, 1]);
+        assert!(queue.is_empty());
+    }
+
+    fn write(unit: u8, index: u16) -> Request {
+        Request::new(
+            UnitId::new(unit),
+            RequestId::next(),
+            std::time::Duration::from_secs(1),
+            RequestDetails::WriteSingleRegister(SingleWrite::new(
+                Indexed::new(index, 0),
+                crate::client::message::Promise::new(|_| {}),
+            )),
+        )
+    }
+
+    #[test]
+    fn writes_overtake_the_reads() {
+        let mut queue = FairQueue::new();
+        queue.set_write_priority(true);
+        for request in [read(1, 0), read(2, 0), write(2, 1), read(1, 1), write(1, 2)] {
+            queue.push(request);
+        }
+
+        let mut order = Vec::new();
+        while let Some(request) = queue.pop(RequestFairness::Fifo) {
+            order.push((request.id.value, request.details.range().start));
+        }
+        assert_eq!(order, [(2, 1), (1, 2), (1, 0), (2, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn duplicates_are_removed_from_the_queue() {
+        let (_tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let mut queue = FairQueue::new();
+        for (unit, start) in [(1, 0), (1, 5), (2, 0), (1, 0)] {
+            queue.push(read(unit, start));
+        }
+
+        let duplicates = queue.take_duplicates(&mut rx, &read(1, 0));
+        assert_eq!(duplicates.len(), 2);
+
+        let mut order = Vec::new();
+        while let Some(request) = queue.pop(RequestFairness::Fifo) {
+            order.push((request.id.value, request.details.range().start));
+        }
+        assert_eq!(order, [(1, 5), (2, 0)]);
+    }
+}
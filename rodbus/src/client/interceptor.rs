@@ -0,0 +1,26 @@
+use crate::error::RequestError;
+use crate::types::UnitId;
+
+/// Inspects every request sent and every response received on a channel
+///
+/// An interceptor is invoked synchronously from the channel task, inside the tracing span of
+/// the transaction. Anything it logs is therefore annotated with the transaction id.
+///
+/// Each method receives the unit id from the frame header, the raw function code, and the raw
+/// PDU (function code followed by the data). Returning an error vetoes the frame:
+///
+/// * a vetoed request is never transmitted and fails with the returned error
+/// * a vetoed response is discarded and the pending request fails with the returned error
+///
+/// Every method has a default implementation that accepts the frame.
+pub trait Interceptor: Send {
+    /// Called before a request is written to the physical layer
+    fn on_request(&mut self, _id: UnitId, _function: u8, _pdu: &[u8]) -> Result<(), RequestError> {
+        Ok(())
+    }
+
+    /// Called when a response to the pending request is received, before it is parsed
+    fn on_response(&mut self, _id: UnitId, _function: u8, _pdu: &[u8]) -> Result<(), RequestError> {
+        Ok(())
+    }
+}
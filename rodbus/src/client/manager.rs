@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::task::JoinHandle;
+
+use crate::client::{
+    CallbackSession, Channel, ChannelConfig, ChannelStatistics, ClientState, Listener,
+    SessionConfig,
+};
+use crate::MaybeAsync;
+
+/// State of a channel owned by a [`ChannelManager`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ManagedState {
+    /// State of a TCP or TLS channel
+    Client(ClientState),
+    /// State of an RTU channel
+    #[cfg(feature = "serial")]
+    Port(crate::client::PortState),
+}
+
+/// Change of state of one of the channels of a [`ChannelManager`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelEvent {
+    /// Name of the channel
+    pub channel: String,
+    /// New state of the channel
+    pub state: ManagedState,
+}
+
+/// A channel with the same name is already owned by the [`ChannelManager`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateChannel(pub String);
+
+impl std::error::Error for DuplicateChannel {}
+
+impl std::fmt::Display for DuplicateChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "channel name already in use: {}", self.0)
+    }
+}
+
+struct ManagedChannel {
+    channel: Channel,
+    statistics: ChannelStatistics,
+    task: JoinHandle<()>,
+    shutdown: ManagedState,
+}
+
+/// Owns the channels of an application that communicates with many devices
+///
+/// Each channel is identified by a unique name. The manager records the [`ChannelStatistics`]
+/// of every channel and reports the state changes of all of them as [`ChannelEvent`] on a single
+/// queue. The queue must be drained, otherwise the channel tasks stop once it is full.
+///
+/// Dropping the manager, or calling [`ChannelManager::shutdown`], stops every channel task, even
+/// if clones of the channels or sessions obtained from the manager are still alive. Requests
+/// made on those afterwards fail with [`crate::RequestError::Shutdown`].
+pub struct ChannelManager {
+    channels: BTreeMap<String, ManagedChannel>,
+    events: Sender<ChannelEvent>,
+}
+
+impl ChannelManager {
+    /// Create an empty manager and the receiver of the state changes of its channels
+    pub fn new(max_queued_events: usize) -> (Self, Receiver<ChannelEvent>) {
+        let (events, rx) = tokio::sync::mpsc::channel(max_queued_events);
+        let manager = Self {
+            channels: BTreeMap::new(),
+            events,
+        };
+        (manager, rx)
+    }
+
+    /// Spawn the channel described by `config` under the given name
+    ///
+    /// The state listener of the configuration, if any, is still invoked before the change is
+    /// reported on the queue of the manager.
+    ///
+    /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+    pub async fn add(
+        &mut self,
+        name: &str,
+        mut config: ChannelConfig,
+    ) -> Result<Channel, DuplicateChannel> {
+        if self.channels.contains_key(name) {
+            return Err(DuplicateChannel(name.to_string()));
+        }
+
+        let shutdown = match config.endpoint {
+            #[cfg(feature = "serial")]
+            crate::client::Endpoint::Rtu { .. } => {
+                ManagedState::Port(crate::client::PortState::Shutdown)
+            }
+            _ => ManagedState::Client(ClientState::Shutdown),
+        };
+        let inner = config.client_listener.take();
+        config.client_listener = Some(Box::new(ForwardingListener {
+            channel: name.to_string(),
+            inner,
+            events: self.events.clone(),
+            wrap: ManagedState::Client,
+        }));
+        #[cfg(feature = "serial")]
+        {
+            let inner = config.port_listener.take();
+            config.port_listener = Some(Box::new(ForwardingListener {
+                channel: name.to_string(),
+                inner,
+                events: self.events.clone(),
+                wrap: ManagedState::Port,
+            }));
+        }
+
+        let (channel, task) = config.create();
+        let task = tokio::spawn(task);
+        let statistics = ChannelStatistics::new();
+        // the task was just spawned, so it can't have shut down
+        let _ = channel
+            .set_metrics_listener(Box::new(statistics.clone()))
+            .await;
+
+        self.channels.insert(
+            name.to_string(),
+            ManagedChannel {
+                channel: channel.clone(),
+                statistics,
+                task,
+                shutdown,
+            },
+        );
+        Ok(channel)
+    }
+
+    /// Names of the channels, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.channels.keys().map(|name| name.as_str())
+    }
+
+    /// Channel with the given name
+    pub fn channel(&self, name: &str) -> Option<Channel> {
+        self.channels.get(name).map(|x| x.channel.clone())
+    }
+
+    /// Session communicating with a unit through the channel with the given name
+    pub fn session(&self, name: &str, config: SessionConfig) -> Option<CallbackSession> {
+        self.channel(name).map(|channel| config.create(channel))
+    }
+
+    /// Statistics of the channel with the given name
+    pub fn statistics(&self, name: &str) -> Option<ChannelStatistics> {
+        self.channels.get(name).map(|x| x.statistics.clone())
+    }
+
+    /// Statistics of every channel, in alphabetical order of their names
+    pub fn all_statistics(&self) -> Vec<(String, ChannelStatistics)> {
+        self.channels
+            .iter()
+            .map(|(name, x)| (name.clone(), x.statistics.clone()))
+            .collect()
+    }
+
+    /// Stop the channel with the given name and stop managing it
+    ///
+    /// Returns `false` if there is no channel with this name.
+    pub async fn remove(&mut self, name: &str) -> bool {
+        match self.channels.remove_entry(name) {
+            Some((name, channel)) => {
+                self.stop(name, channel).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop every channel and wait for their tasks to complete
+    pub async fn shutdown(mut self) {
+        for (name, channel) in std::mem::take(&mut self.channels) {
+            self.stop(name, channel).await;
+        }
+    }
+
+    async fn stop(&self, name: String, channel: ManagedChannel) {
+        channel.task.abort();
+        // the task doesn't report its shutdown when aborted
+        let _ = channel.task.await;
+        let _ = self
+            .events
+            .send(ChannelEvent {
+                channel: name,
+                state: channel.shutdown,
+            })
+            .await;
+    }
+}
+
+impl Drop for ChannelManager {
+    fn drop(&mut self) {
+        for channel in self.channels.values() {
+            channel.task.abort();
+        }
+    }
+}
+
+struct ForwardingListener<T> {
+    channel: String,
+    inner: Option<Box<dyn Listener<T>>>,
+    events: Sender<ChannelEvent>,
+    wrap: fn(T) -> ManagedState,
+}
+
+impl<T> Listener<T> for ForwardingListener<T>
+where
+    T: Copy + Send + 'static,
+{
+    fn update(&mut self, value: T) -> MaybeAsync<()> {
+        let inner = self.inner.as_mut().map(|inner| inner.update(value));
+        let events = self.events.clone();
+        let event = ChannelEvent {
+            channel: self.channel.clone(),
+            state: (self.wrap)(value),
+        };
+        MaybeAsync::asynchronous(async move {
+            if let Some(inner) = inner {
+                inner.get().await;
+            }
+            let _ = events.send(event).await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Endpoint, HostAddr};
+    use crate::types::{AddressRange, UnitId};
+    use crate::RequestError;
+
+    fn tcp(port: u16) -> ChannelConfig {
+        ChannelConfig::new(Endpoint::Tcp(HostAddr::ip(
+            std::net::Ipv4Addr::LOCALHOST.into(),
+            port,
+        )))
+    }
+
+    async fn read_error(session: &CallbackSession) -> RequestError {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let range = AddressRange::try_from(0, 1).unwrap();
+        session
+            .read_coils(range, move |_, result| {
+                let _ = tx.send(result.map(|_| ()));
+            })
+            .await;
+        rx.await.unwrap().unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn reports_the_state_of_every_channel_and_shuts_them_down_together() {
+        let (mut manager, mut events) = ChannelManager::new(16);
+        manager.add("first", tcp(40006)).await.unwrap();
+        manager.add("second", tcp(40007)).await.unwrap();
+        assert_eq!(
+            manager.add("first", tcp(40008)).await.unwrap_err(),
+            DuplicateChannel("first".to_string())
+        );
+        assert_eq!(manager.names().collect::<Vec<_>>(), ["first", "second"]);
+        assert_eq!(manager.all_statistics().len(), 2);
+
+        let session = manager
+            .session("second", SessionConfig::new(UnitId::new(1)))
+            .unwrap();
+        assert!(manager
+            .session("third", SessionConfig::new(UnitId::new(1)))
+            .is_none());
+        // the channels are disabled until enabled
+        assert_eq!(read_error(&session).await, RequestError::NoConnection);
+        assert!(manager.statistics("second").is_some());
+
+        let mut disabled = BTreeMap::new();
+        for _ in 0..2 {
+            let event = events.recv().await.unwrap();
+            assert_eq!(event.state, ManagedState::Client(ClientState::Disabled));
+            disabled.insert(event.channel, ());
+        }
+        assert_eq!(disabled.len(), 2);
+
+        manager.shutdown().await;
+        let mut shutdown = BTreeMap::new();
+        while let Some(event) = events.recv().await {
+            if event.state == ManagedState::Client(ClientState::Shutdown) {
+                shutdown.insert(event.channel, ());
+            }
+        }
+        assert_eq!(shutdown.len(), 2);
+        assert_eq!(read_error(&session).await, RequestError::Shutdown);
+    }
+}
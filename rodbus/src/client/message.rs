@@ -1,3 +1,4 @@
+use crate::audit::{AuditSink, WrittenValue, WrittenValues};
 use crate::common::function::FunctionCode;
 use crate::common::traits::Loggable;
 use crate::decode::AppDecodeLevel;
@@ -6,18 +7,44 @@ use crate::error::*;
 use crate::exception::ExceptionCode;
 use crate::DecodeLevel;
 
+use crate::client::capture::PcapWriter;
+use crate::client::channel::{RequestId, RequestScheduling, ResponseParsing, UnitIdPolicy};
+use crate::client::completion::{Completed, CompletionSlot};
+use crate::client::fairness::RequestFairness;
+use crate::client::interceptor::Interceptor;
+use crate::client::metrics::MetricsListener;
 use crate::client::requests::read_bits::ReadBits;
 use crate::client::requests::read_registers::ReadRegisters;
 use crate::client::requests::write_multiple::MultipleWriteRequest;
 use crate::client::requests::write_single::SingleWrite;
 use crate::common::traits::Serialize;
-use crate::types::{Indexed, UnitId};
+use crate::types::{AddressRange, Indexed, UnitId};
 
 use scursor::{ReadCursor, WriteCursor};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub(crate) enum Setting {
     DecodeLevel(DecodeLevel),
+    Metrics(Box<dyn MetricsListener>),
+    Interceptor(Box<dyn Interceptor>),
+    Audit(Option<Arc<dyn AuditSink>>),
+    Capture(Option<PcapWriter>),
+    UnexpectedFrameLogging(Option<tracing::Level>),
+    ReadBufferCapacity(usize),
+    MaxPduLength(usize),
+    ResponseParsing(ResponseParsing),
+    UnitIdPolicy(UnitIdPolicy),
+    Scheduling(RequestScheduling),
+    Fairness(RequestFairness),
+    UnitWeight(UnitId, u32),
+    DeduplicateReads(bool),
+    WritePriority(bool),
+    #[cfg(feature = "tls")]
+    TlsConfig(crate::tcp::tls::client::TlsClientConfig),
+    #[cfg(feature = "serial")]
+    SerialSettings(crate::serial::SerialSettings),
+    Endpoint(crate::client::HostAddr),
     Enable,
     Disable,
 }
@@ -31,8 +58,14 @@ pub(crate) enum Command {
 
 pub(crate) struct Request {
     pub(crate) id: UnitId,
-    pub(crate) timeout: Duration,
+    pub(crate) request_id: RequestId,
+    /// The response timeout elapsed from the time the request was made, including its time
+    /// in the queue
+    pub(crate) deadline: tokio::time::Instant,
     pub(crate) details: RequestDetails,
+    /// OpenTelemetry context that was current when the request was issued
+    #[cfg(feature = "otel")]
+    pub(crate) context: opentelemetry::Context,
 }
 
 // possible requests that can be sent through the channel
@@ -48,11 +81,19 @@ pub(crate) enum RequestDetails {
 }
 
 impl Request {
-    pub(crate) fn new(id: UnitId, timeout: Duration, details: RequestDetails) -> Self {
+    pub(crate) fn new(
+        id: UnitId,
+        request_id: RequestId,
+        timeout: Duration,
+        details: RequestDetails,
+    ) -> Self {
         Self {
             id,
-            timeout,
+            request_id,
+            deadline: tokio::time::Instant::now() + timeout,
             details,
+            #[cfg(feature = "otel")]
+            context: current_context(),
         }
     }
 
@@ -60,7 +101,52 @@ impl Request {
         &mut self,
         payload: &[u8],
         decode: AppDecodeLevel,
+        parsing: ResponseParsing,
     ) -> Result<(), RequestError> {
+        let result = match self.handle_pdu(payload, decode) {
+            Err(RequestError::BadResponse(err)) if parsing == ResponseParsing::Tolerant => {
+                match Self::repair(payload, err.error()) {
+                    Some(repaired) => self.handle_pdu(&repaired, decode),
+                    None => Err(RequestError::BadResponse(err)),
+                }
+            }
+            result => result,
+        };
+        // keep the response as received, before any repair
+        result.map_err(|err| match err {
+            RequestError::BadResponse(err) => RequestError::BadResponse(err.with_response(payload)),
+            err => err,
+        })
+    }
+
+    /// Strip the harmless deviations tolerated by [`ResponseParsing::Tolerant`] from a PDU
+    /// rejected with the error
+    fn repair(payload: &[u8], err: AduParseError) -> Option<Vec<u8>> {
+        match err {
+            AduParseError::TrailingBytes(count) if count < payload.len() => {
+                tracing::warn!("ignoring {} trailing bytes after the response", count);
+                Some(payload[..payload.len() - count].to_vec())
+            }
+            // only the byte count of bits can be odd
+            AduParseError::ByteCountMismatch(expected, actual)
+                if expected % 2 == 1
+                    && actual == expected + 1
+                    && payload.len() >= 2 + actual as usize =>
+            {
+                tracing::warn!(
+                    "byte count of {} rounded up from the expected {}",
+                    actual,
+                    expected
+                );
+                let mut repaired = vec![payload[0], expected];
+                repaired.extend_from_slice(&payload[2..2 + expected as usize]);
+                Some(repaired)
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_pdu(&mut self, payload: &[u8], decode: AppDecodeLevel) -> Result<(), RequestError> {
         let expected_function = self.details.function();
         let mut cursor = ReadCursor::new(payload);
         let function = match cursor.read_u8() {
@@ -80,7 +166,15 @@ impl Request {
         self.details.handle_response(cursor, decode)
     }
 
-    fn get_error_for(
+    /// True if both requests read the same values from the same unit
+    pub(crate) fn is_same_read(&self, other: &Request) -> bool {
+        !self.details.is_write()
+            && self.id == other.id
+            && self.details.function() == other.details.function()
+            && self.details.range() == other.details.range()
+    }
+
+    pub(crate) fn get_error_for(
         function: u8,
         expected_function: FunctionCode,
         mut cursor: ReadCursor,
@@ -98,7 +192,7 @@ impl Request {
                         RequestError::Exception(exception)
                     } else {
                         tracing::warn!("invalid modbus exception");
-                        RequestError::BadResponse(AduParseError::TrailingBytes(cursor.remaining()))
+                        AduParseError::TrailingBytes(cursor.remaining()).into()
                     }
                 }
                 Err(err) => err.into(),
@@ -109,15 +203,31 @@ impl Request {
                 function,
                 expected_function.get_value()
             );
-            RequestError::BadResponse(AduParseError::UnknownResponseFunction(
+            AduParseError::UnknownResponseFunction(
                 function,
                 expected_function.get_value(),
                 expected_function.as_error(),
-            ))
+            )
+            .into()
         }
     }
 }
 
+/// The context of the current tracing span if it belongs to an OpenTelemetry trace,
+/// otherwise the context that was attached to the current thread
+#[cfg(feature = "otel")]
+fn current_context() -> opentelemetry::Context {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    if context.has_active_span() {
+        context
+    } else {
+        opentelemetry::Context::current()
+    }
+}
+
 impl RequestDetails {
     pub(crate) fn function(&self) -> FunctionCode {
         match self {
@@ -132,6 +242,76 @@ impl RequestDetails {
         }
     }
 
+    /// True if the request writes values, false if it reads them
+    pub(crate) fn is_write(&self) -> bool {
+        matches!(
+            self,
+            RequestDetails::WriteSingleCoil(_)
+                | RequestDetails::WriteSingleRegister(_)
+                | RequestDetails::WriteMultipleCoils(_)
+                | RequestDetails::WriteMultipleRegisters(_)
+        )
+    }
+
+    /// Range of addresses targeted by the request
+    pub(crate) fn range(&self) -> AddressRange {
+        match self {
+            RequestDetails::ReadCoils(x) => x.request.get(),
+            RequestDetails::ReadDiscreteInputs(x) => x.request.get(),
+            RequestDetails::ReadHoldingRegisters(x) => x.request.get(),
+            RequestDetails::ReadInputRegisters(x) => x.request.get(),
+            RequestDetails::WriteSingleCoil(x) => AddressRange {
+                start: x.request.index,
+                count: 1,
+            },
+            RequestDetails::WriteSingleRegister(x) => AddressRange {
+                start: x.request.index,
+                count: 1,
+            },
+            RequestDetails::WriteMultipleCoils(x) => x.request.range,
+            RequestDetails::WriteMultipleRegisters(x) => x.request.range,
+        }
+    }
+
+    /// Values of a write request, for which the previous values are unknown
+    pub(crate) fn written_values(&self) -> Option<WrittenValues> {
+        fn multiple<T: Copy>(range: AddressRange, values: &[T]) -> Vec<WrittenValue<T>> {
+            (range.start..)
+                .zip(values)
+                .map(|(index, value)| WrittenValue::new(index, None, *value))
+                .collect()
+        }
+
+        match self {
+            RequestDetails::ReadCoils(_)
+            | RequestDetails::ReadDiscreteInputs(_)
+            | RequestDetails::ReadHoldingRegisters(_)
+            | RequestDetails::ReadInputRegisters(_) => None,
+            RequestDetails::WriteSingleCoil(x) => {
+                Some(WrittenValues::Coils(vec![WrittenValue::new(
+                    x.request.index,
+                    None,
+                    x.request.value,
+                )]))
+            }
+            RequestDetails::WriteSingleRegister(x) => {
+                Some(WrittenValues::Registers(vec![WrittenValue::new(
+                    x.request.index,
+                    None,
+                    x.request.value,
+                )]))
+            }
+            RequestDetails::WriteMultipleCoils(x) => Some(WrittenValues::Coils(multiple(
+                x.request.range,
+                &x.request.values,
+            ))),
+            RequestDetails::WriteMultipleRegisters(x) => Some(WrittenValues::Registers(multiple(
+                x.request.range,
+                &x.request.values,
+            ))),
+        }
+    }
+
     pub(crate) fn fail(&mut self, err: RequestError) {
         match self {
             RequestDetails::ReadCoils(x) => x.failure(err),
@@ -248,58 +428,84 @@ impl std::fmt::Display for RequestDetailsDisplay<'_> {
     }
 }
 
-pub(crate) trait Callback<T>:
-    FnOnce(Result<T, RequestError>) + Send + Sync + 'static
-{
+/// Values with which a request completes, which may borrow the response they are parsed from
+pub(crate) trait Response {
+    type Value<'a>;
 }
 
-impl<F, T> Callback<T> for F where F: FnOnce(Result<T, RequestError>) + Send + Sync + 'static {}
+/// Values that don't borrow the response, e.g. the echo of a write
+pub(crate) struct Owned<T>(std::marker::PhantomData<T>);
+
+impl<T> Response for Owned<T> {
+    type Value<'a> = T;
+}
 
-pub(crate) struct Promise<T>
-where
-    T: Send + 'static,
+pub(crate) trait Callback<R: Response>:
+    for<'a> FnOnce(Result<R::Value<'a>, RequestError>) + Send + Sync + 'static
 {
-    callback: Option<Box<dyn Callback<T>>>,
 }
 
-impl<T> Promise<T>
-where
-    T: Send + 'static,
+impl<F, R: Response> Callback<R> for F where
+    F: for<'a> FnOnce(Result<R::Value<'a>, RequestError>) + Send + Sync + 'static
 {
+}
+
+enum Completion<R: Response> {
+    Callback(Box<dyn Callback<R>>),
+    Slot(
+        CompletionSlot,
+        RequestId,
+        for<'a> fn(R::Value<'a>) -> Completed,
+    ),
+}
+
+/// Completes a request exactly once, failing it with [`RequestError::Shutdown`] if it is dropped
+/// without being completed
+pub(crate) struct ResponsePromise<R: Response> {
+    completion: Option<Completion<R>>,
+}
+
+/// Promise of a request whose values don't borrow the response
+pub(crate) type Promise<T> = ResponsePromise<Owned<T>>;
+
+impl<R: Response> ResponsePromise<R> {
     pub(crate) fn new<F>(callback: F) -> Self
     where
-        F: Callback<T>,
+        F: Callback<R>,
     {
         Self {
-            callback: Some(Box::new(callback)),
+            completion: Some(Completion::Callback(Box::new(callback))),
         }
     }
 
-    pub(crate) fn channel(tx: tokio::sync::oneshot::Sender<Result<T, RequestError>>) -> Self {
-        Self::new(|x: Result<T, RequestError>| {
-            let _ = tx.send(x);
-        })
+    pub(crate) fn slot(
+        slot: CompletionSlot,
+        id: RequestId,
+        convert: for<'a> fn(R::Value<'a>) -> Completed,
+    ) -> Self {
+        Self {
+            completion: Some(Completion::Slot(slot, id, convert)),
+        }
     }
 
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.complete(Err(err))
     }
 
-    pub(crate) fn success(&mut self, value: T) {
+    pub(crate) fn success(&mut self, value: R::Value<'_>) {
         self.complete(Ok(value))
     }
 
-    fn complete(&mut self, result: Result<T, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(result)
+    fn complete(&mut self, result: Result<R::Value<'_>, RequestError>) {
+        match self.completion.take() {
+            Some(Completion::Callback(callback)) => callback(result),
+            Some(Completion::Slot(slot, id, convert)) => slot.complete(id, result.map(convert)),
+            None => {}
         }
     }
 }
 
-impl<T> Drop for Promise<T>
-where
-    T: Send + 'static,
-{
+impl<R: Response> Drop for ResponsePromise<R> {
     fn drop(&mut self) {
         self.failure(RequestError::Shutdown);
     }
@@ -307,13 +513,18 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::client::message::{Promise, RequestDetails};
+    use crate::client::channel::{RequestId, ResponseParsing};
+    use crate::client::message::{Promise, Request, RequestDetails};
     use crate::client::requests::read_bits::ReadBits;
     use crate::client::requests::read_registers::ReadRegisters;
     use crate::client::requests::write_single::SingleWrite;
-    use crate::{AddressRange, BitIterator, Indexed, RegisterIterator, RequestError};
+    use crate::decode::AppDecodeLevel;
+    use crate::error::AduParseError;
+    use crate::{AddressRange, BitIterator, Indexed, RegisterIterator, RequestError, UnitId};
+    use scursor::ReadCursor;
     use std::collections::VecDeque;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     #[derive(Clone)]
     struct Errors {
@@ -392,4 +603,132 @@ mod test {
             assert_eq!(remaining, 0);
         }
     }
+
+    fn parse_error(details: &mut RequestDetails, pdu: &[u8]) -> RequestError {
+        details
+            .handle_response(ReadCursor::new(pdu), AppDecodeLevel::Nothing)
+            .unwrap_err()
+    }
+
+    #[test]
+    fn read_response_with_wrong_byte_count_is_rejected() {
+        let mut details = create_read_registers(Errors::new());
+        let mut pdu = vec![0x04];
+        pdu.extend_from_slice(&[0; 10]);
+        assert_eq!(
+            parse_error(&mut details, &pdu),
+            RequestError::from(AduParseError::ByteCountMismatch(10, 4))
+        );
+    }
+
+    #[test]
+    fn tolerant_parsing_accepts_padded_responses() {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let create = |values: Arc<Mutex<Vec<bool>>>| {
+            let range = AddressRange::try_from(0, 5)
+                .unwrap()
+                .of_read_bits()
+                .unwrap();
+            let callback = move |result: Result<BitIterator, RequestError>| {
+                values
+                    .lock()
+                    .unwrap()
+                    .extend(result.unwrap().map(|x| x.value));
+            };
+            Request::new(
+                UnitId::new(1),
+                RequestId::next(),
+                Duration::from_secs(1),
+                RequestDetails::ReadCoils(ReadBits::new(
+                    range,
+                    crate::client::requests::read_bits::Promise::new(callback),
+                )),
+            )
+        };
+
+        // the byte count of 5 coils rounded up to 2
+        let padded = [0x01, 0x02, 0x1F, 0x00];
+        let mut request = create(values.clone());
+        assert_eq!(
+            request.handle_response(&padded, AppDecodeLevel::Nothing, ResponseParsing::Strict),
+            Err(RequestError::from(AduParseError::ByteCountMismatch(1, 2)))
+        );
+        request
+            .handle_response(&padded, AppDecodeLevel::Nothing, ResponseParsing::Tolerant)
+            .unwrap();
+        assert_eq!(*values.lock().unwrap(), [true; 5]);
+
+        values.lock().unwrap().clear();
+        let trailing = [0x01, 0x01, 0x1F, 0xAA, 0xAA];
+        create(values.clone())
+            .handle_response(
+                &trailing,
+                AppDecodeLevel::Nothing,
+                ResponseParsing::Tolerant,
+            )
+            .unwrap();
+        assert_eq!(*values.lock().unwrap(), [true; 5]);
+    }
+
+    #[test]
+    fn parse_errors_carry_the_raw_response() {
+        let mut request = Request::new(
+            UnitId::new(1),
+            RequestId::next(),
+            Duration::from_secs(1),
+            create_write_coil(Errors::new()),
+        );
+        let pdu = [0x05, 0x00, 0x01, 0xFF, 0x00];
+        let err =
+            match request.handle_response(&pdu, AppDecodeLevel::Nothing, ResponseParsing::Tolerant)
+            {
+                Err(RequestError::BadResponse(err)) => err,
+                result => panic!("unexpected result: {:?}", result),
+            };
+        assert_eq!(err.error(), AduParseError::ReplyAddressMismatch(0, 1));
+        assert_eq!(err.response(), Some(pdu.as_slice()));
+        assert!(err.to_string().ends_with(" - response: 05 00 01 FF 00"));
+    }
+
+    #[test]
+    fn write_response_with_wrong_echo_is_rejected() {
+        let mut details = create_write_coil(Errors::new());
+        assert_eq!(
+            parse_error(&mut details, &[0x00, 0x01, 0xFF, 0x00]),
+            RequestError::from(AduParseError::ReplyAddressMismatch(0, 1))
+        );
+
+        let mut details = create_write_coil(Errors::new());
+        assert_eq!(
+            parse_error(&mut details, &[0x00, 0x00, 0x00, 0x00]),
+            RequestError::from(AduParseError::ReplyValueMismatch(0xFF00, 0x0000))
+        );
+    }
+
+    #[test]
+    fn request_details_report_the_targeted_range() {
+        let errors = Errors::new();
+        assert_eq!(
+            create_read_bits(errors.clone()).range(),
+            AddressRange::try_from(0, 5).unwrap()
+        );
+        assert_eq!(
+            create_write_coil(errors).range(),
+            AddressRange::try_from(0, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_write_requests_have_written_values() {
+        let errors = Errors::new();
+        assert_eq!(create_read_bits(errors.clone()).written_values(), None);
+        assert_eq!(
+            create_write_coil(errors).written_values(),
+            Some(crate::WrittenValues::Coils(vec![crate::WrittenValue {
+                index: 0,
+                old: None,
+                new: true,
+            }]))
+        );
+    }
 }
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use crate::error::{ConnectError, RequestError};
+use crate::types::UnitId;
+
+/// Reason why a received frame was not matched with the outstanding request
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnexpectedFrame {
+    /// A frame was received while no request was outstanding, e.g. a response that arrived
+    /// after its request timed out or a reply from a second device using the same unit id
+    Unsolicited,
+    /// A TCP frame was received with a transaction id that does not match the outstanding request.
+    /// The frame is discarded.
+    TxIdMismatch,
+    /// A response was received from a unit id other than the one the request was sent to.
+    /// Whether the response is discarded depends on the [`crate::client::UnitIdPolicy`].
+    UnitIdMismatch,
+    /// The response to a request that timed out was received afterwards. The frame is discarded.
+    ///
+    /// TCP responses are recognized by their transaction id. RTU responses are recognized by the
+    /// unit id and function code of the request, so a late response can't be told apart from the
+    /// response to an identical request sent next to the same unit.
+    LateResponse,
+}
+
+impl std::fmt::Display for UnexpectedFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnexpectedFrame::Unsolicited => f.write_str("unsolicited frame"),
+            UnexpectedFrame::TxIdMismatch => f.write_str("transaction id mismatch"),
+            UnexpectedFrame::UnitIdMismatch => f.write_str("unit id mismatch"),
+            UnexpectedFrame::LateResponse => f.write_str("late response"),
+        }
+    }
+}
+
+/// Callbacks used to collect metrics about the traffic on a channel
+///
+/// Implementations can feed these values to a metrics system such as Prometheus or statsd.
+/// The methods are invoked synchronously from the channel task and must not block. The
+/// arguments are passed by value and no allocation is performed to invoke them.
+///
+/// Every method has a default implementation that does nothing.
+pub trait MetricsListener: Send {
+    /// A request was written to the physical layer
+    ///
+    /// * `id` - unit id of the request
+    /// * `function` - raw function code of the request
+    fn request_started(&mut self, _id: UnitId, _function: u8) {}
+
+    /// A request that was previously started has completed
+    ///
+    /// * `id` - unit id of the request
+    /// * `function` - raw function code of the request
+    /// * `latency` - time elapsed between the start of the request and its completion
+    /// * `result` - outcome of the request. Timeouts are reported as [`RequestError::ResponseTimeout`]
+    ///   and Modbus exceptions as [`RequestError::Exception`]
+    fn request_completed(
+        &mut self,
+        _id: UnitId,
+        _function: u8,
+        _latency: Duration,
+        _result: Result<(), RequestError>,
+    ) {
+    }
+
+    /// A frame was received that does not match the outstanding request
+    ///
+    /// * `id` - unit id contained in the received frame
+    /// * `reason` - why the frame did not match
+    fn unexpected_frame(&mut self, _id: UnitId, _reason: UnexpectedFrame) {}
+
+    /// The channel established a connection (or opened the serial port) and
+    /// started processing requests
+    fn connected(&mut self) {}
+
+    /// The channel lost its connection (or closed the serial port)
+    fn disconnected(&mut self) {}
+
+    /// The channel failed to establish a connection (or to open the serial port)
+    ///
+    /// * `err` - reason why the attempt failed
+    fn connect_failed(&mut self, _err: ConnectError) {}
+}
@@ -1,21 +1,52 @@
 use std::net::{IpAddr, SocketAddr};
 
 use crate::decode::DecodeLevel;
+use crate::error::ConnectError;
 
 /// persistent communication channel such as a TCP connection
+pub(crate) mod cache;
+pub(crate) mod capture;
 pub(crate) mod channel;
+pub(crate) mod completion;
+pub(crate) mod config;
+pub(crate) mod fairness;
+pub(crate) mod interceptor;
 pub(crate) mod listener;
+pub(crate) mod manager;
 pub(crate) mod message;
+pub(crate) mod metrics;
+pub(crate) mod outbox;
+pub(crate) mod pool;
 pub(crate) mod requests;
+pub(crate) mod scan;
+pub(crate) mod statistics;
+pub(crate) mod stream;
 pub(crate) mod task;
+pub(crate) mod typed;
 
+pub use crate::client::cache::{CachedResponse, ReadCache};
+pub use crate::client::capture::{CaptureDirection, PcapWriter, RecordedFrame, Recording};
 pub use crate::client::channel::*;
+pub use crate::client::config::*;
+pub use crate::client::fairness::RequestFairness;
+pub use crate::client::interceptor::*;
 pub use crate::client::listener::*;
+pub use crate::client::manager::*;
+pub use crate::client::metrics::*;
+pub use crate::client::outbox::*;
 pub use crate::client::requests::write_multiple::WriteMultiple;
+pub use crate::client::scan::*;
+pub use crate::client::statistics::*;
+pub use crate::client::stream::*;
+pub use crate::client::typed::*;
 pub use crate::retry::*;
 
 #[cfg(feature = "tls")]
 pub use crate::tcp::tls::client::TlsClientConfig;
+#[cfg(feature = "dangerous-tls")]
+pub use crate::tcp::tls::verify::{
+    BadFingerprint, CertificateFingerprint, CertificateVerifier, FingerprintVerifier,
+};
 #[cfg(feature = "tls")]
 pub use crate::tcp::tls::*;
 
@@ -57,6 +88,9 @@ impl HostAddr {
     }
 
     /// Construct a `HostAddr` from a DNS name and port
+    ///
+    /// The name is resolved again on every connection attempt, so a server whose address changes,
+    /// e.g. when it is assigned by DHCP, is reached once the channel reconnects.
     pub fn dns(name: String, port: u16) -> Self {
         Self {
             addr: HostType::Dns(name),
@@ -64,12 +98,105 @@ impl HostAddr {
         }
     }
 
-    pub(crate) async fn connect(&self) -> std::io::Result<tokio::net::TcpStream> {
-        match &self.addr {
-            HostType::Dns(x) => tokio::net::TcpStream::connect((x.as_str(), self.port)).await,
-            HostType::IpAddr(x) => tokio::net::TcpStream::connect((*x, self.port)).await,
+    pub(crate) async fn connect(&self) -> Result<tokio::net::TcpStream, ConnectError> {
+        let addrs: Vec<SocketAddr> = match &self.addr {
+            HostType::Dns(x) => tokio::net::lookup_host((x.as_str(), self.port))
+                .await
+                .map_err(|err| ConnectError::Dns(err.into()))?
+                .collect(),
+            HostType::IpAddr(x) => vec![SocketAddr::new(*x, self.port)],
+        };
+
+        connect_any(interleave(addrs), CONNECTION_ATTEMPT_DELAY).await
+    }
+}
+
+/// Delay after which the next address is tried while the previous attempt is still pending, as
+/// recommended by RFC 8305
+const CONNECTION_ATTEMPT_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Alternate the address families, starting with the family of the first address
+///
+/// A name that resolves to both IPv6 and IPv4 addresses is then reachable even if one of the
+/// families is unreachable, without waiting for each of its addresses to time out first.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_v6 = match addrs.first() {
+        Some(addr) => addr.is_ipv6(),
+        None => return addrs,
+    };
+    let (mut first, mut second): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_v6);
+
+    let mut result = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.drain(..);
+    let mut second = second.drain(..);
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return result,
+            (x, y) => result.extend(x.into_iter().chain(y)),
+        }
+    }
+}
+
+/// Connect to the first address that accepts the connection, per the "Happy Eyeballs" algorithm
+///
+/// The attempts are started in order, the next one starting when the previous one fails or once
+/// `delay` has elapsed. The first successful attempt wins and the others are abandoned. If every
+/// attempt fails, the error of the last one to fail is reported.
+async fn connect_any(
+    addrs: Vec<SocketAddr>,
+    delay: std::time::Duration,
+) -> Result<tokio::net::TcpStream, ConnectError> {
+    let mut pending = addrs.into_iter();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut attempts = Vec::new();
+    let mut in_flight = 0;
+    let mut result = Err(ConnectError::Dns(std::io::ErrorKind::NotFound.into()));
+
+    let mut start_next = |attempts: &mut Vec<tokio::task::JoinHandle<()>>| match pending.next() {
+        Some(addr) => {
+            let tx = tx.clone();
+            attempts.push(tokio::spawn(async move {
+                let _ = tx.send(tokio::net::TcpStream::connect(addr).await);
+            }));
+            true
+        }
+        None => false,
+    };
+
+    if start_next(&mut attempts) {
+        in_flight += 1;
+    }
+    while in_flight > 0 {
+        tokio::select! {
+            attempt = rx.recv() => {
+                in_flight -= 1;
+                match attempt {
+                    Some(Ok(socket)) => {
+                        result = Ok(socket);
+                        break;
+                    }
+                    Some(Err(err)) => result = Err(ConnectError::Tcp(err.into())),
+                    None => break,
+                }
+                // don't wait for the delay after a failure
+                if start_next(&mut attempts) {
+                    in_flight += 1;
+                }
+            }
+            _ = tokio::time::sleep(delay) => {
+                if start_next(&mut attempts) {
+                    in_flight += 1;
+                }
+            }
         }
     }
+
+    for attempt in attempts {
+        attempt.abort();
+    }
+    result
 }
 
 /// Spawns a channel task onto the runtime that maintains a TCP connection and processes
@@ -100,6 +227,33 @@ pub fn spawn_tcp_client_task(
     )
 }
 
+/// Spawns several channel tasks onto the runtime that each maintain a TCP connection to the same
+/// server, and a task that distributes the requests of the returned channel across them.
+/// The tasks complete when the returned channel handle is dropped.
+///
+/// Many gateways limit the throughput of each connection but accept several of them. Requests are
+/// assigned round-robin to the connections that are established, skipping those whose queue is
+/// full. Settings changed on the channel apply to every connection. A metrics listener,
+/// interceptor or capture installed on the channel is shared by the connections, and is notified
+/// of the connection events of each of them.
+///
+/// * `host` - Address/port of the remote server. Can be a IP address or name on which to perform DNS resolution.
+/// * `connections` - Number of connections to open, at least one
+/// * `max_queued_requests` - The maximum size of the request queue of the channel and of each connection
+/// * `retry` - Creates the boxed trait object that controls when each connection is retried on failure
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn spawn_tcp_client_pool(
+    host: HostAddr,
+    connections: usize,
+    max_queued_requests: usize,
+    retry: impl Fn() -> Box<dyn RetryStrategy>,
+    decode: DecodeLevel,
+) -> Channel {
+    pool::spawn_tcp_pool(host, connections, max_queued_requests, retry, decode)
+}
+
 /// Spawns a channel task onto the runtime that opens a serial port and processes
 /// requests. The task completes when the returned channel handle
 /// is dropped.
@@ -166,3 +320,72 @@ pub fn spawn_tls_client_task(
         listener.unwrap_or_else(|| NullListener::create()),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refused_connection_is_reported_as_tcp_error() {
+        // bind a port and immediately release it so that nothing is listening on it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let err = HostAddr::from(addr).connect().await.unwrap_err();
+        assert_eq!(
+            err,
+            ConnectError::Tcp(std::io::ErrorKind::ConnectionRefused.into())
+        );
+    }
+
+    #[test]
+    fn alternates_the_address_families() {
+        let v4 = |x| SocketAddr::new(std::net::Ipv4Addr::new(10, 0, 0, x).into(), 502);
+        let v6 = |x| {
+            SocketAddr::new(
+                std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, x).into(),
+                502,
+            )
+        };
+
+        assert_eq!(
+            interleave(vec![v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(
+            interleave(vec![v4(1), v4(2), v6(1)]),
+            vec![v4(1), v6(1), v4(2)]
+        );
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn connects_to_the_next_address_when_an_attempt_fails_or_stalls() {
+        let refused = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused_addr = refused.local_addr().unwrap();
+        drop(refused);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // the refused attempt fails immediately, the next one doesn't wait for the long delay
+        let socket = connect_any(vec![refused_addr, addr], std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(socket.peer_addr().unwrap(), addr);
+
+        // the attempt to the unroutable address is still pending when the next one starts
+        let unroutable = SocketAddr::new(std::net::Ipv4Addr::new(192, 0, 2, 1).into(), 502);
+        let socket = connect_any(vec![unroutable, addr], std::time::Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(socket.peer_addr().unwrap(), addr);
+
+        assert_eq!(
+            connect_any(vec![refused_addr], std::time::Duration::from_millis(10))
+                .await
+                .unwrap_err(),
+            ConnectError::Tcp(std::io::ErrorKind::ConnectionRefused.into())
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::client::{Channel, RequestParam, TypedRequest, TypedResponse};
+use crate::error::{IoError, RequestError, RequestFailure};
+use crate::types::UnitId;
+
+/// A write waiting in an [`Outbox`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEntry {
+    /// Position of the write in the outbox, assigned in increasing order
+    pub sequence: u64,
+    /// Unit the write is sent to
+    pub unit: UnitId,
+    /// Response timeout of each attempt
+    pub response_timeout: Duration,
+    /// One of the write requests
+    pub request: TypedRequest,
+}
+
+/// Durable storage of the writes waiting in an [`Outbox`]
+///
+/// Implementations persist the entries, e.g. to a file or a database, so that the writes made
+/// before the process stopped are replayed by the next one.
+///
+/// The methods may block. `load` is called by [`Outbox::spawn`] and `insert` by
+/// [`Outbox::write`], on the thread of their caller. `remove` is called on the blocking thread
+/// pool of Tokio. The outbox holds a lock during these calls, so a write waits for the store
+/// to remove the previous entry and vice versa.
+pub trait OutboxStore: Send {
+    /// Entries stored by a previous process, which are sent in the order of their sequence
+    fn load(&mut self) -> std::io::Result<Vec<OutboxEntry>>;
+
+    /// Store an entry added to the outbox
+    fn insert(&mut self, entry: &OutboxEntry) -> std::io::Result<()>;
+
+    /// Remove an entry that was acknowledged by the unit, or rejected by it
+    fn remove(&mut self, sequence: u64) -> std::io::Result<()>;
+}
+
+/// [`OutboxStore`] which keeps nothing, so the writes survive the loss of the connection but
+/// not a restart of the process
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemoryStore;
+
+impl OutboxStore for MemoryStore {
+    fn load(&mut self) -> std::io::Result<Vec<OutboxEntry>> {
+        Ok(Vec::new())
+    }
+
+    fn insert(&mut self, _entry: &OutboxEntry) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn remove(&mut self, _sequence: u64) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write that completed, reported by an [`Outbox`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboxEvent {
+    /// Sequence of the entry
+    pub sequence: u64,
+    /// Response of the unit, or the error that made the outbox give up on the write
+    pub result: Result<TypedResponse, RequestFailure>,
+}
+
+/// Error returned when adding a write to an [`Outbox`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutboxError {
+    /// Only write requests can be added
+    NotAWrite,
+    /// The store failed to persist the entry
+    Store(IoError),
+}
+
+impl std::error::Error for OutboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutboxError::NotAWrite => None,
+            OutboxError::Store(err) => Some(err.get_ref()),
+        }
+    }
+}
+
+impl std::fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OutboxError::NotAWrite => f.write_str("only write requests can be added to an outbox"),
+            OutboxError::Store(err) => write!(f, "unable to store the write: {}", err),
+        }
+    }
+}
+
+struct State {
+    entries: VecDeque<OutboxEntry>,
+    store: Box<dyn OutboxStore>,
+    next_sequence: u64,
+}
+
+/// Queue of writes that are sent in order through a channel until the unit acknowledges them
+///
+/// Writes that fail with a transient error (see [`RequestError::is_transient`]), e.g. because
+/// the connection was lost, are retried after a delay, and the following writes wait for them.
+/// The channel doesn't expose its connection state, so the write is retried on this fixed delay
+/// rather than as soon as the channel reconnects. While the channel is disconnected, each retry
+/// fails immediately with [`RequestError::NoConnection`], so a short delay replays the writes
+/// soon after the reconnection at the cost of more attempts in the meantime.
+/// A write that is rejected, e.g. with a Modbus exception, is given up on. Every completion is
+/// reported as an [`OutboxEvent`] on a queue that must be drained.
+///
+/// A write whose response timed out may have been performed by the unit, in which case it is
+/// performed twice. The sequence of writes should therefore be idempotent.
+///
+/// Dropping the outbox stops sending the writes. Those not completed remain in the store.
+pub struct Outbox {
+    state: Arc<Mutex<State>>,
+    notify: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl Outbox {
+    /// Spawn a task sending the writes through the channel, starting with those loaded from the
+    /// store
+    ///
+    /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+    pub fn spawn(
+        channel: Channel,
+        mut store: Box<dyn OutboxStore>,
+        retry_delay: Duration,
+        max_queued_events: usize,
+    ) -> std::io::Result<(Self, Receiver<OutboxEvent>)> {
+        let mut entries = store.load()?;
+        entries.sort_by_key(|x| x.sequence);
+        let next_sequence = entries.last().map(|x| x.sequence + 1).unwrap_or(0);
+        let state = Arc::new(Mutex::new(State {
+            entries: entries.into(),
+            store,
+            next_sequence,
+        }));
+        let notify = Arc::new(Notify::new());
+        let (events, rx) = tokio::sync::mpsc::channel(max_queued_events);
+        let task = tokio::spawn(run(
+            channel,
+            state.clone(),
+            notify.clone(),
+            retry_delay,
+            events,
+        ));
+        Ok((
+            Self {
+                state,
+                notify,
+                task,
+            },
+            rx,
+        ))
+    }
+
+    /// Add a write to the outbox, returning the sequence of its entry once it has been stored
+    pub fn write(&self, param: RequestParam, request: TypedRequest) -> Result<u64, OutboxError> {
+        if !is_write(&request) {
+            return Err(OutboxError::NotAWrite);
+        }
+        let sequence = {
+            let mut state = lock(&self.state);
+            let entry = OutboxEntry {
+                sequence: state.next_sequence,
+                unit: param.id,
+                response_timeout: param.response_timeout,
+                request,
+            };
+            state
+                .store
+                .insert(&entry)
+                .map_err(|err| OutboxError::Store(err.into()))?;
+            state.next_sequence += 1;
+            state.entries.push_back(entry);
+            state.next_sequence - 1
+        };
+        self.notify.notify_one();
+        Ok(sequence)
+    }
+
+    /// Number of writes that have not completed
+    pub fn pending(&self) -> usize {
+        lock(&self.state).entries.len()
+    }
+}
+
+impl Drop for Outbox {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    // every update leaves the state consistent, so a poisoned lock can be recovered
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn is_write(request: &TypedRequest) -> bool {
+    matches!(
+        request,
+        TypedRequest::WriteSingleCoil(_)
+            | TypedRequest::WriteSingleRegister(_)
+            | TypedRequest::WriteMultipleCoils(_)
+            | TypedRequest::WriteMultipleRegisters(_)
+    )
+}
+
+async fn run(
+    channel: Channel,
+    state: Arc<Mutex<State>>,
+    notify: Arc<Notify>,
+    retry_delay: Duration,
+    events: Sender<OutboxEvent>,
+) {
+    loop {
+        let next = lock(&state).entries.front().cloned();
+        let entry = match next {
+            Some(entry) => entry,
+            None => {
+                notify.notified().await;
+                continue;
+            }
+        };
+
+        let param = RequestParam::new(entry.unit, entry.response_timeout);
+        let result = match channel.call(param, entry.request.clone()).await {
+            // the writes remain in the store
+            Err(err) if *err.error() == RequestError::Shutdown => return,
+            Err(err) if err.error().is_transient() => {
+                tracing::warn!("write {} failed, retrying: {}", entry.sequence, err);
+                tokio::time::sleep(retry_delay).await;
+                continue;
+            }
+            result => result,
+        };
+
+        let removed = {
+            let state = state.clone();
+            let sequence = entry.sequence;
+            tokio::task::spawn_blocking(move || {
+                let mut state = lock(&state);
+                if let Err(err) = state.store.remove(sequence) {
+                    tracing::warn!(
+                        "unable to remove write {} from the store: {}",
+                        sequence,
+                        err
+                    );
+                }
+                state.entries.pop_front();
+            })
+            .await
+        };
+        if removed.is_err() {
+            // the runtime is shutting down
+            return;
+        }
+        let _ = events
+            .send(OutboxEvent {
+                sequence: entry.sequence,
+                result,
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::Command;
+    use crate::decode::AppDecodeLevel;
+    use crate::types::{AddressRange, Indexed};
+    use crate::ExceptionCode;
+
+    #[derive(Clone, Default)]
+    struct SharedStore {
+        entries: Arc<Mutex<Vec<OutboxEntry>>>,
+    }
+
+    impl OutboxStore for SharedStore {
+        fn load(&mut self) -> std::io::Result<Vec<OutboxEntry>> {
+            Ok(self.entries.lock().unwrap().clone())
+        }
+
+        fn insert(&mut self, entry: &OutboxEntry) -> std::io::Result<()> {
+            self.entries.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+
+        fn remove(&mut self, sequence: u64) -> std::io::Result<()> {
+            self.entries
+                .lock()
+                .unwrap()
+                .retain(|x| x.sequence != sequence);
+            Ok(())
+        }
+    }
+
+    struct FullStore;
+
+    impl OutboxStore for FullStore {
+        fn load(&mut self) -> std::io::Result<Vec<OutboxEntry>> {
+            Ok(Vec::new())
+        }
+
+        fn insert(&mut self, _entry: &OutboxEntry) -> std::io::Result<()> {
+            Err(std::io::Error::other("disk full"))
+        }
+
+        fn remove(&mut self, _sequence: u64) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn write_register(index: u16, value: u16) -> TypedRequest {
+        TypedRequest::WriteSingleRegister(Indexed::new(index, value))
+    }
+
+    #[tokio::test]
+    async fn replays_stored_writes_in_order_until_acknowledged() {
+        let store = SharedStore::default();
+        store.entries.lock().unwrap().push(OutboxEntry {
+            sequence: 5,
+            unit: UnitId::new(1),
+            response_timeout: Duration::from_secs(1),
+            request: write_register(1, 10),
+        });
+
+        // the first attempt finds no connection, then registers are acknowledged and coils
+        // rejected
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let device = tokio::spawn(async move {
+            let mut written = Vec::new();
+            let mut attempts = 0;
+            while let Some(cmd) = rx.recv().await {
+                let mut request = match cmd {
+                    Command::Request(request) => request,
+                    Command::Setting(_) => continue,
+                };
+                attempts += 1;
+                let range = request.details.range();
+                let result = match request.details.function().get_value() {
+                    _ if attempts == 1 => Err(RequestError::NoConnection),
+                    0x06 => {
+                        written.push(range.start);
+                        let [hi, lo] = range.start.to_be_bytes();
+                        let value = if range.start == 1 { 10 } else { 20 };
+                        let [vhi, vlo] = u16::to_be_bytes(value);
+                        request.handle_response(
+                            &[0x06, hi, lo, vhi, vlo],
+                            AppDecodeLevel::Nothing,
+                            crate::client::ResponseParsing::Strict,
+                        )
+                    }
+                    _ => Err(RequestError::Exception(ExceptionCode::IllegalDataAddress)),
+                };
+                if let Err(err) = result {
+                    request.details.fail(err);
+                }
+                if attempts == 4 {
+                    return written;
+                }
+            }
+            written
+        });
+
+        let (outbox, mut events) = Outbox::spawn(
+            Channel::new(tx),
+            Box::new(store.clone()),
+            Duration::from_millis(1),
+            16,
+        )
+        .unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        assert_eq!(
+            outbox.write(
+                param,
+                TypedRequest::ReadCoils(AddressRange::try_from(0, 1).unwrap())
+            ),
+            Err(OutboxError::NotAWrite)
+        );
+        assert_eq!(outbox.write(param, write_register(2, 20)), Ok(6));
+        assert_eq!(
+            outbox.write(param, TypedRequest::WriteSingleCoil(Indexed::new(3, true))),
+            Ok(7)
+        );
+
+        let mut completed = Vec::new();
+        for _ in 0..3 {
+            let event = events.recv().await.unwrap();
+            completed.push((event.sequence, event.result.is_ok()));
+        }
+        assert_eq!(completed, [(5, true), (6, true), (7, false)]);
+        assert_eq!(device.await.unwrap(), [1, 2]);
+        assert_eq!(outbox.pending(), 0);
+        assert!(store.entries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn store_errors_keep_the_io_error() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (outbox, _events) = Outbox::spawn(
+            Channel::new(tx),
+            Box::new(FullStore),
+            Duration::from_millis(1),
+            16,
+        )
+        .unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let err = outbox.write(param, write_register(1, 10)).unwrap_err();
+        assert_eq!(err.to_string(), "unable to store the write: disk full");
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "disk full");
+        assert_eq!(outbox.pending(), 0);
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::Instrument;
+
+use crate::client::interceptor::Interceptor;
+use crate::client::listener::{ClientState, Listener};
+use crate::client::message::{Command, Setting};
+use crate::client::metrics::{MetricsListener, UnexpectedFrame};
+use crate::client::{Channel, HostAddr, RetryStrategy};
+use crate::decode::DecodeLevel;
+use crate::error::{ConnectError, RequestError};
+use crate::types::UnitId;
+use crate::MaybeAsync;
+
+pub(crate) fn spawn_tcp_pool(
+    host: HostAddr,
+    connections: usize,
+    max_queued_requests: usize,
+    retry: impl Fn() -> Box<dyn RetryStrategy>,
+    decode: DecodeLevel,
+) -> Channel {
+    let connections = (0..connections.max(1))
+        .map(|index| {
+            let connected = Arc::new(AtomicBool::new(false));
+            let (channel, task) = crate::tcp::client::create_tcp_channel(
+                host.clone(),
+                max_queued_requests,
+                retry(),
+                decode,
+                Box::new(ConnectionListener {
+                    connected: connected.clone(),
+                }),
+            );
+            tokio::spawn(task.instrument(tracing::info_span!(
+                "Modbus-Client-Pool",
+                connection = index
+            )));
+            Connection {
+                tx: channel.tx,
+                connected,
+            }
+        })
+        .collect();
+
+    let (tx, rx) = tokio::sync::mpsc::channel(max_queued_requests);
+    tokio::spawn(Pool::new(connections).run(rx));
+    Channel::new(tx)
+}
+
+/// Tracks whether a connection of the pool can currently process requests
+struct ConnectionListener {
+    connected: Arc<AtomicBool>,
+}
+
+impl Listener<ClientState> for ConnectionListener {
+    fn update(&mut self, value: ClientState) -> MaybeAsync<()> {
+        self.connected
+            .store(value == ClientState::Connected, Ordering::Relaxed);
+        MaybeAsync::ready(())
+    }
+}
+
+struct Connection {
+    tx: Sender<Command>,
+    connected: Arc<AtomicBool>,
+}
+
+/// Distributes the requests of a channel across several connections to the same endpoint
+///
+/// Requests are assigned round-robin to the connections that are connected, skipping those whose
+/// queue is full. If every connection is busy, the dispatcher waits on the next one in turn. If
+/// none is connected, requests are assigned to all of them so that they fail the same way they
+/// would on a single connection.
+///
+/// Settings are applied to every connection. Metrics listeners, interceptors and captures are
+/// shared by the connections.
+struct Pool {
+    connections: Vec<Connection>,
+    next: usize,
+}
+
+impl Pool {
+    fn new(connections: Vec<Connection>) -> Self {
+        Self {
+            connections,
+            next: 0,
+        }
+    }
+
+    async fn run(mut self, mut rx: Receiver<Command>) {
+        while let Some(command) = rx.recv().await {
+            match command {
+                Command::Request(request) => self.dispatch(Command::Request(request)).await,
+                Command::Setting(setting) => self.apply(setting).await,
+            }
+        }
+        // dropping the connection senders shuts down their tasks
+    }
+
+    async fn dispatch(&mut self, mut command: Command) {
+        let start = self.next;
+        self.next = (self.next + 1) % self.connections.len();
+
+        let any_connected = self
+            .connections
+            .iter()
+            .any(|x| x.connected.load(Ordering::Relaxed));
+
+        let mut fallback = None;
+        for offset in 0..self.connections.len() {
+            let index = (start + offset) % self.connections.len();
+            let connection = &self.connections[index];
+            if any_connected && !connection.connected.load(Ordering::Relaxed) {
+                continue;
+            }
+            command = match connection.tx.try_send(command) {
+                Ok(()) => return,
+                Err(TrySendError::Full(x)) | Err(TrySendError::Closed(x)) => x,
+            };
+            fallback.get_or_insert(index);
+        }
+
+        // every eligible queue is full, so wait for the first one in turn
+        let index = fallback.unwrap_or(start);
+        // the request fails with RequestError::Shutdown if the connection task has ended
+        let _ = self.connections[index].tx.send(command).await;
+    }
+
+    async fn apply(&mut self, setting: Setting) {
+        let replicate: Box<dyn Fn() -> Setting + Send> = match setting {
+            Setting::DecodeLevel(x) => Box::new(move || Setting::DecodeLevel(x)),
+            Setting::Metrics(x) => {
+                let shared = Shared::new(x);
+                Box::new(move || Setting::Metrics(Box::new(shared.clone())))
+            }
+            Setting::Interceptor(x) => {
+                let shared = Shared::new(x);
+                Box::new(move || Setting::Interceptor(Box::new(shared.clone())))
+            }
+            Setting::Audit(x) => Box::new(move || Setting::Audit(x.clone())),
+            Setting::Capture(x) => {
+                Box::new(move || Setting::Capture(x.as_ref().map(|x| x.share())))
+            }
+            Setting::UnexpectedFrameLogging(x) => {
+                Box::new(move || Setting::UnexpectedFrameLogging(x))
+            }
+            Setting::ReadBufferCapacity(x) => Box::new(move || Setting::ReadBufferCapacity(x)),
+            Setting::MaxPduLength(x) => Box::new(move || Setting::MaxPduLength(x)),
+            Setting::ResponseParsing(x) => Box::new(move || Setting::ResponseParsing(x)),
+            Setting::UnitIdPolicy(x) => Box::new(move || Setting::UnitIdPolicy(x)),
+            Setting::Scheduling(x) => Box::new(move || Setting::Scheduling(x)),
+            Setting::Fairness(x) => Box::new(move || Setting::Fairness(x)),
+            Setting::UnitWeight(unit, x) => Box::new(move || Setting::UnitWeight(unit, x)),
+            Setting::DeduplicateReads(x) => Box::new(move || Setting::DeduplicateReads(x)),
+            Setting::WritePriority(x) => Box::new(move || Setting::WritePriority(x)),
+            #[cfg(feature = "tls")]
+            Setting::TlsConfig(x) => Box::new(move || Setting::TlsConfig(x.clone())),
+            #[cfg(feature = "serial")]
+            Setting::SerialSettings(x) => Box::new(move || Setting::SerialSettings(x)),
+            Setting::Endpoint(x) => Box::new(move || Setting::Endpoint(x.clone())),
+            Setting::Enable => Box::new(|| Setting::Enable),
+            Setting::Disable => Box::new(|| Setting::Disable),
+        };
+
+        for connection in self.connections.iter() {
+            let _ = connection.tx.send(Command::Setting(replicate())).await;
+        }
+    }
+}
+
+/// Trait object shared by the connections of a pool
+struct Shared<T: ?Sized> {
+    inner: Arc<Mutex<Box<T>>>,
+}
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> Shared<T> {
+    fn new(value: Box<T>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(value)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Box<T>> {
+        // the callbacks are invoked one at a time, so a poisoned lock can be recovered
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl MetricsListener for Shared<dyn MetricsListener> {
+    fn request_started(&mut self, id: UnitId, function: u8) {
+        self.lock().request_started(id, function)
+    }
+
+    fn request_completed(
+        &mut self,
+        id: UnitId,
+        function: u8,
+        latency: Duration,
+        result: Result<(), RequestError>,
+    ) {
+        self.lock().request_completed(id, function, latency, result)
+    }
+
+    fn unexpected_frame(&mut self, id: UnitId, reason: UnexpectedFrame) {
+        self.lock().unexpected_frame(id, reason)
+    }
+
+    fn connected(&mut self) {
+        self.lock().connected()
+    }
+
+    fn disconnected(&mut self) {
+        self.lock().disconnected()
+    }
+
+    fn connect_failed(&mut self, err: ConnectError) {
+        self.lock().connect_failed(err)
+    }
+}
+
+impl Interceptor for Shared<dyn Interceptor> {
+    fn on_request(&mut self, id: UnitId, function: u8, pdu: &[u8]) -> Result<(), RequestError> {
+        self.lock().on_request(id, function, pdu)
+    }
+
+    fn on_response(&mut self, id: UnitId, function: u8, pdu: &[u8]) -> Result<(), RequestError> {
+        self.lock().on_response(id, function, pdu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::message::{Promise, Request, RequestDetails};
+    use crate::client::requests::write_single::SingleWrite;
+    use crate::client::RequestId;
+    use crate::types::Indexed;
+
+    fn pool(count: usize, capacity: usize) -> (Pool, Vec<Receiver<Command>>) {
+        let (connections, receivers) = (0..count)
+            .map(|_| {
+                let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+                let connection = Connection {
+                    tx,
+                    connected: Arc::new(AtomicBool::new(false)),
+                };
+                (connection, rx)
+            })
+            .unzip();
+        (Pool::new(connections), receivers)
+    }
+
+    fn request() -> Command {
+        Command::Request(Request::new(
+            UnitId::new(1),
+            RequestId::next(),
+            Duration::from_secs(1),
+            RequestDetails::WriteSingleCoil(SingleWrite::new(
+                Indexed::new(0, true),
+                Promise::new(|_| {}),
+            )),
+        ))
+    }
+
+    fn queued(receivers: &mut [Receiver<Command>]) -> Vec<usize> {
+        receivers
+            .iter_mut()
+            .map(|rx| {
+                let mut count = 0;
+                while rx.try_recv().is_ok() {
+                    count += 1;
+                }
+                count
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn distributes_requests_round_robin() {
+        let (mut pool, mut receivers) = pool(3, 10);
+        for _ in 0..6 {
+            pool.dispatch(request()).await;
+        }
+        assert_eq!(queued(&mut receivers), [2, 2, 2]);
+    }
+
+    #[tokio::test]
+    async fn prefers_connected_connections_and_skips_full_queues() {
+        let (mut pool, mut receivers) = pool(3, 1);
+        pool.connections[0].connected.store(true, Ordering::Relaxed);
+        pool.connections[2].connected.store(true, Ordering::Relaxed);
+
+        // the queue of the first connection is now full
+        pool.dispatch(request()).await;
+        // the turns of the disconnected connection, of the last one, and of the full one
+        for _ in 0..3 {
+            pool.dispatch(request()).await;
+            assert!(receivers[2].recv().await.is_some());
+        }
+        assert_eq!(queued(&mut receivers), [1, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn applies_settings_to_every_connection() {
+        let (mut pool, mut receivers) = pool(2, 10);
+        pool.apply(Setting::Enable).await;
+        pool.apply(Setting::ReadBufferCapacity(1024)).await;
+        assert_eq!(queued(&mut receivers), [2, 2]);
+    }
+}
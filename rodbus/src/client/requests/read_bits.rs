@@ -1,53 +1,20 @@
+use crate::client::message::{Response, ResponsePromise};
 use crate::common::function::FunctionCode;
 use crate::common::traits::Serialize;
 use crate::decode::AppDecodeLevel;
-use crate::error::RequestError;
+use crate::error::{AduParseError, RequestError};
 use crate::types::{AddressRange, BitIterator, BitIteratorDisplay, ReadBitsRange};
-use crate::Indexed;
 
 use scursor::{ReadCursor, WriteCursor};
 
-pub(crate) trait BitsCallback:
-    FnOnce(Result<BitIterator, RequestError>) + Send + Sync + 'static
-{
-}
-impl<T> BitsCallback for T where T: FnOnce(Result<BitIterator, RequestError>) + Send + Sync + 'static
-{}
+/// Bits of a response, which borrow it
+pub(crate) struct Bits;
 
-pub(crate) struct Promise {
-    callback: Option<Box<dyn BitsCallback>>,
+impl Response for Bits {
+    type Value<'a> = BitIterator<'a>;
 }
 
-impl Drop for Promise {
-    fn drop(&mut self) {
-        self.failure(RequestError::Shutdown);
-    }
-}
-
-impl Promise {
-    pub(crate) fn new<T>(callback: T) -> Self
-    where
-        T: BitsCallback,
-    {
-        Self {
-            callback: Some(Box::new(callback)),
-        }
-    }
-
-    pub(crate) fn failure(&mut self, err: RequestError) {
-        self.complete(Err(err))
-    }
-
-    pub(crate) fn success(&mut self, iter: BitIterator) {
-        self.complete(Ok(iter))
-    }
-
-    fn complete(&mut self, result: Result<BitIterator, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(result)
-        }
-    }
-}
+pub(crate) type Promise = ResponsePromise<Bits>;
 
 pub(crate) struct ReadBits {
     pub(crate) request: ReadBitsRange,
@@ -59,18 +26,6 @@ impl ReadBits {
         Self { request, promise }
     }
 
-    pub(crate) fn channel(
-        request: ReadBitsRange,
-        tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<bool>>, RequestError>>,
-    ) -> Self {
-        Self::new(
-            request,
-            Promise::new(|x: Result<BitIterator, RequestError>| {
-                let _ = tx.send(x.map(|x| x.collect()));
-            }),
-        )
-    }
-
     pub(crate) fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
         self.request.get().serialize(cursor)
     }
@@ -99,12 +54,16 @@ impl ReadBits {
         Ok(())
     }
 
-    fn parse_bits_response<'a>(
+    pub(crate) fn parse_bits_response<'a>(
         range: AddressRange,
         cursor: &'a mut ReadCursor,
     ) -> Result<BitIterator<'a>, RequestError> {
-        // there's a byte-count here that we don't actually need
-        cursor.read_u8()?;
+        // the byte count must match the requested quantity
+        let expected = crate::common::bits::num_bytes_for_bits(range.count) as u8;
+        let byte_count = cursor.read_u8()?;
+        if byte_count != expected {
+            return Err(AduParseError::ByteCountMismatch(expected, byte_count).into());
+        }
         // the rest is a sequence of bits
         BitIterator::parse_all(range, cursor)
     }
@@ -1,56 +1,20 @@
+use crate::client::message::{Response, ResponsePromise};
 use crate::common::function::FunctionCode;
 use crate::common::traits::Serialize;
 use crate::decode::AppDecodeLevel;
-use crate::error::RequestError;
-use crate::types::{
-    AddressRange, Indexed, ReadRegistersRange, RegisterIterator, RegisterIteratorDisplay,
-};
+use crate::error::{AduParseError, RequestError};
+use crate::types::{AddressRange, ReadRegistersRange, RegisterIterator, RegisterIteratorDisplay};
 
 use scursor::{ReadCursor, WriteCursor};
 
-pub(crate) trait RegistersCallback:
-    FnOnce(Result<RegisterIterator, RequestError>) + Send + Sync + 'static
-{
-}
-impl<T> RegistersCallback for T where
-    T: FnOnce(Result<RegisterIterator, RequestError>) + Send + Sync + 'static
-{
-}
+/// Registers of a response, which borrow it
+pub(crate) struct Registers;
 
-pub(crate) struct Promise {
-    callback: Option<Box<dyn RegistersCallback>>,
-}
-
-impl Drop for Promise {
-    fn drop(&mut self) {
-        self.failure(RequestError::Shutdown);
-    }
+impl Response for Registers {
+    type Value<'a> = RegisterIterator<'a>;
 }
 
-impl Promise {
-    pub(crate) fn new<T>(callback: T) -> Self
-    where
-        T: RegistersCallback,
-    {
-        Self {
-            callback: Some(Box::new(callback)),
-        }
-    }
-
-    pub(crate) fn failure(&mut self, err: RequestError) {
-        self.complete(Err(err))
-    }
-
-    pub(crate) fn success(&mut self, iter: RegisterIterator) {
-        self.complete(Ok(iter))
-    }
-
-    fn complete(&mut self, x: Result<RegisterIterator, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(x)
-        }
-    }
-}
+pub(crate) type Promise = ResponsePromise<Registers>;
 
 pub(crate) struct ReadRegisters {
     pub(crate) request: ReadRegistersRange,
@@ -62,18 +26,6 @@ impl ReadRegisters {
         Self { request, promise }
     }
 
-    pub(crate) fn channel(
-        request: ReadRegistersRange,
-        tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<u16>>, RequestError>>,
-    ) -> Self {
-        Self::new(
-            request,
-            Promise::new(|x: Result<RegisterIterator, RequestError>| {
-                let _ = tx.send(x.map(|x| x.collect()));
-            }),
-        )
-    }
-
     pub(crate) fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
         self.request.get().serialize(cursor)
     }
@@ -102,12 +54,16 @@ impl ReadRegisters {
         Ok(())
     }
 
-    fn parse_registers_response<'a>(
+    pub(crate) fn parse_registers_response<'a>(
         range: AddressRange,
         cursor: &'a mut ReadCursor,
     ) -> Result<RegisterIterator<'a>, RequestError> {
-        // there's a byte-count here that we don't actually need
-        cursor.read_u8()?;
+        // the byte count must match the requested quantity
+        let expected = 2 * (range.count as usize) as u8;
+        let byte_count = cursor.read_u8()?;
+        if byte_count != expected {
+            return Err(AduParseError::ByteCountMismatch(expected, byte_count).into());
+        }
         // the reset is a sequence of bits
         RegisterIterator::parse_all(range, cursor)
     }
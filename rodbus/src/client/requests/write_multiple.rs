@@ -12,7 +12,7 @@ use std::convert::TryFrom;
 /// Collection of values and starting address
 ///
 /// Used when making write multiple coil/register requests
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WriteMultiple<T> {
     /// starting address
     pub(crate) range: AddressRange,
@@ -120,10 +120,26 @@ where
         Ok(())
     }
 
-    fn parse_all(&self, mut cursor: ReadCursor) -> Result<AddressRange, RequestError> {
+    fn parse_all(&self, cursor: ReadCursor) -> Result<AddressRange, RequestError> {
+        Self::parse_response(self.request.range, cursor)
+    }
+
+    pub(crate) fn parse_response(
+        request: AddressRange,
+        mut cursor: ReadCursor,
+    ) -> Result<AddressRange, RequestError> {
         let range = AddressRange::parse(&mut cursor)?;
-        if range != self.request.range {
-            return Err(RequestError::BadResponse(AduParseError::ReplyEchoMismatch));
+        if range.start != request.start {
+            return Err(RequestError::from(AduParseError::ReplyAddressMismatch(
+                request.start,
+                range.start,
+            )));
+        }
+        if range.count != request.count {
+            return Err(RequestError::from(AduParseError::ReplyCountMismatch(
+                request.count,
+                range.count,
+            )));
         }
         cursor.expect_empty()?;
         Ok(range)
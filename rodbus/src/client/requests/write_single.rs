@@ -12,6 +12,8 @@ use scursor::{ReadCursor, WriteCursor};
 pub(crate) trait SingleWriteOperation: Sized + PartialEq {
     fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError>;
     fn parse(cursor: &mut ReadCursor) -> Result<Self, RequestError>;
+    fn address(&self) -> u16;
+    fn raw_value(&self) -> u16;
 }
 
 pub(crate) struct SingleWrite<T>
@@ -56,11 +58,24 @@ where
         Ok(())
     }
 
-    fn parse_all(&self, mut cursor: ReadCursor) -> Result<T, RequestError> {
+    fn parse_all(&self, cursor: ReadCursor) -> Result<T, RequestError> {
+        Self::parse_response(&self.request, cursor)
+    }
+
+    pub(crate) fn parse_response(request: &T, mut cursor: ReadCursor) -> Result<T, RequestError> {
         let response = T::parse(&mut cursor)?;
         cursor.expect_empty()?;
-        if self.request != response {
-            return Err(AduParseError::ReplyEchoMismatch.into());
+        if request.address() != response.address() {
+            return Err(
+                AduParseError::ReplyAddressMismatch(request.address(), response.address()).into(),
+            );
+        }
+        if request.raw_value() != response.raw_value() {
+            return Err(AduParseError::ReplyValueMismatch(
+                request.raw_value(),
+                response.raw_value(),
+            )
+            .into());
         }
         Ok(response)
     }
@@ -79,6 +94,14 @@ impl SingleWriteOperation for Indexed<bool> {
             coil_from_u16(cursor.read_u16_be()?)?,
         ))
     }
+
+    fn address(&self) -> u16 {
+        self.index
+    }
+
+    fn raw_value(&self) -> u16 {
+        coil_to_u16(self.value)
+    }
 }
 
 impl SingleWriteOperation for Indexed<u16> {
@@ -91,4 +114,12 @@ impl SingleWriteOperation for Indexed<u16> {
     fn parse(cursor: &mut ReadCursor) -> Result<Self, RequestError> {
         Ok(Indexed::new(cursor.read_u16_be()?, cursor.read_u16_be()?))
     }
+
+    fn address(&self) -> u16 {
+        self.index
+    }
+
+    fn raw_value(&self) -> u16 {
+        self.value
+    }
 }
@@ -0,0 +1,316 @@
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::client::{Channel, RequestParam};
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+use crate::types::{AddressRange, UnitId};
+
+/// Request sent to each unit id by [`scan_units`]
+///
+/// Each probe reads a single value at an address, which every device is expected to answer
+/// quickly, either with the value or with an exception.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScanProbe {
+    /// Read one holding register (function code 0x03)
+    HoldingRegister(u16),
+    /// Read one input register (function code 0x04)
+    InputRegister(u16),
+    /// Read one coil (function code 0x01)
+    Coil(u16),
+    /// Read one discrete input (function code 0x02)
+    DiscreteInput(u16),
+}
+
+/// Options of [`scan_units`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Request sent to each unit id
+    pub probe: ScanProbe,
+    /// Response timeout of each probe
+    pub timeout: Duration,
+    /// Maximum number of probes outstanding at the same time
+    ///
+    /// Serial channels process one request at a time, so values above 1 only help on TCP
+    /// channels connected to a gateway that forwards requests concurrently.
+    pub parallelism: usize,
+}
+
+impl ScanOptions {
+    /// Create `ScanOptions` from its fields
+    pub fn new(probe: ScanProbe, timeout: Duration, parallelism: usize) -> Self {
+        Self {
+            probe,
+            timeout,
+            parallelism,
+        }
+    }
+}
+
+impl ScanProbe {
+    pub(crate) async fn send(
+        self,
+        channel: &Channel,
+        param: RequestParam,
+    ) -> Result<(), RequestError> {
+        let single = |start| AddressRange { start, count: 1 };
+        match self {
+            ScanProbe::HoldingRegister(x) => {
+                channel.read_holding_registers(param, single(x)).await?;
+            }
+            ScanProbe::InputRegister(x) => {
+                channel.read_input_registers(param, single(x)).await?;
+            }
+            ScanProbe::Coil(x) => {
+                channel.read_coils(param, single(x)).await?;
+            }
+            ScanProbe::DiscreteInput(x) => {
+                channel.read_discrete_inputs(param, single(x)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the outcome of a probe is interpreted
+pub(crate) enum ProbeOutcome {
+    /// The unit answered, possibly with an exception
+    Answered(Option<ExceptionCode>),
+    /// Nothing valid was received from the unit
+    NoAnswer,
+    /// The request could not be sent, which would be the same for every unit
+    Failed(RequestError),
+}
+
+impl From<Result<(), RequestError>> for ProbeOutcome {
+    fn from(result: Result<(), RequestError>) -> Self {
+        match result {
+            Ok(()) => Self::Answered(None),
+            Err(RequestError::Exception(
+                ExceptionCode::GatewayPathUnavailable
+                | ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            )) => Self::NoAnswer,
+            Err(RequestError::Exception(ex)) => Self::Answered(Some(ex)),
+            Err(
+                RequestError::ResponseTimeout
+                | RequestError::BadFrame(_)
+                | RequestError::BadResponse(_)
+                | RequestError::BadRequest(_),
+            ) => Self::NoAnswer,
+            Err(err) => Self::Failed(err),
+        }
+    }
+}
+
+impl Default for ScanOptions {
+    /// Read holding register 0 with a 100 ms timeout, one unit at a time
+    fn default() -> Self {
+        Self::new(ScanProbe::HoldingRegister(0), Duration::from_millis(100), 1)
+    }
+}
+
+/// Unit id that answered a probe of [`scan_units`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredUnit {
+    /// Unit id of the device
+    pub unit: UnitId,
+    /// Exception with which the device answered, if it did not return the value
+    pub exception: Option<ExceptionCode>,
+}
+
+/// Probe a range of unit ids and return which of them answered, in increasing order
+///
+/// A unit answers if it returns the value or an exception, other than the gateway exceptions
+/// through which a gateway reports that the unit is unavailable. Timeouts and malformed
+/// responses, e.g. from colliding replies on a misconfigured RS-485 bus, count as no answer.
+///
+/// The channel must be enabled. The scan stops at the first error that affects every unit, i.e.
+/// the loss of the connection or the shutdown of the channel, and returns it.
+pub async fn scan_units(
+    channel: &Channel,
+    units: RangeInclusive<u8>,
+    options: ScanOptions,
+) -> Result<Vec<DiscoveredUnit>, RequestError> {
+    let units = Arc::new(Mutex::new(units));
+    let aborted = Arc::new(AtomicBool::new(false));
+
+    let workers: Vec<_> = (0..options.parallelism.max(1))
+        .map(|_| {
+            let worker = Worker {
+                channel: channel.clone(),
+                units: units.clone(),
+                aborted: aborted.clone(),
+                options,
+            };
+            tokio::spawn(worker.run())
+        })
+        .collect();
+
+    let mut discovered = Vec::new();
+    let mut error = None;
+    for worker in workers {
+        match worker.await {
+            Ok(Ok(x)) => discovered.extend(x),
+            Ok(Err(err)) => error = error.or(Some(err)),
+            // the runtime is shutting down
+            Err(_) => error = error.or(Some(RequestError::Shutdown)),
+        }
+    }
+
+    if let Some(err) = error {
+        return Err(err);
+    }
+
+    discovered.sort_by_key(|x: &DiscoveredUnit| x.unit.value);
+    Ok(discovered)
+}
+
+/// Probes the unit ids that no other worker has claimed yet
+struct Worker {
+    channel: Channel,
+    units: Arc<Mutex<RangeInclusive<u8>>>,
+    aborted: Arc<AtomicBool>,
+    options: ScanOptions,
+}
+
+impl Worker {
+    async fn run(self) -> Result<Vec<DiscoveredUnit>, RequestError> {
+        let mut discovered = Vec::new();
+        while let Some(unit) = self.next_unit() {
+            let unit = UnitId::new(unit);
+            let param = RequestParam::new(unit, self.options.timeout);
+            let result = self.options.probe.send(&self.channel, param).await;
+            let exception = match ProbeOutcome::from(result) {
+                ProbeOutcome::Answered(x) => x,
+                ProbeOutcome::NoAnswer => continue,
+                ProbeOutcome::Failed(err) => {
+                    self.aborted.store(true, Ordering::Relaxed);
+                    return Err(err);
+                }
+            };
+            tracing::info!("unit {} answered the scan", unit);
+            discovered.push(DiscoveredUnit { unit, exception });
+        }
+        Ok(discovered)
+    }
+
+    fn next_unit(&self) -> Option<u8> {
+        if self.aborted.load(Ordering::Relaxed) {
+            return None;
+        }
+        // the range is only advanced while locked, so a poisoned lock can be recovered
+        self.units
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::Receiver;
+
+    use super::*;
+    use crate::client::message::{Command, Request};
+    use crate::decode::AppDecodeLevel;
+
+    fn answer(request: &mut Request) {
+        let result = match request.id.value {
+            // a register with the value 0
+            1 | 4 => request.handle_response(
+                &[0x03, 0x02, 0x00, 0x00],
+                AppDecodeLevel::Nothing,
+                crate::client::ResponseParsing::Strict,
+            ),
+            2 => Err(RequestError::Exception(ExceptionCode::IllegalDataAddress)),
+            3 => Err(RequestError::Exception(
+                ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            )),
+            _ => Err(RequestError::ResponseTimeout),
+        };
+        if let Err(err) = result {
+            request.details.fail(err);
+        }
+    }
+
+    async fn next_request(rx: &mut Receiver<Command>) -> Option<Request> {
+        loop {
+            match rx.recv().await? {
+                Command::Request(request) => return Some(request),
+                Command::Setting(_) => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_the_units_that_answer_with_a_value_or_an_exception() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(mut request) = next_request(&mut rx).await {
+                answer(&mut request);
+            }
+        });
+
+        let options = ScanOptions {
+            parallelism: 3,
+            ..Default::default()
+        };
+        let discovered = scan_units(&Channel::new(tx), 0..=6, options).await;
+        assert_eq!(
+            discovered,
+            Ok(vec![
+                DiscoveredUnit {
+                    unit: UnitId::new(1),
+                    exception: None
+                },
+                DiscoveredUnit {
+                    unit: UnitId::new(2),
+                    exception: Some(ExceptionCode::IllegalDataAddress)
+                },
+                DiscoveredUnit {
+                    unit: UnitId::new(4),
+                    exception: None
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_the_number_of_outstanding_probes() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let task = tokio::spawn(async move {
+            let mut count = 0;
+            loop {
+                let mut first = match next_request(&mut rx).await {
+                    Some(x) => x,
+                    None => return count,
+                };
+                let mut second = next_request(&mut rx).await.unwrap();
+                // no other probe is sent until one of these completes
+                tokio::task::yield_now().await;
+                assert!(rx.try_recv().is_err());
+                answer(&mut first);
+                answer(&mut second);
+                count += 2;
+            }
+        });
+
+        let options = ScanOptions {
+            parallelism: 2,
+            ..Default::default()
+        };
+        let discovered = scan_units(&Channel::new(tx), 1..=4, options).await.unwrap();
+        assert_eq!(discovered.len(), 3);
+        assert_eq!(task.await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_channel_is_shut_down() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        drop(rx);
+        let result = scan_units(&Channel::new(tx), 1..=247, ScanOptions::default()).await;
+        assert_eq!(result, Err(RequestError::Shutdown));
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::client::metrics::{MetricsListener, UnexpectedFrame};
+use crate::error::RequestError;
+use crate::types::UnitId;
+
+/// Upper bounds (inclusive) of the latency histogram buckets. Responses slower than
+/// the last bound are counted in a final overflow bucket.
+pub const LATENCY_BUCKET_BOUNDS: [Duration; 12] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_millis(1000),
+    Duration::from_millis(2000),
+    Duration::from_millis(5000),
+];
+
+/// Distribution of the response times of a particular function code sent to a particular unit
+///
+/// Only requests that received a response (including Modbus exceptions) are part of the
+/// distribution. Timeouts are counted separately.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Number of responses in each bucket of [`LATENCY_BUCKET_BOUNDS`], plus the overflow bucket
+    pub buckets: [u64; LATENCY_BUCKET_BOUNDS.len() + 1],
+    /// Number of responses that were received
+    pub count: u64,
+    /// Number of requests that timed out waiting for a response
+    pub timeouts: u64,
+    /// Sum of all the response times
+    pub total: Duration,
+    /// Fastest response time
+    pub min: Option<Duration>,
+    /// Slowest response time
+    pub max: Option<Duration>,
+}
+
+impl LatencyHistogram {
+    /// Average response time, if any response was received
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.total / self.count as u32)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKET_BOUNDS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += latency;
+        self.min = Some(self.min.map_or(latency, |x| x.min(latency)));
+        self.max = Some(self.max.map_or(latency, |x| x.max(latency)));
+    }
+}
+
+/// Number of received frames that did not match the outstanding request
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnexpectedFrameCounts {
+    /// See [`UnexpectedFrame::Unsolicited`]
+    pub unsolicited: u64,
+    /// See [`UnexpectedFrame::TxIdMismatch`]
+    pub tx_id_mismatch: u64,
+    /// See [`UnexpectedFrame::UnitIdMismatch`]
+    pub unit_id_mismatch: u64,
+    /// See [`UnexpectedFrame::LateResponse`]
+    pub late_response: u64,
+}
+
+/// Outcome counters of the requests sent to a particular unit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitStatistics {
+    /// Number of requests that completed successfully
+    pub successes: u64,
+    /// Number of requests that timed out waiting for a response
+    pub timeouts: u64,
+    /// Number of requests answered with a Modbus exception
+    pub exceptions: u64,
+    /// Number of requests that failed for any other reason (I/O error, bad response, etc)
+    pub failures: u64,
+}
+
+impl UnitStatistics {
+    /// Total number of requests sent to the unit
+    pub fn total(&self) -> u64 {
+        self.successes + self.timeouts + self.exceptions + self.failures
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    units: BTreeMap<UnitId, UnitStatistics>,
+    latencies: BTreeMap<(UnitId, u8), LatencyHistogram>,
+    unexpected_frames: UnexpectedFrameCounts,
+}
+
+/// Statistics collected on a channel
+///
+/// This type is a cheaply cloneable handle to shared state. Install a clone on a channel using
+/// [`crate::client::Channel::set_metrics_listener`] and read the values from another clone at
+/// any time.
+#[derive(Clone, Debug, Default)]
+pub struct ChannelStatistics {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ChannelStatistics {
+    /// Create an empty set of statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Outcome counters of the requests sent to a particular unit
+    pub fn unit(&self, id: UnitId) -> Option<UnitStatistics> {
+        self.lock().units.get(&id).copied()
+    }
+
+    /// Outcome counters of every unit to which a request was sent
+    pub fn units(&self) -> Vec<(UnitId, UnitStatistics)> {
+        self.lock()
+            .units
+            .iter()
+            .map(|(id, stats)| (*id, *stats))
+            .collect()
+    }
+
+    /// Latency distribution of a function code sent to particular unit
+    pub fn latency(&self, id: UnitId, function: u8) -> Option<LatencyHistogram> {
+        self.lock().latencies.get(&(id, function)).copied()
+    }
+
+    /// Latency distributions of every unit id and function code pair that was used
+    pub fn latencies(&self) -> Vec<(UnitId, u8, LatencyHistogram)> {
+        self.lock()
+            .latencies
+            .iter()
+            .map(|((id, function), histogram)| (*id, *function, *histogram))
+            .collect()
+    }
+
+    /// Number of received frames that did not match the outstanding request
+    pub fn unexpected_frames(&self) -> UnexpectedFrameCounts {
+        self.lock().unexpected_frames
+    }
+
+    /// Render the statistics in the Prometheus text exposition format
+    ///
+    /// The output can be served as-is from a `/metrics` endpoint. Every metric name starts with `rodbus_`:
+    ///
+    /// * `rodbus_requests_total` - counter of requests by `unit` and `outcome`
+    /// * `rodbus_response_time_seconds` - histogram of the response times by `unit` and `function`
+    /// * `rodbus_response_timeouts_total` - counter of timeouts by `unit` and `function`
+    /// * `rodbus_unexpected_frames_total` - counter of unexpected frames by `reason`
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let inner = self.lock();
+        let mut out = String::new();
+
+        out.push_str("# HELP rodbus_requests_total Number of requests by unit id and outcome\n");
+        out.push_str("# TYPE rodbus_requests_total counter\n");
+        for (id, stats) in inner.units.iter() {
+            for (outcome, value) in [
+                ("success", stats.successes),
+                ("timeout", stats.timeouts),
+                ("exception", stats.exceptions),
+                ("failure", stats.failures),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "rodbus_requests_total{{unit=\"{}\",outcome=\"{}\"}} {}",
+                    id.value, outcome, value
+                );
+            }
+        }
+
+        out.push_str(
+            "# HELP rodbus_response_time_seconds Response times by unit id and function code\n",
+        );
+        out.push_str("# TYPE rodbus_response_time_seconds histogram\n");
+        for ((id, function), histogram) in inner.latencies.iter() {
+            let labels = format!("unit=\"{}\",function=\"{}\"", id.value, function);
+            // Prometheus buckets are cumulative
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKET_BOUNDS.iter().zip(histogram.buckets.iter()) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "rodbus_response_time_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels,
+                    bound.as_secs_f64(),
+                    cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "rodbus_response_time_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "rodbus_response_time_seconds_sum{{{}}} {}",
+                labels,
+                histogram.total.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "rodbus_response_time_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        out.push_str("# HELP rodbus_response_timeouts_total Number of response timeouts by unit id and function code\n");
+        out.push_str("# TYPE rodbus_response_timeouts_total counter\n");
+        for ((id, function), histogram) in inner.latencies.iter() {
+            let _ = writeln!(
+                out,
+                "rodbus_response_timeouts_total{{unit=\"{}\",function=\"{}\"}} {}",
+                id.value, function, histogram.timeouts
+            );
+        }
+
+        out.push_str("# HELP rodbus_unexpected_frames_total Number of received frames that did not match the outstanding request\n");
+        out.push_str("# TYPE rodbus_unexpected_frames_total counter\n");
+        let counts = inner.unexpected_frames;
+        for (reason, value) in [
+            ("unsolicited", counts.unsolicited),
+            ("tx_id_mismatch", counts.tx_id_mismatch),
+            ("unit_id_mismatch", counts.unit_id_mismatch),
+            ("late_response", counts.late_response),
+        ] {
+            let _ = writeln!(
+                out,
+                "rodbus_unexpected_frames_total{{reason=\"{}\"}} {}",
+                reason, value
+            );
+        }
+
+        out
+    }
+
+    /// Clear all of the statistics
+    pub fn reset(&self) {
+        *self.lock() = Inner::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // the map is always left in a consistent state, so a poisoned lock can be recovered
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl MetricsListener for ChannelStatistics {
+    fn request_completed(
+        &mut self,
+        id: UnitId,
+        function: u8,
+        latency: Duration,
+        result: Result<(), RequestError>,
+    ) {
+        let mut guard = self.lock();
+        let inner = &mut *guard;
+        let unit = inner.units.entry(id).or_default();
+        match result {
+            Ok(()) => unit.successes += 1,
+            Err(RequestError::Exception(_)) => unit.exceptions += 1,
+            Err(RequestError::ResponseTimeout) => unit.timeouts += 1,
+            Err(_) => unit.failures += 1,
+        }
+
+        match result {
+            Ok(()) | Err(RequestError::Exception(_)) => {
+                inner
+                    .latencies
+                    .entry((id, function))
+                    .or_default()
+                    .record(latency);
+            }
+            Err(RequestError::ResponseTimeout) => {
+                inner.latencies.entry((id, function)).or_default().timeouts += 1;
+            }
+            Err(_) => {}
+        }
+    }
+
+    fn unexpected_frame(&mut self, _id: UnitId, reason: UnexpectedFrame) {
+        let mut guard = self.lock();
+        let counts = &mut guard.unexpected_frames;
+        match reason {
+            UnexpectedFrame::Unsolicited => counts.unsolicited += 1,
+            UnexpectedFrame::TxIdMismatch => counts.tx_id_mismatch += 1,
+            UnexpectedFrame::UnitIdMismatch => counts.unit_id_mismatch += 1,
+            UnexpectedFrame::LateResponse => counts.late_response += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exception::ExceptionCode;
+
+    #[test]
+    fn records_latencies_per_unit_and_function() {
+        let stats = ChannelStatistics::new();
+        let mut listener = stats.clone();
+        let unit = UnitId::new(1);
+
+        listener.request_completed(unit, 0x03, Duration::from_millis(3), Ok(()));
+        listener.request_completed(
+            unit,
+            0x03,
+            Duration::from_millis(7000),
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy)),
+        );
+        listener.request_completed(
+            unit,
+            0x03,
+            Duration::from_secs(1),
+            Err(RequestError::ResponseTimeout),
+        );
+        listener.request_completed(UnitId::new(2), 0x01, Duration::from_millis(1), Ok(()));
+
+        let histogram = stats.latency(unit, 0x03).unwrap();
+        assert_eq!(histogram.count, 2);
+        assert_eq!(histogram.timeouts, 1);
+        assert_eq!(histogram.buckets[2], 1);
+        assert_eq!(histogram.buckets[LATENCY_BUCKET_BOUNDS.len()], 1);
+        assert_eq!(histogram.min, Some(Duration::from_millis(3)));
+        assert_eq!(histogram.max, Some(Duration::from_millis(7000)));
+        assert_eq!(histogram.mean(), Some(Duration::from_micros(3_501_500)));
+
+        assert_eq!(stats.latencies().len(), 2);
+
+        assert_eq!(
+            stats.unit(unit),
+            Some(UnitStatistics {
+                successes: 1,
+                timeouts: 1,
+                exceptions: 1,
+                failures: 0,
+            })
+        );
+        assert_eq!(stats.unit(UnitId::new(2)).unwrap().total(), 1);
+        assert_eq!(stats.units().len(), 2);
+        assert!(stats.latency(unit, 0x01).is_none());
+    }
+
+    #[test]
+    fn counts_unexpected_frames() {
+        let stats = ChannelStatistics::new();
+        let mut listener = stats.clone();
+
+        listener.unexpected_frame(UnitId::new(1), UnexpectedFrame::Unsolicited);
+        listener.unexpected_frame(UnitId::new(1), UnexpectedFrame::Unsolicited);
+        listener.unexpected_frame(UnitId::new(2), UnexpectedFrame::UnitIdMismatch);
+
+        assert_eq!(
+            stats.unexpected_frames(),
+            UnexpectedFrameCounts {
+                unsolicited: 2,
+                tx_id_mismatch: 0,
+                unit_id_mismatch: 1,
+                late_response: 0,
+            }
+        );
+
+        stats.reset();
+        assert_eq!(stats.unexpected_frames(), UnexpectedFrameCounts::default());
+    }
+
+    #[test]
+    fn renders_prometheus_text_format() {
+        let stats = ChannelStatistics::new();
+        let mut listener = stats.clone();
+        let unit = UnitId::new(3);
+
+        listener.request_completed(unit, 0x04, Duration::from_millis(4), Ok(()));
+        listener.request_completed(unit, 0x04, Duration::from_millis(15), Ok(()));
+        listener.request_completed(
+            unit,
+            0x04,
+            Duration::from_secs(1),
+            Err(RequestError::ResponseTimeout),
+        );
+        listener.unexpected_frame(unit, UnexpectedFrame::TxIdMismatch);
+        listener.unexpected_frame(unit, UnexpectedFrame::LateResponse);
+        listener.unexpected_frame(unit, UnexpectedFrame::LateResponse);
+
+        let text = stats.to_prometheus();
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "rodbus_requests_total{unit=\"3\",outcome=\"success\"} 2",
+            "rodbus_requests_total{unit=\"3\",outcome=\"timeout\"} 1",
+            "rodbus_response_time_seconds_bucket{unit=\"3\",function=\"4\",le=\"0.002\"} 0",
+            "rodbus_response_time_seconds_bucket{unit=\"3\",function=\"4\",le=\"0.005\"} 1",
+            "rodbus_response_time_seconds_bucket{unit=\"3\",function=\"4\",le=\"0.02\"} 2",
+            "rodbus_response_time_seconds_bucket{unit=\"3\",function=\"4\",le=\"+Inf\"} 2",
+            "rodbus_response_time_seconds_sum{unit=\"3\",function=\"4\"} 0.019",
+            "rodbus_response_time_seconds_count{unit=\"3\",function=\"4\"} 2",
+            "rodbus_response_timeouts_total{unit=\"3\",function=\"4\"} 1",
+            "rodbus_unexpected_frames_total{reason=\"tx_id_mismatch\"} 1",
+            "rodbus_unexpected_frames_total{reason=\"late_response\"} 2",
+        ] {
+            assert!(lines.contains(&expected), "missing: {}", expected);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::Instrument;
+
+use crate::client::task::{ClientLoop, SessionError, StateChange};
+use crate::client::{Channel, ClientState, Listener};
+use crate::common::frame::{FrameWriter, FramedReader};
+use crate::common::phys::PhysLayer;
+use crate::decode::DecodeLevel;
+use crate::error::{ConnectError, Shutdown};
+use crate::retry::RetryStrategy;
+
+/// A bidirectional byte stream that carries Modbus TCP (MBAP) frames
+///
+/// This is implemented for every type that implements tokio's [`AsyncRead`] and [`AsyncWrite`].
+pub trait ClientStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T> ClientStream for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+
+/// Future returned by [`Connector::connect`]
+pub type ConnectFuture<'a> =
+    Pin<Box<dyn Future<Output = std::io::Result<Box<dyn ClientStream>>> + Send + 'a>>;
+
+/// Establishes the streams used by a channel created with [`spawn_stream_client_task`]
+///
+/// This is the extension point for transports that this library does not implement, e.g. a
+/// WebSocket bridge that tunnels Modbus TCP frames to a gateway.
+pub trait Connector: Send {
+    /// Open a new stream
+    ///
+    /// Called every time the channel is enabled or needs to reconnect. A failure is reported as a
+    /// [`ConnectError::Stream`] and retried according to the [`RetryStrategy`] of the channel.
+    fn connect(&mut self) -> ConnectFuture<'_>;
+}
+
+/// Spawns a channel task onto the runtime that obtains its streams from a user-supplied
+/// [`Connector`] and processes requests. The task completes when the returned channel handle
+/// is dropped.
+///
+/// Frames are exchanged using the Modbus TCP (MBAP) framing.
+///
+/// * `connector` - Opens the streams used by the channel
+/// * `max_queued_requests` - The maximum size of the request queue
+/// * `retry` - A boxed trait object that controls when the connection is retried on failure
+/// * `decode` - Decode log level
+/// * `listener` - Optional callback to monitor the connection state
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn spawn_stream_client_task(
+    connector: Box<dyn Connector>,
+    max_queued_requests: usize,
+    retry: Box<dyn RetryStrategy>,
+    decode: DecodeLevel,
+    listener: Option<Box<dyn Listener<ClientState>>>,
+) -> Channel {
+    let (tx, rx) = tokio::sync::mpsc::channel(max_queued_requests);
+    let mut task = StreamChannelTask {
+        connector,
+        connect_retry: retry,
+        client_loop: ClientLoop::new(rx, FrameWriter::tcp(), FramedReader::tcp(), decode),
+        listener: listener.unwrap_or_else(|| crate::client::NullListener::create()),
+    };
+    tokio::spawn(
+        async move {
+            task.run().await;
+        }
+        .instrument(tracing::info_span!("Modbus-Client-Stream")),
+    );
+    Channel::new(tx)
+}
+
+struct StreamChannelTask {
+    connector: Box<dyn Connector>,
+    connect_retry: Box<dyn RetryStrategy>,
+    client_loop: ClientLoop,
+    listener: Box<dyn Listener<ClientState>>,
+}
+
+impl StreamChannelTask {
+    // runs until it is shut down
+    async fn run(&mut self) -> Shutdown {
+        self.listener.update(ClientState::Disabled).get().await;
+        let ret = self.run_inner().await;
+        self.listener.update(ClientState::Shutdown).get().await;
+        ret
+    }
+
+    async fn run_inner(&mut self) -> Shutdown {
+        loop {
+            if let Err(Shutdown) = self.client_loop.wait_for_enabled().await {
+                return Shutdown;
+            }
+
+            if let Err(StateChange::Shutdown) = self.try_connect_and_run().await {
+                return Shutdown;
+            }
+
+            if !self.client_loop.is_enabled() {
+                self.listener.update(ClientState::Disabled).get().await;
+            }
+        }
+    }
+
+    async fn try_connect_and_run(&mut self) -> Result<(), StateChange> {
+        self.listener.update(ClientState::Connecting).get().await;
+        match self.connector.connect().await {
+            Err(err) => {
+                let err = ConnectError::Stream(err.into());
+                self.client_loop.report_connect_failure(err.clone());
+                let delay = self.connect_retry.after_failed_connect();
+                tracing::warn!(
+                    "{} - waiting {} ms before next attempt",
+                    err,
+                    delay.as_millis()
+                );
+                self.listener
+                    .update(ClientState::WaitAfterFailedConnect(delay))
+                    .get()
+                    .await;
+                self.client_loop.fail_requests_for(delay).await
+            }
+            Ok(stream) => {
+                tracing::info!("stream opened");
+                self.listener.update(ClientState::Connected).get().await;
+                self.connect_retry.reset();
+                let mut phys = PhysLayer::new_stream(stream);
+                match self.client_loop.run(&mut phys).await {
+                    // the mpsc was closed, end the task
+                    SessionError::Shutdown => Err(StateChange::Shutdown),
+                    // open a new stream
+                    SessionError::Disabled | SessionError::IoError(_) | SessionError::BadFrame => {
+                        let delay = self.connect_retry.after_disconnect();
+                        tracing::warn!("waiting {:?} to reconnect", delay);
+                        self.listener
+                            .update(ClientState::WaitAfterDisconnect(delay))
+                            .get()
+                            .await;
+                        self.client_loop.fail_requests_for(delay).await
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RequestParam;
+    use crate::retry::default_retry_strategy;
+    use crate::test_util::PairConnector;
+    use crate::types::{AddressRange, Indexed, UnitId};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn exchanges_frames_over_user_supplied_stream() {
+        let (client, mut server) = tokio::io::duplex(256);
+        let channel = spawn_stream_client_task(
+            Box::new(PairConnector {
+                stream: Some(client),
+            }),
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let request = tokio::spawn(async move {
+            channel
+                .read_holding_registers(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    AddressRange::try_from(7, 1).unwrap(),
+                )
+                .await
+        });
+
+        let mut buffer = [0u8; 12];
+        server.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(
+            buffer,
+            [0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x07, 0x00, 0x01]
+        );
+        server
+            .write_all(&[
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x01, 0x03, 0x02, 0x00, 0x2A,
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(request.await.unwrap().unwrap(), vec![Indexed::new(7, 42)]);
+    }
+}
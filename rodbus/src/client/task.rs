@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::Duration;
 
 use tracing::Instrument;
@@ -5,9 +7,16 @@ use tracing::Instrument;
 use crate::common::phys::PhysLayer;
 use tokio::time::Instant;
 
+use crate::audit::{AuditOrigin, AuditSink, WriteRecord};
+use crate::client::capture::{PcapWriter, Protocol};
+use crate::client::channel::{RequestScheduling, ResponseParsing, UnitIdPolicy};
+use crate::client::fairness::{FairQueue, RequestFairness};
+use crate::client::interceptor::Interceptor;
 use crate::client::message::{Command, Request, Setting};
-use crate::common::frame::{FrameHeader, FrameWriter, FramedReader, TxId};
+use crate::client::metrics::{MetricsListener, UnexpectedFrame};
+use crate::common::frame::{Frame, FrameHeader, FrameWriter, FramedReader, TxId};
 use crate::error::*;
+use crate::types::UnitId;
 use crate::DecodeLevel;
 
 /**
@@ -53,7 +62,7 @@ impl std::fmt::Display for SessionError {
 impl SessionError {
     pub(crate) fn from(err: &RequestError) -> Option<Self> {
         match err {
-            RequestError::Io(x) => Some(SessionError::IoError(*x)),
+            RequestError::Io(x) => Some(SessionError::IoError(x.kind())),
             RequestError::BadFrame(_) => Some(SessionError::BadFrame),
             // all other errors don't kill the loop
             _ => None,
@@ -61,6 +70,41 @@ impl SessionError {
     }
 }
 
+/// The request that timed out last, whose response may still arrive
+#[derive(Copy, Clone)]
+struct TimedOut {
+    // only TCP frames carry a transaction id
+    tx_id: Option<TxId>,
+    unit: UnitId,
+    function: u8,
+}
+
+impl TimedOut {
+    fn matches(&self, frame: &Frame) -> bool {
+        match (self.tx_id, frame.header.tx_id) {
+            (Some(expected), Some(received)) => expected == received,
+            _ => {
+                frame.header.destination.into_unit_id() == self.unit
+                    && frame.payload().first().map(|x| x & 0x7F) == Some(self.function)
+            }
+        }
+    }
+}
+
+/// Maximum number of requests taken off the queue that wait for their unit to become available
+const MAX_DEFERRED_REQUESTS: usize = 16;
+
+/// A request waiting for its response while others are outstanding
+struct InFlight {
+    request: Request,
+    tx_id: TxId,
+    start: Instant,
+    deadline: Instant,
+    span: tracing::Span,
+    // identical reads completed with the response
+    duplicates: Vec<Request>,
+}
+
 pub(crate) struct ClientLoop {
     rx: tokio::sync::mpsc::Receiver<Command>,
     writer: FrameWriter,
@@ -68,6 +112,23 @@ pub(crate) struct ClientLoop {
     tx_id: TxId,
     decode: DecodeLevel,
     enabled: bool,
+    metrics: Option<Box<dyn MetricsListener>>,
+    interceptor: Option<Box<dyn Interceptor>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    capture: Option<PcapWriter>,
+    unexpected_frame_level: Option<tracing::Level>,
+    response_parsing: ResponseParsing,
+    unit_id_policy: UnitIdPolicy,
+    timed_out: Option<TimedOut>,
+    scheduling: RequestScheduling,
+    fairness: RequestFairness,
+    queue: FairQueue,
+    deduplicate_reads: bool,
+    #[cfg(feature = "tls")]
+    tls_config: Option<crate::tcp::tls::client::TlsClientConfig>,
+    #[cfg(feature = "serial")]
+    serial_settings: Option<crate::serial::SerialSettings>,
+    endpoint: Option<crate::client::HostAddr>,
 }
 
 impl ClientLoop {
@@ -84,6 +145,23 @@ impl ClientLoop {
             tx_id: TxId::default(),
             decode,
             enabled: false,
+            metrics: None,
+            interceptor: None,
+            audit: None,
+            capture: None,
+            unexpected_frame_level: Some(tracing::Level::WARN),
+            response_parsing: ResponseParsing::Strict,
+            unit_id_policy: UnitIdPolicy::Warn,
+            timed_out: None,
+            scheduling: RequestScheduling::Serialized,
+            fairness: RequestFairness::Fifo,
+            queue: FairQueue::new(),
+            deduplicate_reads: false,
+            #[cfg(feature = "tls")]
+            tls_config: None,
+            #[cfg(feature = "serial")]
+            serial_settings: None,
+            endpoint: None,
         }
     }
 
@@ -91,9 +169,35 @@ impl ClientLoop {
         self.enabled
     }
 
+    /// Take the TLS configuration received since the last connection, if any
+    #[cfg(feature = "tls")]
+    pub(crate) fn take_tls_config(&mut self) -> Option<crate::tcp::tls::client::TlsClientConfig> {
+        self.tls_config.take()
+    }
+
+    /// Take the serial settings received since the port was last opened, if any
+    #[cfg(feature = "serial")]
+    pub(crate) fn take_serial_settings(&mut self) -> Option<crate::serial::SerialSettings> {
+        self.serial_settings.take()
+    }
+
+    /// Take the endpoint received since the last connection, if any
+    pub(crate) fn take_endpoint(&mut self) -> Option<crate::client::HostAddr> {
+        self.endpoint.take()
+    }
+
     async fn run_cmd(&mut self, cmd: Command, io: &mut PhysLayer) -> Result<(), SessionError> {
         match cmd {
             Command::Setting(setting) => {
+                // the requests queued before the settings have completed, apply them to the open port
+                #[cfg(feature = "serial")]
+                if let Setting::SerialSettings(settings) = &setting {
+                    if let Err(err) = io.reconfigure_serial(settings) {
+                        tracing::warn!("unable to apply the serial settings: {}", err);
+                        self.change_setting(setting);
+                        return Err(SessionError::IoError(err.kind()));
+                    }
+                }
                 self.change_setting(setting);
                 if !self.enabled {
                     return Err(SessionError::Disabled);
@@ -117,28 +221,63 @@ impl ClientLoop {
     }
 
     pub(crate) async fn run(&mut self, io: &mut PhysLayer) -> SessionError {
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.connected();
+        }
+        let err = self.run_session(io).await;
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.disconnected();
+        }
+        err
+    }
+
+    pub(crate) fn report_connect_failure(&mut self, err: ConnectError) {
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.connect_failed(err);
+        }
+    }
+
+    async fn run_session(&mut self, io: &mut PhysLayer) -> SessionError {
+        // the scheduling of the requests can change during the session
+        loop {
+            let result = if self.is_pipelined() {
+                self.run_pipelined_session(io).await
+            } else {
+                self.run_serialized_session(io).await
+            };
+            if let Err(err) = result {
+                return err;
+            }
+        }
+    }
+
+    fn is_pipelined(&self) -> bool {
+        // RTU responses can't be matched with their request
+        self.scheduling == RequestScheduling::PerUnit && self.writer.is_tcp()
+    }
+
+    async fn run_serialized_session(&mut self, io: &mut PhysLayer) -> Result<(), SessionError> {
         loop {
             tokio::select! {
                 frame = self.reader.next_frame(io, self.decode) => {
                     match frame {
-                        Ok(frame) => {
-                            tracing::warn!("Received unexpected frame while idle: {:?}", frame.header);
-                        }
+                        Ok(frame) => self.report_unmatched_frame(&frame, None),
                         Err(err) => {
                             if let Some(err) = SessionError::from(&err) {
                                 tracing::warn!("{}", err);
-                                return err;
+                                return Err(err);
                             }
                         }
                     }
                 }
-                cmd = self.rx.recv() => {
+                cmd = self.queue.next_command(&mut self.rx, self.fairness) => {
                     match cmd {
                         // other side has closed the request channel
-                        None => return SessionError::Shutdown,
+                        None => return Err(SessionError::Shutdown),
                         Some(cmd) => {
-                            if let Err(err) = self.run_cmd(cmd, io).await {
-                                return err;
+                            self.run_cmd(cmd, io).await?;
+                            if self.is_pipelined() {
+                                return Ok(());
                             }
                         }
                     }
@@ -147,26 +286,320 @@ impl ClientLoop {
         }
     }
 
+    async fn run_pipelined_session(&mut self, io: &mut PhysLayer) -> Result<(), SessionError> {
+        let mut in_flight = Vec::new();
+        let mut deferred = VecDeque::new();
+        let result = self.run_pipeline(io, &mut in_flight, &mut deferred).await;
+        if result.is_err() {
+            for mut x in in_flight {
+                let _ = self.finish_request(
+                    &mut x.request,
+                    x.duplicates,
+                    x.start,
+                    Err(RequestError::NoConnection),
+                );
+            }
+            for mut request in deferred {
+                request.details.fail(RequestError::NoConnection);
+            }
+        }
+        result
+    }
+
+    async fn run_pipeline(
+        &mut self,
+        io: &mut PhysLayer,
+        in_flight: &mut Vec<InFlight>,
+        deferred: &mut VecDeque<Request>,
+    ) -> Result<(), SessionError> {
+        loop {
+            // start the deferred requests whose unit is no longer busy
+            let mut index = 0;
+            while index < deferred.len() {
+                let unit = deferred[index].id;
+                if in_flight.iter().any(|x| x.request.id == unit) {
+                    index += 1;
+                } else if let Some(request) = deferred.remove(index) {
+                    self.start_pipelined_request(io, request, in_flight).await?;
+                }
+            }
+
+            let pipelined = self.is_pipelined();
+            if !pipelined && in_flight.is_empty() && deferred.is_empty() {
+                return Ok(());
+            }
+
+            let deadline = in_flight.iter().map(|x| x.deadline).min();
+            let timeout = async move {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                frame = self.reader.next_frame(io, self.decode) => {
+                    match frame {
+                        Ok(frame) => self.complete_pipelined_request(frame, in_flight)?,
+                        Err(err) => {
+                            if let Some(err) = SessionError::from(&err) {
+                                tracing::warn!("{}", err);
+                                return Err(err);
+                            }
+                        }
+                    }
+                }
+                _ = timeout => {
+                    let now = Instant::now();
+                    let mut index = 0;
+                    while index < in_flight.len() {
+                        if in_flight[index].deadline > now {
+                            index += 1;
+                            continue;
+                        }
+                        let mut x = in_flight.remove(index);
+                        self.timed_out = Some(TimedOut {
+                            tx_id: Some(x.tx_id),
+                            unit: x.request.id,
+                            function: x.request.details.function().get_value(),
+                        });
+                        self.finish_request(&mut x.request, x.duplicates, x.start, Err(RequestError::ResponseTimeout))?;
+                    }
+                }
+                // stop taking requests from the queue while too many wait for their unit
+                cmd = self.queue.next_command(&mut self.rx, self.fairness), if pipelined && deferred.len() < MAX_DEFERRED_REQUESTS => {
+                    match cmd {
+                        None => return Err(SessionError::Shutdown),
+                        Some(Command::Setting(setting)) => {
+                            self.change_setting(setting);
+                            if !self.enabled {
+                                return Err(SessionError::Disabled);
+                            }
+                        }
+                        Some(Command::Request(request)) => {
+                            if in_flight.iter().any(|x| x.request.id == request.id) {
+                                deferred.push_back(request);
+                            } else {
+                                self.start_pipelined_request(io, request, in_flight).await?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn start_pipelined_request(
+        &mut self,
+        io: &mut PhysLayer,
+        mut request: Request,
+        in_flight: &mut Vec<InFlight>,
+    ) -> Result<(), SessionError> {
+        if Self::expired(&mut request) {
+            return Ok(());
+        }
+        let duplicates = self.take_duplicates(&request);
+        let (tx_id, start, span) = self.start_request(&request);
+        match self
+            .send_request(io, &mut request, tx_id)
+            .instrument(span.clone())
+            .await
+        {
+            Ok(()) => {
+                in_flight.push(InFlight {
+                    deadline: request.deadline,
+                    request,
+                    tx_id,
+                    start,
+                    span,
+                    duplicates,
+                });
+                Ok(())
+            }
+            Err(err) => self.finish_request(&mut request, duplicates, start, Err(err)),
+        }
+    }
+
+    fn complete_pipelined_request(
+        &mut self,
+        frame: Frame,
+        in_flight: &mut Vec<InFlight>,
+    ) -> Result<(), SessionError> {
+        let index = match frame
+            .header
+            .tx_id
+            .and_then(|tx_id| in_flight.iter().position(|x| x.tx_id == tx_id))
+        {
+            Some(index) => index,
+            None => {
+                let expected = in_flight.first().map(|x| x.tx_id);
+                self.report_unmatched_frame(&frame, expected);
+                return Ok(());
+            }
+        };
+
+        if !self.accept_unit_id(&frame, in_flight[index].request.id) {
+            return Ok(());
+        }
+
+        let mut x = in_flight.remove(index);
+        let span = x.span.clone();
+        let result = span.in_scope(|| self.handle_frame(&mut x.request, &mut x.duplicates, &frame));
+        self.finish_request(&mut x.request, x.duplicates, x.start, result)
+    }
+
+    /// Report a frame that doesn't answer any outstanding request
+    ///
+    /// `expected` is the transaction id of an outstanding request, if any
+    fn report_unmatched_frame(&mut self, frame: &Frame, expected: Option<TxId>) {
+        let unit = frame.header.destination.into_unit_id();
+        if self.is_late_response(frame) {
+            self.report_unexpected_frame(
+                unit,
+                UnexpectedFrame::LateResponse,
+                format_args!("received late response: {:?}", frame.header),
+            );
+            return;
+        }
+        match (frame.header.tx_id, expected) {
+            (Some(received), Some(expected)) => self.report_unexpected_frame(
+                unit,
+                UnexpectedFrame::TxIdMismatch,
+                format_args!("received {:?} while expecting {:?}", received, expected),
+            ),
+            _ => self.report_unexpected_frame(
+                unit,
+                UnexpectedFrame::Unsolicited,
+                format_args!("received unexpected frame while idle: {:?}", frame.header),
+            ),
+        }
+    }
+
+    /// Apply the [`UnitIdPolicy`] to a response, returning `false` if it must be discarded
+    fn accept_unit_id(&mut self, frame: &Frame, expected: UnitId) -> bool {
+        let received_id = frame.header.destination.into_unit_id();
+        if received_id == expected || self.unit_id_policy == UnitIdPolicy::Ignore {
+            return true;
+        }
+        self.report_unexpected_frame(
+            received_id,
+            UnexpectedFrame::UnitIdMismatch,
+            format_args!(
+                "received response from unit {} while expecting {}",
+                received_id, expected
+            ),
+        );
+        self.unit_id_policy != UnitIdPolicy::Require
+    }
+
     async fn run_one_request(
         &mut self,
         io: &mut PhysLayer,
         request: &mut Request,
     ) -> Result<(), SessionError> {
-        let tx_id = self.tx_id.next();
+        if Self::expired(request) {
+            return Ok(());
+        }
+        let mut duplicates = self.take_duplicates(request);
+        let (tx_id, start, span) = self.start_request(request);
         let result = self
-            .execute_request(io, request, tx_id)
-            .instrument(tracing::info_span!("Transaction", tx_id = %tx_id))
+            .execute_request(io, request, &mut duplicates, tx_id)
+            .instrument(span)
             .await;
+        self.finish_request(request, duplicates, start, result)
+    }
+
+    /// Take the identical reads waiting in the queue if reads are deduplicated
+    fn take_duplicates(&mut self, request: &Request) -> Vec<Request> {
+        if !self.deduplicate_reads {
+            return Vec::new();
+        }
+        let duplicates = self.queue.take_duplicates(&mut self.rx, request);
+        if !duplicates.is_empty() {
+            tracing::debug!(
+                "{} identical reads completed by {} request to unit {} ({})",
+                duplicates.len(),
+                request.details.function(),
+                request.id,
+                request.details.range(),
+            );
+        }
+        duplicates
+    }
+
+    /// Fail a request whose response timeout elapsed while it waited in the queue
+    fn expired(request: &mut Request) -> bool {
+        if Instant::now() < request.deadline {
+            return false;
+        }
+        tracing::warn!(
+            "{} request to unit {} ({}) expired in the queue",
+            request.details.function(),
+            request.id,
+            request.details.range(),
+        );
+        request.details.fail(RequestError::ResponseTimeout);
+        true
+    }
+
+    fn start_request(&mut self, request: &Request) -> (TxId, Instant, tracing::Span) {
+        let tx_id = self.tx_id.next();
+        let start = Instant::now();
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.request_started(request.id, request.details.function().get_value());
+        }
+        let span =
+            tracing::info_span!("Transaction", tx_id = %tx_id, request_id = %request.request_id);
+        #[cfg(feature = "otel")]
+        {
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+            span.set_parent(request.context.clone());
+        }
+        (tx_id, start, span)
+    }
+
+    fn finish_request(
+        &mut self,
+        request: &mut Request,
+        duplicates: Vec<Request>,
+        start: Instant,
+        result: Result<(), RequestError>,
+    ) -> Result<(), SessionError> {
+        let function = request.details.function().get_value();
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.request_completed(request.id, function, start.elapsed(), result.clone());
+        }
+        if let Some(audit) = self.audit.as_ref() {
+            if let Some(values) = request.details.written_values() {
+                audit.record(&WriteRecord::new(
+                    AuditOrigin::Client,
+                    request.id,
+                    function,
+                    values,
+                    result.clone(),
+                ));
+            }
+        }
 
         if let Err(err) = result {
             // Fail the request in ONE place. If the whole future
             // gets dropped, then the request gets failed with Shutdown
-            tracing::warn!("request error: {}", err);
+            tracing::warn!(
+                "{} request to unit {} ({}) failed: {}",
+                request.details.function(),
+                request.id,
+                request.details.range(),
+                err
+            );
+            for mut duplicate in duplicates {
+                duplicate.details.fail(err.clone());
+            }
+            let session_error = SessionError::from(&err);
             request.details.fail(err);
 
             // some request errors are a session error that will
             // bubble up and close the session
-            if let Some(err) = SessionError::from(&err) {
+            if let Some(err) = session_error {
                 return Err(err);
             }
         }
@@ -174,27 +607,65 @@ impl ClientLoop {
         Ok(())
     }
 
-    async fn execute_request(
+    fn protocol(&self) -> Protocol {
+        if self.writer.is_tcp() {
+            Protocol::Tcp
+        } else {
+            Protocol::Rtu
+        }
+    }
+
+    async fn send_request(
         &mut self,
         io: &mut PhysLayer,
         request: &mut Request,
         tx_id: TxId,
     ) -> Result<(), RequestError> {
+        let protocol = self.protocol();
+        let function = request.details.function();
         let bytes = self.writer.format_request(
             FrameHeader::new_tcp_header(request.id, tx_id),
-            request.details.function(),
+            function,
             &request.details,
             self.decode,
         )?;
 
-        io.write(bytes, self.decode.physical).await?;
+        if let Some(interceptor) = self.interceptor.as_mut() {
+            interceptor.on_request(request.id, function.get_value(), bytes.pdu)?;
+        }
+
+        if let Some(capture) = self.capture.as_mut() {
+            if let Err(err) = capture.write_request(protocol, bytes.frame) {
+                tracing::warn!("stopping capture after write error: {}", err);
+                self.capture = None;
+            }
+        }
 
-        let deadline = Instant::now() + request.timeout;
+        io.write(bytes.frame, self.decode.physical).await?;
+        Ok(())
+    }
+
+    async fn execute_request(
+        &mut self,
+        io: &mut PhysLayer,
+        request: &mut Request,
+        duplicates: &mut Vec<Request>,
+        tx_id: TxId,
+    ) -> Result<(), RequestError> {
+        self.send_request(io, request, tx_id).await?;
+
+        let function = request.details.function();
+        let deadline = request.deadline;
 
         // loop until we get a response with the correct tx id or we timeout
         let response = loop {
             let frame = tokio::select! {
                 _ = tokio::time::sleep_until(deadline) => {
+                    self.timed_out = Some(TimedOut {
+                        tx_id: self.writer.is_tcp().then_some(tx_id),
+                        unit: request.id,
+                        function: function.get_value(),
+                    });
                     return Err(RequestError::ResponseTimeout);
                 }
                 frame = self.reader.next_frame(io, self.decode) => {
@@ -205,17 +676,118 @@ impl ClientLoop {
             if let Some(received_tx_id) = frame.header.tx_id {
                 // Check that the received transaction ID matches (only in TCP MBAP)
                 if received_tx_id != tx_id {
-                    tracing::warn!("received {:?} while expecting {:?}", received_tx_id, tx_id);
+                    self.report_unmatched_frame(&frame, Some(tx_id));
                     continue; // next iteration of loop
                 }
             }
 
+            // without a transaction id, only a late response to a different request can be
+            // recognized
+            let current = TimedOut {
+                tx_id: None,
+                unit: request.id,
+                function: function.get_value(),
+            };
+            if frame.header.tx_id.is_none()
+                && !current.matches(&frame)
+                && self.is_late_response(&frame)
+            {
+                self.report_unexpected_frame(
+                    frame.header.destination.into_unit_id(),
+                    UnexpectedFrame::LateResponse,
+                    format_args!("received late response: {:?}", frame.header),
+                );
+                continue;
+            }
+
+            if !self.accept_unit_id(&frame, request.id) {
+                continue;
+            }
+
             break frame;
         };
 
+        self.handle_frame(request, duplicates, &response)
+    }
+
+    /// Handle the response to a request, completing the identical reads with it if the request
+    /// succeeds. Otherwise the reads are left to fail with the same error.
+    fn handle_frame(
+        &mut self,
+        request: &mut Request,
+        duplicates: &mut Vec<Request>,
+        response: &Frame,
+    ) -> Result<(), RequestError> {
+        let protocol = self.protocol();
+        if let Some(capture) = self.capture.as_mut() {
+            if let Err(err) = capture.write_response(protocol, response) {
+                tracing::warn!("stopping capture after write error: {}", err);
+                self.capture = None;
+            }
+        }
+
+        if let Some(interceptor) = self.interceptor.as_mut() {
+            let pdu = response.payload();
+            let function = pdu.first().copied().unwrap_or(0);
+            interceptor.on_response(response.header.destination.into_unit_id(), function, pdu)?;
+        }
+
         // once we have a response, handle it. This may complete a promise
         // successfully or bubble up an error
-        request.handle_response(response.payload(), self.decode.app)
+        let result =
+            request.handle_response(response.payload(), self.decode.app, self.response_parsing);
+        if let Err(RequestError::BadResponse(err)) = &result {
+            // the error includes the offending PDU, so that a malformed response
+            // can be diagnosed from the logs alone, regardless of the decode level
+            tracing::warn!("unable to parse response: {}", err);
+        }
+        if result.is_ok() {
+            for mut duplicate in duplicates.drain(..) {
+                if let Err(err) = duplicate.handle_response(
+                    response.payload(),
+                    self.decode.app,
+                    self.response_parsing,
+                ) {
+                    duplicate.details.fail(err);
+                }
+            }
+        }
+        result
+    }
+
+    /// Check whether the frame answers the request that timed out last
+    ///
+    /// On RTU, the late response precedes any other response, so the timed out request is
+    /// forgotten once a frame is matched
+    fn is_late_response(&mut self, frame: &Frame) -> bool {
+        match self.timed_out {
+            Some(timed_out) if timed_out.matches(frame) => {
+                if timed_out.tx_id.is_none() {
+                    self.timed_out = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn report_unexpected_frame(
+        &mut self,
+        id: UnitId,
+        reason: UnexpectedFrame,
+        details: std::fmt::Arguments,
+    ) {
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.unexpected_frame(id, reason);
+        }
+        match self.unexpected_frame_level {
+            None => {}
+            Some(tracing::Level::ERROR) => tracing::error!("{}", details),
+            Some(tracing::Level::WARN) => tracing::warn!("{}", details),
+            Some(tracing::Level::INFO) => tracing::info!("{}", details),
+            Some(tracing::Level::DEBUG) => tracing::debug!("{}", details),
+            Some(tracing::Level::TRACE) => tracing::trace!("{}", details),
+        }
     }
 
     pub(crate) fn change_setting(&mut self, setting: Setting) {
@@ -224,6 +796,66 @@ impl ClientLoop {
                 tracing::info!("Decode level changed: {:?}", level);
                 self.decode = level;
             }
+            Setting::Metrics(listener) => {
+                self.metrics = Some(listener);
+            }
+            Setting::Interceptor(interceptor) => {
+                self.interceptor = Some(interceptor);
+            }
+            Setting::Audit(audit) => {
+                self.audit = audit;
+            }
+            Setting::Capture(capture) => {
+                self.capture = capture;
+            }
+            Setting::UnexpectedFrameLogging(level) => {
+                self.unexpected_frame_level = level;
+            }
+            Setting::ReadBufferCapacity(capacity) => {
+                self.reader.set_buffer_capacity(capacity);
+            }
+            Setting::MaxPduLength(length) => {
+                self.reader.set_max_pdu_length(length);
+                self.writer.set_max_pdu_length(length);
+            }
+            Setting::ResponseParsing(parsing) => {
+                self.response_parsing = parsing;
+            }
+            Setting::UnitIdPolicy(policy) => {
+                self.unit_id_policy = policy;
+            }
+            Setting::Scheduling(scheduling) => {
+                self.scheduling = scheduling;
+            }
+            Setting::Fairness(fairness) => {
+                self.fairness = fairness;
+            }
+            Setting::DeduplicateReads(enabled) => {
+                self.deduplicate_reads = enabled;
+            }
+            Setting::WritePriority(enabled) => {
+                self.queue.set_write_priority(enabled);
+            }
+            Setting::UnitWeight(unit, weight) => {
+                self.queue.set_weight(unit, weight);
+            }
+            #[cfg(feature = "tls")]
+            Setting::TlsConfig(config) => {
+                tracing::info!("TLS configuration changed, applies to the next connection");
+                self.tls_config = Some(config);
+            }
+            #[cfg(feature = "serial")]
+            Setting::SerialSettings(settings) => {
+                tracing::info!("serial settings changed: {:?}", settings);
+                self.serial_settings = Some(settings);
+            }
+            Setting::Endpoint(host) => {
+                tracing::info!(
+                    "endpoint changed to {}, applies to the next connection",
+                    host
+                );
+                self.endpoint = Some(host);
+            }
             Setting::Enable => {
                 if !self.enabled {
                     self.enabled = true;
@@ -240,7 +872,7 @@ impl ClientLoop {
     }
 
     async fn fail_next_request(&mut self) -> Result<(), StateChange> {
-        match self.rx.recv().await {
+        match self.queue.next_command(&mut self.rx, self.fairness).await {
             None => return Err(StateChange::Disable),
             Some(cmd) => match cmd {
                 Command::Request(mut req) => {
@@ -288,7 +920,7 @@ mod tests {
     use crate::decode::*;
     use crate::server::response::BitWriter;
     use crate::types::{AddressRange, UnitId};
-    use crate::{ExceptionCode, Indexed, ReadBitsRange};
+    use crate::{ExceptionCode, Indexed, ReadBitsRange, ReadResult};
 
     use sfio_tokio_mock_io::Event;
 
@@ -309,7 +941,7 @@ mod tests {
             let mut phys = PhysLayer::new_mock(mock);
             client_loop.run(&mut phys).await
         });
-        let channel = Channel { tx };
+        let channel = Channel::new(tx);
         (channel, join_handle, io_handle)
     }
 
@@ -322,7 +954,7 @@ mod tests {
         let bytes = fmt
             .format_request(header, function, payload, DecodeLevel::nothing())
             .unwrap();
-        Vec::from(bytes)
+        Vec::from(bytes.frame)
     }
 
     #[tokio::test]
@@ -334,7 +966,7 @@ mod tests {
 
     #[tokio::test]
     async fn returns_io_error_when_write_fails() {
-        let (mut channel, _task, mut io) = spawn_client_loop();
+        let (channel, _task, mut io) = spawn_client_loop();
 
         let error_kind = ErrorKind::ConnectionReset;
 
@@ -349,12 +981,12 @@ mod tests {
             )
             .await;
 
-        assert_eq!(result, Err(RequestError::Io(error_kind)));
+        assert_eq!(result.unwrap_err(), RequestError::Io(error_kind.into()));
     }
 
     #[tokio::test]
     async fn returns_timeout_when_no_response() {
-        let (mut channel, _task, mut io) = spawn_client_loop();
+        let (channel, _task, mut io) = spawn_client_loop();
 
         // the expected request
         let range = AddressRange::try_from(7, 2).unwrap();
@@ -375,13 +1007,89 @@ mod tests {
         // pausing the time will cause the timer to "auto advance"
         tokio::time::pause();
 
+        let err = request_task.await.unwrap().unwrap_err();
+        assert_eq!(err, RequestError::ResponseTimeout);
+        assert_eq!(
+            err.context(),
+            RequestContext {
+                unit: UnitId::new(1),
+                function: FunctionCode::ReadCoils,
+                range,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "READ COILS (0x01) request to unit 0x01 (start: 0x0007 qty: 2) failed: response timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn discards_responses_from_other_units_when_required() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+        channel
+            .set_unit_id_policy(UnitIdPolicy::Require)
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        // the response is from unit 1
+        let response = get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        );
+
+        let request_task = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(2), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        assert!(matches!(io.next_event().await, Event::Write(_)));
+        io.read(&response);
+
+        tokio::time::pause();
         let result = request_task.await.unwrap();
-        assert_eq!(result, Err(RequestError::ResponseTimeout));
+        assert_eq!(result.unwrap_err(), RequestError::ResponseTimeout);
+    }
+
+    #[tokio::test]
+    async fn requests_expire_in_the_queue() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let read = |channel: &Channel, timeout| {
+            let channel = channel.clone();
+            tokio::spawn(async move {
+                channel
+                    .read_coils(RequestParam::new(UnitId::new(1), timeout), range)
+                    .await
+            })
+        };
+
+        let first = read(&channel, Duration::from_secs(5));
+        assert!(matches!(io.next_event().await, Event::Write(_)));
+        // waits in the queue for longer than its timeout
+        let second = read(&channel, Duration::from_secs(1));
+
+        tokio::time::pause();
+        assert_eq!(
+            first.await.unwrap().unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+        assert_eq!(
+            second.await.unwrap().unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+        assert_eq!(io.pop_event(), None);
     }
 
     #[tokio::test]
     async fn returns_shutdown_when_task_dropped() {
-        let (mut channel, task, mut io) = spawn_client_loop();
+        let (channel, task, mut io) = spawn_client_loop();
 
         // the expected request
         let range = AddressRange::try_from(7, 2).unwrap();
@@ -405,7 +1113,7 @@ mod tests {
 
         // the promise will get dropped causing the request to fail with Shutdown
         let res = request_task.await.unwrap();
-        assert_eq!(res, Err(RequestError::Shutdown));
+        assert_eq!(res.unwrap_err(), RequestError::Shutdown);
     }
 
     #[tokio::test]
@@ -419,7 +1127,7 @@ mod tests {
 
     #[tokio::test]
     async fn transmit_read_coils_when_requested() {
-        let (mut channel, _task, mut io) = spawn_client_loop();
+        let (channel, _task, mut io) = spawn_client_loop();
 
         let range = AddressRange::try_from(7, 2).unwrap();
         let request = get_framed_adu(FunctionCode::ReadCoils, &range);
@@ -449,4 +1157,449 @@ mod tests {
             vec![Indexed::new(7, true), Indexed::new(8, false)]
         );
     }
+
+    #[tokio::test]
+    async fn returns_contiguous_read_result() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let response = get_framed_adu(
+            FunctionCode::ReadDiscreteInputs,
+            &BitWriter::new(ReadBitsRange { inner: range }, |idx| Ok(idx == 8)),
+        );
+
+        let inputs = tokio::spawn(async move {
+            channel
+                .read_discrete_inputs_result(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+
+        assert!(matches!(io.next_event().await, Event::Write(_)));
+        io.read(&response);
+
+        assert_eq!(
+            inputs.await.unwrap().unwrap(),
+            ReadResult::new(7, vec![false, true])
+        );
+    }
+
+    type MetricsEvent = (u8, Option<Result<(), RequestError>>);
+
+    #[derive(Clone, Default)]
+    struct MetricsLog {
+        events: Arc<std::sync::Mutex<Vec<MetricsEvent>>>,
+    }
+
+    impl MetricsListener for MetricsLog {
+        fn request_started(&mut self, _id: UnitId, function: u8) {
+            self.events.lock().unwrap().push((function, None));
+        }
+
+        fn request_completed(
+            &mut self,
+            _id: UnitId,
+            function: u8,
+            _latency: Duration,
+            result: Result<(), RequestError>,
+        ) {
+            self.events.lock().unwrap().push((function, Some(result)));
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_listener_is_informed_of_request_outcomes() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        let metrics = MetricsLog::default();
+        channel.enable().await.unwrap();
+        channel
+            .set_metrics_listener(Box::new(metrics.clone()))
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let request = get_framed_adu(FunctionCode::ReadCoils, &range);
+
+        let request_task = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(io.next_event().await, Event::Write(request));
+
+        tokio::time::pause();
+        assert_eq!(
+            request_task.await.unwrap().unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+
+        assert_eq!(
+            *metrics.events.lock().unwrap(),
+            vec![
+                (0x01, None),
+                (0x01, Some(Err(RequestError::ResponseTimeout)))
+            ]
+        );
+    }
+
+    struct RejectWrites;
+
+    impl Interceptor for RejectWrites {
+        fn on_request(
+            &mut self,
+            _id: UnitId,
+            function: u8,
+            pdu: &[u8],
+        ) -> Result<(), RequestError> {
+            assert_eq!(pdu.first(), Some(&function));
+            if function == FunctionCode::WriteSingleCoil.get_value() {
+                return Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn interceptor_can_veto_requests() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+        channel
+            .set_interceptor(Box::new(RejectWrites))
+            .await
+            .unwrap();
+
+        let result = channel
+            .write_single_coil(
+                RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                Indexed::new(1, true),
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err(),
+            RequestError::Exception(ExceptionCode::ServerDeviceBusy)
+        );
+
+        // reads are still transmitted, the vetoed request consumed a transaction id
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let request = get_framed_adu(FunctionCode::ReadCoils, &range);
+        let _read = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+        match io.next_event().await {
+            Event::Write(bytes) => assert_eq!(bytes[2..], request[2..]),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn frames_received_while_idle_are_counted() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        let stats = crate::client::ChannelStatistics::new();
+        channel.enable().await.unwrap();
+        channel
+            .set_metrics_listener(Box::new(stats.clone()))
+            .await
+            .unwrap();
+
+        // let the client loop process the settings before the frame arrives
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        io.read(&get_framed_adu(FunctionCode::ReadCoils, &range));
+
+        for _ in 0..100 {
+            if stats.unexpected_frames().unsolicited > 0 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(stats.unexpected_frames().unsolicited, 1);
+    }
+
+    #[tokio::test]
+    async fn late_responses_are_discarded() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        let stats = crate::client::ChannelStatistics::new();
+        channel.enable().await.unwrap();
+        channel
+            .set_metrics_listener(Box::new(stats.clone()))
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let late = get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(false)),
+        );
+        let mut response = get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        );
+        // the second request has the next transaction id
+        response[1] = 1;
+
+        let first_channel = channel.clone();
+        let first = tokio::spawn(async move { first_channel.read_coils(param, range).await });
+        assert!(matches!(io.next_event().await, Event::Write(_)));
+        tokio::time::pause();
+        assert_eq!(
+            first.await.unwrap().unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+        tokio::time::resume();
+
+        let second = tokio::spawn(async move { channel.read_coils(param, range).await });
+        assert!(matches!(io.next_event().await, Event::Write(_)));
+        io.read(&late);
+        io.read(&response);
+
+        assert_eq!(
+            second.await.unwrap().unwrap(),
+            vec![Indexed::new(7, true), Indexed::new(8, true)]
+        );
+        assert_eq!(stats.unexpected_frames().late_response, 1);
+        assert_eq!(stats.unexpected_frames().tx_id_mismatch, 0);
+    }
+
+    #[tokio::test]
+    async fn requests_to_different_units_are_outstanding_together() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+        channel
+            .set_request_scheduling(RequestScheduling::PerUnit)
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let with_header = |mut frame: Vec<u8>, unit: u8, tx_id: u8| {
+            frame[1] = tx_id;
+            frame[6] = unit;
+            frame
+        };
+        let request =
+            |unit, tx_id| with_header(get_framed_adu(FunctionCode::ReadCoils, &range), unit, tx_id);
+        let response = |unit, tx_id| {
+            let body = BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true));
+            with_header(get_framed_adu(FunctionCode::ReadCoils, &body), unit, tx_id)
+        };
+        let read = |channel: &Channel, unit| {
+            let channel = channel.clone();
+            tokio::spawn(async move {
+                channel
+                    .read_coils(
+                        RequestParam::new(UnitId::new(unit), Duration::from_secs(5)),
+                        range,
+                    )
+                    .await
+            })
+        };
+
+        let first = read(&channel, 1);
+        assert_eq!(io.next_event().await, Event::Write(request(1, 0)));
+        let second = read(&channel, 2);
+        assert_eq!(io.next_event().await, Event::Write(request(2, 1)));
+        // waits for the response to the first request
+        let third = read(&channel, 1);
+
+        io.read(&response(2, 1));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert!(second.await.unwrap().is_ok());
+        io.read(&response(1, 0));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert!(first.await.unwrap().is_ok());
+
+        assert_eq!(io.next_event().await, Event::Write(request(1, 2)));
+        io.read(&response(1, 2));
+        assert!(third.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn shared_channel_completes_requests_answered_out_of_order() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+        channel
+            .set_request_scheduling(RequestScheduling::PerUnit)
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let response = |unit: u8, tx_id: u8| {
+            let body = BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(unit == 1));
+            let mut frame = get_framed_adu(FunctionCode::ReadCoils, &body);
+            frame[1] = tx_id;
+            frame[6] = unit;
+            frame
+        };
+        let read = |unit| {
+            channel.read_coils(
+                RequestParam::new(UnitId::new(unit), Duration::from_secs(5)),
+                range,
+            )
+        };
+
+        let device = async {
+            assert!(matches!(io.next_event().await, Event::Write(_)));
+            assert!(matches!(io.next_event().await, Event::Write(_)));
+            io.read(&response(2, 1));
+            assert_eq!(io.next_event().await, Event::Read);
+            io.read(&response(1, 0));
+            assert_eq!(io.next_event().await, Event::Read);
+        };
+
+        // both requests are issued through the same reference to the channel
+        let (first, second, ()) = tokio::join!(read(1), read(2), device);
+        assert!(first.unwrap().iter().all(|x| x.value));
+        assert!(second.unwrap().iter().all(|x| !x.value));
+    }
+
+    #[tokio::test]
+    async fn identical_queued_reads_share_one_transaction() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        channel.enable().await.unwrap();
+        channel.set_deduplicate_reads(true).await.unwrap();
+
+        let with_tx_id = |mut frame: Vec<u8>, tx_id: u8| {
+            frame[1] = tx_id;
+            frame
+        };
+        let request = |range: AddressRange, tx_id| {
+            with_tx_id(get_framed_adu(FunctionCode::ReadCoils, &range), tx_id)
+        };
+        let response = |range: AddressRange, tx_id| {
+            let body = BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true));
+            with_tx_id(get_framed_adu(FunctionCode::ReadCoils, &body), tx_id)
+        };
+        let read = |channel: &Channel, range| {
+            let channel = channel.clone();
+            tokio::spawn(async move {
+                channel
+                    .read_coils(
+                        RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                        range,
+                    )
+                    .await
+            })
+        };
+
+        let other = AddressRange::try_from(0, 1).unwrap();
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let first = read(&channel, other);
+        assert_eq!(io.next_event().await, Event::Write(request(other, 0)));
+        // both wait for the response to the first request
+        let second = read(&channel, range);
+        let third = read(&channel, range);
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        io.read(&response(other, 0));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert!(first.await.unwrap().is_ok());
+
+        assert_eq!(io.next_event().await, Event::Write(request(range, 1)));
+        io.read(&response(range, 1));
+        assert_eq!(io.next_event().await, Event::Read);
+        let expected = vec![Indexed::new(7, true), Indexed::new(8, true)];
+        assert_eq!(second.await.unwrap(), Ok(expected.clone()));
+        assert_eq!(third.await.unwrap(), Ok(expected));
+        assert_eq!(io.pop_event(), None);
+    }
+
+    #[tokio::test]
+    async fn callback_session_passes_request_id_to_callback() {
+        let (channel, _task, _io) = spawn_client_loop();
+        let session = crate::client::CallbackSession::new(
+            channel,
+            RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+        );
+
+        // a range that is too large fails immediately without being sent
+        let range = AddressRange::try_from(0, 0x7D1).unwrap();
+        let (tx1, rx1) = tokio::sync::oneshot::channel();
+        let (tx2, rx2) = tokio::sync::oneshot::channel();
+        // requests are issued concurrently through a shared reference
+        let (first, second) = tokio::join!(
+            session.read_coils(range, move |id, result| {
+                let _ = tx1.send((id, result.is_err()));
+            }),
+            session.read_holding_registers(range, move |id, result| {
+                let _ = tx2.send((id, result.is_err()));
+            }),
+        );
+        assert_eq!(rx1.await.unwrap(), (first, true));
+        assert_eq!(rx2.await.unwrap(), (second, true));
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn full_queue_is_distinguished_from_shutdown() {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let channel = Channel::new(tx);
+        channel.set_fail_when_queue_full(true);
+        let session = crate::client::CallbackSession::new(
+            channel,
+            RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+        );
+        let range = AddressRange::try_from(0, 1).unwrap();
+
+        // the first request occupies the only slot in the queue
+        session.read_coils(range, |_, _| {}).await;
+
+        let (tx, result) = tokio::sync::oneshot::channel();
+        session
+            .read_coils(range, move |_, result| {
+                let _ = tx.send(result.err());
+            })
+            .await;
+        assert_eq!(result.await.unwrap(), Some(RequestError::QueueFull));
+
+        drop(rx);
+        let (tx, result) = tokio::sync::oneshot::channel();
+        session
+            .read_coils(range, move |_, result| {
+                let _ = tx.send(result.err());
+            })
+            .await;
+        assert_eq!(result.await.unwrap(), Some(RequestError::Shutdown));
+    }
+
+    #[tokio::test]
+    async fn full_queue_setting_is_copied_by_later_clones() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let channel = Channel::new(tx);
+        let earlier = channel.clone();
+        channel.set_fail_when_queue_full(true);
+        let later = channel.clone();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let range = AddressRange::try_from(0, 1).unwrap();
+
+        // the first request occupies the only slot in the queue
+        crate::client::CallbackSession::new(channel, param)
+            .read_coils(range, |_, _| {})
+            .await;
+
+        assert_eq!(
+            later.read_coils(param, range).await.unwrap_err(),
+            RequestError::QueueFull
+        );
+        // the handle cloned before the change still waits for space in the queue
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), earlier.read_coils(param, range))
+                .await
+                .is_err()
+        );
+    }
 }
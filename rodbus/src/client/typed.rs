@@ -0,0 +1,126 @@
+use crate::client::{Channel, RequestParam, WriteMultiple};
+use crate::error::RequestFailure;
+use crate::types::{AddressRange, Indexed};
+
+/// A request of any of the supported function codes
+///
+/// This allows requests to be handled generically, e.g. queued, logged or passed through
+/// middleware, and then performed using [`Channel::call`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedRequest {
+    /// Read coils (0x01)
+    ReadCoils(AddressRange),
+    /// Read discrete inputs (0x02)
+    ReadDiscreteInputs(AddressRange),
+    /// Read holding registers (0x03)
+    ReadHoldingRegisters(AddressRange),
+    /// Read input registers (0x04)
+    ReadInputRegisters(AddressRange),
+    /// Write single coil (0x05)
+    WriteSingleCoil(Indexed<bool>),
+    /// Write single register (0x06)
+    WriteSingleRegister(Indexed<u16>),
+    /// Write multiple coils (0x0F)
+    WriteMultipleCoils(WriteMultiple<bool>),
+    /// Write multiple registers (0x10)
+    WriteMultipleRegisters(WriteMultiple<u16>),
+}
+
+/// Response to a [`TypedRequest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedResponse {
+    /// Values of the coils or discrete inputs that were read
+    Bits(Vec<Indexed<bool>>),
+    /// Values of the holding or input registers that were read
+    Registers(Vec<Indexed<u16>>),
+    /// Coil echoed by a write single coil request
+    SingleCoil(Indexed<bool>),
+    /// Register echoed by a write single register request
+    SingleRegister(Indexed<u16>),
+    /// Range written by a write multiple coils or registers request
+    Multiple(AddressRange),
+}
+
+impl Channel {
+    /// Perform a request of any type
+    pub async fn call(
+        &self,
+        param: RequestParam,
+        request: TypedRequest,
+    ) -> Result<TypedResponse, RequestFailure> {
+        let response = match request {
+            TypedRequest::ReadCoils(range) => {
+                TypedResponse::Bits(self.read_coils(param, range).await?)
+            }
+            TypedRequest::ReadDiscreteInputs(range) => {
+                TypedResponse::Bits(self.read_discrete_inputs(param, range).await?)
+            }
+            TypedRequest::ReadHoldingRegisters(range) => {
+                TypedResponse::Registers(self.read_holding_registers(param, range).await?)
+            }
+            TypedRequest::ReadInputRegisters(range) => {
+                TypedResponse::Registers(self.read_input_registers(param, range).await?)
+            }
+            TypedRequest::WriteSingleCoil(value) => {
+                TypedResponse::SingleCoil(self.write_single_coil(param, value).await?)
+            }
+            TypedRequest::WriteSingleRegister(value) => {
+                TypedResponse::SingleRegister(self.write_single_register(param, value).await?)
+            }
+            TypedRequest::WriteMultipleCoils(values) => {
+                TypedResponse::Multiple(self.write_multiple_coils(param, values).await?)
+            }
+            TypedRequest::WriteMultipleRegisters(values) => {
+                TypedResponse::Multiple(self.write_multiple_registers(param, values).await?)
+            }
+        };
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::spawn_stream_client_task;
+    use crate::decode::DecodeLevel;
+    use crate::retry::default_retry_strategy;
+    use crate::test_util::PairConnector;
+    use crate::types::UnitId;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn performs_typed_request() {
+        let (client, mut server) = tokio::io::duplex(256);
+        let channel = spawn_stream_client_task(
+            Box::new(PairConnector {
+                stream: Some(client),
+            }),
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let response = tokio::spawn(async move {
+            channel
+                .call(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    TypedRequest::WriteSingleRegister(Indexed::new(2, 0xCAFE)),
+                )
+                .await
+        });
+
+        // the server echoes the request
+        let mut frame = [0u8; 12];
+        server.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[7..], [0x06, 0x00, 0x02, 0xCA, 0xFE]);
+        server.write_all(&frame).await.unwrap();
+
+        assert_eq!(
+            response.await.unwrap(),
+            Ok(TypedResponse::SingleRegister(Indexed::new(2, 0xCAFE)))
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! The functions of this module operate on byte slices only. They perform no I/O, do not depend on
+//! the Tokio runtime, and never panic on malformed input, which makes them suitable targets for
+//! fuzzing and reusable by analysis tools. They use the same framers and parsers as the channels.
+//!
+//! [`ClientMachine`](crate::codec::ClientMachine) builds on them to implement the client transaction lifecycle for event loops
+//! that don't use Tokio.
+
+use scursor::{ReadCursor, WriteCursor};
+
+pub use self::client::*;
+
+mod client;
+
+use crate::client::message::Request;
+use crate::client::requests::read_bits::ReadBits;
+use crate::client::requests::read_registers::ReadRegisters;
+use crate::client::requests::write_multiple::MultipleWriteRequest;
+use crate::client::requests::write_single::SingleWrite;
+use crate::client::{TypedRequest, TypedResponse, WriteMultiple};
+use crate::common::buffer::ReadBuffer;
+use crate::common::frame::Frame;
+use crate::common::function::FunctionCode;
+use crate::common::traits::Serialize;
+use crate::decode::FrameDecodeLevel;
+use crate::error::{AduParseError, FrameParseError, RequestError};
+use crate::exception::ExceptionCode;
+use crate::tcp::frame::MbapParser;
+use crate::types::{Indexed, UnitId};
+
+/// Modbus TCP ADU parsed by [`parse_mbap`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MbapAdu<'a> {
+    /// Transaction identifier
+    pub tx_id: u16,
+    /// Unit identifier
+    pub unit_id: UnitId,
+    /// Function code and data
+    pub pdu: &'a [u8],
+}
+
+/// Modbus RTU ADU parsed by [`parse_rtu_request`] or [`parse_rtu_response`]
+#[cfg(feature = "serial")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RtuAdu<'a> {
+    /// Unit identifier, which is 0 for broadcast requests
+    pub unit_id: UnitId,
+    /// Function code and data, without the CRC
+    pub pdu: &'a [u8],
+}
+
+/// Parse the Modbus TCP ADU at the beginning of `bytes`
+///
+/// Returns `Ok(None)` if more bytes are required, otherwise the ADU and the number of bytes it
+/// occupies. The error is a [`RequestError::BadFrame`] if the MBAP header is invalid.
+pub fn parse_mbap(bytes: &[u8]) -> Result<Option<(MbapAdu<'_>, usize)>, RequestError> {
+    let (frame, consumed) = match parse_frame(bytes, |buffer| {
+        MbapParser::new().parse(buffer, FrameDecodeLevel::Nothing)
+    })? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let pdu_len = frame.payload().len();
+    let adu = MbapAdu {
+        tx_id: frame.header.tx_id.map(|x| x.to_u16()).unwrap_or(0),
+        unit_id: frame.header.destination.into_unit_id(),
+        pdu: &bytes[consumed - pdu_len..consumed],
+    };
+    Ok(Some((adu, consumed)))
+}
+
+/// Parse the Modbus RTU request at the beginning of `bytes`
+///
+/// Returns `Ok(None)` if more bytes are required, otherwise the ADU and the number of bytes it
+/// occupies. The error is a [`RequestError::BadFrame`] if the function code is unknown, the
+/// frame is too large or the CRC is invalid.
+#[cfg(feature = "serial")]
+pub fn parse_rtu_request(bytes: &[u8]) -> Result<Option<(RtuAdu<'_>, usize)>, RequestError> {
+    parse_rtu(bytes, crate::serial::frame::RtuParser::new_request_parser())
+}
+
+/// Parse the Modbus RTU response at the beginning of `bytes`
+///
+/// The length of RTU frames depends on their direction, see [`parse_rtu_request`].
+#[cfg(feature = "serial")]
+pub fn parse_rtu_response(bytes: &[u8]) -> Result<Option<(RtuAdu<'_>, usize)>, RequestError> {
+    parse_rtu(
+        bytes,
+        crate::serial::frame::RtuParser::new_response_parser(),
+    )
+}
+
+#[cfg(feature = "serial")]
+fn parse_rtu(
+    bytes: &[u8],
+    mut parser: crate::serial::frame::RtuParser,
+) -> Result<Option<(RtuAdu<'_>, usize)>, RequestError> {
+    let (frame, consumed) = match parse_frame(bytes, |buffer| {
+        parser.parse(buffer, FrameDecodeLevel::Nothing)
+    })? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    // unit id + PDU + CRC
+    let adu = RtuAdu {
+        unit_id: frame.header.destination.into_unit_id(),
+        pdu: &bytes[1..consumed - 2],
+    };
+    Ok(Some((adu, consumed)))
+}
+
+fn parse_frame<F>(bytes: &[u8], parse: F) -> Result<Option<(Frame, usize)>, RequestError>
+where
+    F: FnOnce(&mut ReadBuffer) -> Result<Option<Frame>, RequestError>,
+{
+    // a frame never exceeds the capacity of the buffer
+    let mut buffer = ReadBuffer::from_slice(bytes);
+    let available = buffer.len();
+    Ok(parse(&mut buffer)?.map(|frame| (frame, available - buffer.len())))
+}
+
+/// Parse the PDU of the response to a request
+///
+/// The response is validated exactly like a channel does: the function code must match the
+/// request, Modbus exceptions are returned as [`RequestError::Exception`], and the content of the
+/// response must be consistent with the request, e.g. the byte count of a read or the echoed
+/// address of a write.
+pub fn parse_response(request: &TypedRequest, pdu: &[u8]) -> Result<TypedResponse, RequestError> {
+    let expected = function_of(request);
+    let mut cursor = ReadCursor::new(pdu);
+    let function = cursor.read_u8()?;
+    if function != expected.get_value() {
+        return Err(Request::get_error_for(function, expected, cursor));
+    }
+
+    let response = match request {
+        TypedRequest::ReadCoils(range) | TypedRequest::ReadDiscreteInputs(range) => {
+            TypedResponse::Bits(ReadBits::parse_bits_response(*range, &mut cursor)?.collect())
+        }
+        TypedRequest::ReadHoldingRegisters(range) | TypedRequest::ReadInputRegisters(range) => {
+            TypedResponse::Registers(
+                ReadRegisters::parse_registers_response(*range, &mut cursor)?.collect(),
+            )
+        }
+        TypedRequest::WriteSingleCoil(value) => {
+            TypedResponse::SingleCoil(SingleWrite::<Indexed<bool>>::parse_response(value, cursor)?)
+        }
+        TypedRequest::WriteSingleRegister(value) => {
+            TypedResponse::SingleRegister(SingleWrite::<Indexed<u16>>::parse_response(
+                value, cursor,
+            )?)
+        }
+        TypedRequest::WriteMultipleCoils(values) => TypedResponse::Multiple(
+            MultipleWriteRequest::<bool>::parse_response(values.range, cursor)?,
+        ),
+        TypedRequest::WriteMultipleRegisters(values) => TypedResponse::Multiple(
+            MultipleWriteRequest::<u16>::parse_response(values.range, cursor)?,
+        ),
+    };
+    Ok(response)
+}
+
+/// Encode the PDU of a request into `buffer`, returning the number of bytes written
+///
+/// The request is validated like a channel does, e.g. the count of a read must not exceed the
+/// limit of the function code. A buffer of [`MAX_PDU_LENGTH`] bytes fits any request.
+pub fn encode_request(request: &TypedRequest, buffer: &mut [u8]) -> Result<usize, RequestError> {
+    let body: &dyn Serialize = match request {
+        TypedRequest::ReadCoils(range) | TypedRequest::ReadDiscreteInputs(range) => {
+            range.of_read_bits()?;
+            range
+        }
+        TypedRequest::ReadHoldingRegisters(range) | TypedRequest::ReadInputRegisters(range) => {
+            range.of_read_registers()?;
+            range
+        }
+        TypedRequest::WriteSingleCoil(value) => value,
+        TypedRequest::WriteSingleRegister(value) => value,
+        TypedRequest::WriteMultipleCoils(values) => values,
+        TypedRequest::WriteMultipleRegisters(values) => values,
+    };
+    encode_pdu(function_of(request).get_value(), body, buffer)
+}
+
+/// Parse the PDU of a request
+///
+/// The request is validated exactly like a server does. A server would answer an
+/// [`FrameParseError::UnknownFunctionCode`] error with [`ExceptionCode::IllegalFunction`], and
+/// any other error with [`ExceptionCode::IllegalDataValue`].
+pub fn parse_request(pdu: &[u8]) -> Result<TypedRequest, RequestError> {
+    use crate::server::request::Request;
+
+    let mut cursor = ReadCursor::new(pdu);
+    let value = cursor.read_u8()?;
+    let function = FunctionCode::get(value).ok_or(RequestError::BadFrame(
+        FrameParseError::UnknownFunctionCode(value),
+    ))?;
+
+    let request = match Request::parse(function, &mut cursor)? {
+        Request::ReadCoils(range) => TypedRequest::ReadCoils(range.get()),
+        Request::ReadDiscreteInputs(range) => TypedRequest::ReadDiscreteInputs(range.get()),
+        Request::ReadHoldingRegisters(range) => TypedRequest::ReadHoldingRegisters(range.get()),
+        Request::ReadInputRegisters(range) => TypedRequest::ReadInputRegisters(range.get()),
+        Request::WriteSingleCoil(value) => TypedRequest::WriteSingleCoil(value),
+        Request::WriteSingleRegister(value) => TypedRequest::WriteSingleRegister(value),
+        Request::WriteMultipleCoils(x) => TypedRequest::WriteMultipleCoils(WriteMultiple::from(
+            x.range.start,
+            x.iterator.map(|x| x.value).collect(),
+        )?),
+        Request::WriteMultipleRegisters(x) => TypedRequest::WriteMultipleRegisters(
+            WriteMultiple::from(x.range.start, x.iterator.map(|x| x.value).collect())?,
+        ),
+    };
+    Ok(request)
+}
+
+/// Encode the PDU of the response to a request into `buffer`, returning the number of bytes
+/// written
+///
+/// The function code is the one of the request. The values of a read response are encoded in
+/// order, their addresses are not checked against the range of the request. The error is an
+/// [`AduParseError::ReplyEchoMismatch`] if the kind of response doesn't match the request, e.g.
+/// registers in response to a read of coils. A buffer of [`MAX_PDU_LENGTH`] bytes fits any
+/// valid response.
+pub fn encode_response(
+    request: &TypedRequest,
+    response: &TypedResponse,
+    buffer: &mut [u8],
+) -> Result<usize, RequestError> {
+    let function = function_of(request).get_value();
+    match (request, response) {
+        (
+            TypedRequest::ReadCoils(_) | TypedRequest::ReadDiscreteInputs(_),
+            TypedResponse::Bits(values),
+        ) => {
+            let values: Vec<bool> = values.iter().map(|x| x.value).collect();
+            encode_pdu(function, &values.as_slice(), buffer)
+        }
+        (
+            TypedRequest::ReadHoldingRegisters(_) | TypedRequest::ReadInputRegisters(_),
+            TypedResponse::Registers(values),
+        ) => {
+            let values: Vec<u16> = values.iter().map(|x| x.value).collect();
+            encode_pdu(function, &values.as_slice(), buffer)
+        }
+        (TypedRequest::WriteSingleCoil(_), TypedResponse::SingleCoil(value)) => {
+            encode_pdu(function, value, buffer)
+        }
+        (TypedRequest::WriteSingleRegister(_), TypedResponse::SingleRegister(value)) => {
+            encode_pdu(function, value, buffer)
+        }
+        (
+            TypedRequest::WriteMultipleCoils(_) | TypedRequest::WriteMultipleRegisters(_),
+            TypedResponse::Multiple(range),
+        ) => encode_pdu(function, range, buffer),
+        _ => Err(RequestError::from(AduParseError::ReplyEchoMismatch)),
+    }
+}
+
+/// Encode the PDU of an exception response to a function code into `buffer`, returning the
+/// number of bytes written
+pub fn encode_exception(
+    function: FunctionCode,
+    exception: ExceptionCode,
+    buffer: &mut [u8],
+) -> Result<usize, RequestError> {
+    encode_pdu(function.as_error(), &exception, buffer)
+}
+
+/// Maximum length of a PDU, i.e. of the function code and data
+pub const MAX_PDU_LENGTH: usize = crate::common::frame::constants::MAX_ADU_LENGTH;
+
+fn encode_pdu(
+    function: u8,
+    body: &dyn Serialize,
+    buffer: &mut [u8],
+) -> Result<usize, RequestError> {
+    let mut cursor = WriteCursor::new(buffer);
+    cursor.write_u8(function)?;
+    body.serialize(&mut cursor)?;
+    let length = cursor.position();
+    if length > MAX_PDU_LENGTH {
+        return Err(crate::error::InternalError::FrameTooBig(length, MAX_PDU_LENGTH).into());
+    }
+    Ok(length)
+}
+
+fn function_of(request: &TypedRequest) -> FunctionCode {
+    match request {
+        TypedRequest::ReadCoils(_) => FunctionCode::ReadCoils,
+        TypedRequest::ReadDiscreteInputs(_) => FunctionCode::ReadDiscreteInputs,
+        TypedRequest::ReadHoldingRegisters(_) => FunctionCode::ReadHoldingRegisters,
+        TypedRequest::ReadInputRegisters(_) => FunctionCode::ReadInputRegisters,
+        TypedRequest::WriteSingleCoil(_) => FunctionCode::WriteSingleCoil,
+        TypedRequest::WriteSingleRegister(_) => FunctionCode::WriteSingleRegister,
+        TypedRequest::WriteMultipleCoils(_) => FunctionCode::WriteMultipleCoils,
+        TypedRequest::WriteMultipleRegisters(_) => FunctionCode::WriteMultipleRegisters,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AddressRange;
+
+    const READ_REGISTERS_RESPONSE: &[u8] = &[
+        0x00, 0x07, 0x00, 0x00, 0x00, 0x07, 0x01, 0x03, 0x04, 0x00, 0x2A, 0xCA, 0xFE,
+    ];
+
+    #[test]
+    fn parses_mbap_adu_and_reports_consumed_bytes() {
+        let mut bytes = READ_REGISTERS_RESPONSE.to_vec();
+        bytes.extend_from_slice(&[0x00, 0x08]);
+
+        let (adu, consumed) = parse_mbap(&bytes).unwrap().unwrap();
+        assert_eq!(consumed, READ_REGISTERS_RESPONSE.len());
+        assert_eq!(adu.tx_id, 7);
+        assert_eq!(adu.unit_id, UnitId::new(1));
+        assert_eq!(adu.pdu, &READ_REGISTERS_RESPONSE[7..]);
+
+        for len in 0..READ_REGISTERS_RESPONSE.len() {
+            assert_eq!(parse_mbap(&READ_REGISTERS_RESPONSE[..len]), Ok(None));
+        }
+        assert_eq!(
+            parse_mbap(&[0x00, 0x07, 0xCA, 0xFE, 0x00, 0x07, 0x01]),
+            Err(RequestError::BadFrame(FrameParseError::UnknownProtocolId(
+                0xCAFE
+            )))
+        );
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn parses_rtu_adu() {
+        let request = [0x01, 0x03, 0x00, 0x10, 0x00, 0x02, 0xC5, 0xCE];
+        let (adu, consumed) = parse_rtu_request(&request).unwrap().unwrap();
+        assert_eq!(consumed, request.len());
+        assert_eq!(adu.unit_id, UnitId::new(1));
+        assert_eq!(adu.pdu, &request[1..6]);
+        assert_eq!(parse_rtu_request(&request[..7]), Ok(None));
+        assert!(matches!(
+            parse_rtu_response(&request),
+            Err(RequestError::BadFrame(_)) | Ok(None)
+        ));
+    }
+
+    #[test]
+    fn parses_and_validates_responses() {
+        let read = TypedRequest::ReadHoldingRegisters(AddressRange::try_from(10, 2).unwrap());
+        assert_eq!(
+            parse_response(&read, &READ_REGISTERS_RESPONSE[7..]),
+            Ok(TypedResponse::Registers(vec![
+                Indexed::new(10, 0x2A),
+                Indexed::new(11, 0xCAFE)
+            ]))
+        );
+        assert_eq!(
+            parse_response(&read, &[0x83, 0x02]),
+            Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+        );
+        assert_eq!(
+            parse_response(&read, &[0x03, 0x02, 0x00, 0x2A]),
+            Err(AduParseError::ByteCountMismatch(4, 2).into())
+        );
+
+        let write = TypedRequest::WriteSingleCoil(Indexed::new(3, true));
+        assert_eq!(
+            parse_response(&write, &[0x05, 0x00, 0x03, 0xFF, 0x00]),
+            Ok(TypedResponse::SingleCoil(Indexed::new(3, true)))
+        );
+        assert_eq!(
+            parse_response(&write, &[0x05, 0x00, 0x04, 0xFF, 0x00]),
+            Err(AduParseError::ReplyAddressMismatch(3, 4).into())
+        );
+    }
+
+    #[test]
+    fn encoded_requests_and_responses_parse_back() {
+        let range = AddressRange::try_from(10, 2).unwrap();
+        let cases = [
+            (
+                TypedRequest::ReadCoils(range),
+                TypedResponse::Bits(vec![Indexed::new(10, true), Indexed::new(11, false)]),
+            ),
+            (
+                TypedRequest::ReadInputRegisters(range),
+                TypedResponse::Registers(vec![Indexed::new(10, 0x2A), Indexed::new(11, 0xCAFE)]),
+            ),
+            (
+                TypedRequest::WriteSingleCoil(Indexed::new(3, true)),
+                TypedResponse::SingleCoil(Indexed::new(3, true)),
+            ),
+            (
+                TypedRequest::WriteMultipleRegisters(
+                    WriteMultiple::from(10, vec![0x2A, 0xCAFE]).unwrap(),
+                ),
+                TypedResponse::Multiple(range),
+            ),
+        ];
+
+        let mut buffer = [0u8; MAX_PDU_LENGTH];
+        for (request, response) in cases {
+            let length = encode_request(&request, &mut buffer).unwrap();
+            assert_eq!(parse_request(&buffer[..length]), Ok(request.clone()));
+
+            let length = encode_response(&request, &response, &mut buffer).unwrap();
+            assert_eq!(parse_response(&request, &buffer[..length]), Ok(response));
+        }
+
+        let read = TypedRequest::ReadHoldingRegisters(range);
+        let length = encode_request(&read, &mut buffer).unwrap();
+        assert_eq!(&buffer[..length], &[0x03, 0x00, 0x0A, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn encodes_exceptions_and_rejects_inconsistent_pdus() {
+        let mut buffer = [0u8; MAX_PDU_LENGTH];
+        let length = encode_exception(
+            FunctionCode::ReadHoldingRegisters,
+            ExceptionCode::IllegalDataAddress,
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(&buffer[..length], &[0x83, 0x02]);
+
+        let read = TypedRequest::ReadCoils(AddressRange::try_from(0, 1).unwrap());
+        assert_eq!(
+            encode_response(&read, &TypedResponse::Registers(Vec::new()), &mut buffer),
+            Err(AduParseError::ReplyEchoMismatch.into())
+        );
+        let too_many = TypedRequest::ReadHoldingRegisters(AddressRange::try_from(0, 126).unwrap());
+        assert!(encode_request(&too_many, &mut buffer).is_err());
+        assert!(encode_request(&read, &mut buffer[..2]).is_err());
+
+        assert_eq!(
+            parse_request(&[0x11]),
+            Err(FrameParseError::UnknownFunctionCode(0x11).into())
+        );
+        assert!(parse_request(&[0x03, 0x00, 0x00, 0x00, 0x7E]).is_err());
+        assert!(parse_request(&[0x03, 0x00, 0x00, 0x00, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn never_panics_on_arbitrary_input() {
+        let requests = [
+            TypedRequest::ReadCoils(AddressRange::try_from(0, 9).unwrap()),
+            TypedRequest::ReadInputRegisters(AddressRange::try_from(0, 3).unwrap()),
+            TypedRequest::WriteSingleRegister(Indexed::new(1, 2)),
+        ];
+        // xorshift, so that every run covers the same inputs
+        let mut state: u32 = 0x1234_5678;
+        let mut bytes = [0u8; 300];
+        for _ in 0..10000 {
+            for byte in bytes.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                *byte = state as u8;
+            }
+            // make some of the inputs pass the first checks
+            bytes[2] = 0;
+            bytes[3] = 0;
+            let input = &bytes[..state as usize % bytes.len()];
+            let _ = parse_mbap(input);
+            let _ = parse_request(input);
+            #[cfg(feature = "serial")]
+            {
+                let _ = parse_rtu_request(input);
+                let _ = parse_rtu_response(input);
+            }
+            for request in &requests {
+                let _ = parse_response(request, input);
+            }
+        }
+    }
+}
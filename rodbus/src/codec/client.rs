@@ -0,0 +1,364 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::client::{TypedRequest, TypedResponse};
+use crate::codec::{encode_request, parse_mbap, parse_response, MAX_PDU_LENGTH};
+use crate::error::RequestError;
+use crate::types::UnitId;
+
+/// Framing used by a [`ClientMachine`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// MBAP header, as used on TCP and TLS connections
+    Mbap,
+    /// Unit identifier and CRC, as used on serial lines
+    #[cfg(feature = "serial")]
+    Rtu,
+}
+
+/// Identifier of a transaction submitted to a [`ClientMachine`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TransactionId(u64);
+
+/// Outcome of a transaction, returned by [`ClientMachine::poll_event`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// Transaction that completed
+    pub id: TransactionId,
+    /// Response, or the reason why there is none
+    pub result: Result<TypedResponse, RequestError>,
+}
+
+struct Pending {
+    id: TransactionId,
+    unit_id: UnitId,
+    request: TypedRequest,
+    timeout: Duration,
+}
+
+struct Outstanding {
+    pending: Pending,
+    tx_id: u16,
+    deadline: Option<Instant>,
+}
+
+/// Client transaction lifecycle without any I/O
+///
+/// The machine queues the submitted requests and performs them one at a time, validating the
+/// responses like a channel does. It is driven by the caller's event loop:
+///
+/// * [`ClientMachine::poll_transmit`] returns the next ADU to write to the transport, if any
+/// * [`ClientMachine::feed_bytes`] processes the bytes read from the transport
+/// * [`ClientMachine::poll_timeout`] tells when [`ClientMachine::handle_timeout`] must be called
+/// * [`ClientMachine::poll_event`] returns the completed transactions
+///
+/// Frames that don't match the outstanding request, e.g. late responses to a request that timed
+/// out, are discarded. When the framing is broken, the received bytes are discarded and
+/// the outstanding request fails, so the caller should then reset the connection and call
+/// [`ClientMachine::disconnected`].
+pub struct ClientMachine {
+    framing: Framing,
+    next_id: u64,
+    next_tx_id: u16,
+    queue: VecDeque<Pending>,
+    outstanding: Option<Outstanding>,
+    received: Vec<u8>,
+    events: VecDeque<Completion>,
+}
+
+impl ClientMachine {
+    /// Create a machine with an empty queue
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            next_id: 0,
+            next_tx_id: 0,
+            queue: VecDeque::new(),
+            outstanding: None,
+            received: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Queue a request to a unit, which fails with [`RequestError::ResponseTimeout`] if no
+    /// response is received within `timeout` of its transmission
+    ///
+    /// Requests that cannot be encoded are rejected immediately.
+    pub fn submit(
+        &mut self,
+        unit_id: UnitId,
+        request: TypedRequest,
+        timeout: Duration,
+    ) -> Result<TransactionId, RequestError> {
+        encode_request(&request, &mut [0; MAX_PDU_LENGTH])?;
+        let id = TransactionId(self.next_id);
+        self.next_id += 1;
+        self.queue.push_back(Pending {
+            id,
+            unit_id,
+            request,
+            timeout,
+        });
+        Ok(id)
+    }
+
+    /// ADU of the next request to send, if no request is outstanding
+    ///
+    /// The response timeout of the request starts at `now`.
+    pub fn poll_transmit(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self.outstanding.is_some() {
+            return None;
+        }
+        let pending = self.queue.pop_front()?;
+
+        let mut pdu = [0; MAX_PDU_LENGTH];
+        let length = match encode_request(&pending.request, &mut pdu) {
+            Ok(x) => x,
+            // validated when submitted
+            Err(err) => {
+                self.complete(pending.id, Err(err));
+                return None;
+            }
+        };
+        let pdu = &pdu[..length];
+
+        let tx_id = self.next_tx_id;
+        self.next_tx_id = self.next_tx_id.wrapping_add(1);
+        let adu = match self.framing {
+            Framing::Mbap => {
+                let mut adu = Vec::with_capacity(7 + pdu.len());
+                adu.extend_from_slice(&tx_id.to_be_bytes());
+                adu.extend_from_slice(&[0, 0]);
+                // the length field includes the unit identifier
+                adu.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+                adu.push(pending.unit_id.value);
+                adu.extend_from_slice(pdu);
+                adu
+            }
+            #[cfg(feature = "serial")]
+            Framing::Rtu => {
+                let mut adu = Vec::with_capacity(3 + pdu.len());
+                adu.push(pending.unit_id.value);
+                adu.extend_from_slice(pdu);
+                let crc = crate::serial::frame::CRC.checksum(&adu);
+                adu.extend_from_slice(&crc.to_le_bytes());
+                adu
+            }
+        };
+
+        self.received.clear();
+        self.outstanding = Some(Outstanding {
+            deadline: now.checked_add(pending.timeout),
+            pending,
+            tx_id,
+        });
+        Some(adu)
+    }
+
+    /// Process bytes received from the transport, which may contain partial or several frames
+    pub fn feed_bytes(&mut self, bytes: &[u8]) {
+        self.received.extend_from_slice(bytes);
+        loop {
+            let parsed = match self.framing {
+                Framing::Mbap => parse_mbap(&self.received)
+                    .map(|x| x.map(|(adu, len)| (Some(adu.tx_id), adu.unit_id, adu.pdu, len))),
+                #[cfg(feature = "serial")]
+                Framing::Rtu => crate::codec::parse_rtu_response(&self.received)
+                    .map(|x| x.map(|(adu, len)| (None, adu.unit_id, adu.pdu, len))),
+            };
+            match parsed {
+                Ok(None) => return,
+                Ok(Some((tx_id, unit_id, pdu, consumed))) => {
+                    let completion = self.outstanding.as_ref().and_then(|outstanding| {
+                        let expected = tx_id.map(|x| x == outstanding.tx_id).unwrap_or(true)
+                            && unit_id == outstanding.pending.unit_id;
+                        expected.then(|| parse_response(&outstanding.pending.request, pdu))
+                    });
+                    if completion.is_none() {
+                        tracing::warn!(
+                            "discarding a frame that doesn't match the outstanding request"
+                        );
+                    }
+                    self.received.drain(..consumed);
+                    if let Some(result) = completion {
+                        self.finish(result);
+                    }
+                }
+                Err(err) => {
+                    self.received.clear();
+                    self.finish(Err(err));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Time at which [`ClientMachine::handle_timeout`] must be called, if a request is outstanding
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.outstanding.as_ref().and_then(|x| x.deadline)
+    }
+
+    /// Fail the outstanding request if its response timeout has elapsed
+    pub fn handle_timeout(&mut self, now: Instant) {
+        if let Some(deadline) = self.poll_timeout() {
+            if now >= deadline {
+                self.received.clear();
+                self.finish(Err(RequestError::ResponseTimeout));
+            }
+        }
+    }
+
+    /// Inform the machine that the transport was closed
+    ///
+    /// The outstanding request fails with [`RequestError::NoConnection`]. The queued requests are
+    /// transmitted once the transport is available again.
+    pub fn disconnected(&mut self) {
+        self.received.clear();
+        self.finish(Err(RequestError::NoConnection));
+    }
+
+    /// Next completed transaction
+    pub fn poll_event(&mut self) -> Option<Completion> {
+        self.events.pop_front()
+    }
+
+    fn finish(&mut self, result: Result<TypedResponse, RequestError>) {
+        if let Some(outstanding) = self.outstanding.take() {
+            self.complete(outstanding.pending.id, result);
+        }
+    }
+
+    fn complete(&mut self, id: TransactionId, result: Result<TypedResponse, RequestError>) {
+        self.events.push_back(Completion { id, result });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exception::ExceptionCode;
+    use crate::types::{AddressRange, Indexed};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn read(start: u16) -> TypedRequest {
+        TypedRequest::ReadHoldingRegisters(AddressRange::try_from(start, 1).unwrap())
+    }
+
+    #[test]
+    fn performs_the_queued_requests_one_at_a_time() {
+        let mut machine = ClientMachine::new(Framing::Mbap);
+        let now = Instant::now();
+        let first = machine.submit(UnitId::new(1), read(7), TIMEOUT).unwrap();
+        let second = machine.submit(UnitId::new(1), read(8), TIMEOUT).unwrap();
+
+        assert_eq!(
+            machine.poll_transmit(now).unwrap(),
+            [0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x07, 0x00, 0x01]
+        );
+        assert_eq!(machine.poll_transmit(now), None);
+        assert_eq!(machine.poll_timeout(), Some(now + TIMEOUT));
+
+        // a response with another transaction id, then the response in two parts
+        machine.feed_bytes(&[
+            0x00, 0x05, 0x00, 0x00, 0x00, 0x05, 0x01, 0x03, 0x02, 0x00, 0x01,
+        ]);
+        machine.feed_bytes(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x01]);
+        assert_eq!(machine.poll_event(), None);
+        machine.feed_bytes(&[0x03, 0x02, 0xCA, 0xFE]);
+        assert_eq!(
+            machine.poll_event(),
+            Some(Completion {
+                id: first,
+                result: Ok(TypedResponse::Registers(vec![Indexed::new(7, 0xCAFE)])),
+            })
+        );
+
+        let request = machine.poll_transmit(now).unwrap();
+        assert_eq!(&request[..2], &[0x00, 0x01]);
+        machine.feed_bytes(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x01, 0x83, 0x02]);
+        assert_eq!(
+            machine.poll_event(),
+            Some(Completion {
+                id: second,
+                result: Err(RequestError::Exception(ExceptionCode::IllegalDataAddress)),
+            })
+        );
+        assert_eq!(machine.poll_timeout(), None);
+    }
+
+    #[test]
+    fn fails_the_outstanding_request_on_timeout_and_disconnection() {
+        let mut machine = ClientMachine::new(Framing::Mbap);
+        let now = Instant::now();
+        let first = machine.submit(UnitId::new(1), read(0), TIMEOUT).unwrap();
+        let second = machine.submit(UnitId::new(1), read(0), TIMEOUT).unwrap();
+
+        machine.poll_transmit(now).unwrap();
+        machine.handle_timeout(now + TIMEOUT / 2);
+        assert_eq!(machine.poll_event(), None);
+        machine.handle_timeout(now + TIMEOUT);
+        assert_eq!(
+            machine.poll_event(),
+            Some(Completion {
+                id: first,
+                result: Err(RequestError::ResponseTimeout),
+            })
+        );
+
+        machine.poll_transmit(now).unwrap();
+        machine.disconnected();
+        assert_eq!(
+            machine.poll_event(),
+            Some(Completion {
+                id: second,
+                result: Err(RequestError::NoConnection),
+            })
+        );
+
+        let too_many = TypedRequest::ReadHoldingRegisters(AddressRange::try_from(0, 126).unwrap());
+        assert!(machine.submit(UnitId::new(1), too_many, TIMEOUT).is_err());
+        assert_eq!(machine.poll_transmit(now), None);
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn frames_requests_for_serial_lines() {
+        let mut machine = ClientMachine::new(Framing::Rtu);
+        let now = Instant::now();
+        let id = machine
+            .submit(
+                UnitId::new(1),
+                TypedRequest::ReadHoldingRegisters(AddressRange::try_from(0x10, 2).unwrap()),
+                TIMEOUT,
+            )
+            .unwrap();
+        assert_eq!(
+            machine.poll_transmit(now).unwrap(),
+            [0x01, 0x03, 0x00, 0x10, 0x00, 0x02, 0xC5, 0xCE]
+        );
+
+        // a response from another unit is ignored
+        let mut response = vec![0x02, 0x03, 0x04, 0x00, 0x01, 0x00, 0x02];
+        let crc = crate::serial::frame::CRC.checksum(&response);
+        response.extend_from_slice(&crc.to_le_bytes());
+        machine.feed_bytes(&response);
+        assert_eq!(machine.poll_event(), None);
+
+        response[0] = 0x01;
+        response.truncate(7);
+        let crc = crate::serial::frame::CRC.checksum(&response);
+        response.extend_from_slice(&crc.to_le_bytes());
+        machine.feed_bytes(&response);
+        assert_eq!(
+            machine.poll_event(),
+            Some(Completion {
+                id,
+                result: Ok(TypedResponse::Registers(vec![
+                    Indexed::new(0x10, 1),
+                    Indexed::new(0x11, 2)
+                ])),
+            })
+        );
+    }
+}
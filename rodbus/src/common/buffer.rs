@@ -1,66 +1,87 @@
+use bytes::{Buf, Bytes, BytesMut};
+
 use crate::common::phys::PhysLayer;
 
 use crate::error::InternalError;
 use crate::PhysDecodeLevel;
 
+/// Bytes received from the physical layer that have not been parsed yet
+///
+/// The payloads of the frames are split off this buffer without copying them. Their memory is
+/// reclaimed by subsequent reads once the frames have been dropped.
 pub(crate) struct ReadBuffer {
-    buffer: [u8; crate::common::frame::constants::MAX_FRAME_LENGTH],
-    begin: usize,
-    end: usize,
+    buffer: BytesMut,
+    capacity: usize,
 }
 
 impl ReadBuffer {
     pub(crate) fn new() -> Self {
+        Self::with_capacity(crate::common::frame::constants::MAX_FRAME_LENGTH)
+    }
+
+    /// Create a buffer that reads up to `capacity` bytes at once, which is never less than the
+    /// size of the largest frame
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(crate::common::frame::constants::MAX_FRAME_LENGTH);
+        ReadBuffer {
+            buffer: BytesMut::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Change the capacity of the buffer, preserving the bytes that have not been read yet
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        let capacity = capacity
+            .max(crate::common::frame::constants::MAX_FRAME_LENGTH)
+            .max(self.len());
+        if capacity < self.buffer.capacity() {
+            // the memory of a BytesMut never shrinks, so release it by moving to a new allocation
+            let mut buffer = BytesMut::with_capacity(capacity);
+            buffer.extend_from_slice(&self.buffer);
+            self.buffer = buffer;
+        }
+        self.capacity = capacity;
+    }
+
+    /// Create a buffer holding the beginning of `bytes`, up to the size of the largest frame
+    pub(crate) fn from_slice(bytes: &[u8]) -> Self {
+        let count = bytes
+            .len()
+            .min(crate::common::frame::constants::MAX_FRAME_LENGTH);
         ReadBuffer {
-            buffer: [0; crate::common::frame::constants::MAX_FRAME_LENGTH],
-            begin: 0,
-            end: 0,
+            buffer: BytesMut::from(&bytes[..count]),
+            capacity: crate::common::frame::constants::MAX_FRAME_LENGTH,
         }
     }
 
     pub(crate) fn len(&self) -> usize {
-        self.end - self.begin
+        self.buffer.len()
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.begin == self.end
+        self.buffer.is_empty()
     }
 
-    pub(crate) fn read(&mut self, count: usize) -> Result<&[u8], InternalError> {
+    pub(crate) fn read(&mut self, count: usize) -> Result<Bytes, InternalError> {
         if self.len() < count {
             return Err(InternalError::InsufficientBytesForRead(count, self.len()));
         }
 
-        match self.buffer.get(self.begin..(self.begin + count)) {
-            Some(ret) => {
-                self.begin += count;
-                Ok(ret)
-            }
-            None => Err(InternalError::InsufficientBytesForRead(count, self.len())),
-        }
+        Ok(self.buffer.split_to(count).freeze())
     }
 
     pub(crate) fn read_u8(&mut self) -> Result<u8, InternalError> {
         if self.is_empty() {
             return Err(InternalError::InsufficientBytesForRead(1, 0));
         }
-        match self.buffer.get(self.begin) {
-            Some(ret) => {
-                self.begin += 1;
-                Ok(*ret)
-            }
-            None => Err(InternalError::InsufficientBytesForRead(1, 0)),
-        }
+        Ok(self.buffer.get_u8())
     }
 
     #[cfg(feature = "serial")]
     pub(crate) fn peek_at(&mut self, idx: usize) -> Result<u8, InternalError> {
         let len = self.len();
-        if len < idx {
-            return Err(InternalError::InsufficientBytesForRead(idx + 1, len));
-        }
         self.buffer
-            .get(self.begin + idx)
+            .get(idx)
             .copied()
             .ok_or(InternalError::InsufficientBytesForRead(idx + 1, len))
     }
@@ -83,27 +104,16 @@ impl ReadBuffer {
         io: &mut PhysLayer,
         decode_level: PhysDecodeLevel,
     ) -> Result<usize, std::io::Error> {
-        // before we read any data, check to see if the buffer is empty and adjust the indices
-        // this allows use to make the biggest read possible, and avoids subsequent buffer shifting later
-        if self.is_empty() {
-            self.begin = 0;
-            self.end = 0;
-        }
-
-        // if we've reached capacity, but still need more data we have to shift
-        if self.end == self.len() {
-            let length = self.len();
-            self.buffer.copy_within(self.begin..self.end, 0);
-            self.begin = 0;
-            self.end = length;
-        }
+        // make room for the largest frame, which only allocates if previous frames are still in use
+        let remaining =
+            crate::common::frame::constants::MAX_FRAME_LENGTH.saturating_sub(self.len());
+        self.buffer.reserve(remaining);
 
-        let count = io.read(&mut self.buffer[self.end..], decode_level).await?;
+        let count = io.read(&mut self.buffer, decode_level).await?;
 
         if count == 0 {
             return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
         }
-        self.end += count;
         Ok(count)
     }
 }
@@ -135,8 +145,7 @@ mod tests {
         let mut phys = PhysLayer::new_mock(io);
 
         {
-            let mut task =
-                tokio_test::task::spawn(buffer.read_some(&mut phys, PhysDecodeLevel::Nothing));
+            let mut task = task::spawn(buffer.read_some(&mut phys, PhysDecodeLevel::Nothing));
             tokio_test::assert_pending!(task.poll());
         }
 
@@ -151,7 +160,7 @@ mod tests {
             assert_ready_eq!(task.poll(), 3);
         }
 
-        assert_eq!(buffer.read(2).unwrap(), &[0x01, 0x02]);
+        assert_eq!(buffer.read(2).unwrap()[..], [0x01, 0x02]);
 
         {
             let mut task = task::spawn(async {
@@ -164,6 +173,45 @@ mod tests {
             assert_ready_eq!(task.poll(), 2);
         }
 
-        assert_eq!(buffer.read(3).unwrap(), &[0x03, 0x04, 0x05]);
+        assert_eq!(buffer.read(3).unwrap()[..], [0x03, 0x04, 0x05]);
+    }
+
+    #[test]
+    fn payloads_remain_valid_while_buffer_is_refilled() {
+        let mut buffer = ReadBuffer::new();
+
+        let (io, mut io_handle) = sfio_tokio_mock_io::mock();
+        let mut phys = PhysLayer::new_mock(io);
+
+        let data: Vec<u8> = (0..=255).collect();
+        let mut payloads = Vec::new();
+        for chunk in data.chunks(100) {
+            let mut task = task::spawn(async {
+                buffer
+                    .read_some(&mut phys, PhysDecodeLevel::Nothing)
+                    .await
+                    .unwrap()
+            });
+            io_handle.read(chunk);
+            assert_ready_eq!(task.poll(), chunk.len());
+            drop(task);
+            payloads.push(buffer.read(chunk.len()).unwrap());
+        }
+
+        let received: Vec<u8> = payloads.iter().flat_map(|x| x.iter().copied()).collect();
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn capacity_is_at_least_the_largest_frame_and_preserves_data() {
+        let max = crate::common::frame::constants::MAX_FRAME_LENGTH;
+        assert_eq!(ReadBuffer::with_capacity(16).capacity, max);
+
+        let mut buffer = ReadBuffer::from_slice(&[0x01, 0x02, 0x03]);
+        buffer.set_capacity(4 * max);
+        assert_eq!(buffer.capacity, 4 * max);
+        buffer.set_capacity(0);
+        assert_eq!(buffer.capacity, max);
+        assert_eq!(buffer.read(3).unwrap()[..], [0x01, 0x02, 0x03]);
     }
 }
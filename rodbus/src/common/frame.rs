@@ -4,11 +4,12 @@ use std::ops::Range;
 use crate::common::buffer::ReadBuffer;
 use crate::common::function::FunctionCode;
 use crate::common::traits::{Loggable, LoggableDisplay, Serialize};
-use crate::error::RequestError;
+use crate::error::{InternalError, RequestError};
 use crate::tcp::frame::{MbapDisplay, MbapHeader, MbapParser};
 use crate::types::UnitId;
 use crate::{DecodeLevel, ExceptionCode, FrameDecodeLevel};
 
+use bytes::Bytes;
 use scursor::WriteCursor;
 
 pub(crate) mod constants {
@@ -20,7 +21,17 @@ pub(crate) mod constants {
         }
     }
 
-    pub(crate) const MAX_ADU_LENGTH: usize = 253;
+    /// the default limit on the length of a PDU
+    pub(crate) const MAX_ADU_LENGTH: usize = crate::constants::limits::MAX_PDU_LENGTH;
+
+    /// the largest limit that can be configured, which sizes the buffers
+    pub(crate) const MAX_CONFIGURABLE_ADU_LENGTH: usize =
+        crate::constants::limits::MAX_CONFIGURABLE_PDU_LENGTH;
+
+    /// a limit on the PDU length can't be smaller than a function code and an exception code
+    pub(crate) fn clamp_adu_length(length: usize) -> usize {
+        length.clamp(2, MAX_CONFIGURABLE_ADU_LENGTH)
+    }
 
     #[cfg(feature = "serial")]
     const fn serial_frame_size() -> usize {
@@ -142,31 +153,29 @@ impl FrameHeader {
 
 pub(crate) struct Frame {
     pub(crate) header: FrameHeader,
-    length: usize,
-    pdu: [u8; constants::MAX_ADU_LENGTH],
+    // shares the memory of the read buffer it was parsed from
+    pdu: Bytes,
 }
 
 impl Frame {
     pub(crate) fn new(header: FrameHeader) -> Frame {
         Frame {
             header,
-            length: 0,
-            pdu: [0; constants::MAX_ADU_LENGTH],
+            pdu: Bytes::new(),
         }
     }
 
-    pub(crate) fn set(&mut self, src: &[u8]) -> bool {
-        if src.len() > self.pdu.len() {
+    pub(crate) fn set(&mut self, pdu: Bytes) -> bool {
+        if pdu.len() > constants::MAX_CONFIGURABLE_ADU_LENGTH {
             return false;
         }
 
-        self.pdu[0..src.len()].copy_from_slice(src);
-        self.length = src.len();
+        self.pdu = pdu;
         true
     }
 
     pub(crate) fn payload(&self) -> &[u8] {
-        &self.pdu[0..self.length]
+        &self.pdu
     }
 }
 
@@ -204,6 +213,14 @@ impl FrameParser {
             FrameParser::Tcp(x) => x.reset(),
         }
     }
+
+    pub(crate) fn set_max_pdu_length(&mut self, length: usize) {
+        match self {
+            #[cfg(feature = "serial")]
+            FrameParser::Rtu(x) => x.set_max_pdu_length(length),
+            FrameParser::Tcp(x) => x.set_max_pdu_length(length),
+        }
+    }
 }
 
 pub(crate) enum FrameType {
@@ -251,8 +268,22 @@ impl FormatType {
     }
 }
 
+/// Request formatted by a [FrameWriter]
+pub(crate) struct FormattedRequest<'a> {
+    /// Complete frame including the transport-specific header and trailer
+    pub(crate) frame: &'a [u8],
+    /// PDU contained in the frame (function code and body)
+    pub(crate) pdu: &'a [u8],
+}
+
+struct FormattedRanges {
+    frame: Range<usize>,
+    pdu: Range<usize>,
+}
+
 pub(crate) struct FrameWriter {
     format_type: FormatType,
+    max_pdu_length: usize,
     buffer: [u8; constants::MAX_FRAME_LENGTH],
 }
 
@@ -298,10 +329,15 @@ impl FrameWriter {
     fn new(format_type: FormatType) -> Self {
         Self {
             format_type,
+            max_pdu_length: constants::MAX_ADU_LENGTH,
             buffer: [0; constants::MAX_FRAME_LENGTH],
         }
     }
 
+    pub(crate) fn set_max_pdu_length(&mut self, length: usize) {
+        self.max_pdu_length = constants::clamp_adu_length(length);
+    }
+
     pub(crate) fn format_reply<T>(
         &mut self,
         header: FrameHeader,
@@ -313,7 +349,7 @@ impl FrameWriter {
         T: Serialize + Loggable,
     {
         match self.format_generic(header, FunctionField::Valid(function), body, decode_level) {
-            Ok(x) => Ok(&self.buffer[x]),
+            Ok(x) => Ok(&self.buffer[x.frame]),
             Err(RequestError::Exception(ex)) => {
                 self.format_ex(header, FunctionField::Exception(function), ex, decode_level)
             }
@@ -327,13 +363,16 @@ impl FrameWriter {
         function: FunctionCode,
         body: &T,
         decode_level: DecodeLevel,
-    ) -> Result<&[u8], RequestError>
+    ) -> Result<FormattedRequest<'_>, RequestError>
     where
         T: Serialize + Loggable,
     {
-        let range =
+        let ranges =
             self.format_generic(header, FunctionField::Valid(function), body, decode_level)?;
-        Ok(&self.buffer[range])
+        Ok(FormattedRequest {
+            frame: &self.buffer[ranges.frame],
+            pdu: &self.buffer[ranges.pdu],
+        })
     }
 
     pub(crate) fn format_ex(
@@ -349,9 +388,9 @@ impl FrameWriter {
             FunctionField::UnknownFunction(x) => FunctionField::UnknownFunction(x),
         };
 
-        let range = self.format_generic(header, function, &ex, decode_level)?;
+        let ranges = self.format_generic(header, function, &ex, decode_level)?;
 
-        Ok(&self.buffer[range])
+        Ok(&self.buffer[ranges.frame])
     }
 
     fn format_generic<T>(
@@ -360,19 +399,30 @@ impl FrameWriter {
         function: FunctionField,
         body: &T,
         decode_level: DecodeLevel,
-    ) -> Result<Range<usize>, RequestError>
+    ) -> Result<FormattedRanges, RequestError>
     where
         T: Serialize + Loggable,
     {
-        let (frame_type, frame_bytes, pdu_body) = {
+        let (frame_type, frame_bytes, pdu_range, pdu_body) = {
             let mut cursor = WriteCursor::new(self.buffer.as_mut());
             let info = self
                 .format_type
                 .format(&mut cursor, header, function, body)?;
             let end = cursor.position();
-            (info.frame_type, 0..end, &self.buffer[info.pdu_body])
+            // the PDU begins with the function code that precedes the body
+            let pdu_range = (info.pdu_body.start - 1)..info.pdu_body.end;
+            (
+                info.frame_type,
+                0..end,
+                pdu_range,
+                &self.buffer[info.pdu_body],
+            )
         };
 
+        if pdu_range.len() > self.max_pdu_length {
+            return Err(InternalError::FrameTooBig(pdu_range.len(), self.max_pdu_length).into());
+        }
+
         if decode_level.app.enabled() {
             tracing::info!(
                 "PDU TX - {} {}",
@@ -405,13 +455,20 @@ impl FrameWriter {
             }
         }
 
-        Ok(frame_bytes)
+        Ok(FormattedRanges {
+            frame: frame_bytes,
+            pdu: pdu_range,
+        })
     }
 
     pub(crate) fn tcp() -> Self {
         Self::new(FormatType::Tcp)
     }
 
+    pub(crate) fn is_tcp(&self) -> bool {
+        matches!(self.format_type, FormatType::Tcp)
+    }
+
     #[cfg(feature = "serial")]
     pub(crate) fn rtu() -> Self {
         Self::new(FormatType::Rtu)
@@ -449,6 +506,15 @@ impl FramedReader {
         }
     }
 
+    pub(crate) fn set_buffer_capacity(&mut self, capacity: usize) {
+        self.buffer.set_capacity(capacity);
+    }
+
+    pub(crate) fn set_max_pdu_length(&mut self, length: usize) {
+        self.parser
+            .set_max_pdu_length(constants::clamp_adu_length(length));
+    }
+
     pub(crate) async fn next_frame(
         &mut self,
         io: &mut PhysLayer,
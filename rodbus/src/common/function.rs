@@ -11,19 +11,56 @@ mod constants {
     pub(crate) const WRITE_MULTIPLE_REGISTERS: u8 = 16;
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// Function codes supported by the library
+///
+/// Use `u8::from` and `FunctionCode::try_from` to convert to and from the value on the wire.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
-pub(crate) enum FunctionCode {
+pub enum FunctionCode {
+    /// Read coils (0x01)
     ReadCoils = constants::READ_COILS,
+    /// Read discrete inputs (0x02)
     ReadDiscreteInputs = constants::READ_DISCRETE_INPUTS,
+    /// Read holding registers (0x03)
     ReadHoldingRegisters = constants::READ_HOLDING_REGISTERS,
+    /// Read input registers (0x04)
     ReadInputRegisters = constants::READ_INPUT_REGISTERS,
+    /// Write single coil (0x05)
     WriteSingleCoil = constants::WRITE_SINGLE_COIL,
+    /// Write single register (0x06)
     WriteSingleRegister = constants::WRITE_SINGLE_REGISTER,
+    /// Write multiple coils (0x0F)
     WriteMultipleCoils = constants::WRITE_MULTIPLE_COILS,
+    /// Write multiple registers (0x10)
     WriteMultipleRegisters = constants::WRITE_MULTIPLE_REGISTERS,
 }
 
+/// The value is not one of the [`FunctionCode`] supported by the library
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownFunctionCode(pub u8);
+
+impl std::error::Error for UnknownFunctionCode {}
+
+impl Display for UnknownFunctionCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown function code: {:#04X}", self.0)
+    }
+}
+
+impl From<FunctionCode> for u8 {
+    fn from(x: FunctionCode) -> Self {
+        x.get_value()
+    }
+}
+
+impl TryFrom<u8> for FunctionCode {
+    type Error = UnknownFunctionCode;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::get(value).ok_or(UnknownFunctionCode(value))
+    }
+}
+
 impl Display for FunctionCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
@@ -76,3 +113,23 @@ impl FunctionCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_and_from_u8() {
+        for value in 0..=u8::MAX {
+            match FunctionCode::try_from(value) {
+                Ok(code) => assert_eq!(u8::from(code), value),
+                Err(err) => assert_eq!(err, UnknownFunctionCode(value)),
+            }
+        }
+        assert_eq!(
+            FunctionCode::try_from(0x10),
+            Ok(FunctionCode::WriteMultipleRegisters)
+        );
+        assert_eq!(FunctionCode::try_from(0x11), Err(UnknownFunctionCode(0x11)));
+    }
+}
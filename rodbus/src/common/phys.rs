@@ -6,18 +6,26 @@ pub(crate) struct PhysLayer {
     layer: PhysLayerImpl,
 }
 
+#[cfg(feature = "serial")]
+pub(crate) struct SerialLayer {
+    stream: tokio_serial::SerialStream,
+    inter_character_delay: tokio::time::Duration,
+    last_activity: Option<tokio::time::Instant>,
+    // driver enable control and the time to transmit a single character
+    driver_enable: Option<(crate::serial::DriverEnable, tokio::time::Duration)>,
+    echo: Option<crate::serial::echo::EchoFilter>,
+}
+
 // encapsulates all possible physical layers as an enum
 pub(crate) enum PhysLayerImpl {
     Tcp(tokio::net::TcpStream),
     #[cfg(feature = "serial")]
-    Serial(
-        tokio_serial::SerialStream,
-        tokio::time::Duration,
-        Option<tokio::time::Instant>,
-    ),
+    Serial(SerialLayer),
     // TLS type is boxed because its size is huge
     #[cfg(feature = "tls")]
     Tls(Box<tokio_rustls::TlsStream<tokio::net::TcpStream>>),
+    // user-supplied stream, e.g. a WebSocket bridge
+    Stream(Box<dyn crate::client::ClientStream>),
     #[cfg(test)]
     Mock(sfio_tokio_mock_io::Mock),
 }
@@ -27,9 +35,10 @@ impl std::fmt::Debug for PhysLayer {
         match &self.layer {
             PhysLayerImpl::Tcp(_) => f.write_str("Tcp"),
             #[cfg(feature = "serial")]
-            PhysLayerImpl::Serial(_, _, _) => f.write_str("Serial"),
+            PhysLayerImpl::Serial(..) => f.write_str("Serial"),
             #[cfg(feature = "tls")]
             PhysLayerImpl::Tls(_) => f.write_str("Tls"),
+            PhysLayerImpl::Stream(_) => f.write_str("Stream"),
             #[cfg(test)]
             PhysLayerImpl::Mock(_) => f.write_str("Mock"),
         }
@@ -44,10 +53,32 @@ impl PhysLayer {
     }
 
     #[cfg(feature = "serial")]
-    pub(crate) fn new_serial(stream: tokio_serial::SerialStream) -> Self {
-        let calculate_inter_character_delay = calculate_inter_character_delay(&stream);
+    pub(crate) fn new_serial(
+        stream: tokio_serial::SerialStream,
+        settings: &crate::serial::SerialSettings,
+    ) -> Self {
+        let mut layer = SerialLayer {
+            stream,
+            inter_character_delay: tokio::time::Duration::ZERO,
+            last_activity: None,
+            driver_enable: None,
+            echo: None,
+        };
+        layer.configure(settings);
         Self {
-            layer: PhysLayerImpl::Serial(stream, calculate_inter_character_delay, None),
+            layer: PhysLayerImpl::Serial(layer),
+        }
+    }
+
+    /// Apply new settings to an open serial port, other layers are left untouched
+    #[cfg(feature = "serial")]
+    pub(crate) fn reconfigure_serial(
+        &mut self,
+        settings: &crate::serial::SerialSettings,
+    ) -> Result<(), std::io::Error> {
+        match &mut self.layer {
+            PhysLayerImpl::Serial(x) => x.reconfigure(settings),
+            _ => Ok(()),
         }
     }
 
@@ -58,6 +89,12 @@ impl PhysLayer {
         }
     }
 
+    pub(crate) fn new_stream(stream: Box<dyn crate::client::ClientStream>) -> Self {
+        Self {
+            layer: PhysLayerImpl::Stream(stream),
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn new_mock(mock: sfio_tokio_mock_io::Mock) -> Self {
         Self {
@@ -67,21 +104,24 @@ impl PhysLayer {
 
     pub(crate) async fn read(
         &mut self,
-        buffer: &mut [u8],
+        buffer: &mut bytes::BytesMut,
         decode_level: PhysDecodeLevel,
     ) -> Result<usize, std::io::Error> {
+        // appending to the buffer is cancel safe, nothing is added unless bytes were read
+        let start = buffer.len();
         let length = match &mut self.layer {
-            PhysLayerImpl::Tcp(x) => x.read(buffer).await?,
+            PhysLayerImpl::Tcp(x) => x.read_buf(buffer).await?,
             #[cfg(feature = "serial")]
-            PhysLayerImpl::Serial(x, _, _) => x.read(buffer).await?,
+            PhysLayerImpl::Serial(x) => x.read(buffer).await?,
             #[cfg(feature = "tls")]
-            PhysLayerImpl::Tls(x) => x.read(buffer).await?,
+            PhysLayerImpl::Tls(x) => x.read_buf(buffer).await?,
+            PhysLayerImpl::Stream(x) => x.read_buf(buffer).await?,
             #[cfg(test)]
-            PhysLayerImpl::Mock(x) => x.read(buffer).await?,
+            PhysLayerImpl::Mock(x) => x.read_buf(buffer).await?,
         };
 
         if decode_level.enabled() {
-            if let Some(x) = buffer.get(0..length) {
+            if let Some(x) = buffer.get(start..start + length) {
                 tracing::info!("PHYS RX - {}", PhysDisplay::new(decode_level, x))
             }
         }
@@ -101,23 +141,84 @@ impl PhysLayer {
         match &mut self.layer {
             PhysLayerImpl::Tcp(x) => x.write_all(data).await,
             #[cfg(feature = "serial")]
-            PhysLayerImpl::Serial(x, inter_char_delay, last_activity) => {
-                // Respect inter-character delay
-                if let Some(last_activity) = last_activity {
-                    tokio::time::sleep_until(*last_activity + *inter_char_delay).await;
-                }
-                *last_activity = Some(tokio::time::Instant::now());
-
-                x.write_all(data).await
-            }
+            PhysLayerImpl::Serial(x) => x.write(data).await,
             #[cfg(feature = "tls")]
             PhysLayerImpl::Tls(x) => x.write_all(data).await,
+            PhysLayerImpl::Stream(x) => x.write_all(data).await,
             #[cfg(test)]
             PhysLayerImpl::Mock(x) => x.write_all(data).await,
         }
     }
 }
 
+#[cfg(feature = "serial")]
+impl SerialLayer {
+    /// Derive the timing and the filtering from the settings and the actual port configuration
+    fn configure(&mut self, settings: &crate::serial::SerialSettings) {
+        let stream = &self.stream;
+        self.inter_character_delay = calculate_inter_character_delay(stream);
+        self.driver_enable = settings.driver_enable.map(|config| {
+            let character_time = crate::serial::driver::character_time(stream).unwrap_or_else(|| {
+                tracing::warn!("unable to determine the character time, the driver will be disabled as soon as the data is written");
+                tokio::time::Duration::ZERO
+            });
+            (config, character_time)
+        });
+        self.echo = settings
+            .suppress_echo
+            .then(crate::serial::echo::EchoFilter::default);
+    }
+
+    fn reconfigure(
+        &mut self,
+        settings: &crate::serial::SerialSettings,
+    ) -> Result<(), std::io::Error> {
+        settings.reconfigure(&mut self.stream)?;
+        self.configure(settings);
+        Ok(())
+    }
+
+    async fn read(&mut self, buffer: &mut bytes::BytesMut) -> Result<usize, std::io::Error> {
+        let echo = match &mut self.echo {
+            Some(echo) => echo,
+            None => return self.stream.read_buf(buffer).await,
+        };
+
+        // keep reading until something other than the echo is received, as 0 means end of stream
+        let start = buffer.len();
+        let mut chunk = [0; 256];
+        loop {
+            let count = self.stream.read(&mut chunk).await?;
+            if count == 0 {
+                return Ok(0);
+            }
+            echo.received(&chunk[..count], buffer);
+            if buffer.len() > start {
+                return Ok(buffer.len() - start);
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
+        // Respect inter-character delay
+        if let Some(last_activity) = self.last_activity {
+            tokio::time::sleep_until(last_activity + self.inter_character_delay).await;
+        }
+        self.last_activity = Some(tokio::time::Instant::now());
+
+        if let Some(echo) = &mut self.echo {
+            echo.transmitted(data);
+        }
+
+        match &self.driver_enable {
+            Some((config, character_time)) => {
+                config.write(&mut self.stream, *character_time, data).await
+            }
+            None => self.stream.write_all(data).await,
+        }
+    }
+}
+
 pub(crate) struct PhysDisplay<'a> {
     level: PhysDecodeLevel,
     data: &'a [u8],
@@ -140,7 +241,9 @@ impl<'a> std::fmt::Display for PhysDisplay<'a> {
 }
 
 #[cfg(feature = "serial")]
-fn calculate_inter_character_delay(serial: &tokio_serial::SerialStream) -> tokio::time::Duration {
+pub(crate) fn calculate_inter_character_delay(
+    serial: &tokio_serial::SerialStream,
+) -> tokio::time::Duration {
     use tokio::time::Duration;
     use tokio_serial::SerialPort;
 
@@ -0,0 +1,555 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::client::{Channel, ChannelConfig, Endpoint, HostAddr, RequestParam};
+use crate::error::RequestFailure;
+use crate::types::{AddressRange, UnitId};
+
+pub use crate::addressing::Table;
+
+/// Channels, devices and polls of a client deployment
+///
+/// The model can be deserialized with any `serde` data format, e.g. TOML with the `toml` crate,
+/// YAML with `serde_yaml` or JSON with `serde_json`. In TOML:
+///
+/// ```toml
+/// [[channels]]
+/// name = "line1"
+/// endpoint = { type = "tcp", host = "192.168.0.10", port = 502 }
+///
+/// [[channels.sessions]]
+/// name = "meter"
+/// unit_id = 1
+/// polls = [{ name = "measurements", table = "holding_registers", start = 0, count = 4, period_ms = 1000 }]
+/// points = [{ name = "voltage", table = "holding_registers", address = 2 }]
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientDeployment {
+    /// Channels to create
+    pub channels: Vec<ChannelDefinition>,
+}
+
+/// Channel to a server or to the devices of a serial line
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelDefinition {
+    /// Unique name of the channel, reported in the poll events
+    pub name: String,
+    /// Where to connect
+    pub endpoint: EndpointDefinition,
+    /// Maximum size of the request queue
+    #[serde(default = "default_max_queued_requests")]
+    pub max_queued_requests: usize,
+    /// Delays between the attempts to connect, the default strategy if not specified
+    #[serde(default)]
+    pub retry: Option<RetryDefinition>,
+    /// Devices accessed through the channel
+    #[serde(default)]
+    pub sessions: Vec<SessionDefinition>,
+}
+
+/// Remote end of a channel
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EndpointDefinition {
+    /// Modbus TCP server
+    Tcp {
+        /// IP address or name on which to perform DNS resolution
+        host: String,
+        /// TCP port
+        #[serde(default = "default_port")]
+        port: u16,
+    },
+    /// Modbus RTU devices on a serial port
+    #[cfg(feature = "serial")]
+    Rtu {
+        /// Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+        path: String,
+        /// Baud rate of the port
+        #[serde(default = "default_baud_rate")]
+        baud_rate: u32,
+        /// Parity setting
+        #[serde(default)]
+        parity: ParityDefinition,
+        /// Number of stop bits, 1 or 2
+        #[serde(default = "default_stop_bits")]
+        stop_bits: u8,
+    },
+}
+
+/// Parity of a serial port
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParityDefinition {
+    /// No parity bit
+    #[default]
+    None,
+    /// Even parity
+    Even,
+    /// Odd parity
+    Odd,
+}
+
+/// Delays of a [`crate::doubling_retry_strategy`]
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryDefinition {
+    /// Delay after the first failure
+    pub min_delay_ms: u64,
+    /// Maximum delay after consecutive failures
+    pub max_delay_ms: u64,
+}
+
+/// Device of a channel, its register map and how it is polled
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionDefinition {
+    /// Name of the session, unique within the channel
+    pub name: String,
+    /// Unit id of the device
+    pub unit_id: u8,
+    /// Response timeout of the requests
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Ranges read periodically
+    #[serde(default)]
+    pub polls: Vec<PollGroup>,
+    /// Named values of the device, reported when a poll reads them
+    #[serde(default)]
+    pub points: Vec<PointDefinition>,
+}
+
+/// Range of a table read periodically with a single request
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PollGroup {
+    /// Name of the poll, unique within the session
+    pub name: String,
+    /// Table that is read
+    pub table: Table,
+    /// Address of the first value
+    pub start: u16,
+    /// Number of values
+    pub count: u16,
+    /// Period of the poll
+    pub period_ms: u64,
+}
+
+/// Named value of a register map
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointDefinition {
+    /// Name of the value
+    pub name: String,
+    /// Table of the value
+    pub table: Table,
+    /// Address of the value
+    pub address: u16,
+}
+
+fn default_max_queued_requests() -> usize {
+    ChannelConfig::DEFAULT_MAX_QUEUED_REQUESTS
+}
+
+fn default_port() -> u16 {
+    502
+}
+
+#[cfg(feature = "serial")]
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+#[cfg(feature = "serial")]
+fn default_stop_bits() -> u8 {
+    1
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// Error returned when a [`ClientDeployment`] is inconsistent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Two channels, two sessions of a channel, or two polls of a session have the same name
+    DuplicateName(String),
+    /// The range of a poll is empty, too large for a single request, or overflows
+    InvalidRange(String),
+    /// The period of a poll is zero
+    InvalidPeriod(String),
+    /// A serial port setting is not supported
+    InvalidSerialSettings(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::DuplicateName(x) => write!(f, "duplicate name: {}", x),
+            ConfigError::InvalidRange(x) => write!(f, "invalid range of poll {}", x),
+            ConfigError::InvalidPeriod(x) => write!(f, "invalid period of poll {}", x),
+            ConfigError::InvalidSerialSettings(x) => {
+                write!(f, "invalid serial settings of channel {}", x)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Value read by a poll
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointValue {
+    /// Value of a coil or a discrete input
+    Bit(bool),
+    /// Value of a holding or input register
+    Register(u16),
+}
+
+/// Outcome of a poll of a [`RunningDeployment`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PollEvent {
+    /// Name of the channel
+    pub channel: String,
+    /// Name of the session
+    pub session: String,
+    /// Name of the poll
+    pub poll: String,
+    /// Values of the points in the range of the poll, or the error of the request
+    pub result: Result<Vec<(String, PointValue)>, RequestFailure>,
+}
+
+/// Channels and polls instantiated from a [`ClientDeployment`]
+///
+/// The polls stop when the events receiver is dropped, and the channels when their handles are.
+#[derive(Debug)]
+pub struct RunningDeployment {
+    /// Enabled channels, by name
+    pub channels: Vec<(String, Channel)>,
+    /// Outcome of every poll
+    pub events: Receiver<PollEvent>,
+}
+
+impl ClientDeployment {
+    /// Check the consistency of the deployment
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        unique(self.channels.iter().map(|x| x.name.as_str()))?;
+        for channel in &self.channels {
+            channel.settings()?;
+            unique(channel.sessions.iter().map(|x| x.name.as_str()))?;
+            for session in &channel.sessions {
+                unique(session.polls.iter().map(|x| x.name.as_str()))?;
+                for poll in &session.polls {
+                    poll.range()?;
+                    if poll.period_ms == 0 {
+                        return Err(ConfigError::InvalidPeriod(poll.name.clone()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the deployment, then spawn and enable its channels and spawn a task per poll
+    ///
+    /// At most `max_queued_events` events wait in the receiver, after which the polls wait for
+    /// the events to be consumed.
+    ///
+    /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+    pub async fn start(&self, max_queued_events: usize) -> Result<RunningDeployment, ConfigError> {
+        self.validate()?;
+
+        let (tx, events) = tokio::sync::mpsc::channel(max_queued_events.max(1));
+        let mut channels = Vec::new();
+        for definition in &self.channels {
+            let channel = definition.config()?.spawn();
+            // the channel task can only have ended if the runtime is shutting down
+            let _ = channel.enable().await;
+            for session in &definition.sessions {
+                for poll in &session.polls {
+                    let task = PollTask {
+                        channel: channel.clone(),
+                        names: (definition.name.clone(), session.name.clone()),
+                        param: RequestParam::new(
+                            UnitId::new(session.unit_id),
+                            Duration::from_millis(session.timeout_ms),
+                        ),
+                        poll: poll.clone(),
+                        points: session
+                            .points
+                            .iter()
+                            .filter(|x| x.table == poll.table)
+                            .cloned()
+                            .collect(),
+                        tx: tx.clone(),
+                    };
+                    tokio::spawn(task.run());
+                }
+            }
+            channels.push((definition.name.clone(), channel));
+        }
+
+        Ok(RunningDeployment { channels, events })
+    }
+}
+
+impl ChannelDefinition {
+    fn config(&self) -> Result<ChannelConfig, ConfigError> {
+        let endpoint = match &self.endpoint {
+            EndpointDefinition::Tcp { host, port } => Endpoint::Tcp(match host.parse::<IpAddr>() {
+                Ok(ip) => HostAddr::ip(ip, *port),
+                Err(_) => HostAddr::dns(host.clone(), *port),
+            }),
+            #[cfg(feature = "serial")]
+            EndpointDefinition::Rtu { path, .. } => Endpoint::Rtu {
+                path: path.clone(),
+                settings: self.settings()?,
+            },
+        };
+        let mut config = ChannelConfig::new(endpoint).max_queued_requests(self.max_queued_requests);
+        if let Some(retry) = self.retry {
+            config = config.retry(crate::doubling_retry_strategy(
+                Duration::from_millis(retry.min_delay_ms),
+                Duration::from_millis(retry.max_delay_ms),
+            ));
+        }
+        Ok(config)
+    }
+
+    #[cfg(feature = "serial")]
+    fn settings(&self) -> Result<crate::serial::SerialSettings, ConfigError> {
+        use crate::serial::{Parity, StopBits};
+
+        match &self.endpoint {
+            EndpointDefinition::Rtu {
+                baud_rate,
+                parity,
+                stop_bits,
+                ..
+            } => Ok(crate::serial::SerialSettings {
+                baud_rate: *baud_rate,
+                parity: match parity {
+                    ParityDefinition::None => Parity::None,
+                    ParityDefinition::Even => Parity::Even,
+                    ParityDefinition::Odd => Parity::Odd,
+                },
+                stop_bits: match stop_bits {
+                    1 => StopBits::One,
+                    2 => StopBits::Two,
+                    _ => return Err(ConfigError::InvalidSerialSettings(self.name.clone())),
+                },
+                ..Default::default()
+            }),
+            _ => Ok(Default::default()),
+        }
+    }
+
+    #[cfg(not(feature = "serial"))]
+    fn settings(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+impl PollGroup {
+    fn range(&self) -> Result<AddressRange, ConfigError> {
+        let invalid = || ConfigError::InvalidRange(self.name.clone());
+        let range = AddressRange::try_from(self.start, self.count).map_err(|_| invalid())?;
+        let valid = match self.table {
+            Table::Coils | Table::DiscreteInputs => range.of_read_bits().is_ok(),
+            Table::HoldingRegisters | Table::InputRegisters => range.of_read_registers().is_ok(),
+        };
+        valid.then_some(range).ok_or_else(invalid)
+    }
+}
+
+fn unique<'a>(names: impl Iterator<Item = &'a str>) -> Result<(), ConfigError> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            return Err(ConfigError::DuplicateName(name.to_string()));
+        }
+    }
+    Ok(())
+}
+
+struct PollTask {
+    channel: Channel,
+    names: (String, String),
+    param: RequestParam,
+    poll: PollGroup,
+    points: Vec<PointDefinition>,
+    tx: Sender<PollEvent>,
+}
+
+impl PollTask {
+    async fn run(mut self) {
+        let mut interval = tokio::time::interval(Duration::from_millis(self.poll.period_ms));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let result = self.read().await;
+            let event = PollEvent {
+                channel: self.names.0.clone(),
+                session: self.names.1.clone(),
+                poll: self.poll.name.clone(),
+                result,
+            };
+            if self.tx.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn read(&mut self) -> Result<Vec<(String, PointValue)>, RequestFailure> {
+        // the range was validated before the task was spawned
+        let range = AddressRange {
+            start: self.poll.start,
+            count: self.poll.count,
+        };
+        let values: Vec<(u16, PointValue)> = match self.poll.table {
+            Table::Coils => bits(self.channel.read_coils(self.param, range).await?),
+            Table::DiscreteInputs => {
+                bits(self.channel.read_discrete_inputs(self.param, range).await?)
+            }
+            Table::HoldingRegisters => registers(
+                self.channel
+                    .read_holding_registers(self.param, range)
+                    .await?,
+            ),
+            Table::InputRegisters => {
+                registers(self.channel.read_input_registers(self.param, range).await?)
+            }
+        };
+
+        Ok(self
+            .points
+            .iter()
+            .filter_map(|point| {
+                let (_, value) = values.iter().find(|(index, _)| *index == point.address)?;
+                Some((point.name.clone(), *value))
+            })
+            .collect())
+    }
+}
+
+fn bits(values: Vec<crate::types::Indexed<bool>>) -> Vec<(u16, PointValue)> {
+    values
+        .into_iter()
+        .map(|x| (x.index, PointValue::Bit(x.value)))
+        .collect()
+}
+
+fn registers(values: Vec<crate::types::Indexed<u16>>) -> Vec<(u16, PointValue)> {
+    values
+        .into_iter()
+        .map(|x| (x.index, PointValue::Register(x.value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{AddressFilter, RequestHandler, ServerHandlerMap};
+    use crate::{DecodeLevel, ExceptionCode};
+
+    const DEPLOYMENT: &str = r#"{
+        "channels": [{
+            "name": "line1",
+            "endpoint": { "type": "tcp", "host": "127.0.0.1", "port": 40003 },
+            "retry": { "min_delay_ms": 10, "max_delay_ms": 10 },
+            "sessions": [{
+                "name": "meter",
+                "unit_id": 1,
+                "polls": [{ "name": "measurements", "table": "holding_registers", "start": 0, "count": 4, "period_ms": 10 }],
+                "points": [
+                    { "name": "voltage", "table": "holding_registers", "address": 2 },
+                    { "name": "alarm", "table": "coils", "address": 2 },
+                    { "name": "elsewhere", "table": "holding_registers", "address": 10 }
+                ]
+            }]
+        }]
+    }"#;
+
+    fn deployment() -> ClientDeployment {
+        serde_json::from_str(DEPLOYMENT).unwrap()
+    }
+
+    struct Meter;
+
+    impl RequestHandler for Meter {
+        fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+            Ok(address * 100)
+        }
+    }
+
+    #[test]
+    fn inconsistent_deployments_are_rejected() {
+        let mut config = deployment();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.channels[0].max_queued_requests, 16);
+        assert_eq!(config.channels[0].sessions[0].timeout_ms, 1000);
+
+        config.channels[0].sessions[0].polls[0].count = 126;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidRange("measurements".to_string()))
+        );
+
+        let mut config = deployment();
+        config.channels[0].sessions[0].polls[0].period_ms = 0;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidPeriod("measurements".to_string()))
+        );
+
+        let mut config = deployment();
+        let duplicate = config.channels[0].clone();
+        config.channels.push(duplicate);
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::DuplicateName("line1".to_string()))
+        );
+
+        assert!(serde_json::from_str::<ClientDeployment>(r#"{"chanels": []}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn polls_report_the_values_of_the_points() {
+        let _server = crate::server::spawn_tcp_server_task(
+            1,
+            "127.0.0.1:40003".parse().unwrap(),
+            ServerHandlerMap::single(UnitId::new(1), Meter.wrap()),
+            AddressFilter::Any,
+            DecodeLevel::nothing(),
+        )
+        .await
+        .unwrap();
+
+        let mut running = deployment().start(4).await.unwrap();
+        assert_eq!(running.channels[0].0, "line1");
+        loop {
+            let event = running.events.recv().await.unwrap();
+            assert_eq!(
+                (
+                    event.channel.as_str(),
+                    event.session.as_str(),
+                    event.poll.as_str()
+                ),
+                ("line1", "meter", "measurements")
+            );
+            // the first polls may fail while the channel connects
+            if let Ok(values) = event.result {
+                assert_eq!(
+                    values,
+                    vec![("voltage".to_string(), PointValue::Register(200))]
+                );
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,720 @@
+//! The checks exchange raw Modbus TCP frames with the server, so that they can send requests that a
+//! [`Channel`](crate::client::Channel) refuses to build, e.g. a read of zero registers. Writes only
+//! write back the values that were just read, but they are skipped unless explicitly enabled.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::client::TypedRequest;
+use crate::codec::{parse_mbap, parse_response};
+use crate::constants::exceptions;
+use crate::constants::limits;
+use crate::error::RequestError;
+use crate::types::{AddressRange, Indexed, UnitId};
+
+// unassigned by the specification, and outside the ranges reserved for user-defined functions
+const UNASSIGNED_FUNCTION: u8 = 0x55;
+
+/// Description of the server targeted by [`run_conformance`]
+///
+/// Each configured range must cover addresses that exist on the server, and the address that
+/// follows it must not exist. The checks of the tables that are not configured are skipped.
+#[derive(Copy, Clone, Debug)]
+pub struct ConformanceTarget {
+    /// Unit id of the server
+    pub unit_id: UnitId,
+    /// Coils that exist on the server
+    pub coils: Option<AddressRange>,
+    /// Discrete inputs that exist on the server
+    pub discrete_inputs: Option<AddressRange>,
+    /// Holding registers that exist on the server
+    pub holding_registers: Option<AddressRange>,
+    /// Input registers that exist on the server
+    pub input_registers: Option<AddressRange>,
+    /// Perform the checks that write coils and holding registers
+    pub write: bool,
+    /// Maximum time to wait for each response
+    pub timeout: Duration,
+}
+
+impl ConformanceTarget {
+    /// Describe a server without any known addresses. Writes are disabled and the timeout is 1 second.
+    pub fn new(unit_id: UnitId) -> Self {
+        Self {
+            unit_id,
+            coils: None,
+            discrete_inputs: None,
+            holding_registers: None,
+            input_registers: None,
+            write: false,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Outcome of a conformance check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The server behaved as required by the specification
+    Passed,
+    /// The server did not behave as required, with a description of what happened
+    Failed(String),
+    /// The check was not performed, with the reason why
+    Skipped(&'static str),
+}
+
+/// Result of a single conformance check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Description of the check
+    pub name: String,
+    /// Outcome of the check
+    pub outcome: Outcome,
+}
+
+/// Results of all the checks performed by [`run_conformance`], in the order they were performed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    /// Result of every check
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    /// Number of checks that passed
+    pub fn passed(&self) -> usize {
+        self.count(|x| matches!(x, Outcome::Passed))
+    }
+
+    /// Number of checks that failed
+    pub fn failed(&self) -> usize {
+        self.count(|x| matches!(x, Outcome::Failed(_)))
+    }
+
+    /// Number of checks that were skipped
+    pub fn skipped(&self) -> usize {
+        self.count(|x| matches!(x, Outcome::Skipped(_)))
+    }
+
+    /// True if no check failed
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.checks.iter().filter(|x| predicate(&x.outcome)).count()
+    }
+
+    fn push(&mut self, name: String, outcome: Outcome) {
+        self.checks.push(CheckResult { name, outcome });
+    }
+}
+
+impl std::fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "PASS {}", check.name)?,
+                Outcome::Failed(reason) => writeln!(f, "FAIL {} - {}", check.name, reason)?,
+                Outcome::Skipped(reason) => writeln!(f, "SKIP {} - {}", check.name, reason)?,
+            }
+        }
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed(),
+            self.failed(),
+            self.skipped()
+        )
+    }
+}
+
+/// Run the conformance checks against a server using the Modbus TCP framing
+///
+/// `stream` is typically a [`tokio::net::TcpStream`] connected to the server. If the stream fails,
+/// the check in progress and all the remaining checks fail.
+pub async fn run_conformance<S>(stream: S, target: &ConformanceTarget) -> ConformanceReport
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut runner = Runner {
+        stream,
+        unit_id: target.unit_id,
+        timeout: target.timeout,
+        tx_id: 0,
+        broken: None,
+        report: ConformanceReport::default(),
+    };
+
+    let tables = [
+        (Table::Coils, target.coils),
+        (Table::DiscreteInputs, target.discrete_inputs),
+        (Table::HoldingRegisters, target.holding_registers),
+        (Table::InputRegisters, target.input_registers),
+    ];
+    for (table, range) in tables {
+        runner.check_reads(table, range).await;
+    }
+
+    runner
+        .check(
+            format!("function {:#04X} is rejected", UNASSIGNED_FUNCTION),
+            &[UNASSIGNED_FUNCTION],
+            |pdu| expect_exception(pdu, UNASSIGNED_FUNCTION, &[exceptions::ILLEGAL_FUNCTION]),
+        )
+        .await;
+
+    if target.write {
+        runner.check_register_writes(target.holding_registers).await;
+        runner.check_coil_writes(target.coils).await;
+    } else {
+        runner.report.push(
+            "write checks".to_string(),
+            Outcome::Skipped("writes are not enabled"),
+        );
+    }
+
+    runner.report
+}
+
+#[derive(Copy, Clone)]
+enum Table {
+    Coils,
+    DiscreteInputs,
+    HoldingRegisters,
+    InputRegisters,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Table::Coils => "coils",
+            Table::DiscreteInputs => "discrete inputs",
+            Table::HoldingRegisters => "holding registers",
+            Table::InputRegisters => "input registers",
+        }
+    }
+
+    fn read_function(self) -> u8 {
+        match self {
+            Table::Coils => 0x01,
+            Table::DiscreteInputs => 0x02,
+            Table::HoldingRegisters => 0x03,
+            Table::InputRegisters => 0x04,
+        }
+    }
+
+    fn max_read_count(self) -> u16 {
+        match self {
+            Table::Coils | Table::DiscreteInputs => limits::MAX_READ_COILS_COUNT,
+            Table::HoldingRegisters | Table::InputRegisters => limits::MAX_READ_REGISTERS_COUNT,
+        }
+    }
+
+    fn read_request(self, range: AddressRange) -> TypedRequest {
+        match self {
+            Table::Coils => TypedRequest::ReadCoils(range),
+            Table::DiscreteInputs => TypedRequest::ReadDiscreteInputs(range),
+            Table::HoldingRegisters => TypedRequest::ReadHoldingRegisters(range),
+            Table::InputRegisters => TypedRequest::ReadInputRegisters(range),
+        }
+    }
+}
+
+struct Runner<S> {
+    stream: S,
+    unit_id: UnitId,
+    timeout: Duration,
+    tx_id: u16,
+    /// set once the stream has failed
+    broken: Option<String>,
+    report: ConformanceReport,
+}
+
+impl<S> Runner<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    async fn check_reads(&mut self, table: Table, range: Option<AddressRange>) {
+        let range = match range {
+            Some(x) => x,
+            None => {
+                self.report.push(
+                    format!("{}: reads", table.name()),
+                    Outcome::Skipped("no addresses configured"),
+                );
+                return;
+            }
+        };
+        let function = table.read_function();
+        let max = table.max_read_count();
+
+        let valid = AddressRange {
+            start: range.start,
+            count: range.count.min(max),
+        };
+        let request = table.read_request(valid);
+        self.check(
+            format!(
+                "{}: read {} values at {}",
+                table.name(),
+                valid.count,
+                valid.start
+            ),
+            &read_pdu(function, valid.start, valid.count),
+            |pdu| match parse_response(&request, pdu) {
+                Ok(_) => Outcome::Passed,
+                Err(err) => Outcome::Failed(format!("invalid response: {}", err)),
+            },
+        )
+        .await;
+
+        for count in [0, max + 1] {
+            self.check(
+                format!("{}: read of {} values is rejected", table.name(), count),
+                &read_pdu(function, range.start, count),
+                |pdu| expect_exception(pdu, function, &[exceptions::ILLEGAL_DATA_VALUE]),
+            )
+            .await;
+        }
+
+        match range.start.checked_add(range.count) {
+            Some(end) => {
+                self.check(
+                    format!("{}: read at {} is rejected", table.name(), end),
+                    &read_pdu(function, end, 1),
+                    |pdu| expect_exception(pdu, function, &[exceptions::ILLEGAL_DATA_ADDRESS]),
+                )
+                .await
+            }
+            None => self.report.push(
+                format!("{}: read after the last address is rejected", table.name()),
+                Outcome::Skipped("the range ends at the last address"),
+            ),
+        }
+
+        // the address check is the last step of the processing defined by the specification, but
+        // some implementations reject a range that overflows when they validate the quantity
+        self.check(
+            format!("{}: read beyond address 65535 is rejected", table.name()),
+            &read_pdu(function, u16::MAX, 2),
+            |pdu| {
+                expect_exception(
+                    pdu,
+                    function,
+                    &[
+                        exceptions::ILLEGAL_DATA_ADDRESS,
+                        exceptions::ILLEGAL_DATA_VALUE,
+                    ],
+                )
+            },
+        )
+        .await;
+    }
+
+    async fn check_register_writes(&mut self, range: Option<AddressRange>) {
+        let range = match range {
+            Some(x) => x,
+            None => {
+                self.report.push(
+                    "holding registers: writes".to_string(),
+                    Outcome::Skipped("no addresses configured"),
+                );
+                return;
+            }
+        };
+        let count = range.count.min(2);
+        let values = match self.read_current(0x03, range.start, count).await {
+            Ok(x) => x,
+            Err(reason) => {
+                self.report.push(
+                    "holding registers: writes".to_string(),
+                    Outcome::Failed(reason),
+                );
+                return;
+            }
+        };
+        // registers are big-endian in the response
+        let first = u16::from_be_bytes([values[0], values[1]]);
+
+        let mut pdu = vec![0x06];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(&first.to_be_bytes());
+        let request = TypedRequest::WriteSingleRegister(Indexed::new(range.start, first));
+        self.check(
+            "holding registers: write single register is echoed".to_string(),
+            &pdu,
+            |pdu| expect_valid(&request, pdu),
+        )
+        .await;
+
+        // write the values that were read back
+        let mut pdu = vec![0x10];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(&count.to_be_bytes());
+        pdu.push(values.len() as u8);
+        pdu.extend_from_slice(&values);
+        self.check(
+            "holding registers: write multiple registers is acknowledged".to_string(),
+            &pdu,
+            |pdu| expect_echo(pdu, &[0x10], range.start, count),
+        )
+        .await;
+
+        // the byte count does not match the quantity
+        let mut pdu = vec![0x10];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(&1u16.to_be_bytes());
+        pdu.extend_from_slice(&[0x04, 0x00, 0x00, 0x00, 0x00]);
+        self.check(
+            "holding registers: write multiple registers with a bad byte count is rejected"
+                .to_string(),
+            &pdu,
+            |pdu| expect_exception(pdu, 0x10, &[exceptions::ILLEGAL_DATA_VALUE]),
+        )
+        .await;
+
+        if let Some(end) = range.start.checked_add(range.count) {
+            let mut pdu = vec![0x06];
+            pdu.extend_from_slice(&end.to_be_bytes());
+            pdu.extend_from_slice(&[0x00, 0x00]);
+            self.check(
+                format!(
+                    "holding registers: write single register at {} is rejected",
+                    end
+                ),
+                &pdu,
+                |pdu| expect_exception(pdu, 0x06, &[exceptions::ILLEGAL_DATA_ADDRESS]),
+            )
+            .await;
+        }
+    }
+
+    async fn check_coil_writes(&mut self, range: Option<AddressRange>) {
+        let range = match range {
+            Some(x) => x,
+            None => {
+                self.report.push(
+                    "coils: writes".to_string(),
+                    Outcome::Skipped("no addresses configured"),
+                );
+                return;
+            }
+        };
+        let count = range.count.min(8);
+        let values = match self.read_current(0x01, range.start, count).await {
+            Ok(x) => x,
+            Err(reason) => {
+                self.report
+                    .push("coils: writes".to_string(), Outcome::Failed(reason));
+                return;
+            }
+        };
+        let first = values[0] & 0x01 != 0;
+
+        let mut pdu = vec![0x05];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(if first { &[0xFF, 0x00] } else { &[0x00, 0x00] });
+        let request = TypedRequest::WriteSingleCoil(Indexed::new(range.start, first));
+        self.check(
+            "coils: write single coil is echoed".to_string(),
+            &pdu,
+            |pdu| expect_valid(&request, pdu),
+        )
+        .await;
+
+        let mut pdu = vec![0x05];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(&[0x12, 0x34]);
+        self.check(
+            "coils: write single coil with a value other than ON or OFF is rejected".to_string(),
+            &pdu,
+            |pdu| expect_exception(pdu, 0x05, &[exceptions::ILLEGAL_DATA_VALUE]),
+        )
+        .await;
+
+        // write the values that were read back
+        let mut pdu = vec![0x0F];
+        pdu.extend_from_slice(&range.start.to_be_bytes());
+        pdu.extend_from_slice(&count.to_be_bytes());
+        pdu.push(values.len() as u8);
+        pdu.extend_from_slice(&values);
+        self.check(
+            "coils: write multiple coils is acknowledged".to_string(),
+            &pdu,
+            |pdu| expect_echo(pdu, &[0x0F], range.start, count),
+        )
+        .await;
+    }
+
+    /// read values that are written back, returns the data following the byte count
+    async fn read_current(
+        &mut self,
+        function: u8,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<u8>, String> {
+        let response = self.exchange(&read_pdu(function, start, count)).await?;
+        match response.split_first() {
+            Some((x, rest)) if *x == function && rest.len() > 1 => Ok(rest[1..].to_vec()),
+            _ => Err(format!(
+                "unable to read the current values: {:02X?}",
+                response
+            )),
+        }
+    }
+
+    async fn check<F>(&mut self, name: String, request: &[u8], validate: F)
+    where
+        F: FnOnce(&[u8]) -> Outcome,
+    {
+        let outcome = match self.exchange(request).await {
+            Ok(response) => validate(&response),
+            Err(reason) => Outcome::Failed(reason),
+        };
+        self.report.push(name, outcome);
+    }
+
+    /// send a request PDU and return the response PDU after validating the MBAP header
+    async fn exchange(&mut self, pdu: &[u8]) -> Result<Vec<u8>, String> {
+        if let Some(reason) = &self.broken {
+            return Err(reason.clone());
+        }
+        let result = tokio::time::timeout(self.timeout, self.exchange_inner(pdu))
+            .await
+            .unwrap_or_else(|_| Err("no response".to_string()));
+        self.tx_id = self.tx_id.wrapping_add(1);
+        if let Err(reason) = &result {
+            // the stream cannot be resynchronized with an unanswered or invalid frame
+            self.broken = Some(format!(
+                "not performed after the previous failure: {}",
+                reason
+            ));
+        }
+        result
+    }
+
+    async fn exchange_inner(&mut self, pdu: &[u8]) -> Result<Vec<u8>, String> {
+        let mut adu = Vec::with_capacity(7 + pdu.len());
+        adu.extend_from_slice(&self.tx_id.to_be_bytes());
+        adu.extend_from_slice(&[0, 0]);
+        adu.extend_from_slice(&((pdu.len() + 1) as u16).to_be_bytes());
+        adu.push(self.unit_id.value);
+        adu.extend_from_slice(pdu);
+        self.stream
+            .write_all(&adu)
+            .await
+            .map_err(|err| format!("unable to write: {}", err))?;
+
+        let mut buffer = Vec::new();
+        loop {
+            match parse_mbap(&buffer) {
+                Ok(Some((adu, _))) => {
+                    if adu.tx_id != self.tx_id {
+                        return Err(format!(
+                            "transaction id {:#06X} does not match the request ({:#06X})",
+                            adu.tx_id, self.tx_id
+                        ));
+                    }
+                    if adu.unit_id != self.unit_id {
+                        return Err(format!(
+                            "unit id {} does not match the request ({})",
+                            adu.unit_id, self.unit_id
+                        ));
+                    }
+                    return Ok(adu.pdu.to_vec());
+                }
+                Ok(None) => {}
+                Err(err) => return Err(format!("invalid frame: {}", err)),
+            }
+            let mut chunk = [0u8; 256];
+            match self.stream.read(&mut chunk).await {
+                Ok(0) => return Err("connection closed".to_string()),
+                Ok(count) => buffer.extend_from_slice(&chunk[..count]),
+                Err(err) => return Err(format!("unable to read: {}", err)),
+            }
+        }
+    }
+}
+
+fn read_pdu(function: u8, start: u16, count: u16) -> Vec<u8> {
+    let mut pdu = vec![function];
+    pdu.extend_from_slice(&start.to_be_bytes());
+    pdu.extend_from_slice(&count.to_be_bytes());
+    pdu
+}
+
+fn expect_exception(pdu: &[u8], function: u8, allowed: &[u8]) -> Outcome {
+    match pdu {
+        [x, code] if *x == function | 0x80 => {
+            if allowed.contains(code) {
+                Outcome::Passed
+            } else {
+                Outcome::Failed(format!(
+                    "exception {:#04X} instead of {:02X?}",
+                    code, allowed
+                ))
+            }
+        }
+        _ => Outcome::Failed(format!(
+            "expected exception {:02X?} but received {:02X?}",
+            allowed, pdu
+        )),
+    }
+}
+
+fn expect_valid(request: &TypedRequest, pdu: &[u8]) -> Outcome {
+    match parse_response(request, pdu) {
+        Ok(_) => Outcome::Passed,
+        Err(RequestError::Exception(ex)) => Outcome::Failed(format!("exception: {}", ex)),
+        Err(err) => Outcome::Failed(format!("invalid response: {}", err)),
+    }
+}
+
+fn expect_echo(pdu: &[u8], function: &[u8], start: u16, count: u16) -> Outcome {
+    let mut expected = function.to_vec();
+    expected.extend_from_slice(&start.to_be_bytes());
+    expected.extend_from_slice(&count.to_be_bytes());
+    if pdu == expected.as_slice() {
+        Outcome::Passed
+    } else {
+        Outcome::Failed(format!(
+            "expected {:02X?} but received {:02X?}",
+            expected, pdu
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::frame::{FrameWriter, FramedReader};
+    use crate::common::phys::PhysLayer;
+    use crate::decode::DecodeLevel;
+    use crate::exception::ExceptionCode;
+    use crate::server::task::{AuthorizationType, SessionTask};
+    use crate::server::*;
+
+    struct Registers {
+        coils: [bool; 10],
+        holding: [u16; 10],
+    }
+
+    impl RequestHandler for Registers {
+        fn read_coil(&self, address: u16) -> Result<bool, ExceptionCode> {
+            self.coils
+                .get(address as usize)
+                .copied()
+                .ok_or(ExceptionCode::IllegalDataAddress)
+        }
+
+        fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+            self.holding
+                .get(address as usize)
+                .copied()
+                .ok_or(ExceptionCode::IllegalDataAddress)
+        }
+
+        fn write_single_coil(&mut self, value: Indexed<bool>) -> Result<(), ExceptionCode> {
+            match self.coils.get_mut(value.index as usize) {
+                Some(x) => {
+                    *x = value.value;
+                    Ok(())
+                }
+                None => Err(ExceptionCode::IllegalDataAddress),
+            }
+        }
+
+        fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+            match self.holding.get_mut(value.index as usize) {
+                Some(x) => {
+                    *x = value.value;
+                    Ok(())
+                }
+                None => Err(ExceptionCode::IllegalDataAddress),
+            }
+        }
+
+        fn write_multiple_coils(&mut self, values: WriteCoils) -> Result<(), ExceptionCode> {
+            for x in values.iterator {
+                self.write_single_coil(x)?;
+            }
+            Ok(())
+        }
+
+        fn write_multiple_registers(
+            &mut self,
+            values: WriteRegisters,
+        ) -> Result<(), ExceptionCode> {
+            for x in values.iterator {
+                self.write_single_register(x)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn spawn_server() -> tokio::io::DuplexStream {
+        let (client, server) = tokio::io::duplex(1024);
+        let handler = Registers {
+            coils: [true; 10],
+            holding: [7; 10],
+        }
+        .wrap();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let mut session = SessionTask::new(
+            ServerHandlerMap::single(UnitId::new(1), handler),
+            AuthorizationType::None,
+            FrameWriter::tcp(),
+            FramedReader::tcp(),
+            rx,
+            DecodeLevel::nothing(),
+        );
+        tokio::spawn(async move {
+            // the session ends when the settings channel is closed
+            let _tx = tx;
+            session
+                .run(&mut PhysLayer::new_stream(Box::new(server)))
+                .await
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn own_server_passes_every_check() {
+        let mut target = ConformanceTarget::new(UnitId::new(1));
+        target.coils = Some(AddressRange::try_from(0, 10).unwrap());
+        target.holding_registers = Some(AddressRange::try_from(0, 10).unwrap());
+        target.write = true;
+
+        let report = run_conformance(spawn_server(), &target).await;
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.passed(), 18);
+        assert_eq!(report.skipped(), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_failures_of_non_conformant_server() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        // answers every request with an empty response to read coils
+        tokio::spawn(async move {
+            let mut request = [0u8; 12];
+            while server.read_exact(&mut request).await.is_ok() {
+                let mut response = request[0..4].to_vec();
+                response.extend_from_slice(&[0x00, 0x03, 0x01, 0x01, 0x00]);
+                if server.write_all(&response).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut target = ConformanceTarget::new(UnitId::new(1));
+        target.coils = Some(AddressRange::try_from(0, 10).unwrap());
+        target.timeout = Duration::from_millis(100);
+        let report = run_conformance(client, &target).await;
+
+        assert!(matches!(report.checks[0].outcome, Outcome::Failed(_)));
+        // the server never answers the request for the unassigned function
+        assert_eq!(report.failed(), 6);
+        assert_eq!(report.passed(), 0);
+    }
+}
@@ -16,6 +16,12 @@ pub mod limits {
     pub const MAX_WRITE_COILS_COUNT: u16 = 0x07B0;
     /// Maximum count allowed in a `write multiple registers` request
     pub const MAX_WRITE_REGISTERS_COUNT: u16 = 0x007B;
+    /// Maximum length of a PDU (function code and data) defined by the specification, which
+    /// channels enforce by default
+    pub const MAX_PDU_LENGTH: usize = 253;
+    /// Largest maximum PDU length a channel accepts, see
+    /// [`crate::client::Channel::set_max_pdu_length`]
+    pub const MAX_CONFIGURABLE_PDU_LENGTH: usize = 512;
 }
 
 /// Modbus exception codes
@@ -57,6 +57,34 @@ impl DecodeLevel {
         Self::default()
     }
 
+    /// construct a `DecodeLevel` that logs function codes and frame headers
+    pub fn headers() -> Self {
+        Self::new(
+            AppDecodeLevel::FunctionCode,
+            FrameDecodeLevel::Header,
+            PhysDecodeLevel::Nothing,
+        )
+    }
+
+    /// construct a `DecodeLevel` that logs frame headers and every decoded PDU field and value
+    pub fn data_values() -> Self {
+        Self::new(
+            AppDecodeLevel::DataValues,
+            FrameDecodeLevel::Header,
+            PhysDecodeLevel::Nothing,
+        )
+    }
+
+    /// construct a `DecodeLevel` with everything enabled, including hex dumps of the frame
+    /// payloads and of the raw bytes read and written at the physical layer
+    pub fn everything() -> Self {
+        Self::new(
+            AppDecodeLevel::DataValues,
+            FrameDecodeLevel::Payload,
+            PhysDecodeLevel::Data,
+        )
+    }
+
     /// construct a `DecodeLevel` from its fields
     pub fn new(pdu: AppDecodeLevel, adu: FrameDecodeLevel, physical: PhysDecodeLevel) -> Self {
         DecodeLevel {
@@ -1,4 +1,5 @@
 use scursor::WriteError;
+use std::sync::Arc;
 
 /// The task processing requests has terminated
 #[derive(Clone, Copy, Debug)]
@@ -12,11 +13,162 @@ impl std::fmt::Display for Shutdown {
     }
 }
 
-/// Top level error type for the client API
+/// Errors returned by [`crate::server::ServerHandle::reload`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReloadError {
+    /// The handlers of the reload are of a different type than those the server was spawned with
+    HandlerType,
+    /// The server task has shut down
+    Shutdown,
+}
+
+impl std::error::Error for ReloadError {}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReloadError::HandlerType => write!(
+                f,
+                "handlers of a different type than those the server was spawned with"
+            ),
+            ReloadError::Shutdown => write!(f, "task shutdown"),
+        }
+    }
+}
+
+impl From<Shutdown> for ReloadError {
+    fn from(_: Shutdown) -> Self {
+        ReloadError::Shutdown
+    }
+}
+
+/// I/O error that can be cloned along with the error that contains it
+///
+/// The original [`std::io::Error`] is the [`source`](std::error::Error::source) of that error.
+/// Two errors are equal if they are of the same [`std::io::ErrorKind`].
+#[derive(Clone, Debug)]
+pub struct IoError {
+    inner: Arc<std::io::Error>,
+}
+
+impl IoError {
+    /// Kind of the I/O error
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.inner.kind()
+    }
+
+    /// The original I/O error
+    pub fn get_ref(&self) -> &std::io::Error {
+        &self.inner
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind()
+    }
+}
+
+impl Eq for IoError {}
+
+impl std::fmt::Display for IoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl From<std::io::Error> for IoError {
+    fn from(err: std::io::Error) -> Self {
+        Self {
+            inner: Arc::new(err),
+        }
+    }
+}
+
+impl From<std::io::ErrorKind> for IoError {
+    fn from(kind: std::io::ErrorKind) -> Self {
+        std::io::Error::from(kind).into()
+    }
+}
+
+/// Errors that prevent a client channel from establishing a connection with the server
+///
+/// These errors are never returned from requests. Requests submitted while the channel is not
+/// connected fail with [`RequestError::NoConnection`] instead. Connection failures are reported to
+/// [`crate::client::MetricsListener::connect_failed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectError {
+    /// The host name could not be resolved
+    Dns(IoError),
+    /// The TCP connection could not be established
+    Tcp(IoError),
+    /// The TLS handshake with the server failed
+    Tls(IoError),
+    /// The serial port could not be opened
+    Serial(IoError),
+    /// A user-supplied [`crate::client::Connector`] failed to establish a stream
+    Stream(IoError),
+}
+
+impl ConnectError {
+    fn io_error(&self) -> &IoError {
+        match self {
+            ConnectError::Dns(err) => err,
+            ConnectError::Tcp(err) => err,
+            ConnectError::Tls(err) => err,
+            ConnectError::Serial(err) => err,
+            ConnectError::Stream(err) => err,
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.io_error().get_ref())
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConnectError::Dns(err) => write!(f, "unable to resolve host: {}", err),
+            ConnectError::Tcp(err) => write!(f, "unable to connect: {}", err),
+            ConnectError::Tls(err) => write!(f, "TLS handshake failed: {}", err),
+            ConnectError::Serial(err) => write!(f, "unable to open serial port: {}", err),
+            ConnectError::Stream(err) => write!(f, "unable to open stream: {}", err),
+        }
+    }
+}
+
+/// Coarse classification of a [`RequestError`]
+///
+/// Matching on the kind is simpler than matching on every detailed variant of [`RequestError`],
+/// and new detailed variants are always assigned to one of the existing kinds.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Communication with the server failed or could not be attempted: I/O error, no connection,
+    /// response timeout, or full request queue
+    Io,
+    /// The server sent a frame or a response that violates the protocol
+    Protocol,
+    /// The request was invalid and was never sent
+    BadRequest,
+    /// The server returned a Modbus exception
+    Exception,
+    /// The channel has been shut down
+    Shutdown,
+    /// An internal error occurred in the library itself
+    Internal,
+}
+
+/// Top level error type for the client API
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum RequestError {
     /// An I/O error occurred
-    Io(::std::io::ErrorKind),
+    Io(IoError),
     /// A Modbus exception was returned by the server
     Exception(crate::exception::ExceptionCode),
     /// Request was not performed because it is invalid
@@ -24,25 +176,206 @@ pub enum RequestError {
     /// Unable to parse a frame from the server
     BadFrame(FrameParseError),
     /// Response ADU was invalid
-    BadResponse(AduParseError),
+    BadResponse(ResponseParseError),
     /// An internal error occurred in the library itself
     ///
     /// These errors should never happen, but are trapped here for reporting purposes in case they ever do occur
     Internal(InternalError),
     /// Timeout occurred before receiving a response from the server
     ResponseTimeout,
-    /// No connection could be made to the Modbus server
+    /// The request was not sent because the channel is not connected to the Modbus server
+    ///
+    /// The reason why the connection could not be established is reported separately as a [`ConnectError`]
     NoConnection,
     /// Task processing requests has been shutdown
     Shutdown,
+    /// The request was not queued because the request queue of the channel is full
+    ///
+    /// Only returned if the channel was configured to fail instead of waiting for space in the queue
+    QueueFull,
 }
 
-impl std::error::Error for RequestError {}
+impl RequestError {
+    /// Coarse classification of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            RequestError::Io(_) => ErrorKind::Io,
+            RequestError::ResponseTimeout => ErrorKind::Io,
+            RequestError::NoConnection => ErrorKind::Io,
+            RequestError::QueueFull => ErrorKind::Io,
+            RequestError::Exception(_) => ErrorKind::Exception,
+            RequestError::BadRequest(_) => ErrorKind::BadRequest,
+            RequestError::BadFrame(_) => ErrorKind::Protocol,
+            RequestError::BadResponse(_) => ErrorKind::Protocol,
+            RequestError::Internal(_) => ErrorKind::Internal,
+            RequestError::Shutdown => ErrorKind::Shutdown,
+        }
+    }
+
+    /// The exception code returned by the server, if the request failed because of a Modbus exception
+    ///
+    /// This allows callers to branch on specific exceptions, e.g. to retry a request that failed
+    /// with [`ExceptionCode::ServerDeviceBusy`](crate::exception::ExceptionCode::ServerDeviceBusy)
+    /// but not one that failed with [`ExceptionCode::IllegalDataAddress`](crate::exception::ExceptionCode::IllegalDataAddress).
+    pub fn exception(&self) -> Option<crate::exception::ExceptionCode> {
+        match self {
+            RequestError::Exception(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the same request might succeed if it is retried later
+    ///
+    /// Transient errors are timeouts, loss of the connection, a full request queue, and exceptions
+    /// indicating that the server or gateway is temporarily unable to process the request
+    /// ([`ExceptionCode::ServerDeviceBusy`], [`ExceptionCode::Acknowledge`],
+    /// [`ExceptionCode::GatewayPathUnavailable`] and [`ExceptionCode::GatewayTargetDeviceFailedToRespond`]).
+    /// A corrupted frame is also considered transient.
+    ///
+    /// All other errors are permanent: retrying an invalid request or a request rejected with
+    /// e.g. [`ExceptionCode::IllegalFunction`] will produce the same result.
+    ///
+    /// [`ExceptionCode::ServerDeviceBusy`]: crate::exception::ExceptionCode::ServerDeviceBusy
+    /// [`ExceptionCode::Acknowledge`]: crate::exception::ExceptionCode::Acknowledge
+    /// [`ExceptionCode::GatewayPathUnavailable`]: crate::exception::ExceptionCode::GatewayPathUnavailable
+    /// [`ExceptionCode::GatewayTargetDeviceFailedToRespond`]: crate::exception::ExceptionCode::GatewayTargetDeviceFailedToRespond
+    /// [`ExceptionCode::IllegalFunction`]: crate::exception::ExceptionCode::IllegalFunction
+    pub fn is_transient(&self) -> bool {
+        use crate::exception::ExceptionCode;
+
+        match self {
+            RequestError::Io(_) => true,
+            RequestError::ResponseTimeout => true,
+            RequestError::NoConnection => true,
+            RequestError::QueueFull => true,
+            RequestError::BadFrame(_) => true,
+            RequestError::Exception(ex) => matches!(
+                ex,
+                ExceptionCode::Acknowledge
+                    | ExceptionCode::ServerDeviceBusy
+                    | ExceptionCode::GatewayPathUnavailable
+                    | ExceptionCode::GatewayTargetDeviceFailedToRespond
+            ),
+            RequestError::BadRequest(_) => false,
+            RequestError::BadResponse(_) => false,
+            RequestError::Internal(_) => false,
+            RequestError::Shutdown => false,
+        }
+    }
+
+    /// Exception that a gateway returns to its master when a request forwarded to a downstream
+    /// device fails with this error
+    ///
+    /// * the exceptions of the device are returned unchanged
+    /// * [`ExceptionCode::GatewayTargetDeviceFailedToRespond`] is returned if the device didn't
+    ///   respond in time, or with a valid response
+    /// * [`ExceptionCode::GatewayPathUnavailable`] is returned if the request could not reach the
+    ///   device, e.g. because the channel is not connected
+    /// * [`ExceptionCode::IllegalDataValue`] is returned for an invalid request
+    /// * [`ExceptionCode::ServerDeviceFailure`] is returned for an internal error
+    ///
+    /// [`ExceptionCode::GatewayTargetDeviceFailedToRespond`]: crate::exception::ExceptionCode::GatewayTargetDeviceFailedToRespond
+    /// [`ExceptionCode::GatewayPathUnavailable`]: crate::exception::ExceptionCode::GatewayPathUnavailable
+    /// [`ExceptionCode::IllegalDataValue`]: crate::exception::ExceptionCode::IllegalDataValue
+    /// [`ExceptionCode::ServerDeviceFailure`]: crate::exception::ExceptionCode::ServerDeviceFailure
+    pub fn gateway_exception(&self) -> crate::exception::ExceptionCode {
+        use crate::exception::ExceptionCode;
+
+        match self {
+            RequestError::Exception(ex) => *ex,
+            RequestError::ResponseTimeout => ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            RequestError::BadFrame(_) => ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            RequestError::BadResponse(_) => ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            RequestError::Io(_) => ExceptionCode::GatewayPathUnavailable,
+            RequestError::NoConnection => ExceptionCode::GatewayPathUnavailable,
+            RequestError::QueueFull => ExceptionCode::GatewayPathUnavailable,
+            RequestError::Shutdown => ExceptionCode::GatewayPathUnavailable,
+            RequestError::BadRequest(_) => ExceptionCode::IllegalDataValue,
+            RequestError::Internal(_) => ExceptionCode::ServerDeviceFailure,
+        }
+    }
+
+    /// Stable numeric code that identifies the error
+    ///
+    /// Codes never change once assigned, even if the description of the error is reworded,
+    /// so they can be used as keys in log aggregation systems or passed across language bindings.
+    /// Each category of error occupies its own range:
+    ///
+    /// | range     | category                                                                  |
+    /// |-----------|---------------------------------------------------------------------------|
+    /// | 1000      | [`RequestError::Io`]                                                      |
+    /// | 2000-2255 | [`RequestError::Exception`], 2000 plus the raw exception code             |
+    /// | 3001-3005 | [`RequestError::BadRequest`]                                              |
+    /// | 4001-4005 | [`RequestError::BadFrame`]                                                |
+    /// | 5001-5010 | [`RequestError::BadResponse`]                                             |
+    /// | 6001-6005 | [`RequestError::Internal`]                                                |
+    /// | 7001-7004 | [`RequestError::ResponseTimeout`], [`RequestError::NoConnection`], [`RequestError::Shutdown`], [`RequestError::QueueFull`] |
+    pub fn code(&self) -> u16 {
+        match self {
+            RequestError::Io(_) => 1000,
+            RequestError::Exception(ex) => 2000 + u8::from(*ex) as u16,
+            RequestError::BadRequest(err) => match err {
+                InvalidRequest::BadRange(InvalidRange::CountOfZero) => 3001,
+                InvalidRequest::BadRange(InvalidRange::AddressOverflow(_, _)) => 3002,
+                InvalidRequest::BadRange(InvalidRange::CountTooLargeForType(_, _)) => 3003,
+                InvalidRequest::CountTooBigForU16(_) => 3004,
+                InvalidRequest::CountTooBigForType(_, _) => 3005,
+            },
+            RequestError::BadFrame(err) => match err {
+                FrameParseError::MbapLengthZero => 4001,
+                FrameParseError::FrameLengthTooBig(_, _) => 4002,
+                FrameParseError::UnknownProtocolId(_) => 4003,
+                FrameParseError::UnknownFunctionCode(_) => 4004,
+                FrameParseError::CrcValidationFailure(_, _) => 4005,
+            },
+            RequestError::BadResponse(err) => match err.error() {
+                AduParseError::InsufficientBytes => 5001,
+                AduParseError::InsufficientBytesForByteCount(_, _) => 5002,
+                AduParseError::TrailingBytes(_) => 5003,
+                AduParseError::ReplyEchoMismatch => 5004,
+                AduParseError::UnknownResponseFunction(_, _, _) => 5005,
+                AduParseError::UnknownCoilState(_) => 5006,
+                AduParseError::ByteCountMismatch(_, _) => 5007,
+                AduParseError::ReplyAddressMismatch(_, _) => 5008,
+                AduParseError::ReplyValueMismatch(_, _) => 5009,
+                AduParseError::ReplyCountMismatch(_, _) => 5010,
+            },
+            RequestError::Internal(err) => match err {
+                InternalError::InsufficientWriteSpace(_, _) => 6001,
+                InternalError::FrameTooBig(_, _) => 6002,
+                InternalError::InsufficientBytesForRead(_, _) => 6003,
+                InternalError::BadSeekOperation => 6004,
+                InternalError::BadByteCount(_) => 6005,
+            },
+            RequestError::ResponseTimeout => 7001,
+            RequestError::NoConnection => 7002,
+            RequestError::Shutdown => 7003,
+            RequestError::QueueFull => 7004,
+        }
+    }
+}
+
+impl std::error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::Io(err) => Some(err.get_ref()),
+            RequestError::Exception(err) => Some(err),
+            RequestError::BadRequest(err) => Some(err),
+            RequestError::BadFrame(err) => Some(err),
+            RequestError::BadResponse(err) => Some(err),
+            RequestError::Internal(err) => Some(err),
+            RequestError::ResponseTimeout
+            | RequestError::NoConnection
+            | RequestError::Shutdown
+            | RequestError::QueueFull => None,
+        }
+    }
+}
 
 impl std::fmt::Display for RequestError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
-            RequestError::Io(kind) => std::io::Error::from(*kind).fmt(f),
+            RequestError::Io(err) => err.fmt(f),
             RequestError::Exception(err) => err.fmt(f),
             RequestError::BadRequest(err) => err.fmt(f),
             RequestError::BadFrame(err) => err.fmt(f),
@@ -51,10 +384,106 @@ impl std::fmt::Display for RequestError {
             RequestError::ResponseTimeout => f.write_str("response timeout"),
             RequestError::NoConnection => f.write_str("no connection to server"),
             RequestError::Shutdown => f.write_str("channel shutdown"),
+            RequestError::QueueFull => f.write_str("request queue is full"),
         }
     }
 }
 
+/// The request that failed with a [`RequestFailure`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Unit id of the target device
+    pub unit: crate::UnitId,
+    /// Function code of the request
+    pub function: crate::FunctionCode,
+    /// Range of addresses read or written by the request
+    pub range: crate::AddressRange,
+}
+
+impl RequestContext {
+    pub(crate) fn new(
+        unit: crate::UnitId,
+        function: crate::FunctionCode,
+        range: crate::AddressRange,
+    ) -> Self {
+        Self {
+            unit,
+            function,
+            range,
+        }
+    }
+
+    pub(crate) fn fail(self, error: impl Into<RequestError>) -> RequestFailure {
+        RequestFailure {
+            context: self,
+            error: error.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} request to unit {} ({})",
+            self.function, self.unit, self.range
+        )
+    }
+}
+
+/// Error returned by the requests of a [`crate::client::Channel`], along with the request that failed
+///
+/// The message describes the request, e.g. `READ COILS (0x01) request to unit 0x01 (start:
+/// 0x0000 qty: 5) failed: response timeout`, so that a failure can be identified from the logs
+/// of an application that makes many requests. The [`RequestError`] is also the
+/// [`source`](std::error::Error::source) of the failure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestFailure {
+    context: RequestContext,
+    error: RequestError,
+}
+
+impl RequestFailure {
+    /// The request that failed
+    pub fn context(&self) -> RequestContext {
+        self.context
+    }
+
+    /// The reason why the request failed
+    pub fn error(&self) -> &RequestError {
+        &self.error
+    }
+
+    /// Take the reason why the request failed, discarding the request
+    pub fn into_error(self) -> RequestError {
+        self.error
+    }
+}
+
+impl std::error::Error for RequestFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl std::fmt::Display for RequestFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.context, self.error)
+    }
+}
+
+impl PartialEq<RequestError> for RequestFailure {
+    fn eq(&self, other: &RequestError) -> bool {
+        self.error == *other
+    }
+}
+
+impl From<RequestFailure> for RequestError {
+    fn from(failure: RequestFailure) -> Self {
+        failure.error
+    }
+}
+
 impl From<WriteError> for RequestError {
     fn from(err: WriteError) -> Self {
         match err {
@@ -70,7 +499,7 @@ impl From<WriteError> for RequestError {
 
 impl From<std::io::Error> for RequestError {
     fn from(err: std::io::Error) -> Self {
-        RequestError::Io(err.kind())
+        RequestError::Io(err.into())
     }
 }
 
@@ -88,7 +517,7 @@ impl From<InternalError> for RequestError {
 
 impl From<AduParseError> for RequestError {
     fn from(err: AduParseError) -> Self {
-        RequestError::BadResponse(err)
+        RequestError::BadResponse(err.into())
     }
 }
 
@@ -136,18 +565,19 @@ impl From<InvalidRange> for RequestError {
 
 impl From<scursor::ReadError> for RequestError {
     fn from(_: scursor::ReadError) -> Self {
-        RequestError::BadResponse(AduParseError::InsufficientBytes)
+        RequestError::BadResponse(AduParseError::InsufficientBytes.into())
     }
 }
 
 impl From<scursor::TrailingBytes> for RequestError {
     fn from(x: scursor::TrailingBytes) -> Self {
-        RequestError::BadResponse(AduParseError::TrailingBytes(x.count.get()))
+        RequestError::BadResponse(AduParseError::TrailingBytes(x.count.get()).into())
     }
 }
 
 /// Errors that can be produced when validating start/count
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum InvalidRange {
     /// Count of zero not allowed
     CountOfZero,
@@ -159,6 +589,7 @@ pub enum InvalidRange {
 
 /// Errors that indicate faulty logic in the library itself if they occur
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum InternalError {
     /// Insufficient space for write operation
     InsufficientWriteSpace(usize, usize), // written vs remaining space
@@ -206,6 +637,7 @@ impl std::fmt::Display for InternalError {
 
 /// Errors that occur while parsing a frame off a stream (TCP or serial)
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[non_exhaustive]
 pub enum FrameParseError {
     /// Received TCP frame with the length field set to zero
     MbapLengthZero,
@@ -251,6 +683,7 @@ impl std::fmt::Display for FrameParseError {
 
 /// Errors that occur while parsing requests and responses
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[non_exhaustive]
 pub enum AduParseError {
     /// Response is too short to be valid
     InsufficientBytes,
@@ -264,6 +697,14 @@ pub enum AduParseError {
     UnknownResponseFunction(u8, u8, u8), // actual, expected, expected error
     /// Bad value for the coil state
     UnknownCoilState(u16),
+    /// Byte count in a read response doesn't match the quantity that was requested
+    ByteCountMismatch(u8, u8), // expected / actual
+    /// Address echoed in a write response doesn't match the request
+    ReplyAddressMismatch(u16, u16), // expected / actual
+    /// Value echoed in a write single coil/register response doesn't match the request
+    ReplyValueMismatch(u16, u16), // expected / actual
+    /// Quantity echoed in a write multiple coils/registers response doesn't match the request
+    ReplyCountMismatch(u16, u16), // expected / actual
 }
 
 impl std::error::Error for AduParseError {}
@@ -293,12 +734,99 @@ impl std::fmt::Display for AduParseError {
                 "received coil state with unspecified value: 0x{:04X}",
                 value
             ),
+            AduParseError::ByteCountMismatch(expected, actual) => write!(
+                f,
+                "byte count ({}) doesn't match the expected byte count ({}) for the requested quantity",
+                actual, expected
+            ),
+            AduParseError::ReplyAddressMismatch(expected, actual) => write!(
+                f,
+                "echoed address ({:#06X}) doesn't match the requested address ({:#06X})",
+                actual, expected
+            ),
+            AduParseError::ReplyValueMismatch(expected, actual) => write!(
+                f,
+                "echoed value ({:#06X}) doesn't match the requested value ({:#06X})",
+                actual, expected
+            ),
+            AduParseError::ReplyCountMismatch(expected, actual) => write!(
+                f,
+                "echoed quantity ({}) doesn't match the requested quantity ({})",
+                actual, expected
+            ),
         }
     }
 }
 
+/// A response that could not be parsed, along with the raw bytes of its PDU
+///
+/// The bytes are attached by the channel when it parses a received response, and are
+/// included in the message of the error. Two errors are equal if they have the same
+/// [`AduParseError`], regardless of the bytes.
+#[derive(Clone, Debug)]
+pub struct ResponseParseError {
+    error: AduParseError,
+    response: Option<bytes::Bytes>,
+}
+
+impl ResponseParseError {
+    /// Reason why the response could not be parsed
+    pub fn error(&self) -> AduParseError {
+        self.error
+    }
+
+    /// Raw bytes of the PDU of the response, starting with its function code
+    pub fn response(&self) -> Option<&[u8]> {
+        self.response.as_deref()
+    }
+
+    pub(crate) fn with_response(self, response: &[u8]) -> Self {
+        Self {
+            error: self.error,
+            response: Some(bytes::Bytes::copy_from_slice(response)),
+        }
+    }
+}
+
+impl From<AduParseError> for ResponseParseError {
+    fn from(error: AduParseError) -> Self {
+        Self {
+            error,
+            response: None,
+        }
+    }
+}
+
+impl PartialEq for ResponseParseError {
+    fn eq(&self, other: &Self) -> bool {
+        self.error == other.error
+    }
+}
+
+impl Eq for ResponseParseError {}
+
+impl std::error::Error for ResponseParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl std::fmt::Display for ResponseParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        self.error.fmt(f)?;
+        if let Some(response) = &self.response {
+            f.write_str(" - response:")?;
+            for byte in response.iter() {
+                write!(f, " {:02X}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Errors that result because of bad request parameter
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum InvalidRequest {
     /// Request contained an invalid range
     BadRange(InvalidRange),
@@ -308,7 +836,14 @@ pub enum InvalidRequest {
     CountTooBigForType(u16, u16),
 }
 
-impl std::error::Error for InvalidRequest {}
+impl std::error::Error for InvalidRequest {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InvalidRequest::BadRange(err) => Some(err),
+            InvalidRequest::CountTooBigForU16(_) | InvalidRequest::CountTooBigForType(_, _) => None,
+        }
+    }
+}
 
 impl std::fmt::Display for InvalidRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
@@ -329,6 +864,8 @@ impl std::fmt::Display for InvalidRequest {
     }
 }
 
+impl std::error::Error for InvalidRange {}
+
 impl std::fmt::Display for InvalidRange {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
@@ -346,3 +883,142 @@ impl std::fmt::Display for InvalidRange {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exception::ExceptionCode;
+
+    #[test]
+    fn downstream_failures_map_to_gateway_exceptions() {
+        assert_eq!(
+            RequestError::NoConnection.gateway_exception(),
+            ExceptionCode::GatewayPathUnavailable
+        );
+        assert_eq!(
+            RequestError::ResponseTimeout.gateway_exception(),
+            ExceptionCode::GatewayTargetDeviceFailedToRespond
+        );
+        assert_eq!(
+            RequestError::Exception(ExceptionCode::IllegalDataAddress).gateway_exception(),
+            ExceptionCode::IllegalDataAddress
+        );
+    }
+
+    #[test]
+    fn exception_code_is_only_available_for_exceptions() {
+        assert_eq!(
+            RequestError::Exception(ExceptionCode::IllegalDataAddress).exception(),
+            Some(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(
+            RequestError::from(ExceptionCode::from(0x06)).exception(),
+            Some(ExceptionCode::ServerDeviceBusy)
+        );
+        assert_eq!(RequestError::ResponseTimeout.exception(), None);
+    }
+
+    #[test]
+    fn classifies_errors_by_kind() {
+        assert_eq!(RequestError::ResponseTimeout.kind(), ErrorKind::Io);
+        assert_eq!(
+            RequestError::BadFrame(FrameParseError::MbapLengthZero).kind(),
+            ErrorKind::Protocol
+        );
+        assert_eq!(
+            RequestError::from(AduParseError::ReplyEchoMismatch).kind(),
+            ErrorKind::Protocol
+        );
+        assert_eq!(
+            RequestError::Exception(ExceptionCode::IllegalFunction).kind(),
+            ErrorKind::Exception
+        );
+        assert_eq!(RequestError::Shutdown.kind(), ErrorKind::Shutdown);
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        assert!(RequestError::ResponseTimeout.is_transient());
+        assert!(RequestError::NoConnection.is_transient());
+        assert!(RequestError::Io(std::io::ErrorKind::ConnectionReset.into()).is_transient());
+        assert!(RequestError::Exception(ExceptionCode::ServerDeviceBusy).is_transient());
+
+        assert!(!RequestError::Exception(ExceptionCode::IllegalFunction).is_transient());
+        assert!(!RequestError::Exception(ExceptionCode::IllegalDataAddress).is_transient());
+        assert!(!RequestError::BadRequest(InvalidRequest::CountTooBigForU16(70000)).is_transient());
+        assert!(!RequestError::Shutdown.is_transient());
+    }
+
+    #[test]
+    fn source_chain_reaches_the_io_error() {
+        use std::error::Error;
+
+        let err = RequestError::from(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "peer went away",
+        ));
+        let source = err
+            .source()
+            .and_then(|x| x.downcast_ref::<std::io::Error>())
+            .unwrap();
+        assert_eq!(source.to_string(), "peer went away");
+
+        let err = ConnectError::Tcp(std::io::ErrorKind::ConnectionRefused.into());
+        let source = err
+            .source()
+            .and_then(|x| x.downcast_ref::<std::io::Error>())
+            .unwrap();
+        assert_eq!(source.kind(), std::io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn source_chain_contains_the_wrapped_errors() {
+        use std::error::Error;
+
+        let err = RequestError::BadRequest(InvalidRange::CountOfZero.into());
+        let request = err
+            .source()
+            .and_then(|x| x.downcast_ref::<InvalidRequest>())
+            .unwrap();
+        let range = request
+            .source()
+            .and_then(|x| x.downcast_ref::<InvalidRange>())
+            .unwrap();
+        assert_eq!(*range, InvalidRange::CountOfZero);
+        assert!(range.source().is_none());
+    }
+
+    #[test]
+    fn numeric_codes_are_stable() {
+        assert_eq!(
+            RequestError::Io(std::io::ErrorKind::BrokenPipe.into()).code(),
+            1000
+        );
+        assert_eq!(
+            RequestError::Exception(ExceptionCode::IllegalDataAddress).code(),
+            2002
+        );
+        assert_eq!(
+            RequestError::Exception(ExceptionCode::Unknown(0xFF)).code(),
+            2255
+        );
+        assert_eq!(
+            RequestError::BadRequest(InvalidRange::CountOfZero.into()).code(),
+            3001
+        );
+        assert_eq!(
+            RequestError::BadFrame(FrameParseError::CrcValidationFailure(0, 1)).code(),
+            4005
+        );
+        assert_eq!(
+            RequestError::from(AduParseError::UnknownCoilState(0x1234)).code(),
+            5006
+        );
+        assert_eq!(
+            RequestError::Internal(InternalError::BadSeekOperation).code(),
+            6004
+        );
+        assert_eq!(RequestError::ResponseTimeout.code(), 7001);
+        assert_eq!(RequestError::Shutdown.code(), 7003);
+    }
+}
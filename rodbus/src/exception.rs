@@ -1,5 +1,5 @@
 /// Exception codes defined in the Modbus specification
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq, Hash)]
 pub enum ExceptionCode {
     /// The function code received in the query is not an allowable action for the server
     IllegalFunction,
@@ -15,7 +15,7 @@
 //!#[tokio::main(flavor = "multi_thread")]
 //!async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!
-//!    let mut channel = spawn_tcp_client_task(
+//!    let channel = spawn_tcp_client_task(
 //!        HostAddr::ip("127.0.0.1".parse()?, 502),
 //!        10,
 //!        default_retry_strategy(),
@@ -113,16 +113,12 @@
     dead_code,
     arithmetic_overflow,
     invalid_type_param_default,
-    missing_fragment_specifier,
     mutable_transmutes,
     no_mangle_const_items,
     overflowing_literals,
     patterns_in_fns_without_body,
     pub_use_of_private_extern_crate,
     unknown_crate_types,
-    const_err,
-    order_dependent_trait_objects,
-    illegal_floating_point_literal_pattern,
     improper_ctypes,
     late_bound_lifetime_arguments,
     non_camel_case_types,
@@ -130,7 +126,6 @@
     non_snake_case,
     non_upper_case_globals,
     no_mangle_generic_items,
-    private_in_public,
     stable_features,
     type_alias_bounds,
     tyvar_behind_raw_pointer,
@@ -150,7 +145,6 @@
 #![forbid(
     unsafe_code,
     rustdoc::broken_intra_doc_links,
-    unaligned_references,
     while_true,
     bare_trait_objects
 )]
@@ -162,13 +156,25 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Client API
 pub mod client;
+/// Pure functions that frame and parse Modbus messages without performing any I/O
+pub mod codec;
+/// Configuration model of client deployments, deserializable from configuration files
+#[cfg(feature = "config")]
+pub mod config;
+/// Checks of the conformance of a server to the Modbus specification
+pub mod conformance;
 /// Public constant values related to the Modbus specification
 pub mod constants;
 
 /// Server API
 pub mod server;
+/// Support for integration tests that exercise client code against an in-process server
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 // modules that are re-exported
+pub(crate) mod addressing;
+pub(crate) mod audit;
 pub(crate) mod decode;
 pub(crate) mod error;
 pub(crate) mod exception;
@@ -179,6 +185,9 @@ mod serial;
 pub(crate) mod types;
 
 // re-exports
+pub use crate::addressing::*;
+pub use crate::audit::*;
+pub use crate::common::function::{FunctionCode, UnknownFunctionCode};
 pub use crate::decode::*;
 pub use crate::error::*;
 pub use crate::exception::*;
@@ -7,7 +7,7 @@ use crate::client::message::Command;
 use crate::client::task::{ClientLoop, SessionError, StateChange};
 use crate::client::{Listener, PortState, RetryStrategy};
 use crate::common::frame::{FrameWriter, FramedReader};
-use crate::error::Shutdown;
+use crate::error::{ConnectError, Shutdown};
 
 pub(crate) struct SerialChannelTask {
     path: String,
@@ -65,8 +65,15 @@ impl SerialChannelTask {
     }
 
     pub(crate) async fn try_open_and_run(&mut self) -> Result<(), StateChange> {
+        if let Some(settings) = self.client_loop.take_serial_settings() {
+            self.serial_settings = settings;
+        }
         match crate::serial::open(self.path.as_str(), self.serial_settings) {
             Err(err) => {
+                self.client_loop
+                    .report_connect_failure(ConnectError::Serial(
+                        std::io::Error::from(err.clone()).into(),
+                    ));
                 let delay = self.retry.after_failed_connect();
                 self.listener.update(PortState::Wait(delay)).get().await;
                 tracing::warn!("{} - waiting {} ms to re-open port", err, delay.as_millis());
@@ -75,7 +82,7 @@ impl SerialChannelTask {
             Ok(serial) => {
                 self.retry.reset();
                 self.listener.update(PortState::Open).get().await;
-                let mut phys = PhysLayer::new_serial(serial);
+                let mut phys = PhysLayer::new_serial(serial, &self.serial_settings);
                 tracing::info!("serial port open");
                 match self.client_loop.run(&mut phys).await {
                     // the mpsc was closed, end the task
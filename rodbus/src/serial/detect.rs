@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use crate::client::message::Setting;
+use crate::client::scan::ProbeOutcome;
+use crate::client::task::ClientLoop;
+use crate::client::{Channel, RequestParam, ScanProbe};
+use crate::common::frame::{FrameWriter, FramedReader};
+use crate::common::phys::PhysLayer;
+use crate::decode::DecodeLevel;
+use crate::exception::ExceptionCode;
+use crate::serial::{DataBits, DriverEnable, FlowControl, Parity, SerialSettings, StopBits};
+use crate::types::UnitId;
+
+/// Serial settings tried by [`detect_serial_settings`] and the request used to test them
+///
+/// Every combination of baud rate, parity and stop bits is tried, in the order of the lists and
+/// varying the baud rate last.
+#[derive(Clone, Debug)]
+pub struct DetectOptions {
+    /// Baud rates to try
+    pub baud_rates: Vec<u32>,
+    /// Parities to try
+    pub parities: Vec<Parity>,
+    /// Numbers of stop bits to try
+    pub stop_bits: Vec<StopBits>,
+    /// Number of data bits, always 8 for Modbus RTU
+    pub data_bits: DataBits,
+    /// Flow control of the port
+    pub flow_control: FlowControl,
+    /// Transmit-enable control of the RS-485 transceiver, if it requires one
+    pub driver_enable: Option<DriverEnable>,
+    /// Discard the echo of the transmitted bytes
+    pub suppress_echo: bool,
+    /// Unit id of the device
+    pub unit: UnitId,
+    /// Request sent to the device with each combination of settings
+    pub probe: ScanProbe,
+    /// Response timeout of each request
+    pub timeout: Duration,
+    /// Stop at the first combination that works instead of trying all of them
+    pub stop_at_first: bool,
+}
+
+impl DetectOptions {
+    /// Try the common baud rates from 1200 to 115200, with no, even and odd parity and one stop
+    /// bit, by reading holding register 0 of `unit`
+    pub fn new(unit: UnitId) -> Self {
+        Self {
+            baud_rates: vec![9600, 19200, 38400, 57600, 115200, 4800, 2400, 1200],
+            parities: vec![Parity::None, Parity::Even, Parity::Odd],
+            stop_bits: vec![StopBits::One],
+            data_bits: DataBits::Eight,
+            flow_control: FlowControl::None,
+            driver_enable: None,
+            suppress_echo: false,
+            unit,
+            probe: ScanProbe::HoldingRegister(0),
+            timeout: Duration::from_millis(500),
+            stop_at_first: true,
+        }
+    }
+
+    fn candidates(&self) -> impl Iterator<Item = SerialSettings> + '_ {
+        self.baud_rates.iter().flat_map(move |baud_rate| {
+            self.parities.iter().flat_map(move |parity| {
+                self.stop_bits.iter().map(move |stop_bits| SerialSettings {
+                    baud_rate: *baud_rate,
+                    data_bits: self.data_bits,
+                    flow_control: self.flow_control,
+                    stop_bits: *stop_bits,
+                    parity: *parity,
+                    driver_enable: self.driver_enable,
+                    suppress_echo: self.suppress_echo,
+                })
+            })
+        })
+    }
+}
+
+/// Serial settings with which the device answered
+#[derive(Copy, Clone, Debug)]
+pub struct DetectedSettings {
+    /// Settings of the port
+    pub settings: SerialSettings,
+    /// Exception with which the device answered, if it did not return the value
+    pub exception: Option<ExceptionCode>,
+}
+
+/// Try combinations of serial settings against a device and report those with which it answered
+/// with a valid RTU frame, i.e. one whose CRC is correct
+///
+/// The port is re-opened for each combination, so it must not be used by a channel during the
+/// detection. An error is returned if the port cannot be opened. Wrong settings usually produce
+/// no answer or a corrupted one, but a device may misinterpret a request sent at a baud rate
+/// that is a multiple of its own, so it's worth checking the results with more requests.
+pub async fn detect_serial_settings(
+    path: &str,
+    options: &DetectOptions,
+) -> Result<Vec<DetectedSettings>, std::io::Error> {
+    detect(options, |settings| {
+        let port = crate::serial::open(path, settings)?;
+        Ok(PhysLayer::new_serial(port, &settings))
+    })
+    .await
+}
+
+async fn detect<F>(
+    options: &DetectOptions,
+    mut open: F,
+) -> Result<Vec<DetectedSettings>, std::io::Error>
+where
+    F: FnMut(SerialSettings) -> Result<PhysLayer, std::io::Error>,
+{
+    let mut detected = Vec::new();
+    for settings in options.candidates() {
+        let phys = open(settings)?;
+        match probe(phys, options).await {
+            Some(exception) => {
+                tracing::info!("device answered with {:?}", settings);
+                detected.push(DetectedSettings {
+                    settings,
+                    exception,
+                });
+                if options.stop_at_first {
+                    break;
+                }
+            }
+            None => tracing::debug!("no answer with {:?}", settings),
+        }
+    }
+    Ok(detected)
+}
+
+/// Send the probe over the port and return the exception, if any, with which the device answered
+async fn probe(mut phys: PhysLayer, options: &DetectOptions) -> Option<Option<ExceptionCode>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let mut client_loop = ClientLoop::new(
+        rx,
+        FrameWriter::rtu(),
+        FramedReader::rtu_response(),
+        DecodeLevel::nothing(),
+    );
+    client_loop.change_setting(Setting::Enable);
+    // nothing is logged for the frames received with the wrong settings
+    client_loop.change_setting(Setting::UnexpectedFrameLogging(None));
+
+    let channel = Channel::new(tx);
+    let param = RequestParam::new(options.unit, options.timeout);
+    tokio::select! {
+        result = options.probe.send(&channel, param) => match ProbeOutcome::from(result) {
+            ProbeOutcome::Answered(exception) => Some(exception),
+            ProbeOutcome::NoAnswer | ProbeOutcome::Failed(_) => None,
+        },
+        // a corrupted frame or an I/O error ends the session
+        _ = client_loop.run(&mut phys) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::task::{AuthorizationType, SessionTask};
+    use crate::server::{RequestHandler, ServerHandlerMap};
+
+    struct Device;
+
+    impl RequestHandler for Device {
+        fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+            match address {
+                0 => Ok(42),
+                _ => Err(ExceptionCode::IllegalDataAddress),
+            }
+        }
+    }
+
+    fn options() -> DetectOptions {
+        DetectOptions {
+            baud_rates: vec![9600, 19200],
+            parities: vec![Parity::None, Parity::Even],
+            timeout: Duration::from_millis(100),
+            ..DetectOptions::new(UnitId::new(1))
+        }
+    }
+
+    /// Connects to an RTU server session if the settings are those of the device, and to a
+    /// stream of garbage otherwise
+    fn open(settings: SerialSettings) -> Result<PhysLayer, std::io::Error> {
+        let (client, mut server) = tokio::io::duplex(1024);
+        if settings.baud_rate == 19200 && settings.parity == Parity::Even {
+            let (settings, rx) = tokio::sync::mpsc::channel(1);
+            let mut session = SessionTask::new(
+                ServerHandlerMap::single(UnitId::new(1), Device.wrap()),
+                AuthorizationType::None,
+                FrameWriter::rtu(),
+                FramedReader::rtu_request(),
+                rx,
+                DecodeLevel::nothing(),
+            );
+            tokio::spawn(async move {
+                // the session ends when its settings channel is closed
+                let _settings = settings;
+                let mut phys = PhysLayer::new_stream(Box::new(server));
+                session.run(&mut phys).await
+            });
+        } else {
+            tokio::spawn(async move {
+                use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                let mut buffer = [0; 256];
+                while let Ok(count) = server.read(&mut buffer).await {
+                    if count == 0 {
+                        return;
+                    }
+                    let _ = server.write_all(&[0x01, 0x83, 0xFF, 0x11, 0x22]).await;
+                }
+            });
+        }
+        Ok(PhysLayer::new_stream(Box::new(client)))
+    }
+
+    #[tokio::test]
+    async fn reports_the_settings_producing_valid_frames() {
+        let detected = detect(&options(), open).await.unwrap();
+        assert_eq!(detected.len(), 1);
+        assert_eq!(detected[0].settings.baud_rate, 19200);
+        assert_eq!(detected[0].settings.parity, Parity::Even);
+        assert_eq!(detected[0].exception, None);
+    }
+
+    #[tokio::test]
+    async fn exceptions_are_valid_answers_and_every_candidate_can_be_tried() {
+        let options = DetectOptions {
+            probe: ScanProbe::HoldingRegister(1),
+            stop_at_first: false,
+            ..options()
+        };
+        let candidates = options.candidates().count();
+        assert_eq!(candidates, 4);
+
+        let detected = detect(&options, open).await.unwrap();
+        assert_eq!(detected.len(), 1);
+        assert_eq!(
+            detected[0].exception,
+            Some(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_if_the_port_cannot_be_opened() {
+        let result = detect(&options(), |_| {
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
+use tokio_serial::{DataBits, Parity, SerialPort, SerialStream, StopBits};
+
+/// Modem control line driving the transmit-enable input of an RS-485 transceiver
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DriverEnableLine {
+    /// Request To Send
+    Rts,
+    /// Data Terminal Ready
+    Dtr,
+}
+
+/// Toggling of a modem control line around each transmission, for the RS-485 transceivers that
+/// don't switch between transmitting and receiving automatically
+///
+/// Before a frame is written, the line is set to `transmit_level` and `pre_delay` is waited.
+/// Once the frame has been written, the time required to shift its characters out at the baud
+/// rate of the port is waited, then `post_delay`, and the line is set back to the opposite level
+/// so that the response can be received.
+///
+/// The timing relies on the operating system, so converters whose driver introduces latency
+/// may require a `post_delay` of a few milliseconds to avoid truncating the last character.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DriverEnable {
+    /// Line toggled around each transmission
+    pub line: DriverEnableLine,
+    /// Level of the line while transmitting, `true` meaning that the line is asserted
+    pub transmit_level: bool,
+    /// Time between enabling the driver and writing the first character
+    pub pre_delay: Duration,
+    /// Time between the end of the last character and disabling the driver
+    pub post_delay: Duration,
+}
+
+impl DriverEnable {
+    /// Assert `line` while transmitting, without any delay
+    pub fn new(line: DriverEnableLine) -> Self {
+        Self {
+            line,
+            transmit_level: true,
+            pre_delay: Duration::ZERO,
+            post_delay: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn set_level(
+        &self,
+        port: &mut SerialStream,
+        transmit: bool,
+    ) -> tokio_serial::Result<()> {
+        let level = transmit == self.transmit_level;
+        match self.line {
+            DriverEnableLine::Rts => port.write_request_to_send(level),
+            DriverEnableLine::Dtr => port.write_data_terminal_ready(level),
+        }
+    }
+
+    /// Write the data with the driver enabled, then disable it once the data has been transmitted
+    pub(crate) async fn write(
+        &self,
+        port: &mut SerialStream,
+        character_time: Duration,
+        data: &[u8],
+    ) -> Result<(), std::io::Error> {
+        let result = self.transmit(port, character_time, data).await;
+        // the driver must be disabled even if the write failed, or the bus would remain blocked
+        let release = self.set_level(port, false).map_err(std::io::Error::from);
+        result.and(release)
+    }
+
+    async fn transmit(
+        &self,
+        port: &mut SerialStream,
+        character_time: Duration,
+        data: &[u8],
+    ) -> Result<(), std::io::Error> {
+        self.set_level(port, true)?;
+        tokio::time::sleep(self.pre_delay).await;
+
+        // the write completes as soon as the data is buffered by the operating system
+        let start = Instant::now();
+        port.write_all(data).await?;
+        port.flush().await?;
+        tokio::time::sleep_until(start + transmission_time(character_time, data.len())).await;
+
+        tokio::time::sleep(self.post_delay).await;
+        Ok(())
+    }
+}
+
+/// Time to transmit a single character with the settings of the port
+pub(crate) fn character_time(port: &SerialStream) -> Option<Duration> {
+    let bits = bits_per_character(
+        port.data_bits().ok()?,
+        port.parity().ok()?,
+        port.stop_bits().ok()?,
+    );
+    match port.baud_rate().ok()? {
+        0 => None,
+        baud_rate => Some(Duration::from_secs(bits) / baud_rate),
+    }
+}
+
+fn bits_per_character(data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> u64 {
+    let data_bits = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    };
+    let parity = match parity {
+        Parity::None => 0,
+        Parity::Odd | Parity::Even => 1,
+    };
+    let stop_bits = match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    };
+    // plus the start bit
+    1 + data_bits + parity + stop_bits
+}
+
+fn transmission_time(character_time: Duration, length: usize) -> Duration {
+    character_time.saturating_mul(u32::try_from(length).unwrap_or(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transmission_time_accounts_for_every_bit_of_the_characters() {
+        assert_eq!(
+            bits_per_character(DataBits::Eight, Parity::Even, StopBits::One),
+            11
+        );
+        assert_eq!(
+            bits_per_character(DataBits::Eight, Parity::None, StopBits::One),
+            10
+        );
+        assert_eq!(
+            bits_per_character(DataBits::Seven, Parity::Odd, StopBits::Two),
+            11
+        );
+
+        // 8 bytes at 9600 baud with 11-bit characters
+        let character_time = Duration::from_secs(11) / 9600;
+        assert_eq!(
+            transmission_time(character_time, 8),
+            Duration::from_nanos(9_166_664)
+        );
+        assert_eq!(transmission_time(character_time, 0), Duration::ZERO);
+    }
+}
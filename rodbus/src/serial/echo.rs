@@ -0,0 +1,88 @@
+/// Discards the echo of the transmitted frame from the received bytes
+///
+/// The bytes matching the beginning of the echo are held back until the whole echo has been
+/// received. If the received bytes differ from the echo, e.g. because the adapter doesn't echo
+/// after all, the held bytes are released in front of them so that nothing is lost.
+#[derive(Default)]
+pub(crate) struct EchoFilter {
+    expected: Vec<u8>,
+    matched: usize,
+}
+
+impl EchoFilter {
+    pub(crate) fn transmitted(&mut self, data: &[u8]) {
+        if self.matched != 0 {
+            tracing::warn!(
+                "incomplete echo, discarding {} bytes of the previous frame",
+                self.matched
+            );
+        }
+        self.expected.clear();
+        self.expected.extend_from_slice(data);
+        self.matched = 0;
+    }
+
+    /// Append the received bytes that are not part of the echo to the buffer
+    pub(crate) fn received(&mut self, mut data: &[u8], buffer: &mut bytes::BytesMut) {
+        while let Some(expected) = self.expected.get(self.matched) {
+            match data.split_first() {
+                None => return,
+                Some((first, rest)) if first == expected => {
+                    self.matched += 1;
+                    data = rest;
+                }
+                Some(_) => {
+                    tracing::warn!("received bytes do not match the echo of the transmitted frame");
+                    buffer.extend_from_slice(&self.expected[..self.matched]);
+                    break;
+                }
+            }
+        }
+
+        // the echo is complete or will never be
+        self.expected.clear();
+        self.matched = 0;
+        buffer.extend_from_slice(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // read one holding register at address 0 of unit 1, and its response
+    const REQUEST: &[u8] = &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+    const RESPONSE: &[u8] = &[0x01, 0x03, 0x02, 0x00, 0x2A, 0x39, 0x9B];
+
+    #[test]
+    fn discards_the_echo_received_in_any_number_of_chunks() {
+        let mut filter = EchoFilter::default();
+        let mut buffer = bytes::BytesMut::new();
+        filter.transmitted(REQUEST);
+
+        filter.received(&REQUEST[..3], &mut buffer);
+        assert!(buffer.is_empty());
+        // the response may immediately follow the echo
+        filter.received(&[&REQUEST[3..], &RESPONSE[..2]].concat(), &mut buffer);
+        assert_eq!(buffer.as_ref(), &RESPONSE[..2]);
+        filter.received(&RESPONSE[2..], &mut buffer);
+        assert_eq!(buffer.as_ref(), RESPONSE);
+    }
+
+    #[test]
+    fn releases_the_held_bytes_if_there_is_no_echo() {
+        let mut filter = EchoFilter::default();
+        let mut buffer = bytes::BytesMut::new();
+        filter.transmitted(REQUEST);
+
+        // the response starts with the same unit id and function code as the request
+        filter.received(&RESPONSE[..2], &mut buffer);
+        assert!(buffer.is_empty());
+        filter.received(&RESPONSE[2..], &mut buffer);
+        assert_eq!(buffer.as_ref(), RESPONSE);
+
+        // nothing is filtered until the next transmission
+        filter.received(REQUEST, &mut buffer);
+        assert_eq!(buffer.len(), RESPONSE.len() + REQUEST.len());
+    }
+}
@@ -15,11 +15,11 @@ pub(crate) mod constants {
     pub(crate) const FUNCTION_CODE_LENGTH: usize = 1;
     pub(crate) const CRC_LENGTH: usize = 2;
     pub(crate) const MAX_FRAME_LENGTH: usize =
-        HEADER_LENGTH + crate::common::frame::constants::MAX_ADU_LENGTH + CRC_LENGTH;
+        HEADER_LENGTH + crate::common::frame::constants::MAX_CONFIGURABLE_ADU_LENGTH + CRC_LENGTH;
 }
 
 /// precomputes the CRC table as a constant!
-const CRC: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_MODBUS);
+pub(crate) const CRC: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_MODBUS);
 
 #[derive(Clone, Copy)]
 enum ParserType {
@@ -47,6 +47,7 @@ enum LengthMode {
 pub(crate) struct RtuParser {
     state: ParseState,
     parser_type: ParserType,
+    max_pdu_length: usize,
 }
 
 impl RtuParser {
@@ -54,6 +55,7 @@ impl RtuParser {
         Self {
             state: ParseState::Start,
             parser_type: ParserType::Request,
+            max_pdu_length: crate::common::frame::constants::MAX_ADU_LENGTH,
         }
     }
 
@@ -61,9 +63,14 @@ impl RtuParser {
         Self {
             state: ParseState::Start,
             parser_type: ParserType::Response,
+            max_pdu_length: crate::common::frame::constants::MAX_ADU_LENGTH,
         }
     }
 
+    pub(crate) fn set_max_pdu_length(&mut self, length: usize) {
+        self.max_pdu_length = length;
+    }
+
     // Returns how to calculate the length of the body
     fn length_mode(&self, function_code: u8) -> LengthMode {
         // Check exception (only valid for responses)
@@ -153,12 +160,10 @@ impl RtuParser {
                 self.parse(cursor, decode_level)
             }
             ParseState::ReadFullBody(destination, length) => {
-                if constants::FUNCTION_CODE_LENGTH + length
-                    > crate::common::frame::constants::MAX_ADU_LENGTH
-                {
+                if constants::FUNCTION_CODE_LENGTH + length > self.max_pdu_length {
                     return Err(RequestError::BadFrame(FrameParseError::FrameLengthTooBig(
                         constants::FUNCTION_CODE_LENGTH + length,
-                        crate::common::frame::constants::MAX_ADU_LENGTH,
+                        self.max_pdu_length,
                     )));
                 }
 
@@ -561,7 +566,7 @@ mod tests {
             tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
 
         // Send bytes to parser byte per byte
-        for byte in frame.into_iter().take(frame.len() - 1) {
+        for byte in frame.iter().take(frame.len() - 1) {
             io_handle.read(&[*byte]);
             assert!(matches!(task.poll(), Poll::Pending));
         }
@@ -705,7 +710,7 @@ mod tests {
     }
 
     impl<'a> Serialize for MockMessage<'a> {
-        fn serialize(self: &Self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
+        fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
             for byte in &self.frame[2..self.frame.len() - 2] {
                 cursor.write_u8(*byte)?;
             }
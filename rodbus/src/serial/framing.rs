@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+
+use crate::serial::frame::CRC;
+use crate::serial::SerialSettings;
+
+/// Framing of the Modbus traffic on a serial line
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SerialFraming {
+    /// Binary frames delimited by silences and checked by a CRC, which this library implements
+    Rtu,
+    /// Hexadecimal text frames delimited by `:` and CR LF and checked by an LRC
+    ///
+    /// This library does not implement ASCII framing, so the devices using it must be
+    /// reconfigured or accessed through a converter.
+    Ascii,
+}
+
+/// Size of the text kept to look for ASCII frames, larger than the longest ASCII frame
+const MAX_ASCII_BUFFER: usize = 1024;
+
+/// Listen to the traffic of a serial line and report its framing, without sending anything
+///
+/// The traffic is examined until a valid RTU or ASCII frame is received or `duration` elapses,
+/// in which case `None` is returned. The port must not be used by a channel at the same time,
+/// and its settings must match those of the line for frames to be recognized.
+pub async fn sniff_serial_framing(
+    path: &str,
+    settings: SerialSettings,
+    duration: Duration,
+) -> Result<Option<SerialFraming>, std::io::Error> {
+    let mut port = crate::serial::open(path, settings)?;
+    let gap = crate::common::phys::calculate_inter_character_delay(&port);
+    let mut sniffer = Sniffer::default();
+    let mut buffer = [0; 256];
+
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        // the end of an RTU frame is detected by a silence of 3.5 characters
+        let timeout = std::cmp::min(tokio::time::Instant::now() + gap, deadline);
+        match tokio::time::timeout_at(timeout, port.read(&mut buffer)).await {
+            Ok(Ok(0)) => return Ok(None),
+            Ok(Ok(count)) => sniffer.receive(&buffer[..count]),
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                if let Some(framing) = sniffer.end_of_burst() {
+                    return Ok(Some(framing));
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Ok(None);
+                }
+            }
+        }
+        if let Some(framing) = sniffer.ascii() {
+            return Ok(Some(framing));
+        }
+    }
+}
+
+/// Looks for valid frames in the received bytes
+///
+/// ASCII frames may contain long pauses, so the text is examined across bursts, while RTU frames
+/// are examined one burst at a time.
+#[derive(Default)]
+struct Sniffer {
+    burst: Vec<u8>,
+    text: Vec<u8>,
+}
+
+impl Sniffer {
+    fn receive(&mut self, data: &[u8]) {
+        self.burst.extend_from_slice(data);
+        self.text.extend_from_slice(data);
+        if self.text.len() > MAX_ASCII_BUFFER {
+            let excess = self.text.len() - MAX_ASCII_BUFFER;
+            self.text.drain(..excess);
+        }
+    }
+
+    fn end_of_burst(&mut self) -> Option<SerialFraming> {
+        let burst = std::mem::take(&mut self.burst);
+        contains_rtu_frame(&burst).then_some(SerialFraming::Rtu)
+    }
+
+    fn ascii(&self) -> Option<SerialFraming> {
+        contains_ascii_frame(&self.text).then_some(SerialFraming::Ascii)
+    }
+}
+
+/// Returns true if the burst starts with an RTU frame, i.e. bytes terminated by their CRC
+fn contains_rtu_frame(burst: &[u8]) -> bool {
+    // the smallest frame is an address, a function code and the CRC
+    (4..=burst.len()).any(|end| {
+        let (data, crc) = burst[..end].split_at(end - 2);
+        CRC.checksum(data) == u16::from_le_bytes([crc[0], crc[1]])
+    })
+}
+
+/// Returns true if the text contains an ASCII frame whose LRC is valid
+fn contains_ascii_frame(text: &[u8]) -> bool {
+    text.split(|x| *x == b':')
+        .skip(1)
+        // the frame must be complete
+        .filter_map(|x| x.iter().position(|x| *x == b'\r').map(|end| &x[..end]))
+        .any(|hex| {
+            // address, function code and LRC, as pairs of hexadecimal digits
+            if hex.len() < 6 || hex.len() % 2 != 0 {
+                return false;
+            }
+            let bytes: Option<Vec<u8>> = hex
+                .chunks(2)
+                .map(|pair| {
+                    let pair = std::str::from_utf8(pair).ok()?;
+                    u8::from_str_radix(pair, 16).ok()
+                })
+                .collect();
+            match bytes {
+                // the LRC makes the sum of the bytes zero
+                Some(bytes) => bytes.iter().fold(0u8, |sum, x| sum.wrapping_add(*x)) == 0,
+                None => false,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // read one holding register at address 0 of unit 1
+    const RTU_REQUEST: &[u8] = &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+    const ASCII_REQUEST: &[u8] = b":010300000001FB\r\n";
+
+    #[test]
+    fn recognizes_rtu_frames_at_the_start_of_a_burst() {
+        let mut sniffer = Sniffer::default();
+        sniffer.receive(&RTU_REQUEST[..3]);
+        sniffer.receive(&RTU_REQUEST[3..]);
+        assert_eq!(sniffer.ascii(), None);
+        assert_eq!(sniffer.end_of_burst(), Some(SerialFraming::Rtu));
+
+        // a request followed by its response without a silence
+        assert!(contains_rtu_frame(
+            &[RTU_REQUEST, &[0x01, 0x03, 0x02, 0x00]].concat()
+        ));
+        assert!(!contains_rtu_frame(&RTU_REQUEST[..7]));
+        assert!(!contains_rtu_frame(&[
+            0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0B
+        ]));
+    }
+
+    #[test]
+    fn recognizes_ascii_frames_split_across_bursts() {
+        let mut sniffer = Sniffer::default();
+        sniffer.receive(b"noise");
+        sniffer.receive(&ASCII_REQUEST[..6]);
+        assert_eq!(sniffer.end_of_burst(), None);
+        assert_eq!(sniffer.ascii(), None);
+        sniffer.receive(&ASCII_REQUEST[6..]);
+        assert_eq!(sniffer.end_of_burst(), None);
+        assert_eq!(sniffer.ascii(), Some(SerialFraming::Ascii));
+
+        assert!(!contains_ascii_frame(b":010300000001FC\r\n"));
+        assert!(!contains_ascii_frame(b":0103000000G1FB\r\n"));
+    }
+}
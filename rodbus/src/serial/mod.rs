@@ -1,9 +1,21 @@
 use tokio_serial::SerialStream;
 pub use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
 
+pub use detect::{detect_serial_settings, DetectOptions, DetectedSettings};
+pub use driver::{DriverEnable, DriverEnableLine};
+pub use framing::{sniff_serial_framing, SerialFraming};
+pub use ports::{available_serial_ports, AvailableSerialPort, UsbPortInfo};
+pub use sniffer::{spawn_serial_sniffer, SniffedEvent, SniffedFrame};
+
 pub(crate) mod client;
+pub(crate) mod detect;
+pub(crate) mod driver;
+pub(crate) mod echo;
 pub(crate) mod frame;
+pub(crate) mod framing;
+pub(crate) mod ports;
 pub(crate) mod server;
+pub(crate) mod sniffer;
 
 /// Serial port settings
 #[derive(Copy, Clone, Debug)]
@@ -18,6 +30,11 @@ pub struct SerialSettings {
     pub stop_bits: StopBits,
     /// Parity setting
     pub parity: Parity,
+    /// Transmit-enable control of an RS-485 transceiver, if it requires one
+    pub driver_enable: Option<DriverEnable>,
+    /// Discard the echo of the transmitted bytes, for the 2-wire RS-485 adapters that receive
+    /// what they transmit
+    pub suppress_echo: bool,
 }
 
 impl SerialSettings {
@@ -32,6 +49,22 @@ impl SerialSettings {
             .stop_bits(self.stop_bits)
             .parity(self.parity)
     }
+
+    /// Change the settings of an open port
+    pub(crate) fn reconfigure(&self, port: &mut SerialStream) -> tokio_serial::Result<()> {
+        use tokio_serial::SerialPort;
+
+        port.set_baud_rate(self.baud_rate)?;
+        port.set_data_bits(self.data_bits)?;
+        port.set_flow_control(self.flow_control)?;
+        port.set_stop_bits(self.stop_bits)?;
+        port.set_parity(self.parity)?;
+        if let Some(driver_enable) = self.driver_enable {
+            driver_enable.set_level(port, false)?;
+        }
+        // whatever was received with the previous settings is garbage
+        port.clear(tokio_serial::ClearBuffer::Input)
+    }
 }
 
 impl Default for SerialSettings {
@@ -42,11 +75,18 @@ impl Default for SerialSettings {
             flow_control: FlowControl::None,
             stop_bits: StopBits::One,
             parity: Parity::None,
+            driver_enable: None,
+            suppress_echo: false,
         }
     }
 }
 
 pub(crate) fn open(path: &str, settings: SerialSettings) -> tokio_serial::Result<SerialStream> {
     let builder = settings.apply(tokio_serial::new(path, settings.baud_rate));
-    SerialStream::open(&builder)
+    let mut port = SerialStream::open(&builder)?;
+    if let Some(driver_enable) = settings.driver_enable {
+        // listen to the bus until something is transmitted
+        driver_enable.set_level(&mut port, false)?;
+    }
+    Ok(port)
 }
@@ -0,0 +1,167 @@
+/// Serial port found by [`available_serial_ports`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvailableSerialPort {
+    /// Path with which the port is opened, e.g. `/dev/ttyUSB0` on Linux or `COM3` on Windows
+    pub path: String,
+    /// Identification of the USB adapter, if the port is provided by one
+    pub usb: Option<UsbPortInfo>,
+}
+
+/// Identification of a USB serial adapter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsbPortInfo {
+    /// Vendor id
+    pub vid: u16,
+    /// Product id
+    pub pid: u16,
+    /// Serial number of the adapter, if it has one
+    pub serial_number: Option<String>,
+    /// Manufacturer string, if the adapter reports it
+    pub manufacturer: Option<String>,
+    /// Product string, if the adapter reports it
+    pub product: Option<String>,
+}
+
+/// List the serial ports of the system, e.g. to offer a choice of port in a user interface
+///
+/// On Linux the ports are read from `/sys/class/tty`, which doesn't require `libudev`. On the
+/// other platforms they are enumerated by the operating system, and the USB identification may
+/// not be available for every adapter.
+pub fn available_serial_ports() -> Result<Vec<AvailableSerialPort>, std::io::Error> {
+    #[cfg(target_os = "linux")]
+    {
+        sysfs::scan(
+            std::path::Path::new("/sys/class/tty"),
+            std::path::Path::new("/dev"),
+        )
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let ports = tokio_serial::available_ports()?;
+        Ok(ports
+            .into_iter()
+            .map(|port| AvailableSerialPort {
+                path: port.port_name,
+                usb: match port.port_type {
+                    tokio_serial::SerialPortType::UsbPort(info) => Some(UsbPortInfo {
+                        vid: info.vid,
+                        pid: info.pid,
+                        serial_number: info.serial_number,
+                        manufacturer: info.manufacturer,
+                        product: info.product,
+                    }),
+                    _ => None,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sysfs {
+    use std::path::Path;
+
+    use super::{AvailableSerialPort, UsbPortInfo};
+
+    /// List the ttys of the class directory that are backed by a device
+    pub(super) fn scan(
+        class: &Path,
+        dev: &Path,
+    ) -> Result<Vec<AvailableSerialPort>, std::io::Error> {
+        let mut ports = Vec::new();
+        for entry in class.read_dir()? {
+            let entry = entry?;
+            // virtual terminals and pseudo terminals have no device
+            let device = match entry.path().join("device").canonicalize() {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            // the 8250 driver registers ports whose UART is missing with the unknown type
+            if read(&entry.path(), "type").as_deref() == Some("0") {
+                continue;
+            }
+            ports.push(AvailableSerialPort {
+                path: dev.join(entry.file_name()).to_string_lossy().to_string(),
+                usb: device.ancestors().find_map(usb_info),
+            });
+        }
+        ports.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(ports)
+    }
+
+    /// Read the identification of a USB device directory
+    fn usb_info(dir: &Path) -> Option<UsbPortInfo> {
+        let id = |name| u16::from_str_radix(&read(dir, name)?, 16).ok();
+        Some(UsbPortInfo {
+            vid: id("idVendor")?,
+            pid: id("idProduct")?,
+            serial_number: read(dir, "serial"),
+            manufacturer: read(dir, "manufacturer"),
+            product: read(dir, "product"),
+        })
+    }
+
+    fn read(dir: &Path, name: &str) -> Option<String> {
+        let value = std::fs::read_to_string(dir.join(name)).ok()?;
+        Some(value.trim().to_string())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_usb_identification_from_sysfs() {
+        let root = std::env::temp_dir().join(format!("rodbus-ports-{}", std::process::id()));
+        let usb = root.join("devices/usb1/1-1");
+        let interface = usb.join("1-1:1.0/ttyUSB0");
+        let uart = root.join("devices/platform/serial8250");
+        let class = root.join("class/tty");
+        for dir in [&interface, &uart, &class.join("tty0")] {
+            create_dir_all(dir).unwrap();
+        }
+        write(usb.join("idVendor"), "0403\n").unwrap();
+        write(usb.join("idProduct"), "6001\n").unwrap();
+        write(usb.join("product"), "FT232R USB UART\n").unwrap();
+
+        // USB serial ttys have no type, the second UART is missing
+        for (name, device, kind) in [
+            ("ttyUSB0", &interface, None),
+            ("ttyS0", &uart, Some("4\n")),
+            ("ttyS1", &uart, Some("0\n")),
+        ] {
+            create_dir_all(class.join(name)).unwrap();
+            symlink(device, class.join(name).join("device")).unwrap();
+            if let Some(kind) = kind {
+                write(class.join(name).join("type"), kind).unwrap();
+            }
+        }
+
+        let ports = sysfs::scan(&class, Path::new("/dev"));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            ports.unwrap(),
+            vec![
+                AvailableSerialPort {
+                    path: "/dev/ttyS0".to_string(),
+                    usb: None,
+                },
+                AvailableSerialPort {
+                    path: "/dev/ttyUSB0".to_string(),
+                    usb: Some(UsbPortInfo {
+                        vid: 0x0403,
+                        pid: 0x6001,
+                        serial_number: None,
+                        manufacturer: None,
+                        product: Some("FT232R USB UART".to_string()),
+                    }),
+                },
+            ]
+        );
+    }
+}
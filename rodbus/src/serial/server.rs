@@ -24,7 +24,7 @@ where
                     self.retry.reset();
                     tracing::info!("opened port");
                     // run an open port until shutdown or failure
-                    let mut phys = PhysLayer::new_serial(serial);
+                    let mut phys = PhysLayer::new_serial(serial, &self.settings);
                     if let RequestError::Shutdown = self.session.run(&mut phys).await {
                         return Shutdown;
                     }
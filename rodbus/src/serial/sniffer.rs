@@ -0,0 +1,322 @@
+use std::time::{Duration, SystemTime};
+
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use crate::common::buffer::ReadBuffer;
+use crate::decode::FrameDecodeLevel;
+use crate::serial::frame::RtuParser;
+use crate::serial::SerialSettings;
+use crate::types::UnitId;
+
+/// RTU frame observed on a serial line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SniffedFrame {
+    /// Time at which the end of the frame was observed
+    pub timestamp: SystemTime,
+    /// Unit id of the frame
+    pub unit: UnitId,
+    /// Function code and data of the frame, without the unit id and the CRC
+    pub pdu: Vec<u8>,
+}
+
+impl SniffedFrame {
+    /// Raw function code of the frame, with the exception bit of exception responses
+    pub fn function(&self) -> u8 {
+        self.pdu.first().copied().unwrap_or(0)
+    }
+}
+
+/// Traffic observed by a serial sniffer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SniffedEvent {
+    /// A request and the response that answered it
+    Transaction {
+        /// Request sent by the master
+        request: SniffedFrame,
+        /// Response or exception returned by the unit
+        response: SniffedFrame,
+        /// Time between the end of the request and the end of the response
+        latency: Duration,
+    },
+    /// A request that wasn't answered within the response timeout, or a broadcast request
+    Unanswered(SniffedFrame),
+    /// Bytes that are not a valid frame, e.g. because of a collision or a baud rate mismatch
+    Unrecognized(Vec<u8>),
+}
+
+/// Spawns a task onto the runtime that listens to a serial line without ever transmitting, and
+/// decodes the request/response pairs exchanged by the master and the units on the bus
+///
+/// Frames are delimited by the silences of the line. A response is paired with the last request
+/// if it comes from the same unit, has the same function code, and is received within
+/// `response_timeout`. The events are queued in the returned receiver, which can be turned into
+/// a `Stream` with e.g. `tokio_stream::wrappers::ReceiverStream`. Events are dropped when the
+/// queue is full. The task completes when the receiver is dropped or the port fails.
+///
+/// * `path` - Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+/// * `settings` - Serial port settings, which must be those of the bus
+/// * `response_timeout` - Time after which a request is reported as unanswered
+/// * `max_queued_events` - The maximum number of events waiting in the receiver
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn spawn_serial_sniffer(
+    path: &str,
+    settings: SerialSettings,
+    response_timeout: Duration,
+    max_queued_events: usize,
+) -> Result<Receiver<SniffedEvent>, std::io::Error> {
+    let port = crate::serial::open(path, settings)?;
+    let (tx, rx) = tokio::sync::mpsc::channel(max_queued_events.max(1));
+    let path = path.to_string();
+    tokio::spawn(
+        async move {
+            if let Err(err) = sniff(port, response_timeout, tx).await {
+                tracing::warn!("sniffer stopped: {}", err);
+            }
+        }
+        .instrument(tracing::info_span!("Modbus-Sniffer-RTU", "port" = ?path)),
+    );
+    Ok(rx)
+}
+
+async fn sniff(
+    mut port: tokio_serial::SerialStream,
+    response_timeout: Duration,
+    tx: Sender<SniffedEvent>,
+) -> Result<(), std::io::Error> {
+    let gap = crate::common::phys::calculate_inter_character_delay(&port);
+    let mut pairing = Pairing::new(response_timeout);
+    let mut burst = Vec::new();
+    let mut buffer = [0; 256];
+
+    loop {
+        // the end of a frame is detected by a silence of 3.5 characters
+        let deadline = if burst.is_empty() {
+            pairing.deadline()
+        } else {
+            Some(Instant::now() + gap)
+        };
+        let read = async {
+            match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, port.read(&mut buffer)).await,
+                None => Ok(port.read(&mut buffer).await),
+            }
+        };
+
+        let events = tokio::select! {
+            _ = tx.closed() => return Ok(()),
+            result = read => match result {
+                Ok(Ok(0)) => return Ok(()),
+                Ok(Ok(count)) => {
+                    burst.extend_from_slice(&buffer[..count]);
+                    continue;
+                }
+                Ok(Err(err)) => return Err(err),
+                Err(_) => {
+                    let now = Instant::now();
+                    let mut events = pairing.expire(now);
+                    events.extend(pairing.receive(&std::mem::take(&mut burst), now));
+                    events
+                }
+            }
+        };
+
+        for event in events {
+            if tx.try_send(event).is_err() {
+                tracing::warn!("sniffer queue is full, dropping event");
+            }
+        }
+    }
+}
+
+struct PendingRequest {
+    frame: SniffedFrame,
+    received: Instant,
+}
+
+/// Pairs the frames of successive bursts into events
+struct Pairing {
+    response_timeout: Duration,
+    pending: Option<PendingRequest>,
+}
+
+impl Pairing {
+    fn new(response_timeout: Duration) -> Self {
+        Self {
+            response_timeout,
+            pending: None,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending
+            .as_ref()
+            .map(|x| x.received + self.response_timeout)
+    }
+
+    /// Report the pending request as unanswered if its response timeout has elapsed
+    fn expire(&mut self, now: Instant) -> Vec<SniffedEvent> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.take_unanswered().into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn take_unanswered(&mut self) -> Option<SniffedEvent> {
+        self.pending
+            .take()
+            .map(|x| SniffedEvent::Unanswered(x.frame))
+    }
+
+    /// Decode the frames of a burst, which may hold a request immediately followed by its response
+    fn receive(&mut self, mut burst: &[u8], now: Instant) -> Vec<SniffedEvent> {
+        let mut events = Vec::new();
+        while !burst.is_empty() {
+            if let Some((response, length)) = self.parse_response(burst) {
+                if let Some(request) = self.pending.take() {
+                    events.push(SniffedEvent::Transaction {
+                        request: request.frame,
+                        response,
+                        latency: now.saturating_duration_since(request.received),
+                    });
+                    burst = &burst[length..];
+                    continue;
+                }
+            }
+
+            match parse(RtuParser::new_request_parser(), burst) {
+                Some((request, length)) => {
+                    events.extend(self.take_unanswered());
+                    if request.unit == UnitId::broadcast() {
+                        events.push(SniffedEvent::Unanswered(request));
+                    } else {
+                        self.pending = Some(PendingRequest {
+                            frame: request,
+                            received: now,
+                        });
+                    }
+                    burst = &burst[length..];
+                }
+                None => {
+                    events.push(SniffedEvent::Unrecognized(burst.to_vec()));
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    /// Parse the beginning of the burst as the response to the pending request
+    fn parse_response(&self, burst: &[u8]) -> Option<(SniffedFrame, usize)> {
+        let request = &self.pending.as_ref()?.frame;
+        let (response, length) = parse(RtuParser::new_response_parser(), burst)?;
+        let matches =
+            response.unit == request.unit && response.function() & 0x7F == request.function();
+        matches.then_some((response, length))
+    }
+}
+
+/// Parse a frame at the beginning of the bytes and return it with its length including the CRC
+fn parse(mut parser: RtuParser, bytes: &[u8]) -> Option<(SniffedFrame, usize)> {
+    let mut buffer = ReadBuffer::from_slice(bytes);
+    let available = buffer.len();
+    let frame = parser
+        .parse(&mut buffer, FrameDecodeLevel::Nothing)
+        .ok()??;
+    let frame = SniffedFrame {
+        timestamp: SystemTime::now(),
+        unit: frame.header.destination.into_unit_id(),
+        pdu: frame.payload().to_vec(),
+    };
+    Some((frame, available - buffer.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // read one holding register at address 0 of unit 1, and its response
+    const REQUEST: &[u8] = &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+    const RESPONSE: &[u8] = &[0x01, 0x03, 0x02, 0x00, 0x2A, 0x39, 0x9B];
+    const EXCEPTION: &[u8] = &[0x01, 0x83, 0x02, 0xC0, 0xF1];
+
+    fn pdus(event: &SniffedEvent) -> (Vec<u8>, Vec<u8>) {
+        match event {
+            SniffedEvent::Transaction {
+                request, response, ..
+            } => (request.pdu.clone(), response.pdu.clone()),
+            _ => panic!("not a transaction: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn pairs_responses_with_the_request_of_the_same_unit_and_function() {
+        let mut pairing = Pairing::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert!(pairing.receive(REQUEST, start).is_empty());
+
+        let events = pairing.receive(RESPONSE, start + Duration::from_millis(20));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            pdus(&events[0]),
+            (REQUEST[1..6].to_vec(), RESPONSE[1..5].to_vec())
+        );
+        match &events[0] {
+            SniffedEvent::Transaction { latency, .. } => {
+                assert_eq!(*latency, Duration::from_millis(20))
+            }
+            _ => unreachable!(),
+        }
+
+        // exceptions answer requests too, even within the same burst
+        let events = pairing.receive(&[REQUEST, EXCEPTION].concat(), start);
+        assert_eq!(events.len(), 1);
+        assert_eq!(pdus(&events[0]).1, vec![0x83, 0x02]);
+
+        // a response without a request is not recognized
+        assert_eq!(
+            pairing.receive(RESPONSE, start),
+            vec![SniffedEvent::Unrecognized(RESPONSE.to_vec())]
+        );
+    }
+
+    #[test]
+    fn reports_unanswered_requests() {
+        let mut pairing = Pairing::new(Duration::from_secs(1));
+        let start = Instant::now();
+        pairing.receive(REQUEST, start);
+
+        // another request replaces the first one
+        let events = pairing.receive(REQUEST, start);
+        assert!(matches!(events.as_slice(), [SniffedEvent::Unanswered(_)]));
+
+        // and times out
+        assert!(pairing
+            .expire(start + Duration::from_millis(999))
+            .is_empty());
+        let events = pairing.expire(start + Duration::from_secs(1));
+        assert!(matches!(events.as_slice(), [SniffedEvent::Unanswered(x)] if x.function() == 0x03));
+        assert_eq!(pairing.deadline(), None);
+    }
+
+    #[test]
+    fn reports_garbage_and_broadcasts() {
+        let mut pairing = Pairing::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert_eq!(
+            pairing.receive(&REQUEST[..7], start),
+            vec![SniffedEvent::Unrecognized(REQUEST[..7].to_vec())]
+        );
+
+        // write single register 1 to the value 0x0003 on every unit
+        let broadcast = [0x00, 0x06, 0x00, 0x01, 0x00, 0x03, 0x99, 0xDA];
+        let events = pairing.receive(&broadcast, start);
+        assert!(
+            matches!(events.as_slice(), [SniffedEvent::Unanswered(x)] if x.unit == UnitId::broadcast())
+        );
+        assert_eq!(pairing.deadline(), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Connection accepted by a TCP or TLS server, passed to an [`AcceptHandler`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AcceptedConnection<'a> {
+    /// Address of the client
+    pub peer: SocketAddr,
+    /// Role of the client certificate of a Secure Modbus session, see
+    /// [`crate::server::spawn_tls_server_task_with_authz`]
+    pub role: Option<&'a str>,
+}
+
+/// Value attached to a session by an [`AcceptHandler`] and passed to the request handlers with
+/// [`crate::server::RequestHandler::set_session_context`]
+#[derive(Clone)]
+pub struct SessionContext {
+    inner: Arc<dyn Any + Send + Sync>,
+}
+
+impl SessionContext {
+    /// Create a context from any value
+    pub fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Self {
+            inner: Arc::new(value),
+        }
+    }
+
+    /// Value of the context if it is of type `T`
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.inner.downcast_ref()
+    }
+}
+
+impl std::fmt::Debug for SessionContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionContext")
+    }
+}
+
+/// Decision of an [`AcceptHandler`] about a connection
+#[derive(Clone, Debug)]
+pub enum AcceptDecision {
+    /// Close the connection without processing any request
+    Reject,
+    /// Process the requests of the connection, optionally with a context passed to the handlers
+    Accept(Option<SessionContext>),
+}
+
+/// Handler invoked by a TCP or TLS server on each connection it accepts, e.g. to implement a
+/// custom authentication scheme
+///
+/// The handler is invoked once the connection passed the [`crate::server::AddressFilter`] and,
+/// for a TLS server, completed its handshake. It is installed using
+/// [`crate::server::ServerHandle::set_accept_handler`].
+pub trait AcceptHandler: Send + Sync + 'static {
+    /// Moves an accept handler implementation into a `Arc<dyn AcceptHandler>`
+    /// suitable for passing to the server
+    fn wrap(self) -> Arc<dyn AcceptHandler>
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+
+    /// Decide whether the connection is accepted
+    fn accept(&self, connection: AcceptedConnection) -> AcceptDecision;
+}
@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::str::FromStr;
 
 /// Represents IPv4 addresses which may contain "*" wildcards
@@ -42,7 +43,7 @@ impl FromStr for WildcardIPv4 {
 }
 
 impl WildcardIPv4 {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
         fn bm(b: u8, other: Option<u8>) -> bool {
             match other {
                 Some(x) => b == x,
@@ -50,16 +51,138 @@ impl WildcardIPv4 {
             }
         }
 
-        match addr {
-            std::net::IpAddr::V4(x) => {
+        match addr.to_canonical() {
+            IpAddr::V4(x) => {
                 let [b3, b2, b1, b0] = x.octets();
                 bm(b3, self.b3) && bm(b2, self.b2) && bm(b1, self.b1) && bm(b0, self.b0)
             }
-            std::net::IpAddr::V6(_) => false,
+            IpAddr::V6(_) => false,
         }
     }
 }
 
+/// Range of IPv4 or IPv6 addresses in CIDR notation, e.g. `192.168.0.0/24` or `fd00::/8`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+/// Error returned when a network is not in CIDR notation or its prefix length is too long
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BadIpNetwork;
+
+impl IpNetwork {
+    /// Create a network from an address and a prefix length, which may not exceed the length of
+    /// the address (32 bits for IPv4, 128 bits for IPv6)
+    ///
+    /// The bits of the address beyond the prefix are ignored
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, BadIpNetwork> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return Err(BadIpNetwork);
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    /// Network containing a single address
+    pub fn host(addr: IpAddr) -> Self {
+        let prefix = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { addr, prefix }
+    }
+
+    /// Returns true if the address is within the network. IPv4-mapped IPv6 addresses, e.g.
+    /// `::ffff:10.0.0.1`, are compared as the IPv4 address they map. Otherwise IPv4 networks never
+    /// contain IPv6 addresses and vice versa.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        fn mask_eq(a: u128, b: u128, bits: u8, prefix: u8) -> bool {
+            if prefix == 0 {
+                return true;
+            }
+            let shift = u32::from(bits - prefix);
+            (a >> shift) == (b >> shift)
+        }
+
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(x)) => mask_eq(
+                u128::from(u32::from(net)),
+                u128::from(u32::from(x)),
+                32,
+                self.prefix,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(x)) => {
+                mask_eq(u128::from(net), u128::from(x), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = BadIpNetwork;
+
+    /// Parse a network in CIDR notation. An address without a prefix length is a single host.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            None => Ok(Self::host(s.parse().map_err(|_| BadIpNetwork)?)),
+            Some((addr, prefix)) => {
+                let addr = addr.parse().map_err(|_| BadIpNetwork)?;
+                let prefix = prefix.parse().map_err(|_| BadIpNetwork)?;
+                Self::new(addr, prefix)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Lists of networks that are allowed and denied to connect
+///
+/// An address is accepted if it is in none of the denied networks and, unless the allow list is
+/// empty, in at least one of the allowed networks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkFilter {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl NetworkFilter {
+    /// Create a filter that accepts every address
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a network to the allow list
+    pub fn allow(mut self, network: IpNetwork) -> Self {
+        self.allow.push(network);
+        self
+    }
+
+    /// Add a network to the deny list, which takes precedence over the allow list
+    pub fn deny(mut self, network: IpNetwork) -> Self {
+        self.deny.push(network);
+        self
+    }
+
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        if self.deny.iter().any(|x| x.contains(addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|x| x.contains(addr))
+    }
+}
+
 /// Address filter used to control which master address(es) may connect to an outstation.
 ///
 /// Note: User code cannot exhaustively match against this enum as new variants may be added in the future.
@@ -69,28 +192,33 @@ pub enum AddressFilter {
     /// Allow any address
     Any,
     /// Allow a specific address
-    Exact(std::net::IpAddr),
+    Exact(IpAddr),
     /// Allow any of set of addresses
-    AnyOf(std::collections::HashSet<std::net::IpAddr>),
+    AnyOf(std::collections::HashSet<IpAddr>),
     /// Matches against an IPv4 address with wildcards
     WildcardIpv4(WildcardIPv4),
+    /// Matches against lists of allowed and denied networks
+    Networks(NetworkFilter),
 }
 
 impl AddressFilter {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    /// A dual-stack listener sees IPv4 peers as IPv4-mapped IPv6 addresses, which are matched as
+    /// the IPv4 address they map
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
+        let canonical = addr.to_canonical();
         match self {
             AddressFilter::Any => true,
-            AddressFilter::Exact(x) => *x == addr,
-            AddressFilter::AnyOf(set) => set.contains(&addr),
+            AddressFilter::Exact(x) => x.to_canonical() == canonical,
+            AddressFilter::AnyOf(set) => set.contains(&addr) || set.contains(&canonical),
             AddressFilter::WildcardIpv4(wc) => wc.matches(addr),
+            AddressFilter::Networks(filter) => filter.matches(addr),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{BadIpv4Wildcard, WildcardIPv4};
-    use std::net::IpAddr;
+    use super::*;
 
     #[test]
     fn parses_address_with_subnet_wildcard() {
@@ -147,5 +275,69 @@ mod test {
 
         assert!(wc.matches(ip1));
         assert!(!wc.matches(ip2));
+        assert!(wc.matches("::ffff:192.168.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn parses_networks_in_cidr_notation() {
+        let net: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert_eq!(
+            net,
+            IpNetwork::new("10.1.0.0".parse().unwrap(), 16).unwrap()
+        );
+        assert_eq!(net.to_string(), "10.1.0.0/16");
+
+        let host: IpNetwork = "fd00::1".parse().unwrap();
+        assert_eq!(host, IpNetwork::host("fd00::1".parse().unwrap()));
+
+        for x in [
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0/8",
+            "10.0.0.0/",
+            "10.0.0.0/a",
+        ] {
+            assert_eq!(x.parse::<IpNetwork>(), Err(BadIpNetwork));
+        }
+    }
+
+    #[test]
+    fn network_contains_addresses_within_prefix() {
+        let net: IpNetwork = "192.168.4.0/22".parse().unwrap();
+        assert!(net.contains("192.168.7.255".parse().unwrap()));
+        assert!(!net.contains("192.168.8.0".parse().unwrap()));
+        assert!(net.contains("::ffff:192.168.4.1".parse().unwrap()));
+        assert!(!net.contains("::ffff:192.168.8.1".parse().unwrap()));
+
+        let all: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("255.255.255.255".parse().unwrap()));
+
+        let v6: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fdff::1".parse().unwrap()));
+        assert!(!v6.contains("fe80::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn denied_networks_take_precedence_over_allowed_ones() {
+        let filter = AddressFilter::Networks(
+            NetworkFilter::new()
+                .allow("10.0.0.0/8".parse().unwrap())
+                .deny("10.0.5.0/24".parse().unwrap()),
+        );
+        assert!(filter.matches("10.0.4.1".parse().unwrap()));
+        assert!(!filter.matches("10.0.5.1".parse().unwrap()));
+        assert!(!filter.matches("172.16.0.1".parse().unwrap()));
+        // the IPv4 peers of a dual-stack listener
+        assert!(filter.matches("::ffff:10.0.4.1".parse().unwrap()));
+        assert!(!filter.matches("::ffff:10.0.5.1".parse().unwrap()));
+
+        let deny_only =
+            AddressFilter::Networks(NetworkFilter::new().deny("10.0.0.0/8".parse().unwrap()));
+        assert!(deny_only.matches("172.16.0.1".parse().unwrap()));
+        assert!(!deny_only.matches("10.0.0.1".parse().unwrap()));
+        assert!(!deny_only.matches("::ffff:10.0.0.1".parse().unwrap()));
+
+        let exact = AddressFilter::Exact("10.0.0.1".parse().unwrap());
+        assert!(exact.matches("::ffff:10.0.0.1".parse().unwrap()));
     }
 }
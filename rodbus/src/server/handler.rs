@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
 use crate::exception::ExceptionCode;
+use crate::server::accept::SessionContext;
 use crate::server::{WriteCoils, WriteRegisters};
 use crate::types::*;
 
@@ -23,6 +24,13 @@ pub trait RequestHandler: Send + 'static {
         Arc::new(Mutex::new(Box::new(self)))
     }
 
+    /// Receive the context that the [`crate::server::AcceptHandler`] attached to the session of
+    /// the next request, or `None` if it has none
+    ///
+    /// This is invoked before each request, on the same lock of the handler, so that handlers
+    /// shared by several sessions can tell them apart.
+    fn set_session_context(&mut self, _context: Option<&SessionContext>) {}
+
     /// Read single coil or return an ExceptionCode
     fn read_coil(&self, _address: u16) -> Result<bool, ExceptionCode> {
         Err(ExceptionCode::IllegalFunction)
@@ -0,0 +1,36 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::time::Instant;
+
+/// Session closed by a TCP or TLS server that accepts a connection while it has its maximum
+/// number of sessions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SessionEviction {
+    /// Close the session that was accepted first
+    #[default]
+    Oldest,
+    /// Close the session that received a frame the longest time ago
+    LeastRecentlyActive,
+}
+
+/// Time at which a session last received a frame, shared with the server task
+#[derive(Clone)]
+pub(crate) struct Activity {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Activity {
+    pub(crate) fn new() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub(crate) fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    pub(crate) fn last(&self) -> Instant {
+        *self.last.lock().unwrap()
+    }
+}
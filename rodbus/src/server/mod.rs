@@ -1,14 +1,21 @@
+use std::any::TypeId;
 use std::net::SocketAddr;
 
 use tracing::Instrument;
 
+use crate::audit::AuditSink;
 use crate::decode::DecodeLevel;
 use crate::server::task::ServerSetting;
 use crate::tcp::server::{ServerTask, TcpServerConnectionHandler};
 
 /// server handling
+mod accept;
 mod address_filter;
 pub(crate) mod handler;
+pub(crate) mod idle;
+pub(crate) mod permissions;
+pub(crate) mod rate_limit;
+pub(crate) mod reload;
 pub(crate) mod request;
 pub(crate) mod response;
 pub(crate) mod task;
@@ -17,15 +24,24 @@ pub(crate) mod types;
 /// Fine for this to be a constant since the corresponding channel is only used to change settings
 pub(crate) const SERVER_SETTING_CHANNEL_CAPACITY: usize = 8;
 
-use crate::error::Shutdown;
+use crate::error::{ReloadError, Shutdown};
 
+pub use accept::*;
 pub use address_filter::*;
 pub use handler::*;
+pub use idle::SessionEviction;
+pub use permissions::{WriteAccess, WritePermissions};
+pub use rate_limit::{RateLimit, RateLimitAction, RateLimitScope};
+pub use reload::ServerReload;
 pub use types::*;
 
 // re-export to the public API
 #[cfg(feature = "tls")]
 pub use crate::tcp::tls::server::TlsServerConfig;
+#[cfg(feature = "dangerous-tls")]
+pub use crate::tcp::tls::verify::{
+    BadFingerprint, CertificateFingerprint, CertificateVerifier, FingerprintVerifier,
+};
 #[cfg(feature = "tls")]
 pub use crate::tcp::tls::*;
 
@@ -33,14 +49,19 @@ pub use crate::tcp::tls::*;
 #[derive(Debug)]
 pub struct ServerHandle {
     tx: tokio::sync::mpsc::Sender<ServerSetting>,
+    /// Type of the handlers the server was spawned with, which those of a reload must match
+    handlers: TypeId,
 }
 
 impl ServerHandle {
-    /// Construct a [ServerHandle] from its fields
+    /// Construct a [ServerHandle] from its fields and the type of the handlers of the server
     ///
     /// This function is only required for the C bindings
-    pub fn new(tx: tokio::sync::mpsc::Sender<ServerSetting>) -> Self {
-        ServerHandle { tx }
+    pub fn new<T: RequestHandler>(tx: tokio::sync::mpsc::Sender<ServerSetting>) -> Self {
+        ServerHandle {
+            tx,
+            handlers: TypeId::of::<T>(),
+        }
     }
 
     /// Change the decoding level for future sessions and all active sessions
@@ -48,6 +69,137 @@ impl ServerHandle {
         self.tx.send(ServerSetting::ChangeDecoding(level)).await?;
         Ok(())
     }
+
+    /// Override the decoding level of the sessions from an IP address, e.g. to trace the
+    /// requests of one client, or restore the level of the server with `None`
+    ///
+    /// The level applies to the active sessions and future sessions from the address, and takes
+    /// precedence over [`ServerHandle::set_decode_level`] so that the other sessions can keep a
+    /// low level. RTU servers ignore it.
+    pub async fn set_peer_decode_level(
+        &mut self,
+        peer: std::net::IpAddr,
+        level: Option<DecodeLevel>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::PeerDecoding(peer, level))
+            .await?;
+        Ok(())
+    }
+
+    /// Limit the rate at which requests are processed, or remove the limit with `None`
+    ///
+    /// The limit applies to all active sessions and future sessions. Changing it restores the full
+    /// allowance of every session.
+    pub async fn set_rate_limit(&mut self, limit: Option<RateLimit>) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::RateLimit(limit)).await?;
+        Ok(())
+    }
+
+    /// Install an [`AuditSink`] that records every write passed to the handlers of the server,
+    /// replacing any previously installed sink
+    ///
+    /// The sink applies to all active sessions and future sessions. Passing `None` removes it.
+    pub async fn set_audit_sink(
+        &mut self,
+        sink: Option<std::sync::Arc<dyn AuditSink>>,
+    ) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::Audit(sink)).await?;
+        Ok(())
+    }
+
+    /// Restrict which coils and holding registers may be written, or remove the restrictions
+    /// with `None`
+    ///
+    /// The permissions apply to all active sessions and future sessions, and to the handlers of
+    /// every unit id.
+    pub async fn set_write_permissions(
+        &mut self,
+        permissions: Option<WritePermissions>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::WritePermissions(
+                permissions.map(std::sync::Arc::new),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the filter of the addresses that may connect to a TCP or TLS server
+    ///
+    /// The filter is evaluated when connections are accepted, so sessions that are already active
+    /// are not closed.
+    pub async fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::AddressFilter(filter)).await?;
+        Ok(())
+    }
+
+    /// Replace the handlers, write permissions, rate limit and maximum number of sessions of the
+    /// server at once, e.g. when the points it serves are provisioned again
+    ///
+    /// The configuration applies to all active sessions and future sessions, which are not
+    /// closed. A reload whose handlers are of a different type than those the server was spawned
+    /// with fails with [`ReloadError::HandlerType`] and leaves the configuration unchanged.
+    pub async fn reload<T: RequestHandler>(
+        &mut self,
+        config: ServerReload<T>,
+    ) -> Result<(), ReloadError> {
+        if TypeId::of::<T>() != self.handlers {
+            return Err(ReloadError::HandlerType);
+        }
+        self.tx
+            .send(ServerSetting::Reload(config.into_reload()))
+            .await
+            .map_err(Shutdown::from)?;
+        Ok(())
+    }
+
+    /// Close the sessions of a TCP or TLS server that don't receive any frame for the duration,
+    /// or never close them with `None`
+    ///
+    /// The timeout applies to all active sessions and future sessions. It is disabled by
+    /// default. RTU servers ignore it.
+    pub async fn set_idle_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::IdleTimeout(timeout)).await?;
+        Ok(())
+    }
+
+    /// Change which session a TCP or TLS server closes when it accepts a connection while it
+    /// has its maximum number of sessions, which defaults to [`SessionEviction::Oldest`]
+    pub async fn set_session_eviction(
+        &mut self,
+        eviction: SessionEviction,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::SessionEviction(eviction))
+            .await?;
+        Ok(())
+    }
+
+    /// Install an [`AcceptHandler`] invoked on each connection accepted by a TCP or TLS server,
+    /// replacing any previously installed handler, or remove it with `None`
+    ///
+    /// The handler applies to the connections accepted from then on. RTU servers ignore it.
+    pub async fn set_accept_handler(
+        &mut self,
+        handler: Option<std::sync::Arc<dyn AcceptHandler>>,
+    ) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::AcceptHandler(handler)).await?;
+        Ok(())
+    }
+
+    /// Replace the TLS configuration of a TLS server, e.g. to rotate its certificates and keys
+    ///
+    /// The configuration is used by the handshakes of new connections. Established sessions are
+    /// not closed and keep the configuration they were accepted with. TCP servers ignore it.
+    #[cfg(feature = "tls")]
+    pub async fn set_tls_config(&mut self, config: TlsServerConfig) -> Result<(), Shutdown> {
+        self.tx.send(ServerSetting::TlsConfig(config)).await?;
+        Ok(())
+    }
 }
 
 /// Spawns a TCP server task onto the runtime. This method can only
@@ -89,7 +241,7 @@ pub async fn spawn_tcp_server_task<T: RequestHandler>(
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(ServerHandle::new::<T>(tx))
 }
 
 /// Spawns a RTU server task onto the runtime.
@@ -110,9 +262,9 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
     let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
-    let session = crate::server::task::SessionTask::new(
+    let session = task::SessionTask::new(
         handlers,
-        crate::server::task::AuthorizationType::None,
+        task::AuthorizationType::None,
         crate::common::frame::FrameWriter::rtu(),
         crate::common::frame::FramedReader::rtu_request(),
         rx,
@@ -136,7 +288,7 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(ServerHandle::new::<T>(tx))
 }
 
 /// Spawns a "raw" TLS server task onto the runtime. This TLS server does NOT require that
@@ -242,5 +394,5 @@ async fn spawn_tls_server_task_impl<T: RequestHandler>(
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(ServerHandle::new::<T>(tx))
 }
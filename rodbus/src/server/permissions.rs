@@ -0,0 +1,208 @@
+use crate::exception::ExceptionCode;
+use crate::types::AddressRange;
+
+/// Who may write to a range of addresses of a [`WritePermissions`] map
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WriteAccess {
+    /// Writes are rejected with [`ExceptionCode::IllegalDataAddress`]
+    ReadOnly,
+    /// Writes are passed to the handler
+    Writable,
+    /// Writes are passed to the handler for the Secure Modbus sessions whose role is in the list.
+    /// Other sessions are rejected with [`ExceptionCode::IllegalFunction`].
+    WritableBy(Vec<String>),
+}
+
+impl WriteAccess {
+    fn check(&self, role: Option<&str>) -> Result<(), ExceptionCode> {
+        match self {
+            WriteAccess::ReadOnly => Err(ExceptionCode::IllegalDataAddress),
+            WriteAccess::Writable => Ok(()),
+            WriteAccess::WritableBy(roles) => match role {
+                Some(role) if roles.iter().any(|x| x == role) => Ok(()),
+                _ => Err(ExceptionCode::IllegalFunction),
+            },
+        }
+    }
+}
+
+/// Table targeted by a write request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum WriteTable {
+    Coils,
+    HoldingRegisters,
+}
+
+/// Map of the coils and holding registers that may be written, checked by a server before its
+/// handlers are invoked
+///
+/// Each address has the access of the last range added that contains it, or the default access
+/// if no range contains it. A write request is rejected if any of its addresses may not be
+/// written, in which case the handler is not invoked. [`ExceptionCode::IllegalDataAddress`]
+/// takes precedence over [`ExceptionCode::IllegalFunction`] when both apply.
+///
+/// The map is installed using [`crate::server::ServerHandle::set_write_permissions`] or
+/// [`crate::server::ServerHandle::reload`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WritePermissions {
+    default: WriteAccess,
+    coils: Vec<(AddressRange, WriteAccess)>,
+    registers: Vec<(AddressRange, WriteAccess)>,
+}
+
+impl WritePermissions {
+    /// Create a map in which every address has the `default` access
+    pub fn new(default: WriteAccess) -> Self {
+        Self {
+            default,
+            coils: Vec::new(),
+            registers: Vec::new(),
+        }
+    }
+
+    /// Set the access of a range of coils
+    pub fn coils(mut self, range: AddressRange, access: WriteAccess) -> Self {
+        self.coils.push((range, access));
+        self
+    }
+
+    /// Set the access of a range of holding registers
+    pub fn holding_registers(mut self, range: AddressRange, access: WriteAccess) -> Self {
+        self.registers.push((range, access));
+        self
+    }
+
+    pub(crate) fn check(
+        &self,
+        table: WriteTable,
+        range: AddressRange,
+        role: Option<&str>,
+    ) -> Result<(), ExceptionCode> {
+        let entries = match table {
+            WriteTable::Coils => &self.coils,
+            WriteTable::HoldingRegisters => &self.registers,
+        };
+
+        let mut result = Ok(());
+        let mut apply = |access: &WriteAccess| match access.check(role) {
+            Ok(()) => Ok(()),
+            Err(ExceptionCode::IllegalDataAddress) => Err(ExceptionCode::IllegalDataAddress),
+            Err(ex) => {
+                result = Err(ex);
+                Ok(())
+            }
+        };
+
+        // parts of the request that are not in any of the entries checked so far
+        let mut remaining = vec![range];
+        for (entry, access) in entries.iter().rev() {
+            if !remaining.iter().any(|x| x.overlaps(entry)) {
+                continue;
+            }
+            apply(access)?;
+            remaining = remaining
+                .into_iter()
+                .flat_map(|x| difference(x, *entry))
+                .flatten()
+                .collect();
+            if remaining.is_empty() {
+                return result;
+            }
+        }
+        apply(&self.default)?;
+        result
+    }
+}
+
+/// Parts of `range` that are not in `other`
+fn difference(range: AddressRange, other: AddressRange) -> [Option<AddressRange>; 2] {
+    if !range.overlaps(&other) {
+        return [Some(range), None];
+    }
+    let before = (range.start < other.start).then(|| AddressRange {
+        start: range.start,
+        count: other.start - range.start,
+    });
+    let after = (other.last() < range.last()).then(|| AddressRange {
+        start: other.last() + 1,
+        count: range.last() - other.last(),
+    });
+    [before, after]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u16, count: u16) -> AddressRange {
+        AddressRange::try_from(start, count).unwrap()
+    }
+
+    fn permissions() -> WritePermissions {
+        WritePermissions::new(WriteAccess::ReadOnly)
+            .holding_registers(range(0, 100), WriteAccess::Writable)
+            .holding_registers(
+                range(50, 10),
+                WriteAccess::WritableBy(vec!["operator".to_string()]),
+            )
+            .coils(range(0, 8), WriteAccess::Writable)
+    }
+
+    #[test]
+    fn addresses_have_the_access_of_the_last_range_that_contains_them() {
+        let permissions = permissions();
+        let check = |range, role| permissions.check(WriteTable::HoldingRegisters, range, role);
+
+        assert_eq!(check(range(0, 50), None), Ok(()));
+        assert_eq!(check(range(55, 1), Some("operator")), Ok(()));
+        assert_eq!(
+            check(range(55, 1), Some("viewer")),
+            Err(ExceptionCode::IllegalFunction)
+        );
+        assert_eq!(
+            check(range(49, 2), None),
+            Err(ExceptionCode::IllegalFunction)
+        );
+        assert_eq!(check(range(60, 40), None), Ok(()));
+        assert_eq!(
+            check(range(99, 2), None),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn tables_are_independent_and_read_only_takes_precedence() {
+        let permissions = permissions();
+        assert_eq!(
+            permissions.check(WriteTable::Coils, range(7, 1), None),
+            Ok(())
+        );
+        assert_eq!(
+            permissions.check(WriteTable::Coils, range(8, 1), None),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(
+            permissions.check(WriteTable::HoldingRegisters, range(55, 50), None),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn ranges_may_extend_to_the_last_address() {
+        let permissions = WritePermissions::new(WriteAccess::ReadOnly)
+            .coils(range(u16::MAX - 9, 10), WriteAccess::Writable)
+            .coils(range(u16::MAX - 4, 1), WriteAccess::ReadOnly);
+        let check = |range| permissions.check(WriteTable::Coils, range, None);
+
+        assert_eq!(check(range(u16::MAX - 9, 5)), Ok(()));
+        assert_eq!(check(range(u16::MAX - 3, 4)), Ok(()));
+        assert_eq!(
+            check(range(u16::MAX - 5, 3)),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(
+            check(range(u16::MAX - 10, 2)),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Which requests share the same allowance of a [`RateLimit`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RateLimitScope {
+    /// Each session has its own allowance
+    Session,
+    /// All the sessions from the same IP address share an allowance
+    ///
+    /// Sessions without an IP address, e.g. on a serial port, have their own allowance
+    Address,
+}
+
+/// What the server does with a request that exceeds a [`RateLimit`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Reply with a [`crate::ExceptionCode::ServerDeviceBusy`] exception, without invoking the handler
+    Busy,
+    /// Wait until the request is within the limit before processing it. The session does not
+    /// read other requests in the meantime.
+    Delay,
+}
+
+/// Limits the rate at which a server processes requests
+///
+/// The limit is a token bucket: up to `max_requests` can be processed back to back, after which
+/// requests are allowed at an average rate of `max_requests` per `period`. A `max_requests` of 0
+/// answers every request with a [`crate::ExceptionCode::ServerDeviceBusy`] exception, whatever
+/// the action.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Maximum number of requests within `period`
+    pub max_requests: u32,
+    /// Period over which `max_requests` are allowed
+    pub period: Duration,
+    /// Which requests are counted together
+    pub scope: RateLimitScope,
+    /// What is done with the requests that exceed the limit
+    pub action: RateLimitAction,
+}
+
+impl RateLimit {
+    /// Create a `RateLimit` from its fields
+    pub fn new(
+        max_requests: u32,
+        period: Duration,
+        scope: RateLimitScope,
+        action: RateLimitAction,
+    ) -> Self {
+        Self {
+            max_requests,
+            period,
+            scope,
+            action,
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        f64::from(self.max_requests) / self.period.as_secs_f64()
+    }
+}
+
+/// Outcome of checking a request against the rate limit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Decision {
+    Allow,
+    Busy,
+    Delay(Duration),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Session(u128),
+    Address(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate()).min(f64::from(limit.max_requests));
+        self.updated = now;
+    }
+}
+
+struct State {
+    limit: Option<RateLimit>,
+    buckets: HashMap<Key, Bucket>,
+}
+
+/// Rate limit shared by the sessions of a server, which can be changed while they run
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                limit: None,
+                buckets: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Change the limit, which restores the full allowance of every session
+    pub(crate) fn set(&self, limit: Option<RateLimit>) {
+        let mut state = self.lock();
+        state.limit = limit.filter(|x| !x.period.is_zero());
+        state.buckets.clear();
+    }
+
+    pub(crate) fn check(&self, session: u128, addr: Option<IpAddr>) -> Decision {
+        self.check_at(session, addr, Instant::now())
+    }
+
+    fn check_at(&self, session: u128, addr: Option<IpAddr>, now: Instant) -> Decision {
+        let mut state = self.lock();
+        let limit = match state.limit {
+            None => return Decision::Allow,
+            Some(x) => x,
+        };
+
+        let key = match (limit.scope, addr) {
+            (RateLimitScope::Address, Some(addr)) => Key::Address(addr),
+            _ => Key::Session(session),
+        };
+
+        let bucket = state.buckets.entry(key).or_insert(Bucket {
+            tokens: f64::from(limit.max_requests),
+            updated: now,
+        });
+        bucket.refill(&limit, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Decision::Allow;
+        }
+
+        match limit.action {
+            RateLimitAction::Busy => Decision::Busy,
+            RateLimitAction::Delay => {
+                // a limit of 0 requests never refills the bucket
+                let wait = match Duration::try_from_secs_f64((1.0 - bucket.tokens) / limit.rate()) {
+                    Ok(x) => x,
+                    Err(_) => return Decision::Busy,
+                };
+                // reserve the token so that concurrent sessions of the same address queue up
+                bucket.tokens -= 1.0;
+                Decision::Delay(wait)
+            }
+        }
+    }
+
+    /// Release the allowance of a session that has ended
+    pub(crate) fn remove(&self, session: u128) {
+        let mut state = self.lock();
+        state.buckets.remove(&Key::Session(session));
+        // a full bucket behaves like a new one, so the idle addresses can be forgotten
+        if let Some(limit) = state.limit {
+            let now = Instant::now();
+            state.buckets.retain(|_, bucket| {
+                bucket.refill(&limit, now);
+                bucket.tokens < f64::from(limit.max_requests)
+            });
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // every update leaves the state consistent, so a poisoned lock can be recovered
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    fn limiter(scope: RateLimitScope, action: RateLimitAction) -> RateLimiter {
+        let limiter = RateLimiter::new();
+        limiter.set(Some(RateLimit::new(
+            2,
+            Duration::from_secs(1),
+            scope,
+            action,
+        )));
+        limiter
+    }
+
+    #[test]
+    fn allows_bursts_up_to_the_limit_then_refills() {
+        let limiter = limiter(RateLimitScope::Session, RateLimitAction::Busy);
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Allow);
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Allow);
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Busy);
+        // other sessions are not affected
+        assert_eq!(limiter.check_at(1, Some(ADDR), now), Decision::Allow);
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(limiter.check_at(0, Some(ADDR), later), Decision::Allow);
+        assert_eq!(limiter.check_at(0, Some(ADDR), later), Decision::Busy);
+    }
+
+    #[test]
+    fn sessions_of_an_address_share_the_limit() {
+        let limiter = limiter(RateLimitScope::Address, RateLimitAction::Busy);
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Allow);
+        assert_eq!(limiter.check_at(1, Some(ADDR), now), Decision::Allow);
+        assert_eq!(limiter.check_at(2, Some(ADDR), now), Decision::Busy);
+        // without an address, the session has its own allowance
+        assert_eq!(limiter.check_at(3, None, now), Decision::Allow);
+    }
+
+    #[test]
+    fn delays_queue_up_behind_each_other() {
+        let limiter = limiter(RateLimitScope::Address, RateLimitAction::Delay);
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Allow);
+        assert_eq!(limiter.check_at(0, Some(ADDR), now), Decision::Allow);
+        assert_eq!(
+            limiter.check_at(0, Some(ADDR), now),
+            Decision::Delay(Duration::from_millis(500))
+        );
+        assert_eq!(
+            limiter.check_at(1, Some(ADDR), now),
+            Decision::Delay(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn limit_of_zero_requests_rejects_every_request() {
+        for action in [RateLimitAction::Busy, RateLimitAction::Delay] {
+            let limiter = RateLimiter::new();
+            limiter.set(Some(RateLimit::new(
+                0,
+                Duration::from_secs(1),
+                RateLimitScope::Session,
+                action,
+            )));
+            let now = Instant::now();
+            assert_eq!(limiter.check_at(0, None, now), Decision::Busy);
+            assert_eq!(
+                limiter.check_at(0, None, now + Duration::from_secs(10)),
+                Decision::Busy
+            );
+        }
+    }
+
+    #[test]
+    fn removing_the_limit_allows_every_request() {
+        let limiter = limiter(RateLimitScope::Session, RateLimitAction::Busy);
+        let now = Instant::now();
+        for _ in 0..2 {
+            limiter.check_at(0, None, now);
+        }
+        limiter.set(None);
+        assert_eq!(limiter.check_at(0, None, now), Decision::Allow);
+    }
+}
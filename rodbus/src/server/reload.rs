@@ -0,0 +1,67 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::server::handler::{RequestHandler, ServerHandlerMap};
+use crate::server::permissions::WritePermissions;
+use crate::server::rate_limit::RateLimit;
+
+/// Configuration replacing that of a running server with [`crate::server::ServerHandle::reload`]
+///
+/// The handlers, write permissions and rate limit are replaced at once: each session processes
+/// the requests it has read with the previous configuration, and the following ones with this
+/// configuration. Client connections are kept.
+pub struct ServerReload<T: RequestHandler> {
+    /// Handlers keyed by unit id, which must be of the type the server was spawned with
+    pub handlers: ServerHandlerMap<T>,
+    /// Restrictions on the coils and holding registers that may be written, if any
+    pub write_permissions: Option<WritePermissions>,
+    /// Limit of the rate at which requests are processed, if any
+    pub rate_limit: Option<RateLimit>,
+    /// Maximum number of concurrent sessions of a TCP or TLS server
+    ///
+    /// A lower maximum doesn't close sessions until the next connection is accepted, which closes
+    /// the oldest sessions in excess. RTU servers ignore it.
+    pub max_sessions: usize,
+}
+
+impl<T: RequestHandler> ServerReload<T> {
+    /// Create a `ServerReload` without write permissions or rate limit
+    pub fn new(max_sessions: usize, handlers: ServerHandlerMap<T>) -> Self {
+        Self {
+            handlers,
+            write_permissions: None,
+            rate_limit: None,
+            max_sessions,
+        }
+    }
+
+    pub(crate) fn into_reload(self) -> Reload {
+        Reload {
+            handlers: Arc::new(self.handlers),
+            permissions: self.write_permissions.map(Arc::new),
+            rate_limit: Some(self.rate_limit),
+            max_sessions: self.max_sessions,
+        }
+    }
+}
+
+/// [`ServerReload`] with the type of the handlers erased, so that it can be sent to the server
+#[derive(Clone)]
+pub struct Reload {
+    handlers: Arc<dyn Any + Send + Sync>,
+    pub(crate) permissions: Option<Arc<WritePermissions>>,
+    /// Replaced rate limit, taken by the server task when the sessions share its rate limiter
+    pub(crate) rate_limit: Option<Option<RateLimit>>,
+    pub(crate) max_sessions: usize,
+}
+
+impl Reload {
+    /// Handlers of the reload if they are of the type used by the server
+    pub(crate) fn handlers<T: RequestHandler>(&self) -> Option<&ServerHandlerMap<T>> {
+        let handlers = self.handlers.downcast_ref::<ServerHandlerMap<T>>();
+        if handlers.is_none() {
+            tracing::warn!("ignoring reload with handlers of a different type than the server");
+        }
+        handlers
+    }
+}
@@ -1,3 +1,4 @@
+use crate::audit::{WrittenValue, WrittenValues};
 use crate::common::frame::{FrameHeader, FrameWriter, FunctionField};
 use crate::common::function::FunctionCode;
 use crate::common::traits::{Loggable, Parse, Serialize};
@@ -11,7 +12,7 @@ use crate::types::*;
 
 use scursor::ReadCursor;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub(crate) enum Request<'a> {
     ReadCoils(ReadBitsRange),
     ReadDiscreteInputs(ReadBitsRange),
@@ -35,20 +36,28 @@ pub(crate) enum BroadcastRequest<'a> {
 
 impl<'a> BroadcastRequest<'a> {
     // execute a broadcast request against the handler
-    pub(crate) fn execute<T: RequestHandler>(&self, handler: &mut T) {
+    pub(crate) fn execute<T: RequestHandler>(&self, handler: &mut T) -> Result<(), ExceptionCode> {
         match self {
-            BroadcastRequest::WriteSingleCoil(x) => {
-                let _ = handler.write_single_coil(*x);
-            }
-            BroadcastRequest::WriteSingleRegister(x) => {
-                let _ = handler.write_single_register(*x);
-            }
-            BroadcastRequest::WriteMultipleCoils(x) => {
-                let _ = handler.write_multiple_coils(*x);
-            }
-            BroadcastRequest::WriteMultipleRegisters(x) => {
-                let _ = handler.write_multiple_registers(*x);
-            }
+            BroadcastRequest::WriteSingleCoil(x) => handler.write_single_coil(*x),
+            BroadcastRequest::WriteSingleRegister(x) => handler.write_single_register(*x),
+            BroadcastRequest::WriteMultipleCoils(x) => handler.write_multiple_coils(*x),
+            BroadcastRequest::WriteMultipleRegisters(x) => handler.write_multiple_registers(*x),
+        }
+    }
+}
+
+/// Reply to a request, along with the outcome of the handler for a write request
+pub(crate) struct Reply<'b> {
+    pub(crate) frame: &'b [u8],
+    /// Always `Ok` for read requests, whose exceptions are only in the frame
+    pub(crate) write_result: Result<(), ExceptionCode>,
+}
+
+impl<'b> Reply<'b> {
+    fn new(frame: &'b [u8]) -> Self {
+        Self {
+            frame,
+            write_result: Ok(()),
         }
     }
 }
@@ -86,7 +95,7 @@ impl<'a> Request<'a> {
         handler: &mut dyn RequestHandler,
         writer: &'b mut FrameWriter,
         level: DecodeLevel,
-    ) -> Result<&'b [u8], RequestError> {
+    ) -> Result<Reply<'b>, RequestError> {
         fn write_result<T>(
             function: FunctionCode,
             header: FrameHeader,
@@ -109,38 +118,102 @@ impl<'a> Request<'a> {
         match self {
             Request::ReadCoils(range) => {
                 let bits = BitWriter::new(*range, |i| handler.read_coil(i));
-                writer.format_reply(header, function, &bits, level)
+                writer
+                    .format_reply(header, function, &bits, level)
+                    .map(Reply::new)
             }
             Request::ReadDiscreteInputs(range) => {
                 let bits = BitWriter::new(*range, |i| handler.read_discrete_input(i));
-                writer.format_reply(header, function, &bits, level)
+                writer
+                    .format_reply(header, function, &bits, level)
+                    .map(Reply::new)
             }
             Request::ReadHoldingRegisters(range) => {
                 let registers = RegisterWriter::new(*range, |i| handler.read_holding_register(i));
-                writer.format_reply(header, function, &registers, level)
+                writer
+                    .format_reply(header, function, &registers, level)
+                    .map(Reply::new)
             }
             Request::ReadInputRegisters(range) => {
                 let registers = RegisterWriter::new(*range, |i| handler.read_input_register(i));
-                writer.format_reply(header, function, &registers, level)
+                writer
+                    .format_reply(header, function, &registers, level)
+                    .map(Reply::new)
             }
             Request::WriteSingleCoil(request) => {
-                let result = handler.write_single_coil(*request).map(|_| *request);
-                write_result(function, header, writer, result, level)
+                let result = handler.write_single_coil(*request);
+                let frame =
+                    write_result(function, header, writer, result.map(|_| *request), level)?;
+                Ok(Reply {
+                    frame,
+                    write_result: result,
+                })
             }
             Request::WriteSingleRegister(request) => {
-                let result = handler.write_single_register(*request).map(|_| *request);
-                write_result(function, header, writer, result, level)
+                let result = handler.write_single_register(*request);
+                let frame =
+                    write_result(function, header, writer, result.map(|_| *request), level)?;
+                Ok(Reply {
+                    frame,
+                    write_result: result,
+                })
             }
             Request::WriteMultipleCoils(items) => {
-                let result = handler.write_multiple_coils(*items).map(|_| items.range);
-                write_result(function, header, writer, result, level)
+                let result = handler.write_multiple_coils(*items);
+                let frame =
+                    write_result(function, header, writer, result.map(|_| items.range), level)?;
+                Ok(Reply {
+                    frame,
+                    write_result: result,
+                })
             }
             Request::WriteMultipleRegisters(items) => {
-                let result = handler
-                    .write_multiple_registers(*items)
-                    .map(|_| items.range);
-                write_result(function, header, writer, result, level)
+                let result = handler.write_multiple_registers(*items);
+                let frame =
+                    write_result(function, header, writer, result.map(|_| items.range), level)?;
+                Ok(Reply {
+                    frame,
+                    write_result: result,
+                })
+            }
+        }
+    }
+
+    /// Values of a write request, along with the values they replace as read from the handler
+    pub(crate) fn written_values(&self, handler: &dyn RequestHandler) -> Option<WrittenValues> {
+        match self {
+            Request::ReadCoils(_)
+            | Request::ReadDiscreteInputs(_)
+            | Request::ReadHoldingRegisters(_)
+            | Request::ReadInputRegisters(_) => None,
+            Request::WriteSingleCoil(x) => Some(WrittenValues::Coils(vec![WrittenValue::new(
+                x.index,
+                handler.read_coil(x.index).ok(),
+                x.value,
+            )])),
+            Request::WriteSingleRegister(x) => {
+                Some(WrittenValues::Registers(vec![WrittenValue::new(
+                    x.index,
+                    handler.read_holding_register(x.index).ok(),
+                    x.value,
+                )]))
             }
+            Request::WriteMultipleCoils(x) => Some(WrittenValues::Coils(
+                x.iterator
+                    .map(|x| WrittenValue::new(x.index, handler.read_coil(x.index).ok(), x.value))
+                    .collect(),
+            )),
+            Request::WriteMultipleRegisters(x) => Some(WrittenValues::Registers(
+                x.iterator
+                    .map(|x| {
+                        WrittenValue::new(
+                            x.index,
+                            handler.read_holding_register(x.index).ok(),
+                            x.value,
+                        )
+                    })
+                    .collect(),
+            )),
         }
     }
 
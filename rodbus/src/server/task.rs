@@ -1,6 +1,9 @@
+use crate::audit::{AuditOrigin, AuditSink, WriteRecord, WrittenValues};
 use crate::common::phys::PhysLayer;
-use crate::server::{Authorization, AuthorizationHandler};
-use crate::{DecodeLevel, UnitId};
+use crate::server::accept::{AcceptHandler, SessionContext};
+use crate::server::idle::{Activity, SessionEviction};
+use crate::server::{AddressFilter, Authorization, AuthorizationHandler};
+use crate::{AddressRange, DecodeLevel, UnitId};
 
 use crate::common::frame::{
     Frame, FrameDestination, FrameHeader, FrameWriter, FramedReader, FunctionField,
@@ -9,15 +12,38 @@ use crate::common::function::FunctionCode;
 use crate::error::*;
 use crate::exception::ExceptionCode;
 use crate::server::handler::{RequestHandler, ServerHandlerMap};
+use crate::server::permissions::{WritePermissions, WriteTable};
+use crate::server::rate_limit::{Decision, RateLimit, RateLimiter};
+use crate::server::reload::Reload;
 use crate::server::request::{Request, RequestDisplay};
 
 use scursor::ReadCursor;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 /// Messages that can be sent to change server settings dynamically
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum ServerSetting {
     ChangeDecoding(DecodeLevel),
+    PeerDecoding(std::net::IpAddr, Option<DecodeLevel>),
+    RateLimit(Option<RateLimit>),
+    AddressFilter(AddressFilter),
+    Audit(Option<Arc<dyn AuditSink>>),
+    WritePermissions(Option<Arc<WritePermissions>>),
+    Reload(Reload),
+    AcceptHandler(Option<Arc<dyn AcceptHandler>>),
+    IdleTimeout(Option<std::time::Duration>),
+    SessionEviction(SessionEviction),
+    #[cfg(feature = "tls")]
+    TlsConfig(crate::tcp::tls::TlsServerConfig),
+}
+
+/// Settings of a server that apply to each of its sessions
+#[derive(Clone, Default)]
+pub(crate) struct SessionSettings {
+    pub(crate) audit: Option<Arc<dyn AuditSink>>,
+    pub(crate) permissions: Option<Arc<WritePermissions>>,
+    pub(crate) idle_timeout: Option<std::time::Duration>,
 }
 
 pub(crate) struct SessionTask<T>
@@ -30,6 +56,12 @@ where
     writer: FrameWriter,
     reader: FramedReader,
     decode: DecodeLevel,
+    limiter: RateLimiter,
+    session: u128,
+    addr: Option<SocketAddr>,
+    settings: SessionSettings,
+    context: Option<SessionContext>,
+    activity: Activity,
 }
 
 impl<T> SessionTask<T>
@@ -51,9 +83,40 @@ where
             writer,
             reader,
             decode,
+            limiter: RateLimiter::new(),
+            session: 0,
+            addr: None,
+            settings: SessionSettings::default(),
+            context: None,
+            activity: Activity::new(),
         }
     }
 
+    /// Share the rate limit of a server, identifying this session by its id and remote address
+    pub(crate) fn set_rate_limiter(
+        &mut self,
+        limiter: RateLimiter,
+        session: u128,
+        addr: SocketAddr,
+    ) {
+        self.limiter = limiter;
+        self.session = session;
+        self.addr = Some(addr);
+    }
+
+    pub(crate) fn set_settings(&mut self, settings: SessionSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn set_context(&mut self, context: Option<SessionContext>) {
+        self.context = context;
+    }
+
+    /// Share the time at which the session receives frames with the server
+    pub(crate) fn set_activity(&mut self, activity: Activity) {
+        self.activity = activity;
+    }
+
     async fn reply_with_error(
         &mut self,
         io: &mut PhysLayer,
@@ -115,14 +178,32 @@ where
     }
 
     async fn run_one(&mut self, io: &mut PhysLayer) -> Result<(), RequestError> {
+        // only the sessions of a TCP server have a remote address
+        let idle_deadline = self
+            .settings
+            .idle_timeout
+            .filter(|_| self.addr.is_some())
+            .map(|timeout| self.activity.last() + timeout);
+        let idle = async move {
+            match idle_deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
         tokio::select! {
             frame = self.reader.next_frame(io, self.decode) => {
                 let frame = frame?;
+                self.activity.touch();
                 self.handle_frame(io, frame).await
             }
+            _ = idle => {
+                tracing::info!("closing idle session");
+                Err(RequestError::Io(std::io::ErrorKind::TimedOut.into()))
+            }
             cmd = self.commands.recv() => {
                match cmd {
-                    None => Err(crate::error::RequestError::Shutdown),
+                    None => Err(RequestError::Shutdown),
                     Some(setting) => {
                         self.apply_setting(setting);
                         Ok(())
@@ -137,6 +218,36 @@ where
             ServerSetting::ChangeDecoding(level) => {
                 self.decode = level;
             }
+            ServerSetting::RateLimit(limit) => {
+                self.limiter.set(limit);
+            }
+            // connections are filtered by the server task before the sessions are created
+            // the server task forwards the decoding level of the peer as a ChangeDecoding
+            ServerSetting::PeerDecoding(..) => {}
+            ServerSetting::AddressFilter(_)
+            | ServerSetting::AcceptHandler(_)
+            | ServerSetting::SessionEviction(_) => {}
+            ServerSetting::IdleTimeout(timeout) => {
+                self.settings.idle_timeout = timeout;
+            }
+            ServerSetting::Audit(audit) => {
+                self.settings.audit = audit;
+            }
+            ServerSetting::WritePermissions(permissions) => {
+                self.settings.permissions = permissions;
+            }
+            ServerSetting::Reload(reload) => {
+                if let Some(handlers) = reload.handlers::<T>() {
+                    self.handlers = handlers.clone();
+                    self.settings.permissions = reload.permissions;
+                    if let Some(limit) = reload.rate_limit {
+                        self.limiter.set(limit);
+                    }
+                }
+            }
+            // the TLS handshake is performed by the server task before the sessions are created
+            #[cfg(feature = "tls")]
+            ServerSetting::TlsConfig(_) => {}
         }
     }
 
@@ -181,6 +292,25 @@ where
             );
         }
 
+        match self.limiter.check(self.session, self.addr.map(|x| x.ip())) {
+            Decision::Allow => {}
+            Decision::Delay(delay) => tokio::time::sleep(delay).await,
+            Decision::Busy => {
+                tracing::warn!(
+                    "rate limit exceeded for {:?} request",
+                    request.get_function()
+                );
+                return self
+                    .reply_with_error(
+                        io,
+                        frame.header,
+                        request.get_function(),
+                        ExceptionCode::ServerDeviceBusy,
+                    )
+                    .await;
+            }
+        }
+
         // check authorization
         if let Authorization::Deny = self
             .auth
@@ -198,6 +328,16 @@ where
             return Ok(());
         }
 
+        if let Err(ex) = self.check_write_permissions(&request) {
+            tracing::warn!(
+                "{:?} request is not permitted by the write permissions",
+                request.get_function()
+            );
+            return self
+                .reply_with_error(io, frame.header, request.get_function(), ex)
+                .await;
+        }
+
         // if no addresses match, then don't respond
         match frame.header.destination {
             FrameDestination::UnitId(unit_id) => {
@@ -208,22 +348,61 @@ where
                     }
                     Some(handler) => handler,
                 };
-                // get the reply data (or exception reply)
-                let reply: &[u8] = request.get_reply(
-                    frame.header,
-                    handler.lock().unwrap().as_mut(),
-                    &mut self.writer,
-                    self.decode,
-                )?;
-                io.write(reply, self.decode.physical).await?;
+                let (reply, values) = {
+                    let mut handler = handler.lock().unwrap();
+                    handler.set_session_context(self.context.as_ref());
+                    // the previous values are read before the handler is invoked
+                    let values = self
+                        .settings
+                        .audit
+                        .as_ref()
+                        .and_then(|_| request.written_values(handler.as_ref()));
+                    // get the reply data (or exception reply)
+                    let reply = request.get_reply(
+                        frame.header,
+                        handler.as_mut(),
+                        &mut self.writer,
+                        self.decode,
+                    )?;
+                    (reply, values)
+                };
+                if let (Some(audit), Some(values)) = (self.settings.audit.as_ref(), values) {
+                    audit.record(&write_record(
+                        &self.auth,
+                        self.addr,
+                        unit_id,
+                        function,
+                        values,
+                        reply.write_result,
+                    ));
+                }
+                io.write(reply.frame, self.decode.physical).await?;
             }
             FrameDestination::Broadcast => match request.into_broadcast_request() {
                 None => {
                     tracing::warn!("broadcast is not supported for {}", function);
                 }
-                Some(request) => {
+                Some(broadcast) => {
                     for handler in self.handlers.iter_mut() {
-                        request.execute(handler.lock().unwrap().as_mut());
+                        let mut handler = handler.lock().unwrap();
+                        handler.set_session_context(self.context.as_ref());
+                        let values = self
+                            .settings
+                            .audit
+                            .as_ref()
+                            .and_then(|_| request.written_values(handler.as_ref()));
+                        let result = broadcast.execute(handler.as_mut());
+                        if let (Some(audit), Some(values)) = (self.settings.audit.as_ref(), values)
+                        {
+                            audit.record(&write_record(
+                                &self.auth,
+                                self.addr,
+                                UnitId::broadcast(),
+                                function,
+                                values,
+                                result,
+                            ));
+                        }
                     }
                 }
             },
@@ -231,6 +410,56 @@ where
 
         Ok(())
     }
+
+    fn check_write_permissions(&self, request: &Request) -> Result<(), ExceptionCode> {
+        let permissions = match self.settings.permissions.as_ref() {
+            None => return Ok(()),
+            Some(x) => x,
+        };
+        let (table, range) = match request {
+            Request::ReadCoils(_)
+            | Request::ReadDiscreteInputs(_)
+            | Request::ReadHoldingRegisters(_)
+            | Request::ReadInputRegisters(_) => return Ok(()),
+            Request::WriteSingleCoil(x) => (
+                WriteTable::Coils,
+                AddressRange {
+                    start: x.index,
+                    count: 1,
+                },
+            ),
+            Request::WriteSingleRegister(x) => (
+                WriteTable::HoldingRegisters,
+                AddressRange {
+                    start: x.index,
+                    count: 1,
+                },
+            ),
+            Request::WriteMultipleCoils(x) => (WriteTable::Coils, x.range),
+            Request::WriteMultipleRegisters(x) => (WriteTable::HoldingRegisters, x.range),
+        };
+        permissions.check(table, range, self.auth.role())
+    }
+}
+
+fn write_record(
+    auth: &AuthorizationType,
+    peer: Option<SocketAddr>,
+    unit: UnitId,
+    function: FunctionCode,
+    values: WrittenValues,
+    result: Result<(), ExceptionCode>,
+) -> WriteRecord {
+    let mut record = WriteRecord::new(
+        AuditOrigin::Server,
+        unit,
+        function.get_value(),
+        values,
+        result.map_err(RequestError::Exception),
+    );
+    record.peer = peer;
+    record.role = auth.role().map(str::to_string);
+    record
 }
 
 /// Determines how authorization of user defined requests are handled
@@ -243,6 +472,14 @@ pub(crate) enum AuthorizationType {
 }
 
 impl AuthorizationType {
+    /// Role of the client certificate, if the session is authorized by role
+    pub(crate) fn role(&self) -> Option<&str> {
+        match self {
+            AuthorizationType::None => None,
+            AuthorizationType::Handler(_, role) => Some(role),
+        }
+    }
+
     fn check_authorization(
         handler: &dyn AuthorizationHandler,
         unit_id: UnitId,
@@ -7,7 +7,7 @@ use crate::decode::DecodeLevel;
 use crate::client::message::Command;
 use crate::client::task::{ClientLoop, SessionError, StateChange};
 use crate::common::frame::{FrameWriter, FramedReader};
-use crate::error::Shutdown;
+use crate::error::{ConnectError, Shutdown};
 use crate::retry::RetryStrategy;
 
 use tokio::net::TcpStream;
@@ -47,7 +47,7 @@ pub(crate) fn create_tcp_channel(
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel::new(tx), task)
 }
 
 pub(crate) enum TcpTaskConnectionHandler {
@@ -61,7 +61,7 @@ impl TcpTaskConnectionHandler {
         &mut self,
         socket: TcpStream,
         _endpoint: &HostAddr,
-    ) -> Result<PhysLayer, String> {
+    ) -> Result<PhysLayer, ConnectError> {
         match self {
             Self::Tcp => Ok(PhysLayer::new_tcp(socket)),
             #[cfg(feature = "tls")]
@@ -104,6 +104,18 @@ impl TcpChannelTask {
         ret
     }
 
+    #[cfg(feature = "tls")]
+    fn update_tls_config(&mut self) {
+        if let Some(config) = self.client_loop.take_tls_config() {
+            match &mut self.connection_handler {
+                TcpTaskConnectionHandler::Tls(current) => *current = config,
+                TcpTaskConnectionHandler::Tcp => {
+                    tracing::warn!("ignoring TLS configuration of a channel without TLS")
+                }
+            }
+        }
+    }
+
     async fn run_inner(&mut self) -> Shutdown {
         loop {
             if let Err(Shutdown) = self.client_loop.wait_for_enabled().await {
@@ -121,9 +133,15 @@ impl TcpChannelTask {
     }
 
     async fn try_connect_and_run(&mut self) -> Result<(), StateChange> {
+        if let Some(host) = self.client_loop.take_endpoint() {
+            self.host = host;
+        }
+        #[cfg(feature = "tls")]
+        self.update_tls_config();
         self.listener.update(ClientState::Connecting).get().await;
         match self.host.connect().await {
             Err(err) => {
+                self.client_loop.report_connect_failure(err.clone());
                 let delay = self.connect_retry.after_failed_connect();
                 tracing::warn!(
                     "failed to connect to {}: {} - waiting {} ms before next attempt",
@@ -146,6 +164,7 @@ impl TcpChannelTask {
                 }
                 match self.connection_handler.handle(socket, &self.host).await {
                     Err(err) => {
+                        self.client_loop.report_connect_failure(err.clone());
                         let delay = self.connect_retry.after_failed_connect();
                         tracing::warn!(
                             "{} - waiting {} ms before next attempt",
@@ -10,8 +10,9 @@ use scursor::WriteCursor;
 pub(crate) mod constants {
     pub(crate) const HEADER_LENGTH: usize = 7;
     pub(crate) const MAX_FRAME_LENGTH: usize =
-        HEADER_LENGTH + crate::common::frame::constants::MAX_ADU_LENGTH;
-    // cannot be < 1 b/c of the unit identifier
+        HEADER_LENGTH + crate::common::frame::constants::MAX_CONFIGURABLE_ADU_LENGTH;
+    // cannot be < 1 b/c of the unit identifier, the default of `MbapParser`
+    #[cfg(any(test, feature = "test-util"))]
     pub(crate) const MAX_LENGTH_FIELD: usize = crate::common::frame::constants::MAX_ADU_LENGTH + 1;
 }
 
@@ -31,17 +32,23 @@ enum ParseState {
 
 pub(crate) struct MbapParser {
     state: ParseState,
+    max_pdu_length: usize,
 }
 
 impl MbapParser {
     pub(crate) fn new() -> Self {
         Self {
             state: ParseState::Begin,
+            max_pdu_length: crate::common::frame::constants::MAX_ADU_LENGTH,
         }
     }
 
+    pub(crate) fn set_max_pdu_length(&mut self, length: usize) {
+        self.max_pdu_length = length;
+    }
+
     // returns some header fields and the length of the ADU
-    fn parse_header(cursor: &mut ReadBuffer) -> Result<(MbapHeader, usize), RequestError> {
+    fn parse_header(&self, cursor: &mut ReadBuffer) -> Result<(MbapHeader, usize), RequestError> {
         let tx_id = TxId::new(cursor.read_u16_be()?);
         let protocol_id = cursor.read_u16_be()?;
         let len_field = cursor.read_u16_be()?;
@@ -52,10 +59,10 @@ impl MbapParser {
             return Err(FrameParseError::UnknownProtocolId(protocol_id).into());
         }
 
-        if length > constants::MAX_LENGTH_FIELD {
-            return Err(
-                FrameParseError::FrameLengthTooBig(length, constants::MAX_LENGTH_FIELD).into(),
-            );
+        // the unit identifier counts towards the length field
+        let max_length_field = self.max_pdu_length + 1;
+        if length > max_length_field {
+            return Err(FrameParseError::FrameLengthTooBig(length, max_length_field).into());
         }
 
         // The ADU length is the function code + body
@@ -112,7 +119,7 @@ impl MbapParser {
                     return Ok(None);
                 }
 
-                let (header, adu_len) = Self::parse_header(cursor)?;
+                let (header, adu_len) = self.parse_header(cursor)?;
                 self.state = ParseState::Header(header, adu_len);
                 self.parse(cursor, decode_level)
             }
@@ -219,7 +226,7 @@ mod tests {
     }
 
     impl Serialize for MockBody {
-        fn serialize(self: &Self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
+        fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
             for b in self.body {
                 cursor.write_u8(*b)?;
             }
@@ -264,7 +271,7 @@ mod tests {
 
         io_handle.read(input);
         if let Poll::Ready(frame) = task.poll() {
-            return frame.err().unwrap();
+            frame.err().unwrap()
         } else {
             panic!("Task not ready");
         }
@@ -369,4 +376,35 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn enforces_the_configured_max_pdu_length() {
+        let mut oversized = vec![0x00, 0x07, 0x00, 0x00, 0x00, 0xFF, 0x2A, 0x01];
+        oversized.extend_from_slice(&[0xCC; 0xFD]);
+
+        let (io, mut io_handle) = sfio_tokio_mock_io::mock();
+        let mut reader = FramedReader::tcp();
+        reader.set_max_pdu_length(300);
+        let mut layer = PhysLayer::new_mock(io);
+        let mut task =
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+        io_handle.read(&oversized);
+        match task.poll() {
+            Poll::Ready(frame) => assert_eq!(frame.unwrap().payload().len(), 0xFE),
+            Poll::Pending => panic!("Task not ready"),
+        }
+        drop(task);
+
+        reader.set_max_pdu_length(2);
+        let mut task =
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+        io_handle.read(SIMPLE_FRAME);
+        match task.poll() {
+            Poll::Ready(frame) => assert_eq!(
+                frame.err().unwrap(),
+                RequestError::BadFrame(FrameParseError::FrameLengthTooBig(4, 3))
+            ),
+            Poll::Pending => panic!("Task not ready"),
+        }
+    }
 }
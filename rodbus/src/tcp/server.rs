@@ -6,10 +6,13 @@ use crate::common::frame::{FrameWriter, FramedReader};
 use crate::common::phys::PhysLayer;
 use crate::decode::DecodeLevel;
 use crate::server::handler::{RequestHandler, ServerHandlerMap};
-use crate::server::task::{AuthorizationType, ServerSetting};
+use crate::server::idle::{Activity, SessionEviction};
+use crate::server::rate_limit::RateLimiter;
+use crate::server::task::{AuthorizationType, ServerSetting, SessionSettings};
 
-use crate::server::AddressFilter;
+use crate::server::{AcceptDecision, AcceptHandler, AcceptedConnection, AddressFilter};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
 #[cfg(feature = "tls")]
@@ -18,48 +21,90 @@ use crate::server::AuthorizationHandler;
 /// event sent back to the server task when a session ends
 struct SessionClose(u128);
 
+struct SessionRecord {
+    sender: tokio::sync::mpsc::Sender<ServerSetting>,
+    activity: Activity,
+    addr: SocketAddr,
+}
+
 struct SessionTracker {
     max_sessions: usize,
+    eviction: SessionEviction,
     id: u128,
-    sessions: BTreeMap<u128, tokio::sync::mpsc::Sender<ServerSetting>>,
+    sessions: BTreeMap<u128, SessionRecord>,
 }
 
 impl SessionTracker {
     fn new(max_sessions: usize) -> SessionTracker {
-        let max_sessions = if max_sessions == 0 {
+        Self {
+            max_sessions: Self::limit(max_sessions),
+            eviction: SessionEviction::Oldest,
+            id: 0,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    fn limit(max_sessions: usize) -> usize {
+        if max_sessions == 0 {
             tracing::warn!("Max sessions to 0, defaulting to 1");
             1
         } else {
             max_sessions
-        };
-        Self {
-            max_sessions,
-            id: 0,
-            sessions: BTreeMap::new(),
         }
     }
 
+    /// Change the maximum number of sessions, which is enforced when sessions are added
+    fn set_max_sessions(&mut self, max_sessions: usize) {
+        self.max_sessions = Self::limit(max_sessions);
+    }
+
     fn get_next_id(&mut self) -> u128 {
         let ret = self.id;
         self.id += 1;
         ret
     }
 
-    pub(crate) fn add(&mut self, sender: tokio::sync::mpsc::Sender<ServerSetting>) -> u128 {
-        if self.sessions.len() >= self.max_sessions {
-            if let Some(oldest) = self.sessions.keys().next().copied() {
+    /// Session to close when the maximum number of sessions is exceeded
+    fn evicted(&self) -> Option<u128> {
+        match self.eviction {
+            SessionEviction::Oldest => self.sessions.keys().next().copied(),
+            SessionEviction::LeastRecentlyActive => self
+                .sessions
+                .iter()
+                .min_by_key(|(_, record)| record.activity.last())
+                .map(|(id, _)| *id),
+        }
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        sender: tokio::sync::mpsc::Sender<ServerSetting>,
+        activity: Activity,
+        addr: SocketAddr,
+    ) -> u128 {
+        // the maximum may have been lowered since the sessions were added
+        while self.sessions.len() >= self.max_sessions {
+            if let Some(evicted) = self.evicted() {
                 tracing::warn!(
-                    "exceeded max connections, closing oldest session: {}",
-                    oldest
+                    "exceeded max connections, closing session: {} ({:?})",
+                    evicted,
+                    self.eviction
                 );
                 // when the record drops, and there are no more senders,
                 // the other end will stop the task
-                self.sessions.remove(&oldest);
+                self.sessions.remove(&evicted);
             }
         }
 
         let id = self.get_next_id();
-        self.sessions.insert(id, sender);
+        self.sessions.insert(
+            id,
+            SessionRecord {
+                sender,
+                activity,
+                addr,
+            },
+        );
         id
     }
 
@@ -74,7 +119,7 @@ pub(crate) enum TcpServerConnectionHandler {
     #[cfg(feature = "tls")]
     Tls(
         crate::tcp::tls::TlsServerConfig,
-        Option<std::sync::Arc<dyn AuthorizationHandler>>,
+        Option<Arc<dyn AuthorizationHandler>>,
     ),
 }
 
@@ -103,7 +148,11 @@ pub(crate) struct ServerTask<T: RequestHandler> {
     tracker: SessionTracker,
     connection_handler: TcpServerConnectionHandler,
     filter: AddressFilter,
+    accept: Option<Arc<dyn AcceptHandler>>,
     decode: DecodeLevel,
+    peer_decode: BTreeMap<std::net::IpAddr, DecodeLevel>,
+    limiter: RateLimiter,
+    session_settings: SessionSettings,
     tx: tokio::sync::mpsc::Sender<SessionClose>,
     rx: tokio::sync::mpsc::Receiver<SessionClose>,
 }
@@ -128,31 +177,137 @@ where
             tracker: SessionTracker::new(max_sessions),
             connection_handler,
             filter,
+            accept: None,
             decode,
+            peer_decode: BTreeMap::new(),
+            limiter: RateLimiter::new(),
+            session_settings: SessionSettings::default(),
             tx,
             rx,
         }
     }
 
-    async fn change_setting(&mut self, setting: ServerSetting) {
+    async fn change_setting(&mut self, mut setting: ServerSetting) {
         // first, change it locally so that it is applied to new sessions
         match setting {
             ServerSetting::ChangeDecoding(level) => {
                 tracing::info!("changed decoding level to {:?}", level);
                 self.decode = level;
+                // the peers with their own level keep it
+                let peer_decode = &self.peer_decode;
+                Self::send_to(
+                    &mut self.tracker,
+                    |addr| !peer_decode.contains_key(&addr.ip()),
+                    setting,
+                )
+                .await;
+                return;
+            }
+            ServerSetting::PeerDecoding(peer, level) => {
+                tracing::info!("changed decoding level of {} to {:?}", peer, level);
+                match level {
+                    Some(level) => self.peer_decode.insert(peer, level),
+                    None => self.peer_decode.remove(&peer),
+                };
+                let level = self.decode_level(peer);
+                Self::send_to(
+                    &mut self.tracker,
+                    |addr| addr.ip() == peer,
+                    ServerSetting::ChangeDecoding(level),
+                )
+                .await;
+                return;
+            }
+            ServerSetting::RateLimit(limit) => {
+                tracing::info!("changed rate limit to {:?}", limit);
+                // the limiter is shared with the sessions, so there is nothing to forward
+                self.limiter.set(limit);
+                return;
+            }
+            ServerSetting::Audit(ref audit) => {
+                self.session_settings.audit = audit.clone();
+            }
+            ServerSetting::WritePermissions(ref permissions) => {
+                self.session_settings.permissions = permissions.clone();
+            }
+            ServerSetting::Reload(ref mut reload) => match reload.handlers::<T>() {
+                Some(handlers) => {
+                    tracing::info!("reloaded configuration");
+                    self.handlers = handlers.clone();
+                    self.session_settings.permissions = reload.permissions.clone();
+                    self.tracker.set_max_sessions(reload.max_sessions);
+                    // the limiter is shared with the sessions
+                    if let Some(limit) = reload.rate_limit.take() {
+                        self.limiter.set(limit);
+                    }
+                }
+                None => return,
+            },
+            ServerSetting::IdleTimeout(timeout) => {
+                tracing::info!("changed idle timeout to {:?}", timeout);
+                self.session_settings.idle_timeout = timeout;
+            }
+            ServerSetting::SessionEviction(eviction) => {
+                tracing::info!("changed session eviction to {:?}", eviction);
+                self.tracker.eviction = eviction;
+                return;
+            }
+            ServerSetting::AcceptHandler(handler) => {
+                tracing::info!("changed accept handler");
+                // only new connections are passed to the handler
+                self.accept = handler;
+                return;
+            }
+            ServerSetting::AddressFilter(filter) => {
+                tracing::info!("changed address filter to {:?}", filter);
+                // only new connections are filtered
+                self.filter = filter;
+                return;
+            }
+            #[cfg(feature = "tls")]
+            ServerSetting::TlsConfig(config) => {
+                // established sessions keep the configuration of their handshake
+                match &mut self.connection_handler {
+                    TcpServerConnectionHandler::Tls(current, _) => {
+                        tracing::info!("changed TLS configuration");
+                        *current = config;
+                    }
+                    TcpServerConnectionHandler::Tcp => {
+                        tracing::warn!("ignoring TLS configuration of a server without TLS");
+                    }
+                }
+                return;
             }
         }
 
-        for sender in self.tracker.sessions.values_mut() {
-            // best effort to send the setting to each session this isn't critical so we wouldn't
-            // want to slow the server down by awaiting it
-            let _ = sender.send(setting).await;
+        Self::send_to(&mut self.tracker, |_| true, setting).await;
+    }
+
+    /// Send the setting to the sessions whose remote address matches
+    async fn send_to<F>(tracker: &mut SessionTracker, matches: F, setting: ServerSetting)
+    where
+        F: Fn(SocketAddr) -> bool,
+    {
+        for record in tracker.sessions.values_mut() {
+            if matches(record.addr) {
+                // best effort to send the setting to each session this isn't critical so we
+                // wouldn't want to slow the server down by awaiting it
+                let _ = record.sender.send(setting.clone()).await;
+            }
         }
     }
 
+    /// Decoding level of the sessions from an IP address
+    fn decode_level(&self, peer: std::net::IpAddr) -> DecodeLevel {
+        self.peer_decode.get(&peer).copied().unwrap_or(self.decode)
+    }
+
     pub(crate) async fn run(&mut self, mut commands: tokio::sync::mpsc::Receiver<ServerSetting>) {
         loop {
             tokio::select! {
+               // settings apply to the connections accepted after they are made
+               biased;
+
                setting = commands.recv() => {
                     match setting {
                         Some(setting) => self.change_setting(setting).await,
@@ -167,6 +322,7 @@ where
                    let id = shutdown.unwrap().0;
 
                    self.tracker.remove(id);
+                   self.limiter.remove(id);
                }
                result = self.listener.accept() => {
                    match result {
@@ -192,7 +348,8 @@ where
 
     async fn handle(&mut self, socket: tokio::net::TcpStream, addr: SocketAddr) {
         let (tx, rx) = tokio::sync::mpsc::channel(8); // all we do is change settings, so a constant is fine
-        let id = self.tracker.add(tx);
+        let activity = Activity::new();
+        let id = self.tracker.add(tx, activity.clone(), addr);
         tracing::info!(
             "accepted connection from: {} - assigned session id: {}",
             addr,
@@ -202,16 +359,24 @@ where
         #[allow(unused_mut)]
         let mut notify_close = self.tx.clone();
         let connection_handler = self.connection_handler.clone();
+        let accept = self.accept.clone();
         let handler_map = self.handlers.clone();
-        let decode_level = self.decode;
+        let decode_level = self.decode_level(addr.ip());
+        let limiter = self.limiter.clone();
+        let settings = self.session_settings.clone();
 
         let session = async move {
             run_session(
                 socket,
                 addr,
+                id,
+                activity,
                 connection_handler,
+                accept,
                 decode_level,
                 handler_map,
+                limiter,
+                settings,
                 rx,
             )
             .await;
@@ -230,12 +395,18 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_session<T: RequestHandler>(
     socket: tokio::net::TcpStream,
     addr: SocketAddr,
+    id: u128,
+    activity: Activity,
     mut handler: TcpServerConnectionHandler,
+    accept: Option<Arc<dyn AcceptHandler>>,
     decode: DecodeLevel,
     handlers: ServerHandlerMap<T>,
+    limiter: RateLimiter,
+    settings: SessionSettings,
     commands: tokio::sync::mpsc::Receiver<ServerSetting>,
 ) {
     match handler.handle(socket).await {
@@ -243,16 +414,32 @@ async fn run_session<T: RequestHandler>(
             tracing::warn!("error from {}: {}", addr, err);
         }
         Ok((mut phys, auth)) => {
-            let _ = crate::server::task::SessionTask::new(
+            let context = match accept {
+                None => None,
+                Some(accept) => match accept.accept(AcceptedConnection {
+                    peer: addr,
+                    role: auth.role(),
+                }) {
+                    AcceptDecision::Accept(context) => context,
+                    AcceptDecision::Reject => {
+                        tracing::warn!("connection from {} rejected by the accept handler", addr);
+                        return;
+                    }
+                },
+            };
+            let mut session = crate::server::task::SessionTask::new(
                 handlers,
                 auth,
                 FrameWriter::tcp(),
                 FramedReader::tcp(),
                 commands,
                 decode,
-            )
-            .run(&mut phys)
-            .await;
+            );
+            session.set_rate_limiter(limiter, id, addr);
+            session.set_settings(settings);
+            session.set_context(context);
+            session.set_activity(activity);
+            let _ = session.run(&mut phys).await;
         }
     }
 }
@@ -9,12 +9,14 @@ use tracing::Instrument;
 
 use crate::client::{Channel, ClientState, HostAddr, Listener, RetryStrategy};
 use crate::common::phys::PhysLayer;
+use crate::error::ConnectError;
 use crate::tcp::client::{TcpChannelTask, TcpTaskConnectionHandler};
 use crate::tcp::tls::{load_certs, load_private_key, CertificateMode, MinTlsVersion, TlsError};
 
 use crate::DecodeLevel;
 
 /// TLS configuration
+#[derive(Clone)]
 pub struct TlsClientConfig {
     dns_name: rustls::ServerName,
     config: Arc<rustls::ClientConfig>,
@@ -62,7 +64,7 @@ pub(crate) fn create_tls_channel(
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel::new(tx), task)
 }
 
 impl TlsClientConfig {
@@ -140,7 +142,48 @@ impl TlsClientConfig {
         let dns_name = rustls::ServerName::try_from(name).map_err(|_| TlsError::InvalidDnsName)?;
 
         Ok(Self {
-            config: std::sync::Arc::new(config),
+            config: Arc::new(config),
+            dns_name,
+        })
+    }
+
+    /// Create a TLS master config in which the certificates of the server are verified by a
+    /// [`crate::client::CertificateVerifier`] instead of a [`CertificateMode`]
+    ///
+    /// `name` is only sent to the server for it to select its certificate. Checking it is left
+    /// to the verifier.
+    #[cfg(feature = "dangerous-tls")]
+    pub fn with_verifier(
+        name: &str,
+        local_cert_path: &Path,
+        private_key_path: &Path,
+        password: Option<&str>,
+        min_tls_version: MinTlsVersion,
+        verifier: Arc<dyn crate::tcp::tls::verify::CertificateVerifier>,
+    ) -> Result<Self, TlsError> {
+        let local_certs = load_certs(local_cert_path, true)?;
+        let private_key = load_private_key(private_key_path, password)?;
+
+        let config = rustls::ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(min_tls_version.to_rustls())
+            .map_err(|err| TlsError::BadConfig(err.to_string()))?
+            .with_custom_certificate_verifier(crate::tcp::tls::verify::CustomVerifier::new(
+                verifier,
+            ))
+            .with_single_cert(local_certs, private_key)
+            .map_err(|err| {
+                TlsError::InvalidLocalCertificate(io::Error::new(
+                    ErrorKind::InvalidData,
+                    err.to_string(),
+                ))
+            })?;
+
+        let dns_name = rustls::ServerName::try_from(name).map_err(|_| TlsError::InvalidDnsName)?;
+
+        Ok(Self {
+            config: Arc::new(config),
             dns_name,
         })
     }
@@ -149,13 +192,13 @@ impl TlsClientConfig {
         &mut self,
         socket: TcpStream,
         endpoint: &HostAddr,
-    ) -> Result<PhysLayer, String> {
+    ) -> Result<PhysLayer, ConnectError> {
         let connector = tokio_rustls::TlsConnector::from(self.config.clone());
         match connector.connect(self.dns_name.clone(), socket).await {
-            Err(err) => Err(format!(
-                "failed to establish TLS session with {}: {}",
-                endpoint, err
-            )),
+            Err(err) => {
+                tracing::warn!("failed to establish TLS session with {}: {}", endpoint, err);
+                Err(ConnectError::Tls(err.into()))
+            }
             Ok(stream) => Ok(PhysLayer::new_tls(tokio_rustls::TlsStream::from(stream))),
         }
     }
@@ -233,7 +276,7 @@ struct OwnedTrustAnchor {
 
 impl OwnedTrustAnchor {
     /// Get a `webpki::TrustAnchor` by borrowing the owned elements.
-    fn to_trust_anchor(&self) -> webpki::TrustAnchor {
+    fn to_trust_anchor(&self) -> webpki::TrustAnchor<'_> {
         webpki::TrustAnchor {
             subject: &self.subject,
             spki: &self.spki,
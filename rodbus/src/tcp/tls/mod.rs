@@ -1,5 +1,7 @@
 pub(crate) mod client;
 pub(crate) mod server;
+#[cfg(feature = "dangerous-tls")]
+pub(crate) mod verify;
 
 use std::convert::TryFrom;
 use std::io::{self, ErrorKind};
@@ -34,6 +36,7 @@ pub enum CertificateMode {
 
 /// TLS-related errors
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum TlsError {
     /// Invalid peer certificate
     InvalidPeerCertificate(io::Error),
@@ -63,7 +66,16 @@ impl std::fmt::Display for TlsError {
     }
 }
 
-impl std::error::Error for TlsError {}
+impl std::error::Error for TlsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidPeerCertificate(err) => Some(err),
+            Self::InvalidLocalCertificate(err) => Some(err),
+            Self::InvalidPrivateKey(err) => Some(err),
+            Self::InvalidDnsName | Self::BadConfig(_) => None,
+        }
+    }
+}
 
 /// Minimum TLS version to allow
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -246,3 +258,17 @@ impl From<pkcs8::Error> for TlsError {
         TlsError::InvalidPrivateKey(io::Error::new(ErrorKind::InvalidData, from.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificate_errors_expose_the_io_error_as_source() {
+        let err = load_certs(Path::new("does-not-exist.pem"), false).unwrap_err();
+        let source = std::error::Error::source(&err)
+            .and_then(|x| x.downcast_ref::<io::Error>())
+            .unwrap();
+        assert_eq!(source.kind(), ErrorKind::NotFound);
+    }
+}
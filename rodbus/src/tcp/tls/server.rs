@@ -71,6 +71,31 @@ impl TlsServerConfig {
         })
     }
 
+    /// Create a TLS server config in which the certificates of the clients are verified by a
+    /// [`crate::server::CertificateVerifier`] instead of a [`CertificateMode`]
+    #[cfg(feature = "dangerous-tls")]
+    pub fn with_verifier(
+        local_cert_path: &Path,
+        private_key_path: &Path,
+        password: Option<&str>,
+        min_tls_version: MinTlsVersion,
+        verifier: Arc<dyn crate::tcp::tls::verify::CertificateVerifier>,
+    ) -> Result<Self, TlsError> {
+        let local_certs = load_certs(local_cert_path, true)?;
+        let private_key = load_private_key(private_key_path, password)?;
+
+        let config = build_server_config(
+            crate::tcp::tls::verify::CustomVerifier::new(verifier),
+            min_tls_version,
+            local_certs,
+            private_key,
+        )?;
+
+        Ok(TlsServerConfig {
+            inner: Arc::new(config),
+        })
+    }
+
     pub(crate) async fn handle_connection(
         &mut self,
         socket: TcpStream,
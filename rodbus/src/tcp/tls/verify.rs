@@ -0,0 +1,263 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio_rustls::rustls;
+
+use crate::tcp::tls::{load_certs, TlsError};
+
+/// SHA-256 fingerprint of a DER-encoded certificate
+///
+/// The fingerprint is parsed from and displayed as hexadecimal bytes separated by colons, e.g.
+/// the output of `openssl x509 -noout -fingerprint -sha256`. Parsing also accepts the bytes
+/// without separators and is case-insensitive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CertificateFingerprint([u8; 32]);
+
+/// Error returned when a [`CertificateFingerprint`] cannot be parsed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BadFingerprint;
+
+impl std::fmt::Display for BadFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("fingerprint is not 32 hexadecimal bytes")
+    }
+}
+
+impl std::error::Error for BadFingerprint {}
+
+impl CertificateFingerprint {
+    /// Create a fingerprint from the bytes of the digest
+    pub fn new(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+
+    /// Compute the fingerprint of a DER-encoded certificate
+    pub fn of(certificate: &[u8]) -> Self {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, certificate).as_ref());
+        Self(digest)
+    }
+
+    /// Compute the fingerprint of the single certificate of a PEM file
+    pub fn from_file(path: &Path) -> Result<Self, TlsError> {
+        let mut certs = load_certs(path, false)?;
+        match certs.pop() {
+            Some(cert) if certs.is_empty() => Ok(Self::of(&cert.0)),
+            _ => Err(TlsError::InvalidPeerCertificate(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "more than one certificate in pem file",
+            ))),
+        }
+    }
+
+    /// Bytes of the digest
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for CertificateFingerprint {
+    type Err = BadFingerprint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: Vec<u8> = s.bytes().filter(|x| *x != b':').collect();
+        if hex.len() != 64 {
+            return Err(BadFingerprint);
+        }
+
+        let mut digest = [0; 32];
+        for (byte, pair) in digest.iter_mut().zip(hex.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| BadFingerprint)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| BadFingerprint)?;
+        }
+        Ok(Self(digest))
+    }
+}
+
+impl std::fmt::Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the certificates presented by the peer of a TLS connection in place of the
+/// [`crate::client::CertificateMode`] verifications
+///
+/// A verifier is installed with [`crate::client::TlsClientConfig::with_verifier`] or
+/// [`crate::server::TlsServerConfig::with_verifier`]. The handshake signature is always
+/// verified by the TLS library, but everything else is up to the verifier, including the name
+/// of the server. A verifier that accepts any certificate makes the connection vulnerable to
+/// impersonation, which is why this is only available with the `dangerous-tls` feature.
+pub trait CertificateVerifier: Send + Sync {
+    /// Verify the DER-encoded certificates presented by the peer, the end-entity certificate
+    /// first. An error aborts the handshake and is logged.
+    fn verify(&self, certificates: &[&[u8]], now: SystemTime) -> Result<(), String>;
+}
+
+/// Accepts the peers whose end-entity certificate has one of the configured fingerprints and is
+/// currently valid
+///
+/// This allows secure Modbus to be tested before a PKI is in place, without exchanging the
+/// certificates themselves.
+#[derive(Clone, Debug)]
+pub struct FingerprintVerifier {
+    fingerprints: Vec<CertificateFingerprint>,
+}
+
+impl FingerprintVerifier {
+    /// Create a verifier accepting any of the `fingerprints`
+    pub fn new(fingerprints: Vec<CertificateFingerprint>) -> Self {
+        Self { fingerprints }
+    }
+}
+
+impl CertificateVerifier for FingerprintVerifier {
+    fn verify(&self, certificates: &[&[u8]], now: SystemTime) -> Result<(), String> {
+        let end_entity = certificates
+            .first()
+            .ok_or_else(|| "no peer certificate".to_string())?;
+
+        let fingerprint = CertificateFingerprint::of(end_entity);
+        if !self.fingerprints.contains(&fingerprint) {
+            return Err(format!("unknown certificate fingerprint: {}", fingerprint));
+        }
+
+        let parsed = rx509::x509::Certificate::parse(end_entity)
+            .map_err(|err| format!("unable to parse cert with rasn: {:?}", err))?;
+        let now = now
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| "failed to get current time".to_string())?;
+        let now = rx509::der::UtcTime::from_seconds_since_epoch(now.as_secs());
+        if !parsed.tbs_certificate.value.validity.is_valid(now) {
+            return Err("certificate is currently not valid".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Adapts a [`CertificateVerifier`] to the verifier traits of `rustls`
+pub(crate) struct CustomVerifier {
+    inner: Arc<dyn CertificateVerifier>,
+}
+
+impl CustomVerifier {
+    pub(crate) fn new(inner: Arc<dyn CertificateVerifier>) -> Arc<Self> {
+        Arc::new(Self { inner })
+    }
+
+    fn verify(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        now: SystemTime,
+    ) -> Result<(), rustls::Error> {
+        let certificates: Vec<&[u8]> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|x| x.0.as_slice())
+            .collect();
+        self.inner
+            .verify(&certificates, now)
+            .map_err(rustls::Error::InvalidCertificateData)
+    }
+}
+
+impl rustls::client::ServerCertVerifier for CustomVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        self.verify(end_entity, intermediates, now)?;
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+
+impl rustls::server::ClientCertVerifier for CustomVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        Some(true)
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<rustls::DistinguishedNames> {
+        // the acceptable issuers are not known
+        Some(Vec::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        now: SystemTime,
+    ) -> Result<rustls::server::ClientCertVerified, rustls::Error> {
+        self.verify(end_entity, intermediates, now)?;
+        Ok(rustls::server::ClientCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTITY1: &str = "B4:6F:E7:6F:09:DD:0B:0C:A1:2F:89:9A:6B:1B:E6:95:B6:A8:E0:01:7A:72:F4:8D:86:5A:D7:AC:D7:8F:BB:74";
+
+    fn cert(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../certs/self_signed")
+            .join(name);
+        load_certs(&path, false).unwrap().remove(0).0
+    }
+
+    #[test]
+    fn fingerprints_are_parsed_and_displayed_like_openssl() {
+        let fingerprint: CertificateFingerprint = ENTITY1.parse().unwrap();
+        assert_eq!(fingerprint.to_string(), ENTITY1);
+        assert_eq!(
+            ENTITY1.replace(':', "").to_lowercase().parse(),
+            Ok(fingerprint)
+        );
+        assert_eq!(
+            CertificateFingerprint::of(&cert("entity1_cert.pem")),
+            fingerprint
+        );
+        assert_eq!(
+            "B4:6F".parse::<CertificateFingerprint>(),
+            Err(BadFingerprint)
+        );
+        assert_eq!(
+            ENTITY1
+                .replace("B4", "G4")
+                .parse::<CertificateFingerprint>(),
+            Err(BadFingerprint)
+        );
+    }
+
+    #[test]
+    fn fingerprint_verifier_accepts_only_known_certificates() {
+        let verifier = FingerprintVerifier::new(vec![ENTITY1.parse().unwrap()]);
+        let now = SystemTime::now();
+        assert_eq!(verifier.verify(&[&cert("entity1_cert.pem")], now), Ok(()));
+        assert!(verifier.verify(&[&cert("entity2_cert.pem")], now).is_err());
+        assert!(verifier.verify(&[], now).is_err());
+        // certificates must also be valid
+        let expired = SystemTime::UNIX_EPOCH;
+        assert!(verifier
+            .verify(&[&cert("entity1_cert.pem")], expired)
+            .is_err());
+    }
+}
@@ -0,0 +1,1411 @@
+//! # Time
+//!
+//! Every timeout and delay of the library is driven by the Tokio timer, i.e. response timeouts,
+//! the delays of the [`RetryStrategy`](crate::RetryStrategy), the serial inter-frame delays and the
+//! server session timeouts. Enabling this feature also enables the `test-util` feature of Tokio, so
+//! a test can [`pause`](tokio::time::pause) the time and then [`advance`](tokio::time::advance) it
+//! explicitly, or let the runtime advance it automatically whenever every task is idle. This makes
+//! multi-second timeout and backoff scenarios run instantly and deterministically:
+//!
+//! ```no_run
+//! # use rodbus::*;
+//! # use rodbus::client::*;
+//! # use rodbus::server::*;
+//! # use rodbus::test_util::*;
+//! # use std::time::Duration;
+//! #[tokio::test(start_paused = true)]
+//! async fn times_out_without_waiting() {
+//!     let handlers = ServerHandlerMap::single(UnitId::new(1), MockHandler::new().wrap());
+//!     let (channel, _server) = channel_pair(handlers, DecodeLevel::nothing()).await;
+//!     // unit 2 does not exist and never answers, the minute elapses immediately
+//!     let param = RequestParam::new(UnitId::new(2), Duration::from_secs(60));
+//!     let result = channel.read_coils(param, AddressRange::try_from(0, 1).unwrap()).await;
+//!     assert_eq!(result.unwrap_err(), RequestError::ResponseTimeout);
+//! }
+//! ```
+//!
+//! Time can only be paused on the current-thread runtime. Prefer
+//! [`channel_pair`](crate::test_util::channel_pair) over the
+//! [`MockServer`](crate::test_util::MockServer) in such tests: the runtime considers itself idle
+//! while it waits for a socket, so it may auto-advance the time and expire a timeout before the
+//! operating system delivers the data.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+
+use tracing::Instrument;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::client::{
+    spawn_stream_client_task, spawn_tcp_client_task, CaptureDirection, Channel, ClientStream,
+    ConnectFuture, Connector, RecordedFrame, Recording,
+};
+use crate::common::frame::{FrameWriter, FramedReader};
+use crate::common::phys::PhysLayer;
+use crate::decode::DecodeLevel;
+use crate::exception::ExceptionCode;
+use crate::retry::default_retry_strategy;
+use crate::server::task::{AuthorizationType, SessionTask};
+use crate::server::*;
+use crate::tcp::frame::constants::{HEADER_LENGTH, MAX_LENGTH_FIELD};
+use crate::tcp::server::{ServerTask, TcpServerConnectionHandler};
+use crate::types::{Indexed, UnitId};
+
+pub use tokio::time::{advance, pause, resume};
+
+/// Table of the data model of a [`MockHandler`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MockTable {
+    /// Coils
+    Coils,
+    /// Discrete inputs
+    DiscreteInputs,
+    /// Holding registers
+    HoldingRegisters,
+    /// Input registers
+    InputRegisters,
+}
+
+/// Scriptable [`RequestHandler`] for integration tests
+///
+/// Only the addresses that were preloaded exist. Requests for any other address are answered
+/// with [`ExceptionCode::IllegalDataAddress`], and write multiple requests are only applied if
+/// every address exists.
+///
+/// Errors can be programmed in addition to the values:
+///
+/// * [`MockHandler::fail_next`] queues exceptions returned to the next requests, in order
+/// * [`MockHandler::fail_address`] makes every request that touches an address fail
+/// * [`MockHandler::set_read_only`] rejects every write with [`ExceptionCode::IllegalFunction`]
+///
+/// The value of a point can also be simulated by a closure evaluated each time the point is read,
+/// see [`MockHandler::simulate_input_register`] and the ready-made closures of [`behaviors`].
+#[derive(Debug, Default)]
+pub struct MockHandler {
+    coils: BTreeMap<u16, bool>,
+    discrete_inputs: BTreeMap<u16, bool>,
+    holding_registers: BTreeMap<u16, u16>,
+    input_registers: BTreeMap<u16, u16>,
+    address_faults: BTreeMap<(MockTable, u16), ExceptionCode>,
+    // reads only receive &self
+    scripted_faults: RefCell<VecDeque<ExceptionCode>>,
+    read_only: bool,
+    bit_behaviors: Behaviors<bool>,
+    register_behaviors: Behaviors<u16>,
+}
+
+/// Information passed to the closure that simulates a point each time the point is read
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReadContext {
+    /// Time elapsed since the closure was installed, measured with the Tokio clock
+    pub elapsed: std::time::Duration,
+    /// Number of times the point was read before
+    pub count: u64,
+}
+
+struct Behavior<T> {
+    start: tokio::time::Instant,
+    count: u64,
+    evaluate: Box<dyn FnMut(ReadContext) -> T + Send>,
+}
+
+// reads only receive &self
+struct Behaviors<T> {
+    inner: RefCell<BTreeMap<(MockTable, u16), Behavior<T>>>,
+}
+
+impl<T> Default for Behaviors<T> {
+    fn default() -> Self {
+        Self {
+            inner: RefCell::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Behaviors<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.inner.borrow().keys()).finish()
+    }
+}
+
+impl<T> Behaviors<T> {
+    fn insert<F>(&mut self, table: MockTable, address: u16, evaluate: F)
+    where
+        F: FnMut(ReadContext) -> T + Send + 'static,
+    {
+        self.inner.get_mut().insert(
+            (table, address),
+            Behavior {
+                start: tokio::time::Instant::now(),
+                count: 0,
+                evaluate: Box::new(evaluate),
+            },
+        );
+    }
+
+    fn evaluate(&self, table: MockTable, address: u16) -> Option<T> {
+        let mut inner = self.inner.borrow_mut();
+        let behavior = inner.get_mut(&(table, address))?;
+        let context = ReadContext {
+            elapsed: behavior.start.elapsed(),
+            count: behavior.count,
+        };
+        behavior.count += 1;
+        Some((behavior.evaluate)(context))
+    }
+}
+
+impl MockHandler {
+    /// Create a handler without any point
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preload coils starting at an address
+    pub fn with_coils(mut self, start: u16, values: &[bool]) -> Self {
+        load(&mut self.coils, start, values);
+        self
+    }
+
+    /// Preload discrete inputs starting at an address
+    pub fn with_discrete_inputs(mut self, start: u16, values: &[bool]) -> Self {
+        load(&mut self.discrete_inputs, start, values);
+        self
+    }
+
+    /// Preload holding registers starting at an address
+    pub fn with_holding_registers(mut self, start: u16, values: &[u16]) -> Self {
+        load(&mut self.holding_registers, start, values);
+        self
+    }
+
+    /// Preload input registers starting at an address
+    pub fn with_input_registers(mut self, start: u16, values: &[u16]) -> Self {
+        load(&mut self.input_registers, start, values);
+        self
+    }
+
+    /// Current value of a coil
+    pub fn coil(&self, address: u16) -> Option<bool> {
+        self.coils.get(&address).copied()
+    }
+
+    /// Current value of a discrete input
+    pub fn discrete_input(&self, address: u16) -> Option<bool> {
+        self.discrete_inputs.get(&address).copied()
+    }
+
+    /// Current value of a holding register
+    pub fn holding_register(&self, address: u16) -> Option<u16> {
+        self.holding_registers.get(&address).copied()
+    }
+
+    /// Current value of an input register
+    pub fn input_register(&self, address: u16) -> Option<u16> {
+        self.input_registers.get(&address).copied()
+    }
+
+    /// Change or create a discrete input, e.g. to simulate a change in the field
+    pub fn set_discrete_input(&mut self, address: u16, value: bool) {
+        self.discrete_inputs.insert(address, value);
+    }
+
+    /// Change or create an input register, e.g. to simulate a change in the field
+    pub fn set_input_register(&mut self, address: u16, value: u16) {
+        self.input_registers.insert(address, value);
+    }
+
+    /// Answer the next request with an exception
+    ///
+    /// Calling this multiple times queues the exceptions, which are returned to the subsequent requests in order
+    pub fn fail_next(&mut self, exception: ExceptionCode) {
+        self.scripted_faults.get_mut().push_back(exception);
+    }
+
+    /// Answer every request that reads or writes an address with an exception
+    pub fn fail_address(&mut self, table: MockTable, address: u16, exception: ExceptionCode) {
+        self.address_faults.insert((table, address), exception);
+    }
+
+    /// Remove all of the programmed errors
+    pub fn clear_faults(&mut self) {
+        self.address_faults.clear();
+        self.scripted_faults.get_mut().clear();
+    }
+
+    /// Reject every write request with [`ExceptionCode::IllegalFunction`]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Simulate the value of a coil with a closure evaluated each time the coil is read
+    ///
+    /// The coil exists even if it was not preloaded. Writes to the coil are accepted, but reads
+    /// keep returning the simulated value, and [`MockHandler::coil`] returns the last written value.
+    /// This applies to the other `simulate_` methods as well.
+    pub fn simulate_coil<F>(mut self, address: u16, evaluate: F) -> Self
+    where
+        F: FnMut(ReadContext) -> bool + Send + 'static,
+    {
+        self.coils.entry(address).or_insert(false);
+        self.bit_behaviors
+            .insert(MockTable::Coils, address, evaluate);
+        self
+    }
+
+    /// Simulate the value of a discrete input with a closure evaluated each time it is read
+    pub fn simulate_discrete_input<F>(mut self, address: u16, evaluate: F) -> Self
+    where
+        F: FnMut(ReadContext) -> bool + Send + 'static,
+    {
+        self.discrete_inputs.entry(address).or_insert(false);
+        self.bit_behaviors
+            .insert(MockTable::DiscreteInputs, address, evaluate);
+        self
+    }
+
+    /// Simulate the value of a holding register with a closure evaluated each time it is read
+    pub fn simulate_holding_register<F>(mut self, address: u16, evaluate: F) -> Self
+    where
+        F: FnMut(ReadContext) -> u16 + Send + 'static,
+    {
+        self.holding_registers.entry(address).or_insert(0);
+        self.register_behaviors
+            .insert(MockTable::HoldingRegisters, address, evaluate);
+        self
+    }
+
+    /// Simulate the value of an input register with a closure evaluated each time it is read
+    ///
+    /// ```
+    /// # use rodbus::test_util::*;
+    /// // a counter that is incremented on every read
+    /// let handler = MockHandler::new().simulate_input_register(0, |ctx| ctx.count as u16);
+    /// ```
+    pub fn simulate_input_register<F>(mut self, address: u16, evaluate: F) -> Self
+    where
+        F: FnMut(ReadContext) -> u16 + Send + 'static,
+    {
+        self.input_registers.entry(address).or_insert(0);
+        self.register_behaviors
+            .insert(MockTable::InputRegisters, address, evaluate);
+        self
+    }
+
+    fn read_bit(
+        &self,
+        table: MockTable,
+        map: &BTreeMap<u16, bool>,
+        address: u16,
+    ) -> Result<bool, ExceptionCode> {
+        self.check(table, address)?;
+        match self.bit_behaviors.evaluate(table, address) {
+            Some(value) => Ok(value),
+            None => get(map, address),
+        }
+    }
+
+    fn read_register(
+        &self,
+        table: MockTable,
+        map: &BTreeMap<u16, u16>,
+        address: u16,
+    ) -> Result<u16, ExceptionCode> {
+        self.check(table, address)?;
+        match self.register_behaviors.evaluate(table, address) {
+            Some(value) => Ok(value),
+            None => get(map, address),
+        }
+    }
+
+    fn check(&self, table: MockTable, address: u16) -> Result<(), ExceptionCode> {
+        if let Some(exception) = self.scripted_faults.borrow_mut().pop_front() {
+            return Err(exception);
+        }
+        match self.address_faults.get(&(table, address)) {
+            Some(exception) => Err(*exception),
+            None => Ok(()),
+        }
+    }
+
+    fn check_write(
+        &mut self,
+        table: MockTable,
+        start: u16,
+        count: u16,
+    ) -> Result<(), ExceptionCode> {
+        if self.read_only {
+            return Err(ExceptionCode::IllegalFunction);
+        }
+        if let Some(exception) = self.scripted_faults.get_mut().pop_front() {
+            return Err(exception);
+        }
+        for address in (start..=u16::MAX).take(count as usize) {
+            if let Some(exception) = self.address_faults.get(&(table, address)) {
+                return Err(*exception);
+            }
+            let exists = match table {
+                MockTable::Coils => self.coils.contains_key(&address),
+                _ => self.holding_registers.contains_key(&address),
+            };
+            if !exists {
+                return Err(ExceptionCode::IllegalDataAddress);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load<T: Copy>(map: &mut BTreeMap<u16, T>, start: u16, values: &[T]) {
+    for (address, value) in (start..=u16::MAX).zip(values) {
+        map.insert(address, *value);
+    }
+}
+
+fn get<T: Copy>(map: &BTreeMap<u16, T>, address: u16) -> Result<T, ExceptionCode> {
+    map.get(&address)
+        .copied()
+        .ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+impl RequestHandler for MockHandler {
+    fn read_coil(&self, address: u16) -> Result<bool, ExceptionCode> {
+        self.read_bit(MockTable::Coils, &self.coils, address)
+    }
+
+    fn read_discrete_input(&self, address: u16) -> Result<bool, ExceptionCode> {
+        self.read_bit(MockTable::DiscreteInputs, &self.discrete_inputs, address)
+    }
+
+    fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        self.read_register(
+            MockTable::HoldingRegisters,
+            &self.holding_registers,
+            address,
+        )
+    }
+
+    fn read_input_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        self.read_register(MockTable::InputRegisters, &self.input_registers, address)
+    }
+
+    fn write_single_coil(&mut self, value: Indexed<bool>) -> Result<(), ExceptionCode> {
+        self.check_write(MockTable::Coils, value.index, 1)?;
+        self.coils.insert(value.index, value.value);
+        Ok(())
+    }
+
+    fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+        self.check_write(MockTable::HoldingRegisters, value.index, 1)?;
+        self.holding_registers.insert(value.index, value.value);
+        Ok(())
+    }
+
+    fn write_multiple_coils(&mut self, values: WriteCoils) -> Result<(), ExceptionCode> {
+        self.check_write(MockTable::Coils, values.range.start, values.range.count)?;
+        for value in values.iterator {
+            self.coils.insert(value.index, value.value);
+        }
+        Ok(())
+    }
+
+    fn write_multiple_registers(&mut self, values: WriteRegisters) -> Result<(), ExceptionCode> {
+        self.check_write(
+            MockTable::HoldingRegisters,
+            values.range.start,
+            values.range.count,
+        )?;
+        for value in values.iterator {
+            self.holding_registers.insert(value.index, value.value);
+        }
+        Ok(())
+    }
+}
+
+/// Ready-made closures that simulate the dynamics of field values, for the `simulate_` methods of
+/// [`MockHandler`]
+pub mod behaviors {
+    use std::time::Duration;
+
+    use super::ReadContext;
+
+    /// A value that starts at `start` and is incremented by `step` on each read, wrapping around
+    pub fn counter(start: u16, step: u16) -> impl FnMut(ReadContext) -> u16 + Send {
+        move |ctx| start.wrapping_add(step.wrapping_mul(ctx.count as u16))
+    }
+
+    /// A value that changes at scheduled times
+    ///
+    /// The value is `initial` until the time of the first change has elapsed, then the value of the
+    /// last change whose time has elapsed. The changes must be sorted by time.
+    pub fn steps<T>(initial: T, changes: Vec<(Duration, T)>) -> impl FnMut(ReadContext) -> T + Send
+    where
+        T: Copy + Send + 'static,
+    {
+        move |ctx| {
+            changes
+                .iter()
+                .take_while(|(time, _)| *time <= ctx.elapsed)
+                .last()
+                .map_or(initial, |(_, value)| *value)
+        }
+    }
+
+    /// A value that is `true` during the first half of each period and `false` during the second
+    pub fn square_wave(period: Duration) -> impl FnMut(ReadContext) -> bool + Send {
+        move |ctx| {
+            let period = period.as_nanos().max(1);
+            ctx.elapsed.as_nanos() % period < period.div_ceil(2)
+        }
+    }
+
+    /// A value that varies randomly between `center - amplitude` and `center + amplitude` on each read
+    ///
+    /// The sequence only depends on the seed, so a failing test can be reproduced exactly.
+    pub fn noise(center: u16, amplitude: u16, seed: u64) -> impl FnMut(ReadContext) -> u16 + Send {
+        // xorshift cannot leave the zero state
+        let mut state = seed.max(1);
+        move |_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let span = 2 * amplitude as u64 + 1;
+            let offset = (state % span) as i64 - amplitude as i64;
+            (center as i64 + offset).clamp(0, u16::MAX as i64) as u16
+        }
+    }
+}
+
+/// In-process Modbus TCP server backed by a [`MockHandler`]
+///
+/// The server listens on an ephemeral port of the loopback interface and is shut down when dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    address: SocketAddr,
+    handler: ServerHandlerType<MockHandler>,
+    _handle: ServerHandle,
+}
+
+impl MockServer {
+    /// Spawn a server that answers requests for a single unit id
+    ///
+    /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+    pub async fn spawn(id: UnitId, handler: MockHandler) -> Result<Self, std::io::Error> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let handler = handler.wrap();
+        let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+
+        let mut task = ServerTask::new(
+            1,
+            listener,
+            ServerHandlerMap::single(id, handler.clone()),
+            TcpServerConnectionHandler::Tcp,
+            AddressFilter::Any,
+            DecodeLevel::nothing(),
+        );
+        tokio::spawn(
+            async move { task.run(rx).await }
+                .instrument(tracing::info_span!("Modbus-Mock-Server", "listen" = ?address)),
+        );
+
+        Ok(Self {
+            address,
+            handler,
+            _handle: ServerHandle::new::<MockHandler>(tx),
+        })
+    }
+
+    /// Address on which the server is listening
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Handler of the server, to change its values and faults or to inspect the effect of writes
+    pub fn handler(&self) -> &ServerHandlerType<MockHandler> {
+        &self.handler
+    }
+
+    /// Spawn an enabled client channel connected to the server
+    pub async fn client(&self) -> Channel {
+        let channel = spawn_tcp_client_task(
+            self.address.into(),
+            1,
+            default_retry_strategy(),
+            DecodeLevel::nothing(),
+            None,
+        );
+        // the channel was just created, so it cannot be shut down
+        let _ = channel.enable().await;
+        channel
+    }
+}
+
+/// Size of the in-memory buffer in each direction of a [`channel_pair`]
+const PAIR_BUFFER_SIZE: usize = 4096;
+
+/// Yields its stream once, a pair cannot reconnect
+pub(crate) struct PairConnector {
+    pub(crate) stream: Option<tokio::io::DuplexStream>,
+}
+
+impl Connector for PairConnector {
+    fn connect(&mut self) -> ConnectFuture<'_> {
+        let stream = self.stream.take();
+        Box::pin(async move {
+            let stream: Box<dyn ClientStream> = match stream {
+                Some(x) => Box::new(x),
+                None => return Err(std::io::ErrorKind::ConnectionRefused.into()),
+            };
+            Ok(stream)
+        })
+    }
+}
+
+/// Sink of a capture whose contents remain readable by the test
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer {
+    inner: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+}
+
+#[cfg(test)]
+impl SharedBuffer {
+    pub(crate) fn contents(&self) -> Vec<u8> {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Create an enabled client channel connected to a server session through an in-memory stream
+///
+/// No socket or port is used, which makes tests fast and deterministic. Frames are exchanged
+/// using the Modbus TCP framing. The server session ends when the returned [`ServerHandle`] or
+/// the channel is dropped. The channel cannot reconnect once the session has ended.
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn channel_pair<T: RequestHandler>(
+    handlers: ServerHandlerMap<T>,
+    decode: DecodeLevel,
+) -> (Channel, ServerHandle) {
+    let (client, server) = tokio::io::duplex(PAIR_BUFFER_SIZE);
+    spawn_pair(client, server, handlers, decode).await
+}
+
+/// Create a [`channel_pair`] whose requests and responses go through fault plans
+///
+/// See [`inject_faults`] for how the faults are applied.
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn faulty_channel_pair<T: RequestHandler>(
+    handlers: ServerHandlerMap<T>,
+    decode: DecodeLevel,
+    requests: FaultPlan,
+    responses: FaultPlan,
+) -> (Channel, ServerHandle) {
+    let (client, server) = tokio::io::duplex(PAIR_BUFFER_SIZE);
+    let client = inject_faults(client, requests, responses);
+    spawn_pair(client, server, handlers, decode).await
+}
+
+async fn spawn_pair<T: RequestHandler>(
+    client: tokio::io::DuplexStream,
+    server: tokio::io::DuplexStream,
+    handlers: ServerHandlerMap<T>,
+    decode: DecodeLevel,
+) -> (Channel, ServerHandle) {
+    let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let mut session = SessionTask::new(
+        handlers,
+        AuthorizationType::None,
+        FrameWriter::tcp(),
+        FramedReader::tcp(),
+        rx,
+        decode,
+    );
+    tokio::spawn(
+        async move {
+            let mut phys = PhysLayer::new_stream(Box::new(server));
+            session.run(&mut phys).await;
+        }
+        .instrument(tracing::info_span!("Modbus-Pair-Server")),
+    );
+
+    let channel = spawn_stream_client_task(
+        Box::new(PairConnector {
+            stream: Some(client),
+        }),
+        1,
+        default_retry_strategy(),
+        decode,
+        None,
+    );
+    // the channel was just created, so it cannot be shut down
+    let _ = channel.enable().await;
+
+    (channel, ServerHandle::new::<T>(tx))
+}
+
+/// Fault applied to a chunk of data by the streams of [`inject_faults`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Forward the chunk unchanged
+    Pass,
+    /// Discard the chunk
+    Drop,
+    /// Forward the chunk twice
+    Duplicate,
+    /// Forward the chunk after a delay, the following chunks are queued behind it
+    Delay(std::time::Duration),
+    /// Only forward the specified number of leading bytes
+    Truncate(usize),
+    /// Invert the bit at the specified index, counted from the most significant bit of the first
+    /// byte and wrapping around at the end of the chunk
+    FlipBit(usize),
+}
+
+/// Decides which [`Fault`] is applied to each chunk of data
+///
+/// The scripted faults are applied first, in order. Once the script is exhausted, each chunk
+/// draws a random fault according to the configured probabilities, or passes unchanged. The
+/// random sequence only depends on the seed, so a failing test can be reproduced exactly.
+#[derive(Clone, Debug)]
+pub struct FaultPlan {
+    script: VecDeque<Fault>,
+    random: Vec<(f64, Fault)>,
+    state: u64,
+}
+
+impl Default for FaultPlan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultPlan {
+    /// Create a plan that passes everything unchanged
+    pub fn new() -> Self {
+        Self {
+            script: VecDeque::new(),
+            random: Vec::new(),
+            state: 0x853C_49E6_748F_EA9B,
+        }
+    }
+
+    /// Append faults to the script
+    pub fn script<I: IntoIterator<Item = Fault>>(mut self, faults: I) -> Self {
+        self.script.extend(faults);
+        self
+    }
+
+    /// Apply a fault with a probability between 0 and 1 once the script is exhausted
+    ///
+    /// The probabilities of the faults are cumulative, they should not add up to more than 1.
+    pub fn with_probability(mut self, probability: f64, fault: Fault) -> Self {
+        self.random.push((probability, fault));
+        self
+    }
+
+    /// Seed the generator of the random faults
+    pub fn seed(mut self, seed: u64) -> Self {
+        // xorshift cannot leave the zero state
+        self.state = seed.max(1);
+        self
+    }
+
+    /// Fault to apply to the next chunk
+    pub fn next_fault(&mut self) -> Fault {
+        if let Some(fault) = self.script.pop_front() {
+            return fault;
+        }
+        if self.random.is_empty() {
+            return Fault::Pass;
+        }
+        let mut sample = self.next_f64();
+        for (probability, fault) in &self.random {
+            if sample < *probability {
+                return *fault;
+            }
+            sample -= probability;
+        }
+        Fault::Pass
+    }
+
+    /// xorshift64* mapped to [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Size of the chunks forwarded by the streams of [`inject_faults`]
+const FAULT_CHUNK_SIZE: usize = 4096;
+
+/// Wrap a stream so that the data written to and read from it goes through fault plans
+///
+/// Returns the stream to use in place of `stream`. Data written to it is subject to `outbound`
+/// before it reaches `stream`, and data received from `stream` is subject to `inbound` before it
+/// can be read. Faults apply to each chunk of data as it was written by the peer, which is one
+/// frame for the writers of this library. The forwarding runs on spawned tasks that end when
+/// either side is closed.
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn inject_faults<S>(
+    stream: S,
+    outbound: FaultPlan,
+    inbound: FaultPlan,
+) -> tokio::io::DuplexStream
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+{
+    let (user, relay) = tokio::io::duplex(PAIR_BUFFER_SIZE);
+    let (relay_read, relay_write) = tokio::io::split(relay);
+    let (inner_read, inner_write) = tokio::io::split(stream);
+    tokio::spawn(
+        forward_with_faults(relay_read, inner_write, outbound)
+            .instrument(tracing::info_span!("Modbus-Fault-Outbound")),
+    );
+    tokio::spawn(
+        forward_with_faults(inner_read, relay_write, inbound)
+            .instrument(tracing::info_span!("Modbus-Fault-Inbound")),
+    );
+    user
+}
+
+async fn forward_with_faults<R, W>(mut from: R, mut to: W, mut plan: FaultPlan)
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let mut buffer = [0u8; FAULT_CHUNK_SIZE];
+    loop {
+        let count = match from.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(count) => count,
+        };
+        let chunk = &mut buffer[..count];
+        let fault = plan.next_fault();
+        tracing::debug!("{} bytes: {:?}", count, fault);
+        let result = match fault {
+            Fault::Pass => to.write_all(chunk).await,
+            Fault::Drop => Ok(()),
+            Fault::Duplicate => match to.write_all(chunk).await {
+                Ok(()) => to.write_all(chunk).await,
+                Err(err) => Err(err),
+            },
+            Fault::Delay(delay) => {
+                tokio::time::sleep(delay).await;
+                to.write_all(chunk).await
+            }
+            Fault::Truncate(len) => to.write_all(&chunk[..len.min(count)]).await,
+            Fault::FlipBit(bit) => {
+                let bit = bit % (count * 8);
+                chunk[bit / 8] ^= 0x80 >> (bit % 8);
+                to.write_all(chunk).await
+            }
+        };
+        if result.is_err() {
+            break;
+        }
+    }
+    let _ = to.shutdown().await;
+}
+
+/// Error returned when a replayed session diverges from its [`Recording`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The stream failed
+    Io(std::io::ErrorKind),
+    /// The peer sent an invalid MBAP frame
+    BadFrame,
+    /// The peer did not respond within the timeout
+    ResponseTimeout {
+        /// Index of the recorded frame that was expected
+        index: usize,
+    },
+    /// A frame differs from the recording
+    Mismatch {
+        /// Index of the recorded frame that was expected
+        index: usize,
+        /// Frame that was expected, or `None` if the recording had ended
+        expected: Option<RecordedFrame>,
+        /// Frame that was exchanged
+        actual: RecordedFrame,
+    },
+}
+
+impl std::error::Error for ReplayError {}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(kind) => write!(f, "replay failed: {}", std::io::Error::from(*kind)),
+            ReplayError::BadFrame => f.write_str("peer sent an invalid MBAP frame"),
+            ReplayError::ResponseTimeout { index } => {
+                write!(f, "no response received for recorded frame {}", index)
+            }
+            ReplayError::Mismatch {
+                index,
+                expected: Some(expected),
+                actual,
+            } => write!(
+                f,
+                "frame {} differs from the recording - expected: {:02X?} actual: {:02X?}",
+                index, expected.pdu, actual.pdu
+            ),
+            ReplayError::Mismatch {
+                index,
+                expected: None,
+                actual,
+            } => write!(
+                f,
+                "unexpected frame {} after the end of the recording: {:02X?}",
+                index, actual.pdu
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err.kind())
+    }
+}
+
+/// Create an enabled client channel connected to a replay of the server side of a [`Recording`]
+///
+/// Each request of the channel is compared with the next recorded request, and answered with the
+/// recorded responses that follow it. A recorded request without a response, e.g. one that timed
+/// out, is not answered. The returned task completes when the recording has been replayed entirely,
+/// or with an error as soon as a request differs from the recording.
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn replay_server(
+    recording: Recording,
+    decode: DecodeLevel,
+) -> (Channel, tokio::task::JoinHandle<Result<(), ReplayError>>) {
+    let (client, mut server) = tokio::io::duplex(PAIR_BUFFER_SIZE);
+
+    let task = tokio::spawn(
+        async move {
+            let frames = recording.frames();
+            let mut index = 0;
+            while index < frames.len() {
+                let (tx_id, actual) = read_mbap(&mut server, CaptureDirection::Transmit).await?;
+                check_frame(frames, &mut index, actual)?;
+                while let Some(response) = frames
+                    .get(index)
+                    .filter(|x| x.direction == CaptureDirection::Receive)
+                {
+                    write_mbap(&mut server, tx_id, response).await?;
+                    index += 1;
+                }
+            }
+            Ok(())
+        }
+        .instrument(tracing::info_span!("Modbus-Replay-Server")),
+    );
+
+    let channel = spawn_stream_client_task(
+        Box::new(PairConnector {
+            stream: Some(client),
+        }),
+        1,
+        default_retry_strategy(),
+        decode,
+        None,
+    );
+    // the channel was just created, so it cannot be shut down
+    let _ = channel.enable().await;
+
+    (channel, task)
+}
+
+/// Replay the client side of a [`Recording`] against a server using the Modbus TCP framing
+///
+/// The recorded requests are sent in order over the stream, e.g. a [`tokio::net::TcpStream`]
+/// connected to the server, and every response is compared with the recorded one. Each response
+/// must be received within `timeout`.
+pub async fn replay_client<S>(
+    recording: &Recording,
+    mut stream: S,
+    timeout: std::time::Duration,
+) -> Result<(), ReplayError>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let frames = recording.frames();
+    let mut tx_id: u16 = 0;
+    let mut index = 0;
+    while let Some(request) = frames.get(index) {
+        index += 1;
+        if request.direction != CaptureDirection::Transmit {
+            continue;
+        }
+        write_mbap(&mut stream, tx_id, request).await?;
+        while frames
+            .get(index)
+            .is_some_and(|x| x.direction == CaptureDirection::Receive)
+        {
+            let (_, actual) =
+                tokio::time::timeout(timeout, read_mbap(&mut stream, CaptureDirection::Receive))
+                    .await
+                    .map_err(|_| ReplayError::ResponseTimeout { index })??;
+            check_frame(frames, &mut index, actual)?;
+        }
+        tx_id = tx_id.wrapping_add(1);
+    }
+    Ok(())
+}
+
+fn check_frame(
+    frames: &[RecordedFrame],
+    index: &mut usize,
+    actual: RecordedFrame,
+) -> Result<(), ReplayError> {
+    let expected = frames.get(*index);
+    if expected != Some(&actual) {
+        return Err(ReplayError::Mismatch {
+            index: *index,
+            expected: expected.cloned(),
+            actual,
+        });
+    }
+    *index += 1;
+    Ok(())
+}
+
+async fn read_mbap<S>(
+    stream: &mut S,
+    direction: CaptureDirection,
+) -> Result<(u16, RecordedFrame), ReplayError>
+where
+    S: tokio::io::AsyncRead + Unpin,
+{
+    let mut header = [0u8; HEADER_LENGTH];
+    stream.read_exact(&mut header).await?;
+    let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    // the PDU contains at least the function code
+    if !(2..=MAX_LENGTH_FIELD).contains(&length) {
+        return Err(ReplayError::BadFrame);
+    }
+    let mut pdu = vec![0u8; length - 1];
+    stream.read_exact(&mut pdu).await?;
+    let frame = RecordedFrame {
+        direction,
+        unit_id: UnitId::new(header[6]),
+        pdu,
+    };
+    Ok((u16::from_be_bytes([header[0], header[1]]), frame))
+}
+
+async fn write_mbap<S>(stream: &mut S, tx_id: u16, frame: &RecordedFrame) -> std::io::Result<()>
+where
+    S: tokio::io::AsyncWrite + Unpin,
+{
+    let mut adu = Vec::with_capacity(7 + frame.pdu.len());
+    adu.extend_from_slice(&tx_id.to_be_bytes());
+    adu.extend_from_slice(&[0, 0]);
+    adu.extend_from_slice(&((frame.pdu.len() + 1) as u16).to_be_bytes());
+    adu.push(frame.unit_id.value);
+    adu.extend_from_slice(&frame.pdu);
+    stream.write_all(&adu).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientState, Listener, RequestParam, WriteMultiple};
+    use crate::error::RequestError;
+    use crate::retry::doubling_retry_strategy;
+    use crate::types::AddressRange;
+    use crate::MaybeAsync;
+    use std::time::Duration;
+
+    fn param() -> RequestParam {
+        RequestParam::new(UnitId::new(1), Duration::from_secs(1))
+    }
+
+    #[tokio::test]
+    async fn serves_preloaded_values_and_records_writes() {
+        let server = MockServer::spawn(
+            UnitId::new(1),
+            MockHandler::new()
+                .with_holding_registers(10, &[1, 2, 3])
+                .with_coils(0, &[true, false]),
+        )
+        .await
+        .unwrap();
+        let channel = server.client().await;
+
+        let values = channel
+            .read_holding_registers(param(), AddressRange::try_from(10, 3).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Indexed::new(10, 1),
+                Indexed::new(11, 2),
+                Indexed::new(12, 3)
+            ]
+        );
+
+        channel
+            .write_multiple_coils(param(), WriteMultiple::from(0, vec![false, true]).unwrap())
+            .await
+            .unwrap();
+        let handler = server.handler().lock().unwrap();
+        assert_eq!(handler.coil(0), Some(false));
+        assert_eq!(handler.coil(1), Some(true));
+    }
+
+    #[tokio::test]
+    async fn returns_programmed_exceptions() {
+        let mut handler = MockHandler::new().with_input_registers(0, &[7, 8]);
+        handler.fail_next(ExceptionCode::ServerDeviceBusy);
+        handler.fail_address(
+            MockTable::InputRegisters,
+            1,
+            ExceptionCode::ServerDeviceFailure,
+        );
+        let server = MockServer::spawn(UnitId::new(1), handler).await.unwrap();
+        let channel = server.client().await;
+
+        let range = AddressRange::try_from(0, 1).unwrap();
+        assert_eq!(
+            channel
+                .read_input_registers(param(), range)
+                .await
+                .unwrap_err(),
+            RequestError::Exception(ExceptionCode::ServerDeviceBusy)
+        );
+        assert_eq!(
+            channel.read_input_registers(param(), range).await,
+            Ok(vec![Indexed::new(0, 7)])
+        );
+        assert_eq!(
+            channel
+                .read_input_registers(param(), AddressRange::try_from(0, 2).unwrap())
+                .await
+                .unwrap_err(),
+            RequestError::Exception(ExceptionCode::ServerDeviceFailure)
+        );
+        assert_eq!(
+            channel
+                .read_input_registers(param(), AddressRange::try_from(2, 1).unwrap())
+                .await
+                .unwrap_err(),
+            RequestError::Exception(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_writes_when_read_only() {
+        let mut handler = MockHandler::new().with_holding_registers(0, &[0]);
+        handler.set_read_only(true);
+        let server = MockServer::spawn(UnitId::new(1), handler).await.unwrap();
+        let channel = server.client().await;
+
+        assert_eq!(
+            channel
+                .write_single_register(param(), Indexed::new(0, 1))
+                .await
+                .unwrap_err(),
+            RequestError::Exception(ExceptionCode::IllegalFunction)
+        );
+        assert_eq!(
+            server.handler().lock().unwrap().holding_register(0),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_pair_exchanges_requests_in_memory() {
+        let handler = MockHandler::new().with_holding_registers(0, &[0, 0]).wrap();
+        let (channel, _server) = channel_pair(
+            ServerHandlerMap::single(UnitId::new(1), handler.clone()),
+            DecodeLevel::nothing(),
+        )
+        .await;
+
+        channel
+            .write_multiple_registers(param(), WriteMultiple::from(0, vec![3, 4]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            channel
+                .read_holding_registers(param(), AddressRange::try_from(0, 2).unwrap())
+                .await,
+            Ok(vec![Indexed::new(0, 3), Indexed::new(1, 4)])
+        );
+        assert_eq!(handler.lock().unwrap().holding_register(1), Some(4));
+
+        // an unknown unit id is not answered by the server
+        assert_eq!(
+            channel
+                .read_holding_registers(
+                    RequestParam::new(UnitId::new(2), Duration::from_millis(50)),
+                    AddressRange::try_from(0, 1).unwrap()
+                )
+                .await
+                .unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn response_timeout_elapses_in_virtual_time() {
+        let (channel, _server) = channel_pair(
+            ServerHandlerMap::single(UnitId::new(1), MockHandler::new().wrap()),
+            DecodeLevel::nothing(),
+        )
+        .await;
+
+        let start = tokio::time::Instant::now();
+        let result = channel
+            .read_coils(
+                RequestParam::new(UnitId::new(2), Duration::from_secs(60)),
+                AddressRange::try_from(0, 1).unwrap(),
+            )
+            .await;
+        assert_eq!(result.unwrap_err(), RequestError::ResponseTimeout);
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+    }
+
+    struct Recorder(tokio::sync::mpsc::UnboundedSender<(ClientState, tokio::time::Instant)>);
+
+    impl Listener<ClientState> for Recorder {
+        fn update(&mut self, value: ClientState) -> MaybeAsync<()> {
+            let _ = self.0.send((value, tokio::time::Instant::now()));
+            MaybeAsync::ready(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_delays_elapse_in_virtual_time() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let channel = spawn_stream_client_task(
+            Box::new(PairConnector { stream: None }),
+            1,
+            doubling_retry_strategy(Duration::from_secs(1), Duration::from_secs(4)),
+            DecodeLevel::nothing(),
+            Some(Box::new(Recorder(tx))),
+        );
+        channel.enable().await.unwrap();
+
+        let mut waits = Vec::new();
+        let mut last_connect = None;
+        while waits.len() < 4 {
+            match rx.recv().await.unwrap() {
+                (ClientState::Connecting, now) => {
+                    if let Some((delay, started)) = last_connect.take() {
+                        assert_eq!(now - started, delay);
+                    }
+                }
+                (ClientState::WaitAfterFailedConnect(delay), now) => {
+                    waits.push(delay);
+                    last_connect = Some((delay, now));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(waits, [1, 2, 4, 4].map(Duration::from_secs).to_vec());
+    }
+
+    async fn record_session() -> (Recording, MockServer) {
+        let server = MockServer::spawn(
+            UnitId::new(1),
+            MockHandler::new().with_holding_registers(0, &[1, 2]),
+        )
+        .await
+        .unwrap();
+        let channel = server.client().await;
+        let buffer = SharedBuffer::default();
+        channel
+            .set_capture(Some(
+                crate::client::PcapWriter::new(Box::new(buffer.clone())).unwrap(),
+            ))
+            .await
+            .unwrap();
+
+        channel
+            .read_holding_registers(param(), AddressRange::try_from(0, 2).unwrap())
+            .await
+            .unwrap();
+        channel
+            .write_single_register(param(), Indexed::new(1, 7))
+            .await
+            .unwrap();
+        channel.set_capture(None).await.unwrap();
+        // the capture is flushed by the channel task
+        channel
+            .read_holding_registers(param(), AddressRange::try_from(0, 1).unwrap())
+            .await
+            .unwrap();
+
+        let recording = Recording::parse(&buffer.contents()).unwrap();
+        (recording, server)
+    }
+
+    #[tokio::test]
+    async fn replays_server_side_of_recording() {
+        let (recording, _server) = record_session().await;
+        assert_eq!(recording.frames().len(), 4);
+
+        let (channel, task) = replay_server(recording, DecodeLevel::nothing()).await;
+        assert_eq!(
+            channel
+                .read_holding_registers(param(), AddressRange::try_from(0, 2).unwrap())
+                .await,
+            Ok(vec![Indexed::new(0, 1), Indexed::new(1, 2)])
+        );
+        // the replay diverges from the recording
+        channel
+            .write_single_register(param(), Indexed::new(1, 8))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(ReplayError::Mismatch { index: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn replays_client_side_of_recording() {
+        let (recording, server) = record_session().await;
+        let connect = || tokio::net::TcpStream::connect(server.address());
+
+        // the recorded write was applied, so the read now returns a different value
+        let err = replay_client(&recording, connect().await.unwrap(), Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ReplayError::Mismatch { index: 1, .. }));
+
+        server
+            .client()
+            .await
+            .write_single_register(param(), Indexed::new(1, 2))
+            .await
+            .unwrap();
+        replay_client(&recording, connect().await.unwrap(), Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn client_recovers_from_dropped_and_duplicated_frames() {
+        let handler = MockHandler::new().with_input_registers(0, &[5, 6]).wrap();
+        let (channel, _server) = faulty_channel_pair(
+            ServerHandlerMap::single(UnitId::new(1), handler),
+            DecodeLevel::nothing(),
+            FaultPlan::new().script([Fault::Drop]),
+            FaultPlan::new().script([Fault::Duplicate]),
+        )
+        .await;
+        let param = RequestParam::new(UnitId::new(1), Duration::from_millis(100));
+        let range = AddressRange::try_from(0, 2).unwrap();
+        let expected = Ok(vec![Indexed::new(0, 5), Indexed::new(1, 6)]);
+
+        // the request is dropped
+        assert_eq!(
+            channel
+                .read_input_registers(param, range)
+                .await
+                .unwrap_err(),
+            RequestError::ResponseTimeout
+        );
+        // the response is duplicated, the copy is discarded when the next response is expected
+        assert_eq!(channel.read_input_registers(param, range).await, expected);
+        assert_eq!(channel.read_input_registers(param, range).await, expected);
+    }
+
+    #[test]
+    fn fault_plan_is_reproducible() {
+        let plan = || {
+            FaultPlan::new()
+                .script([Fault::Truncate(3)])
+                .with_probability(0.25, Fault::Drop)
+                .with_probability(0.25, Fault::FlipBit(9))
+                .seed(42)
+        };
+        let (mut first, mut second) = (plan(), plan());
+        let faults: Vec<Fault> = (0..1000).map(|_| first.next_fault()).collect();
+        assert_eq!(
+            faults,
+            (0..1000).map(|_| second.next_fault()).collect::<Vec<_>>()
+        );
+
+        assert_eq!(faults[0], Fault::Truncate(3));
+        let drops = faults.iter().filter(|x| **x == Fault::Drop).count();
+        let passes = faults.iter().filter(|x| **x == Fault::Pass).count();
+        assert!((200..300).contains(&drops));
+        assert!((450..550).contains(&passes));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn simulates_values_on_each_read() {
+        let handler = MockHandler::new()
+            .simulate_input_register(0, behaviors::counter(10, 5))
+            .simulate_input_register(
+                1,
+                behaviors::steps(
+                    0,
+                    vec![(Duration::from_secs(1), 100), (Duration::from_secs(3), 50)],
+                ),
+            )
+            .simulate_discrete_input(0, behaviors::square_wave(Duration::from_secs(2)))
+            .wrap();
+        let (channel, _server) = channel_pair(
+            ServerHandlerMap::single(UnitId::new(1), handler),
+            DecodeLevel::nothing(),
+        )
+        .await;
+
+        async fn poll(channel: &Channel) -> (u16, u16, bool) {
+            let registers = channel
+                .read_input_registers(param(), AddressRange::try_from(0, 2).unwrap())
+                .await
+                .unwrap();
+            let input = channel
+                .read_discrete_inputs(param(), AddressRange::try_from(0, 1).unwrap())
+                .await
+                .unwrap();
+            (registers[0].value, registers[1].value, input[0].value)
+        }
+
+        assert_eq!(poll(&channel).await, (10, 0, true));
+        advance(Duration::from_millis(1500)).await;
+        assert_eq!(poll(&channel).await, (15, 100, false));
+        advance(Duration::from_secs(3)).await;
+        assert_eq!(poll(&channel).await, (20, 50, true));
+    }
+
+    #[test]
+    fn noise_stays_within_amplitude() {
+        let mut noise = behaviors::noise(1, 3, 7);
+        let context = ReadContext {
+            elapsed: Duration::ZERO,
+            count: 0,
+        };
+        let values: Vec<u16> = (0..1000).map(|_| noise(context)).collect();
+        assert!(values.iter().all(|x| *x <= 4));
+        assert!(values.contains(&0) && values.contains(&4));
+    }
+}
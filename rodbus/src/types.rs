@@ -2,11 +2,12 @@ use crate::decode::AppDecodeLevel;
 use crate::error::{AduParseError, InvalidRange};
 
 use scursor::ReadCursor;
+use smallvec::SmallVec;
 
 use crate::error::RequestError;
 
 /// Modbus unit identifier, just a type-safe wrapper around `u8`
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Ord, Eq, Hash)]
 pub struct UnitId {
     /// underlying raw value
     pub value: u8,
@@ -14,7 +15,9 @@ pub struct UnitId {
 
 /// Start and count tuple used when making various requests
 /// Cannot be constructed with invalid start/count
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Ranges are ordered by start address, then by count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct AddressRange {
     /// Starting address of the range
     pub start: u16,
@@ -51,7 +54,7 @@ impl ReadRegistersRange {
 }
 
 /// Value and its address
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Indexed<T> {
     /// Address of the value
     pub index: u16,
@@ -80,11 +83,56 @@ pub struct RegisterIterator<'a> {
     pos: u16,
 }
 
+/// Values read from a contiguous range of addresses
+///
+/// The values are stored without their addresses, which are derived from the start address, so
+/// large reads use a single compact allocation. Reads of up to 16 values, which are the most
+/// common polls, are stored inline and don't allocate at all. [`ReadResult::iter`] yields
+/// [`Indexed`] values when the addresses are needed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadResult<T> {
+    start: u16,
+    values: SmallVec<[T; 16]>,
+}
+
+/// Coils or discrete inputs read from a contiguous range of addresses
+pub type ReadBitsResult = ReadResult<bool>;
+
+/// Holding or input registers read from a contiguous range of addresses
+pub type ReadRegistersResult = ReadResult<u16>;
+
+/// Order of the registers holding a value wider than 16 bits
+///
+/// Each register is always big-endian on the wire. Devices disagree on whether the most
+/// significant register comes first, so this is a property of the device being read or written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WordOrder {
+    /// The most significant register is at the lowest address, e.g. 0x11223344 is written as
+    /// `[0x1122, 0x3344]`
+    #[default]
+    HighFirst,
+    /// The least significant register is at the lowest address, e.g. 0x11223344 is written as
+    /// `[0x3344, 0x1122]`
+    LowFirst,
+}
+
 pub(crate) struct RegisterIteratorDisplay<'a> {
     iterator: RegisterIterator<'a>,
     level: AppDecodeLevel,
 }
 
+impl From<u8> for UnitId {
+    fn from(value: u8) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<UnitId> for u8 {
+    fn from(x: UnitId) -> Self {
+        x.value
+    }
+}
+
 impl std::fmt::Display for UnitId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:#04X}", self.value)
@@ -226,6 +274,12 @@ where
     }
 }
 
+impl<T> From<Indexed<T>> for (u16, T) {
+    fn from(x: Indexed<T>) -> Self {
+        (x.index, x.value)
+    }
+}
+
 pub(crate) fn coil_from_u16(value: u16) -> Result<bool, AduParseError> {
     match value {
         crate::constants::coil::ON => Ok(true),
@@ -249,7 +303,7 @@ impl AddressRange {
             return Err(InvalidRange::CountOfZero);
         }
 
-        let max_start = std::u16::MAX - (count - 1);
+        let max_start = u16::MAX - (count - 1);
 
         if start > max_start {
             return Err(InvalidRange::AddressOverflow(start, count));
@@ -265,10 +319,75 @@ impl AddressRange {
         start..end
     }
 
-    pub(crate) fn iter(&self) -> impl Iterator<Item = u16> {
+    /// Iterate over the addresses of the range, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u16> {
         AddressIterator::new(self.start, self.count)
     }
 
+    /// Last address of the range
+    pub fn last(&self) -> u16 {
+        // the count is never zero and the range never overflows
+        self.start + (self.count - 1)
+    }
+
+    /// Check whether the range contains the address
+    pub fn contains(&self, address: u16) -> bool {
+        self.start <= address && address <= self.last()
+    }
+
+    /// Check whether the two ranges have at least one address in common
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.last() && other.start <= self.last()
+    }
+
+    /// Check whether one range ends immediately before the other starts
+    pub fn is_adjacent(&self, other: &Self) -> bool {
+        self.end() == u32::from(other.start) || other.end() == u32::from(self.start)
+    }
+
+    /// Smallest range containing both ranges, if they overlap or are adjacent
+    pub fn merge(&self, other: &Self) -> Option<Self> {
+        if !self.overlaps(other) && !self.is_adjacent(other) {
+            return None;
+        }
+        let start = self.start.min(other.start);
+        let last = self.last().max(other.last());
+        Some(Self {
+            start,
+            count: last - start + 1,
+        })
+    }
+
+    /// Split the range into consecutive ranges of at most `max_count` addresses
+    ///
+    /// Use the [`crate::constants::limits`] of a function code to split a range that is too
+    /// large to be requested at once, e.g. `range.chunks(MAX_READ_REGISTERS_COUNT)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_count` is zero.
+    pub fn chunks(&self, max_count: u16) -> impl Iterator<Item = Self> {
+        assert!(max_count != 0, "max_count must not be zero");
+        let mut start = self.start;
+        let mut remain = self.count;
+        std::iter::from_fn(move || {
+            if remain == 0 {
+                return None;
+            }
+            let count = remain.min(max_count);
+            let chunk = Self { start, count };
+            remain -= count;
+            // wraps only after the last chunk of a range ending at u16::MAX
+            start = start.wrapping_add(count);
+            Some(chunk)
+        })
+    }
+
+    /// Address following the last address of the range
+    fn end(&self) -> u32 {
+        u32::from(self.start) + u32::from(self.count)
+    }
+
     pub(crate) fn of_read_bits(self) -> Result<ReadBitsRange, InvalidRange> {
         Ok(ReadBitsRange {
             inner: self.limited_count(crate::constants::limits::MAX_READ_COILS_COUNT)?,
@@ -289,6 +408,22 @@ impl AddressRange {
     }
 }
 
+/// Conversion of a `(start, count)` tuple, e.g. `let range: AddressRange = (0, 10).try_into()?`
+impl TryFrom<(u16, u16)> for AddressRange {
+    type Error = InvalidRange;
+
+    fn try_from(tuple: (u16, u16)) -> Result<Self, Self::Error> {
+        let (start, count) = tuple;
+        Self::try_from(start, count)
+    }
+}
+
+impl From<AddressRange> for (u16, u16) {
+    fn from(x: AddressRange) -> Self {
+        (x.start, x.count)
+    }
+}
+
 impl std::fmt::Display for AddressRange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "start: {:#06X} qty: {}", self.start, self.count)
@@ -313,7 +448,8 @@ impl Iterator for AddressIterator {
         match self.remain.checked_sub(1) {
             Some(x) => {
                 let ret = self.current;
-                self.current += 1;
+                // wraps only after the last address of a range ending at u16::MAX
+                self.current = self.current.wrapping_add(1);
                 self.remain = x;
                 Some(ret)
             }
@@ -322,6 +458,70 @@ impl Iterator for AddressIterator {
     }
 }
 
+impl WordOrder {
+    /// Split the big-endian bytes of a value into registers in this order
+    pub(crate) fn registers(self, bytes: &[u8]) -> Vec<u16> {
+        let mut registers: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|x| u16::from_be_bytes([x[0], x[1]]))
+            .collect();
+        if self == WordOrder::LowFirst {
+            registers.reverse();
+        }
+        registers
+    }
+
+    /// Join registers in this order into the big-endian bytes of a value, the inverse of
+    /// [`WordOrder::registers`]
+    fn bytes<const N: usize>(self, registers: &[u16]) -> [u8; N] {
+        let mut bytes = [0; N];
+        for (i, register) in registers.iter().enumerate() {
+            let pos = match self {
+                WordOrder::HighFirst => i,
+                WordOrder::LowFirst => registers.len() - 1 - i,
+            };
+            bytes[2 * pos..2 * pos + 2].copy_from_slice(&register.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Decode an unsigned 32-bit integer from 2 registers, as written by
+    /// [`crate::client::Channel::write_u32`]
+    pub fn decode_u32(self, registers: [u16; 2]) -> u32 {
+        u32::from_be_bytes(self.bytes(&registers))
+    }
+
+    /// Decode a signed 32-bit integer from 2 registers, as written by
+    /// [`crate::client::Channel::write_i32`]
+    pub fn decode_i32(self, registers: [u16; 2]) -> i32 {
+        i32::from_be_bytes(self.bytes(&registers))
+    }
+
+    /// Decode a 32-bit float from 2 registers, as written by
+    /// [`crate::client::Channel::write_f32`]
+    pub fn decode_f32(self, registers: [u16; 2]) -> f32 {
+        f32::from_be_bytes(self.bytes(&registers))
+    }
+
+    /// Decode an unsigned 64-bit integer from 4 registers, as written by
+    /// [`crate::client::Channel::write_u64`]
+    pub fn decode_u64(self, registers: [u16; 4]) -> u64 {
+        u64::from_be_bytes(self.bytes(&registers))
+    }
+
+    /// Decode a signed 64-bit integer from 4 registers, as written by
+    /// [`crate::client::Channel::write_i64`]
+    pub fn decode_i64(self, registers: [u16; 4]) -> i64 {
+        i64::from_be_bytes(self.bytes(&registers))
+    }
+
+    /// Decode a 64-bit float from 4 registers, as written by
+    /// [`crate::client::Channel::write_f64`]
+    pub fn decode_f64(self, registers: [u16; 4]) -> f64 {
+        f64::from_be_bytes(self.bytes(&registers))
+    }
+}
+
 impl<T> Indexed<T> {
     /// Create a new indexed value
     pub fn new(index: u16, value: T) -> Self {
@@ -329,6 +529,77 @@ impl<T> Indexed<T> {
     }
 }
 
+impl<T> ReadResult<T>
+where
+    T: Copy,
+{
+    /// Create a result from the address of the first value and the values
+    pub fn new(start: u16, values: Vec<T>) -> Self {
+        Self {
+            start,
+            values: SmallVec::from_vec(values),
+        }
+    }
+
+    /// Address of the first value
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Values in the order of their addresses
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Consume the result, returning the values
+    pub fn into_values(self) -> Vec<T> {
+        self.values.into_vec()
+    }
+
+    /// Number of values actually returned by the server, which is the count of the request
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if there are no values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Value at an address, if the address is in the range that was read
+    pub fn get(&self, address: u16) -> Option<T> {
+        let offset = address.checked_sub(self.start)?;
+        self.values.get(offset as usize).copied()
+    }
+
+    /// Iterate over the values paired with their addresses, for those who need per-point
+    /// indices rather than the contiguous [`ReadResult::values`]
+    pub fn iter(&self) -> impl Iterator<Item = Indexed<T>> + '_ {
+        // the values of a read never extend past the largest address
+        (self.start..=u16::MAX)
+            .zip(self.values.iter())
+            .map(|(index, value)| Indexed::new(index, *value))
+    }
+}
+
+impl<'a> From<BitIterator<'a>> for ReadResult<bool> {
+    fn from(iter: BitIterator<'a>) -> Self {
+        Self {
+            start: iter.range.start,
+            values: iter.map(|x| x.value).collect(),
+        }
+    }
+}
+
+impl<'a> From<RegisterIterator<'a>> for ReadResult<u16> {
+    fn from(iter: RegisterIterator<'a>) -> Self {
+        Self {
+            start: iter.range.start,
+            values: iter.map(|x| x.value).collect(),
+        }
+    }
+}
+
 impl std::fmt::Display for Indexed<bool> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "idx: {:#06X} value: {}", self.index, self.value as i32)
@@ -348,6 +619,7 @@ impl UnitId {
     }
 
     /// Create the default UnitId of `0xFF`
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Self {
         Self { value: 0xFF }
     }
@@ -371,9 +643,47 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn word_order_arranges_the_registers_of_a_value() {
+        let bytes = 0x1122_3344_5566_7788u64.to_be_bytes();
+        assert_eq!(
+            WordOrder::HighFirst.registers(&bytes),
+            [0x1122, 0x3344, 0x5566, 0x7788]
+        );
+        assert_eq!(
+            WordOrder::LowFirst.registers(&bytes),
+            [0x7788, 0x5566, 0x3344, 0x1122]
+        );
+    }
+
+    #[test]
+    fn word_order_decodes_the_registers_it_arranges() {
+        for order in [WordOrder::HighFirst, WordOrder::LowFirst] {
+            let registers = order.registers(&0x1122_3344u32.to_be_bytes());
+            assert_eq!(order.decode_u32([registers[0], registers[1]]), 0x1122_3344);
+            let registers = order.registers(&(-2i32).to_be_bytes());
+            assert_eq!(order.decode_i32([registers[0], registers[1]]), -2);
+            let registers = order.registers(&1.5f32.to_be_bytes());
+            assert_eq!(order.decode_f32([registers[0], registers[1]]), 1.5);
+            let registers: [u16; 4] = order
+                .registers(&0x1122_3344_5566_7788u64.to_be_bytes())
+                .try_into()
+                .unwrap();
+            assert_eq!(order.decode_u64(registers), 0x1122_3344_5566_7788);
+            let registers: [u16; 4] = order.registers(&(-2i64).to_be_bytes()).try_into().unwrap();
+            assert_eq!(order.decode_i64(registers), -2);
+            let registers: [u16; 4] = order.registers(&1.5f64.to_be_bytes()).try_into().unwrap();
+            assert_eq!(order.decode_f64(registers), 1.5);
+        }
+        assert_eq!(
+            WordOrder::LowFirst.decode_u32([0x3344, 0x1122]),
+            0x1122_3344
+        );
+    }
+
     #[test]
     fn address_start_max_count_of_one_is_allowed() {
-        AddressRange::try_from(std::u16::MAX, 1).unwrap();
+        AddressRange::try_from(u16::MAX, 1).unwrap();
     }
 
     #[test]
@@ -394,6 +704,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn iterates_over_the_addresses() {
+        let range = AddressRange::try_from(0xFFFD, 3).unwrap();
+        assert_eq!(
+            range.iter().collect::<Vec<_>>(),
+            vec![0xFFFD, 0xFFFE, 0xFFFF]
+        );
+        assert_eq!(range.last(), u16::MAX);
+        assert!(range.contains(0xFFFD));
+        assert!(!range.contains(0xFFFC));
+    }
+
+    #[test]
+    fn splits_into_chunks_of_at_most_the_limit() {
+        let range = AddressRange::try_from(10, 250).unwrap();
+        let chunks: Vec<_> = range
+            .chunks(crate::constants::limits::MAX_READ_REGISTERS_COUNT)
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                AddressRange::try_from(10, 125).unwrap(),
+                AddressRange::try_from(135, 125).unwrap(),
+            ]
+        );
+
+        let range = AddressRange::try_from(0, 0xFFFF).unwrap();
+        let chunks: Vec<_> = range.chunks(0x1000).collect();
+        assert_eq!(chunks.len(), 16);
+        assert_eq!(chunks[15], AddressRange::try_from(0xF000, 0x0FFF).unwrap());
+    }
+
+    #[test]
+    fn converts_to_and_from_tuples() {
+        let range: AddressRange = (7, 3).try_into().unwrap();
+        assert_eq!(range, AddressRange::try_from(7, 3).unwrap());
+        assert_eq!(<(u16, u16)>::from(range), (7, 3));
+        assert_eq!(
+            AddressRange::try_from(u16::MAX, 2).err(),
+            TryInto::<AddressRange>::try_into((u16::MAX, 2)).err()
+        );
+
+        let value: Indexed<bool> = (4, true).into();
+        assert_eq!(value, Indexed::new(4, true));
+        assert_eq!(<(u16, bool)>::from(value), (4, true));
+
+        assert_eq!(UnitId::from(5), UnitId::new(5));
+        assert_eq!(u8::from(UnitId::new(5)), 5);
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        let range = |start, count| AddressRange::try_from(start, count).unwrap();
+
+        assert!(range(0, 10).overlaps(&range(9, 5)));
+        assert!(!range(0, 10).overlaps(&range(10, 5)));
+        assert!(range(0, 10).is_adjacent(&range(10, 5)));
+        assert!(range(10, 5).is_adjacent(&range(0, 10)));
+        assert!(!range(0, 10).is_adjacent(&range(11, 5)));
+        assert!(!range(0xFFFF, 1).is_adjacent(&range(0, 1)));
+
+        assert_eq!(range(0, 10).merge(&range(10, 5)), Some(range(0, 15)));
+        assert_eq!(range(5, 20).merge(&range(0, 10)), Some(range(0, 25)));
+        assert_eq!(range(5, 2).merge(&range(0, 10)), Some(range(0, 10)));
+        assert_eq!(range(0, 10).merge(&range(11, 5)), None);
+    }
+
     #[test]
     fn correctly_iterates_over_low_order_bits() {
         let mut cursor = ReadCursor::new(&[0x03]);
@@ -437,4 +814,31 @@ mod tests {
         assert!(UnitId::new(255).is_rtu_reserved());
         assert!(!UnitId::new(41).is_rtu_reserved());
     }
+
+    #[test]
+    fn read_result_holds_contiguous_values() {
+        let mut cursor = ReadCursor::new(&[0xFF, 0xFF, 0x01, 0xCC]);
+        let result: ReadRegistersResult =
+            RegisterIterator::parse_all(AddressRange::try_from(1, 2).unwrap(), &mut cursor)
+                .unwrap()
+                .into();
+        assert_eq!(result.start(), 1);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.values(), &[0xFFFF, 0x01CC]);
+        assert_eq!(result.get(0), None);
+        assert_eq!(result.get(2), Some(0x01CC));
+        assert_eq!(result.get(3), None);
+        assert_eq!(
+            result.iter().collect::<Vec<_>>(),
+            vec![Indexed::new(1, 0xFFFF), Indexed::new(2, 0x01CC)]
+        );
+
+        assert!(!result.values.spilled());
+
+        let last: ReadBitsResult = ReadResult::new(u16::MAX, vec![true]);
+        assert_eq!(
+            last.iter().collect::<Vec<_>>(),
+            vec![Indexed::new(u16::MAX, true)]
+        );
+    }
 }
@@ -110,7 +110,7 @@ async fn test_requests_and_responses() {
     .await
     .unwrap();
 
-    let mut channel = spawn_tcp_client_task(
+    let channel = spawn_tcp_client_task(
         HostAddr::ip(addr.ip(), addr.port()),
         10,
         default_retry_strategy(),
@@ -159,6 +159,11 @@ async fn test_requests_and_responses() {
             .unwrap(),
         vec![Indexed::new(0, false), Indexed::new(1, true)]
     );
+    // the coil can also be given as a tuple
+    assert_eq!(
+        channel.write_single_coil(params, (1, true)).await.unwrap(),
+        Indexed::new(1, true)
+    );
 
     // do a single register write and verify that it was written by reading it
     assert_eq!(
@@ -222,6 +227,22 @@ async fn test_requests_and_responses() {
             Indexed::new(2, 0x0506)
         ]
     );
+
+    // write a float with the low register first
+    assert_eq!(
+        channel
+            .write_f32(params, 1, 1.5, WordOrder::LowFirst)
+            .await
+            .unwrap(),
+        AddressRange::try_from(1, 2).unwrap()
+    );
+    assert_eq!(
+        channel
+            .read_holding_registers(params, AddressRange::try_from(1, 2).unwrap())
+            .await
+            .unwrap(),
+        vec![Indexed::new(1, 0x0000), Indexed::new(2, 0x3FC0)]
+    );
 }
 
 #[test]
@@ -229,3 +250,497 @@ fn can_read_and_write_values() {
     let rt = Runtime::new().unwrap();
     rt.block_on(test_requests_and_responses())
 }
+
+async fn test_endpoint_change() {
+    let spawn_server = |port, value| async move {
+        let handler = Handler::new().wrap();
+        handler.lock().unwrap().holding_registers[0] = value;
+        spawn_tcp_server_task(
+            1,
+            SocketAddr::from(([127, 0, 0, 1], port)),
+            ServerHandlerMap::single(UnitId::new(1), handler),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap()
+    };
+    let _first = spawn_server(40004, 1).await;
+    let _second = spawn_server(40005, 2).await;
+
+    let channel = spawn_tcp_client_task(
+        HostAddr::ip([127, 0, 0, 1].into(), 40004),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 1).unwrap();
+    let read = move |channel: &Channel| {
+        let channel = channel.clone();
+        async move {
+            channel
+                .read_holding_registers(params, range)
+                .await
+                .map(|x| x[0].value)
+        }
+    };
+    assert_eq!(read(&channel).await, Ok(1));
+
+    // the established connection is kept
+    channel
+        .set_endpoint(HostAddr::dns("localhost".to_string(), 40005))
+        .await
+        .unwrap();
+    assert_eq!(read(&channel).await, Ok(1));
+
+    // and the new endpoint is used once the channel reconnects
+    channel.disable().await.unwrap();
+    channel.enable().await.unwrap();
+    for _ in 0..100 {
+        if read(&channel).await == Ok(2) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the channel did not connect to the new endpoint");
+}
+
+#[test]
+fn endpoint_can_be_changed_at_runtime() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_endpoint_change())
+}
+
+async fn test_server_reload() {
+    let handler = |value| {
+        let handler = Handler::new().wrap();
+        handler.lock().unwrap().holding_registers[0] = value;
+        ServerHandlerMap::single(UnitId::new(1), handler)
+    };
+    let mut server = spawn_tcp_server_task(
+        1,
+        SocketAddr::from(([127, 0, 0, 1], 40006)),
+        handler(1),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    let channel = spawn_tcp_client_task(
+        HostAddr::ip([127, 0, 0, 1].into(), 40006),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 1).unwrap();
+    let read = move |channel: &Channel| {
+        let channel = channel.clone();
+        async move {
+            channel
+                .read_holding_registers(params, range)
+                .await
+                .map(|x| x[0].value)
+        }
+    };
+    assert_eq!(read(&channel).await, Ok(1));
+
+    // handlers of another type are rejected without changing the configuration
+    let other = ServerHandlerMap::single(UnitId::new(1), ContextHandler::default().wrap());
+    assert_eq!(
+        server.reload(ServerReload::new(1, other)).await,
+        Err(ReloadError::HandlerType)
+    );
+    assert_eq!(read(&channel).await, Ok(1));
+
+    let mut reload = ServerReload::new(1, handler(2));
+    reload.write_permissions = Some(WritePermissions::new(WriteAccess::ReadOnly));
+    server.reload(reload).await.unwrap();
+
+    // the session applies the configuration once it reaches it
+    let mut reloaded = false;
+    for _ in 0..100 {
+        if read(&channel).await == Ok(2) {
+            reloaded = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(reloaded, "the session did not reload its handlers");
+    assert_eq!(
+        channel
+            .write_single_register(params, Indexed::new(0, 3))
+            .await
+            .unwrap_err(),
+        RequestError::Exception(ExceptionCode::IllegalDataAddress)
+    );
+}
+
+#[test]
+fn server_configuration_can_be_reloaded() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_server_reload())
+}
+
+/// Reads the context of the session as its holding registers
+#[derive(Default)]
+struct ContextHandler {
+    context: Option<u16>,
+}
+
+impl RequestHandler for ContextHandler {
+    fn set_session_context(&mut self, context: Option<&SessionContext>) {
+        self.context = context.and_then(|x| x.get::<u16>()).copied();
+    }
+
+    fn read_holding_register(&self, _address: u16) -> Result<u16, ExceptionCode> {
+        self.context.ok_or(ExceptionCode::IllegalDataAddress)
+    }
+}
+
+struct TaggingAcceptHandler {
+    accept: bool,
+}
+
+impl AcceptHandler for TaggingAcceptHandler {
+    fn accept(&self, connection: AcceptedConnection) -> AcceptDecision {
+        assert!(connection.peer.ip().is_loopback());
+        assert_eq!(connection.role, None);
+        if self.accept {
+            AcceptDecision::Accept(Some(SessionContext::new(42u16)))
+        } else {
+            AcceptDecision::Reject
+        }
+    }
+}
+
+async fn test_accept_handler() {
+    let mut server = spawn_tcp_server_task(
+        1,
+        SocketAddr::from(([127, 0, 0, 1], 40007)),
+        ServerHandlerMap::single(UnitId::new(1), ContextHandler::default().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+    server
+        .set_accept_handler(Some(TaggingAcceptHandler { accept: true }.wrap()))
+        .await
+        .unwrap();
+
+    let channel = spawn_tcp_client_task(
+        HostAddr::ip([127, 0, 0, 1].into(), 40007),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        DecodeLevel::default(),
+        None,
+    );
+
+    let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 1).unwrap();
+    // reconnect until a connection is accepted with the handler that was installed last
+    let reconnect = |expected: fn(&Result<u16, RequestError>) -> bool| {
+        let channel = channel.clone();
+        async move {
+            for _ in 0..100 {
+                channel.disable().await.unwrap();
+                channel.enable().await.unwrap();
+                let result = channel
+                    .read_holding_registers(params, range)
+                    .await
+                    .map(|x| x[0].value)
+                    .map_err(RequestFailure::into_error);
+                if expected(&result) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("the accept handler was not applied");
+        }
+    };
+
+    reconnect(|result| *result == Ok(42)).await;
+
+    server
+        .set_accept_handler(Some(TaggingAcceptHandler { accept: false }.wrap()))
+        .await
+        .unwrap();
+    reconnect(|result| result.is_err()).await;
+
+    channel.disable().await.unwrap();
+}
+
+#[test]
+fn accept_handler_can_reject_connections_and_attach_context() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_accept_handler())
+}
+
+async fn test_session_eviction() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const READ_REGISTER: [u8; 12] = [0, 1, 0, 0, 0, 6, 1, 0x03, 0, 0, 0, 1];
+
+    async fn request(stream: &mut TcpStream) {
+        stream.write_all(&READ_REGISTER).await.unwrap();
+        let mut response = [0; 11];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(response[7], 0x03);
+    }
+
+    async fn is_closed(stream: &mut TcpStream) -> bool {
+        let mut buffer = [0; 1];
+        matches!(
+            tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buffer)).await,
+            Ok(Ok(0)) | Ok(Err(_))
+        )
+    }
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 40008));
+    let mut server = spawn_tcp_server_task(
+        2,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+    server
+        .set_session_eviction(SessionEviction::LeastRecentlyActive)
+        .await
+        .unwrap();
+
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    request(&mut first).await;
+    let mut second = TcpStream::connect(addr).await.unwrap();
+    request(&mut second).await;
+    // the first session becomes the most recently active
+    request(&mut first).await;
+
+    let mut third = TcpStream::connect(addr).await.unwrap();
+    request(&mut third).await;
+    assert!(is_closed(&mut second).await);
+    request(&mut first).await;
+
+    // the sessions that don't receive requests are closed
+    server
+        .set_idle_timeout(Some(Duration::from_millis(50)))
+        .await
+        .unwrap();
+    assert!(is_closed(&mut first).await);
+    assert!(is_closed(&mut third).await);
+}
+
+#[test]
+fn idle_sessions_are_evicted() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_session_eviction())
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn cert(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../certs")
+            .join(name)
+    }
+
+    fn self_signed_server() -> TlsServerConfig {
+        TlsServerConfig::new(
+            &cert("self_signed/entity1_cert.pem"),
+            &cert("self_signed/entity2_cert.pem"),
+            &cert("self_signed/entity2_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+            CertificateMode::SelfSigned,
+        )
+        .unwrap()
+    }
+
+    fn ca_chain_server() -> TlsServerConfig {
+        TlsServerConfig::new(
+            &cert("ca_chain/ca_cert.pem"),
+            &cert("ca_chain/server_cert.pem"),
+            &cert("ca_chain/server_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+            CertificateMode::AuthorityBased,
+        )
+        .unwrap()
+    }
+
+    fn self_signed_client() -> TlsClientConfig {
+        TlsClientConfig::new(
+            "test.com",
+            &cert("self_signed/entity2_cert.pem"),
+            &cert("self_signed/entity1_cert.pem"),
+            &cert("self_signed/entity1_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+            CertificateMode::SelfSigned,
+        )
+        .unwrap()
+    }
+
+    fn ca_chain_client() -> TlsClientConfig {
+        TlsClientConfig::new(
+            "test.com",
+            &cert("ca_chain/ca_cert.pem"),
+            &cert("ca_chain/client_cert.pem"),
+            &cert("ca_chain/client_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+            CertificateMode::AuthorityBased,
+        )
+        .unwrap()
+    }
+
+    fn spawn_client(addr: SocketAddr, config: TlsClientConfig) -> Channel {
+        spawn_tls_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+            config,
+            DecodeLevel::default(),
+            None,
+        )
+    }
+
+    async fn read_coil(channel: &Channel) -> Result<Vec<Indexed<bool>>, RequestError> {
+        channel
+            .read_coils(
+                RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                AddressRange::try_from(0, 1).unwrap(),
+            )
+            .await
+            .map_err(RequestFailure::into_error)
+    }
+
+    async fn test_certificate_rotation() {
+        let addr = SocketAddr::from_str("127.0.0.1:40001").unwrap();
+        let mut server = spawn_tls_server_task(
+            2,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+            self_signed_server(),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let channel = spawn_client(addr, self_signed_client());
+        channel.enable().await.unwrap();
+        assert!(read_coil(&channel).await.is_ok());
+
+        // the established session survives the rotation of the server certificates
+        server.set_tls_config(ca_chain_server()).await.unwrap();
+        assert!(read_coil(&channel).await.is_ok());
+
+        // while new connections use the new certificates
+        let other = spawn_client(addr, ca_chain_client());
+        other.enable().await.unwrap();
+        assert!(read_coil(&other).await.is_ok());
+        other.disable().await.unwrap();
+
+        // the client uses its new configuration when it reconnects
+        channel.set_tls_config(ca_chain_client()).await.unwrap();
+        assert!(read_coil(&channel).await.is_ok());
+        channel.disable().await.unwrap();
+        channel.enable().await.unwrap();
+        // requests fail until the channel has reconnected
+        for _ in 0..100 {
+            if read_coil(&channel).await.is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the channel did not reconnect with its new configuration");
+    }
+
+    #[cfg(feature = "dangerous-tls")]
+    async fn test_fingerprint_verification() {
+        let fingerprint = |name| CertificateFingerprint::from_file(&cert(name)).unwrap();
+        let verifier = |name| -> std::sync::Arc<dyn CertificateVerifier> {
+            std::sync::Arc::new(FingerprintVerifier::new(vec![fingerprint(name)]))
+        };
+
+        let addr = SocketAddr::from_str("127.0.0.1:40002").unwrap();
+        let _server = spawn_tls_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+            TlsServerConfig::with_verifier(
+                &cert("self_signed/entity2_cert.pem"),
+                &cert("self_signed/entity2_key.pem"),
+                None,
+                MinTlsVersion::V1_2,
+                verifier("self_signed/entity1_cert.pem"),
+            )
+            .unwrap(),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let channel = spawn_client(
+            addr,
+            TlsClientConfig::with_verifier(
+                "test.com",
+                &cert("self_signed/entity1_cert.pem"),
+                &cert("self_signed/entity1_key.pem"),
+                None,
+                MinTlsVersion::V1_2,
+                verifier("self_signed/entity2_cert.pem"),
+            )
+            .unwrap(),
+        );
+        channel.enable().await.unwrap();
+        assert!(read_coil(&channel).await.is_ok());
+
+        // a client that expects another server certificate doesn't connect
+        let other = spawn_client(
+            addr,
+            TlsClientConfig::with_verifier(
+                "test.com",
+                &cert("self_signed/entity1_cert.pem"),
+                &cert("self_signed/entity1_key.pem"),
+                None,
+                MinTlsVersion::V1_2,
+                verifier("self_signed/entity1_cert.pem"),
+            )
+            .unwrap(),
+        );
+        other.enable().await.unwrap();
+        assert_eq!(read_coil(&other).await, Err(RequestError::NoConnection));
+    }
+
+    #[cfg(feature = "dangerous-tls")]
+    #[test]
+    fn peers_can_be_verified_by_fingerprint() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(test_fingerprint_verification())
+    }
+
+    #[test]
+    fn certificates_can_be_rotated_without_dropping_sessions() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(test_certificate_rotation())
+    }
+}